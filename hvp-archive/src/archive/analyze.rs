@@ -0,0 +1,332 @@
+//! heuristic analysis of the compression of the files, it find entries that are valid
+//! but unusual, which often point to parsing bugs or corrupted retail data.
+//! it also classify the dead space between the data of the files, some rips have zero
+//! filled padding blocks there
+
+use std::{fmt::Display, ops::Range, path::PathBuf};
+
+use ahash::HashMap;
+
+use super::{Archive, verify};
+use crate::provider;
+
+/// minimum number of compressed files with the same extension to look for ratio outliers
+const MIN_SAMPLES: usize = 5;
+/// how many scaled median absolute deviations a ratio should be away from the median
+const OUTLIER_SCORE: f64 = 3.5;
+/// ratios that are closer than this to the median are never outliers, so extensions with
+/// almost identical ratios don't flag tiny differences
+const MIN_DEVIATION: f64 = 0.25;
+
+/// a unusual entry that is found by [`Archive::analyze`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Anomaly {
+    /// file is compressed, but its compressed data is larger than the uncompressed file
+    CompressedLarger {
+        path: PathBuf,
+        stored: usize,
+        uncompressed: usize,
+    },
+    /// stored or uncompressed size of the file is zero, but its data offset isn't.
+    /// empty files are written with a zero offset, and the stored data is ignored
+    ZeroSize {
+        path: PathBuf,
+        offset: u32,
+        stored: u32,
+        uncompressed: u32,
+    },
+    /// compression ratio (stored / uncompressed) of the file is far from the other
+    /// compressed files with the same extension
+    RatioOutlier {
+        path: PathBuf,
+        extension: String,
+        ratio: f64,
+        median: f64,
+    },
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::CompressedLarger {
+                path,
+                stored,
+                uncompressed,
+            } => write!(
+                f,
+                "{}: compressed size {stored} is larger than uncompressed size {uncompressed}",
+                path.display()
+            ),
+            Self::ZeroSize {
+                path,
+                offset,
+                stored,
+                uncompressed,
+            } => write!(
+                f,
+                "{}: stored size is {stored} and uncompressed size is {uncompressed}, but offset is {offset:#x}",
+                path.display()
+            ),
+            Self::RatioOutlier {
+                path,
+                extension,
+                ratio,
+                median,
+            } => write!(
+                f,
+                "{}: compression ratio {:.1}% is far from the {:.1}% median of `.{extension}` files",
+                path.display(),
+                ratio * 100.0,
+                median * 100.0
+            ),
+        }
+    }
+}
+
+/// a region of the archive after the TOC that isn't used by the data of any file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeadSpace {
+    /// offset of the region from the start of the archive
+    pub offset: u64,
+    /// length of the region
+    pub len: u64,
+    /// whatever every byte of the region is zero, `None` when the archive is opened
+    /// with [`crate::provider::ArchiveProvider::open_toc_only`]
+    pub zero_filled: Option<bool>,
+}
+
+impl DeadSpace {
+    /// range of the region in the archive
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.offset + self.len
+    }
+}
+
+/// result of analyzing a archive
+#[derive(Debug, Clone, Default)]
+pub struct AnalysisReport {
+    /// number of files that we checked
+    pub files_checked: usize,
+    /// unusual entries in the [`Archive::files`] order, ratio outliers come last
+    pub anomalies: Vec<Anomaly>,
+    /// dead space of the archive, sorted by offset
+    pub dead_space: Vec<DeadSpace>,
+}
+
+impl AnalysisReport {
+    /// whatever no anomaly is found
+    pub fn is_ok(&self) -> bool {
+        self.anomalies.is_empty()
+    }
+}
+
+impl Archive<'_> {
+    /// look for files with unusual compression, unlike [`Archive::cross_check`] nothing
+    /// that is reported here is invalid, some retail archives have a few of them
+    pub fn analyze(&self) -> AnalysisReport {
+        let mut report = AnalysisReport::default();
+        // ratio of the compressed files of every extension
+        let mut ratios: HashMap<String, Vec<(PathBuf, f64)>> = HashMap::default();
+
+        // structural problems are reported by cross check
+        let sizes = verify::raw_file_sizes(&self.provider.raw_archive, &mut Vec::new());
+
+        // both of them walk the entries in the same depth first order
+        for (file, raw) in self.files().zip(sizes) {
+            report.files_checked += 1;
+
+            if (raw.stored == 0 || raw.uncompressed == 0) && raw.offset != 0 {
+                report.anomalies.push(Anomaly::ZeroSize {
+                    path: file.path.clone(),
+                    offset: raw.offset,
+                    stored: raw.stored,
+                    uncompressed: raw.uncompressed,
+                });
+            }
+
+            let stored = file.raw_bytes.len();
+            let uncompressed = file.uncompressed_size();
+            if stored == 0 || file.compression_info.is_none() {
+                continue;
+            }
+
+            if stored > uncompressed {
+                report.anomalies.push(Anomaly::CompressedLarger {
+                    path: file.path.clone(),
+                    stored,
+                    uncompressed,
+                });
+            }
+
+            if uncompressed != 0 {
+                let extension = file
+                    .path
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                ratios
+                    .entry(extension)
+                    .or_default()
+                    .push((file.path, stored as f64 / uncompressed as f64));
+            }
+        }
+
+        let mut extensions: Vec<_> = ratios.into_iter().collect();
+        extensions.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (extension, files) in extensions {
+            if files.len() < MIN_SAMPLES {
+                continue;
+            }
+
+            let median = median(files.iter().map(|(_, ratio)| *ratio).collect());
+            let mad = median_deviation(&files, median);
+            // 1.4826 scale the deviation to the standard deviation of a normal distribution
+            let threshold = (OUTLIER_SCORE * 1.4826 * mad).max(MIN_DEVIATION);
+
+            report.anomalies.extend(
+                files
+                    .into_iter()
+                    .filter(|(_, ratio)| (ratio - median).abs() > threshold)
+                    .map(|(path, ratio)| Anomaly::RatioOutlier {
+                        path,
+                        extension: extension.clone(),
+                        ratio,
+                        median,
+                    }),
+            );
+        }
+
+        report.dead_space = self.dead_space();
+
+        report
+    }
+
+    /// regions after the TOC that aren't used by the data of any file (including the orphan
+    /// entries), up to the end of the archive file. overlapping files don't make dead space
+    pub fn dead_space(&self) -> Vec<DeadSpace> {
+        let provider = self.provider;
+        let mut ranges = provider::data_ranges(&provider.raw_archive);
+        // an empty range at the end so the region after the last file is a gap too
+        ranges.push(provider.file_len()..provider.file_len());
+        ranges.sort_by_key(|r| r.start);
+
+        let mut gaps = Vec::new();
+        let mut pos = provider.toc_len();
+        for range in ranges {
+            if range.start > pos {
+                gaps.push(pos..range.start);
+            }
+            pos = pos.max(range.end);
+        }
+
+        gaps.into_iter()
+            .map(|gap| DeadSpace {
+                offset: gap.start,
+                len: gap.end - gap.start,
+                zero_filled: provider.has_data().then(|| {
+                    provider
+                        .get_bytes(gap.start as _, (gap.end - gap.start) as _)
+                        .iter()
+                        .all(|b| *b == 0)
+                }),
+            })
+            .collect()
+    }
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    match values.len() % 2 {
+        0 => (values[mid - 1] + values[mid]) / 2.0,
+        _ => values[mid],
+    }
+}
+
+/// median of the absolute deviations from the median
+fn median_deviation(files: &[(PathBuf, f64)], median: f64) -> f64 {
+    self::median(
+        files
+            .iter()
+            .map(|(_, ratio)| (ratio - median).abs())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        archive::{DeadSpacePolicy, Options, entry::UpdateKind},
+        provider::ArchiveProvider,
+        test_utils::{for_each_fixture, reload},
+    };
+
+    const PADDING: usize = 64 * 1024;
+
+    #[test]
+    fn fixtures_dead_space_classify_and_reclaim() {
+        for_each_fixture(|fixture, _| {
+            // a rip with a zero filled padding block after the data
+            let mut bytes = fixture.bytes.to_vec();
+            bytes.resize(bytes.len() + PADDING, 0);
+            let path = std::env::temp_dir().join(format!("hvp_dead_space_{}.hvp", fixture.name));
+            std::fs::write(&path, &bytes).unwrap();
+
+            let toc_only = ArchiveProvider::open_toc_only(&path, None).unwrap();
+            let region = Archive::new(&toc_only).dead_space().pop().unwrap();
+            assert_eq!(region.zero_filled, None);
+            drop(toc_only);
+            let _ = std::fs::remove_file(path);
+
+            let provider = reload(bytes.clone());
+            let mut archive = Archive::new_with_options(
+                &provider,
+                Options {
+                    dead_space: DeadSpacePolicy::Reclaim,
+                    ..Default::default()
+                },
+            );
+
+            let report = archive.analyze();
+            let region = report.dead_space.last().unwrap();
+            assert_eq!(region.zero_filled, Some(true));
+            assert_eq!(region.range().end, bytes.len() as u64);
+            assert!(region.len >= PADDING as u64);
+
+            // bigger than the old data, but fit in the padding
+            let mut entry = archive
+                .files_mut()
+                .min_by_key(|f| f.raw_bytes().len())
+                .unwrap();
+            let updated = vec![0x5A; entry.raw_bytes().len() + 1024];
+            entry.update(UpdateKind::Bytes(updated.clone()));
+            let updated_path = entry.path.clone();
+
+            archive.options.rebuild_skip_compression = true;
+
+            let mut patched = Cursor::new(bytes.clone());
+            let report = archive.patch(&mut patched).unwrap();
+            assert_eq!(report.reclaimed, 1, "{report:?}");
+            assert_eq!(report.appended, 0, "{report:?}");
+
+            let patched = patched.into_inner();
+            assert_eq!(patched.len(), bytes.len());
+
+            let patched_provider = reload(patched);
+            let patched_archive = Archive::new(&patched_provider);
+            assert!(patched_archive.verify().is_ok());
+            assert_eq!(
+                &*patched_archive
+                    .find_file(&updated_path)
+                    .unwrap()
+                    .get_bytes()
+                    .unwrap(),
+                updated.as_slice()
+            );
+        });
+    }
+}
@@ -0,0 +1,160 @@
+//! replace the files of the archive with the matching files of a folder
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{Archive, entry::UpdateKind};
+
+/// how the paths of the folder are matched with the files of the archive.
+///
+/// both `/` and `\` are always treated as separators
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// match the paths whatever their case, the games don't care about it
+    pub ignore_case: bool,
+}
+
+impl MatchOptions {
+    /// key that two matching paths share
+    fn key(&self, path: &Path) -> String {
+        let key = path
+            .to_string_lossy()
+            .split(['/', '\\'])
+            .filter(|c| !c.is_empty() && *c != ".")
+            .collect::<Vec<_>>()
+            .join("/");
+
+        match self.ignore_case {
+            true => key.to_lowercase(),
+            false => key,
+        }
+    }
+}
+
+/// what [`Archive::apply_folder`] and [`Archive::apply_paths`] updated
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ApplyReport {
+    /// path of the updated files inside the archive and the file they are updated from
+    pub matched: Vec<(PathBuf, PathBuf)>,
+    /// paths of the folder that don't match any file of the archive
+    pub unmatched: Vec<PathBuf>,
+    /// paths of the folder that are skipped because another path match the same file,
+    /// only possible with [`MatchOptions::ignore_case`]
+    pub conflicts: Vec<PathBuf>,
+}
+
+impl Archive<'_> {
+    /// update every file of the archive that has a file at the same path inside `folder`
+    pub fn apply_folder(
+        &mut self,
+        folder: impl AsRef<Path>,
+        options: MatchOptions,
+    ) -> io::Result<ApplyReport> {
+        fn list(folder: &Path, dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+            for entry in std::fs::read_dir(folder.join(dir))? {
+                let entry = entry?;
+                let path = dir.join(entry.file_name());
+                match entry.file_type()?.is_dir() {
+                    true => list(folder, &path, paths)?,
+                    false => paths.push(path),
+                }
+            }
+
+            Ok(())
+        }
+
+        let folder = folder.as_ref();
+        let mut paths = Vec::new();
+        list(folder, Path::new(""), &mut paths)?;
+        paths.sort();
+
+        Ok(self.apply_paths(folder, paths, options))
+    }
+
+    /// like [`Archive::apply_folder`], but only the given `paths` (relative to `folder`) are
+    /// matched, so the caller can skip the unchanged files
+    pub fn apply_paths(
+        &mut self,
+        folder: impl AsRef<Path>,
+        paths: impl IntoIterator<Item = PathBuf>,
+        options: MatchOptions,
+    ) -> ApplyReport {
+        let folder = folder.as_ref();
+        let mut report = ApplyReport::default();
+
+        let mut index = ahash::HashMap::default();
+        for path in paths {
+            match index.entry(options.key(&path)) {
+                std::collections::hash_map::Entry::Vacant(entry) => {
+                    entry.insert(path);
+                }
+                std::collections::hash_map::Entry::Occupied(_) => report.conflicts.push(path),
+            }
+        }
+
+        for mut entry in self.files_mut() {
+            let Some(path) = index.remove(&options.key(&entry.path)) else {
+                continue;
+            };
+
+            let source = folder.join(path);
+            entry.update(UpdateKind::File(source.clone()));
+            report.matched.push((entry.path, source));
+        }
+
+        report.unmatched = index.into_values().collect();
+        report.unmatched.sort();
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{for_each_fixture, rebuild, reload};
+
+    #[test]
+    fn apply_folder_to_fixtures() {
+        for_each_fixture(|fixture, provider| {
+            let mut archive = Archive::new(provider);
+            let path = archive.files().last().unwrap().path;
+
+            let folder = std::env::temp_dir().join(format!("hvp_apply_{}", fixture.name));
+            let _ = std::fs::remove_dir_all(&folder);
+            std::fs::create_dir_all(folder.join(path.parent().unwrap())).unwrap();
+            std::fs::write(folder.join(&path), b"new content").unwrap();
+            std::fs::write(folder.join("not_in_archive.bin"), b"extra").unwrap();
+
+            let report = archive
+                .apply_folder(&folder, MatchOptions::default())
+                .expect("failed to apply folder");
+            assert_eq!(report.matched, [(path.clone(), folder.join(&path))]);
+            assert_eq!(report.unmatched, [PathBuf::from("not_in_archive.bin")]);
+
+            let rebuilt_provider = reload(rebuild(&archive));
+            let rebuilt = Archive::new(&rebuilt_provider);
+            let file = rebuilt.find_file(&path).unwrap();
+            assert_eq!(&*file.get_bytes().unwrap(), b"new content");
+
+            let _ = std::fs::remove_dir_all(&folder);
+
+            // the separators are normalized and the case only with `ignore_case`
+            let odd = PathBuf::from(path.to_string_lossy().to_uppercase().replace('/', "\\"));
+            let report = archive.apply_paths("", [odd.clone()], MatchOptions::default());
+            assert_eq!(report.unmatched, std::slice::from_ref(&odd));
+
+            let duplicate = PathBuf::from(path.to_string_lossy().to_uppercase());
+            let report = archive.apply_paths(
+                "",
+                [odd.clone(), duplicate.clone()],
+                MatchOptions { ignore_case: true },
+            );
+            assert_eq!(report.matched, [(path.clone(), odd)]);
+            assert_eq!(report.conflicts, [duplicate]);
+            assert!(report.unmatched.is_empty());
+        });
+    }
+}
@@ -0,0 +1,1131 @@
+//! build new archives from scratch, without an original archive as template
+
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fs, io,
+    io::{Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+use binrw::{BinWrite, Endian};
+
+use super::compress::Compressor;
+use super::compression_rules::CompressionRules;
+use super::entry::{CompressionInfo, CompressionType, DirEntry, Entry, FileEntry, UpdateKind};
+use super::error::RebuildError;
+use super::rebuild_progress::RebuildProgress;
+use super::{
+    Archive, Endianness, Metadata, Obscure2NameMap, final_exam, layout, obscure1, obscure2,
+};
+use crate::Game;
+use crate::provider::{self, RawArchive};
+use crate::structures;
+
+/// errors that can happen when building a new archive
+#[derive(Debug, thiserror::Error)]
+pub enum BuildError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    BinRW(#[from] binrw::Error),
+    #[error(transparent)]
+    Rebuild(#[from] RebuildError),
+    #[error("archive doesn't have any file")]
+    EmptyArchive,
+    #[error("invalid path inside the archive: {0}")]
+    InvalidPath(PathBuf),
+    #[error("{0} conflict with an existing file or directory")]
+    PathConflict(PathBuf),
+    #[error("{0} doesn't exist in the archive")]
+    NotFound(PathBuf),
+    #[error("{0:?} can't be used as a name in this archive")]
+    InvalidName(String),
+    #[error("{0} and {1} would have the same name or name crc32")]
+    NameCollision(PathBuf, PathBuf),
+    #[error("{0} is in the archive more than once")]
+    DuplicateEntry(PathBuf),
+}
+
+/// a file or directory inside the builder
+#[derive(Debug)]
+enum Node<'p> {
+    File(FileData<'p>),
+    Dir(Dir<'p>),
+}
+
+/// children of a directory, in the order that they are written to the archive
+#[derive(Debug, Default)]
+struct Dir<'p> {
+    nodes: Vec<(String, Node<'p>)>,
+    /// position of each name in `nodes`
+    positions: ahash::HashMap<String, usize>,
+}
+
+impl<'p> Dir<'p> {
+    fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&str, &Node<'p>)> {
+        self.nodes.iter().map(|(name, node)| (name.as_str(), node))
+    }
+
+    fn values(&self) -> impl Iterator<Item = &Node<'p>> {
+        self.nodes.iter().map(|(_, node)| node)
+    }
+
+    fn get(&self, name: &str) -> Option<&Node<'p>> {
+        self.positions.get(name).map(|&idx| &self.nodes[idx].1)
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut Node<'p>> {
+        self.positions.get(name).map(|&idx| &mut self.nodes[idx].1)
+    }
+
+    /// add the node after the other children, return `false` if the name is already used
+    fn push(&mut self, name: String, node: Node<'p>) -> bool {
+        if self.positions.contains_key(&name) {
+            return false;
+        }
+
+        self.positions.insert(name.clone(), self.nodes.len());
+        self.nodes.push((name, node));
+        true
+    }
+
+    /// replace the node with the same name, or add it in front of the first child that the
+    /// original archives sort after it (by uppercase name), so the order of the other children
+    /// is kept even if it isn't the sorted one
+    fn insert(&mut self, name: String, node: Node<'p>) {
+        if let Some(old) = self.get_mut(&name) {
+            *old = node;
+            return;
+        }
+
+        let idx = self
+            .nodes
+            .iter()
+            .position(|(other, _)| sort_order(other, &name).is_gt())
+            .unwrap_or(self.nodes.len());
+        for position in self.positions.values_mut().filter(|p| **p >= idx) {
+            *position += 1;
+        }
+        self.positions.insert(name.clone(), idx);
+        self.nodes.insert(idx, (name, node));
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Node<'p>> {
+        let idx = self.positions.remove(name)?;
+        for position in self.positions.values_mut().filter(|p| **p > idx) {
+            *position -= 1;
+        }
+        Some(self.nodes.remove(idx).1)
+    }
+}
+
+impl<'p> IntoIterator for Dir<'p> {
+    type Item = (String, Node<'p>);
+    type IntoIter = std::vec::IntoIter<(String, Node<'p>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.into_iter()
+    }
+}
+
+/// data of a file inside the builder
+#[derive(Debug)]
+enum FileData<'p> {
+    /// a new file, `compress` is false if it should be stored without compression
+    New { data: UpdateKind, compress: bool },
+    /// a file of an existing archive, its raw bytes are copied as is unless it's updated
+    Existing(FileEntry<'p>),
+}
+
+/// the fields of a raw file entry before writing the data
+struct RawFile {
+    uncompressed_size: u32,
+    compressed_size: u32,
+    checksum: i32,
+    is_compressed: bool,
+}
+
+/// ## build a new archive from scratch
+///
+/// files can be added one by one or from a directory on disk. new entries are sorted the same way
+/// that the original archives are, and headers, entry tables, names and checksums are all generated.
+///
+/// obscure 2 only store the crc32 of the names, so the name maps are still needed to read the
+/// names back from the built archive. names of unknown entries (`unk_file_{crc32}.dat` and
+/// `unk_folder_{crc32}`) are stored using their original crc32.
+#[derive(Debug)]
+pub struct ArchiveBuilder<'p> {
+    game: Game,
+    endian: Endian,
+    compression: Compressor,
+    compression_rules: CompressionRules,
+    alignment: Option<u32>,
+    root: Dir<'p>,
+}
+
+impl<'p> ArchiveBuilder<'p> {
+    /// create a new empty builder for the given game
+    pub fn new(game: Game) -> Self {
+        Self {
+            game,
+            endian: Endian::Little,
+            compression: Compressor::default(),
+            compression_rules: CompressionRules::new(),
+            alignment: None,
+            root: Dir::default(),
+        }
+    }
+
+    /// create a builder that contain all the files of the archive, so new files can be added to it.
+    /// the data of the files is copied as is (or replaced if they are updated) and the TOC is generated again.
+    ///
+    /// the entries keep the order of the archive (see [`Archive::reorder`]), and the new ones are
+    /// placed where the original archives would sort them. fail if a directory has two entries
+    /// with the same name.
+    pub fn from_archive(archive: &Archive<'p>) -> Result<Self, BuildError> {
+        fn add<'p>(path: &Path, entries: &[Entry<'p>]) -> Result<Dir<'p>, BuildError> {
+            let mut dir = Dir::default();
+            for entry in entries {
+                let node = match entry {
+                    Entry::File(file) => Node::File(FileData::Existing(file.clone())),
+                    Entry::Dir(child) => Node::Dir(add(&path.join(&child.name), &child.entries)?),
+                };
+
+                if !dir.push(entry.name().to_owned(), node) {
+                    return Err(BuildError::DuplicateEntry(path.join(entry.name())));
+                }
+            }
+
+            Ok(dir)
+        }
+
+        if !archive.provider.has_data() {
+            return Err(RebuildError::NoData.into());
+        }
+
+        let endian = match &archive.provider.raw_archive {
+            RawArchive::Obscure1(_) => Endian::Little,
+            RawArchive::Obscure2(hvp) => hvp.endian(),
+            RawArchive::FinalExam(hvp) => hvp.endian(),
+        };
+
+        let mut builder = Self::new(archive.metadata().game);
+        builder.endian = endian;
+        builder.compression = archive.options.compressor();
+        builder.compression_rules = CompressionRules::from_archive(archive);
+        builder.alignment = archive.options.alignment;
+
+        builder.root = add(Path::new(""), archive.entries())?;
+
+        Ok(builder)
+    }
+
+    /// build a big endian archive (like the wii version of obscure 2), ignored for obscure 1
+    pub fn big_endian(mut self, big_endian: bool) -> Self {
+        self.endian = match big_endian {
+            true => Endian::Big,
+            false => Endian::Little,
+        };
+        self
+    }
+
+    /// store the files without compression
+    pub fn skip_compression(mut self, skip_compression: bool) -> Self {
+        self.compression.skip = skip_compression;
+        self
+    }
+
+    /// compression level of the files, see [`super::Options::compression_level`]
+    pub fn compression_level(mut self, level: Option<u8>) -> Self {
+        self.compression.level = level;
+        self
+    }
+
+    /// rules that decide which of the files that are added after this are compressed, the
+    /// builders that are created with [`ArchiveBuilder::from_archive`] start with the rules of
+    /// the archive (see [`CompressionRules::from_archive`]) and these are added on top of them
+    pub fn compression_rules(mut self, rules: CompressionRules) -> Self {
+        self.compression_rules.extend(rules);
+        self
+    }
+
+    /// alignment of the file data, see [`super::Options::alignment`]
+    pub fn alignment(mut self, alignment: Option<u32>) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    /// add a file to the given path inside the archive, adding a file to the same path again replace it.
+    /// the file is compressed unless the compression rules store the files of its directory
+    pub fn add_file(&mut self, path: impl AsRef<Path>, data: UpdateKind) -> Result<(), BuildError> {
+        let path = path.as_ref();
+        let compress = self.compression_rules.compress(path).unwrap_or(true);
+        self.insert(path, FileData::New { data, compress })
+    }
+
+    /// same as [`ArchiveBuilder::add_file`], but the file is always stored without compression
+    pub fn add_file_uncompressed(
+        &mut self,
+        path: impl AsRef<Path>,
+        data: UpdateKind,
+    ) -> Result<(), BuildError> {
+        self.insert(
+            path.as_ref(),
+            FileData::New {
+                data,
+                compress: false,
+            },
+        )
+    }
+
+    fn insert(&mut self, path: &Path, data: FileData<'p>) -> Result<(), BuildError> {
+        let names = self.path_names(path)?;
+        self.insert_node(path, &names, Node::File(data))
+    }
+
+    /// split the path to its names, and make sure that they can be stored in the archive
+    fn path_names<'a>(&self, path: &'a Path) -> Result<Vec<&'a str>, BuildError> {
+        let mut names = Vec::new();
+        for component in path.components() {
+            let Component::Normal(name) = component else {
+                return Err(BuildError::InvalidPath(path.to_path_buf()));
+            };
+            let name = name
+                .to_str()
+                .ok_or_else(|| BuildError::InvalidPath(path.to_path_buf()))?;
+
+            if self.game == Game::Obscure2 && obscure2::build_name_crc32(name).is_none() {
+                return Err(BuildError::InvalidName(name.to_owned()));
+            }
+
+            names.push(name);
+        }
+
+        match names.is_empty() {
+            true => Err(BuildError::InvalidPath(path.to_path_buf())),
+            false => Ok(names),
+        }
+    }
+
+    /// insert the node, missing parent directories are created and a file replace the old file
+    fn insert_node(
+        &mut self,
+        path: &Path,
+        names: &[&str],
+        node: Node<'p>,
+    ) -> Result<(), BuildError> {
+        let Some((name, dirs)) = names.split_last() else {
+            return Err(BuildError::InvalidPath(path.to_path_buf()));
+        };
+
+        let mut current = &mut self.root;
+        let mut current_path = PathBuf::new();
+        for dir in dirs {
+            current_path.push(dir);
+            if current.get(dir).is_none() {
+                current.insert(dir.to_string(), Node::Dir(Dir::default()));
+            }
+            match current.get_mut(dir).expect("directory is inserted") {
+                Node::Dir(dir) => current = dir,
+                Node::File(_) => return Err(BuildError::PathConflict(current_path)),
+            }
+        }
+
+        match (current.get(name), &node) {
+            (Some(Node::Dir(_)), _) | (Some(Node::File(_)), Node::Dir(_)) => {
+                return Err(BuildError::PathConflict(path.to_path_buf()));
+            }
+            _ => (),
+        }
+
+        current.insert(name.to_string(), node);
+
+        Ok(())
+    }
+
+    fn get(&self, names: &[&str]) -> Option<&Node<'p>> {
+        let (name, dirs) = names.split_last()?;
+
+        let mut current = &self.root;
+        for dir in dirs {
+            match current.get(dir)? {
+                Node::Dir(dir) => current = dir,
+                Node::File(_) => return None,
+            }
+        }
+
+        current.get(name)
+    }
+
+    /// take the node out of the builder, directories that become empty are removed as well
+    fn take(&mut self, names: &[&str]) -> Option<Node<'p>> {
+        fn take<'p>(dir: &mut Dir<'p>, names: &[&str]) -> Option<Node<'p>> {
+            let (name, rest) = names.split_first()?;
+
+            if rest.is_empty() {
+                return dir.remove(name);
+            }
+
+            let Node::Dir(child) = dir.get_mut(name)? else {
+                return None;
+            };
+
+            let node = take(child, rest);
+            if child.is_empty() {
+                dir.remove(name);
+            }
+            node
+        }
+
+        take(&mut self.root, names)
+    }
+
+    /// remove a file or a whole directory from the given path, directories that become empty are
+    /// removed as well. return `false` if nothing exist in the path
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> bool {
+        let names: Option<Vec<&str>> = path
+            .as_ref()
+            .components()
+            .map(|c| match c {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+
+        names.is_some_and(|names| self.take(&names).is_some())
+    }
+
+    /// move a file or a whole directory to a new path, nothing should exist in the new path.
+    /// the names, entry sizes and name crc32s are all generated again when building
+    pub fn rename(
+        &mut self,
+        from: impl AsRef<Path>,
+        to: impl AsRef<Path>,
+    ) -> Result<(), BuildError> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let from_names = self.path_names(from)?;
+        let to_names = self.path_names(to)?;
+
+        if self.get(&from_names).is_none() {
+            return Err(BuildError::NotFound(from.to_path_buf()));
+        }
+
+        // a directory can't be moved inside itself
+        if to_names.starts_with(&from_names) {
+            return Err(BuildError::InvalidPath(to.to_path_buf()));
+        }
+
+        // check everything before taking the node, so nothing is lost in case of an error
+        for end in 1..=to_names.len() {
+            match self.get(&to_names[..end]) {
+                Some(Node::File(_)) => {
+                    return Err(BuildError::PathConflict(to_names[..end].iter().collect()));
+                }
+                Some(Node::Dir(_)) if end == to_names.len() => {
+                    return Err(BuildError::PathConflict(to.to_path_buf()));
+                }
+                _ => (),
+            }
+        }
+
+        let node = self.take(&from_names).expect("node existence is checked");
+        self.insert_node(to, &to_names, node)
+    }
+
+    /// crc32 of the name that the archive store, obscure 1 doesn't store any so the crc32 of the
+    /// name is used like final exam
+    fn name_crc32(&self, name: &str) -> Option<u32> {
+        match self.game {
+            Game::Obscure2 => obscure2::build_name_crc32(name),
+            Game::Obscure1 | Game::FinalExam => Some(crc32fast::hash(name.as_bytes())),
+        }
+    }
+
+    /// rename every file and directory whose name crc32 is in `names`, the crc32 is the one that
+    /// the archive store for the name (see [`Obscure2NameMap::stored_crc32`] for obscure 2), and
+    /// the plain crc32 of the name for the other games.
+    ///
+    /// return the old and new paths of the renamed entries, parents come before their children.
+    /// nothing is renamed if two entries of a directory would end with the same name or name crc32
+    pub fn rename_by_crc32(
+        &mut self,
+        names: &ahash::HashMap<u32, String>,
+    ) -> Result<Vec<(PathBuf, PathBuf)>, BuildError> {
+        fn plan(
+            builder: &ArchiveBuilder,
+            dir: &Dir,
+            (old_parent, new_parent): (&Path, &Path),
+            names: &ahash::HashMap<u32, String>,
+            renamed: &mut Vec<(PathBuf, PathBuf)>,
+        ) -> Result<(), BuildError> {
+            let mut seen_names = ahash::HashMap::<String, PathBuf>::default();
+            let mut seen_crc32s = ahash::HashMap::<u32, PathBuf>::default();
+
+            for (name, node) in dir.iter() {
+                let new_name = builder.new_name(name, names)?;
+                let old_path = old_parent.join(name);
+                let new_path = new_parent.join(new_name);
+
+                // obscure 1 doesn't store the crc32, and the names of the others are compared as is
+                if let Some(other) = seen_names.insert(new_name.to_owned(), old_path.clone()) {
+                    return Err(BuildError::NameCollision(other, old_path));
+                }
+                if builder.game != Game::Obscure1 {
+                    let crc32 = builder.name_crc32(new_name).expect("name is validated");
+                    if let Some(other) = seen_crc32s.insert(crc32, old_path.clone()) {
+                        return Err(BuildError::NameCollision(other, old_path));
+                    }
+                }
+
+                if new_name != name {
+                    renamed.push((old_path.clone(), new_path.clone()));
+                }
+
+                if let Node::Dir(children) = node {
+                    plan(builder, children, (&old_path, &new_path), names, renamed)?;
+                }
+            }
+
+            Ok(())
+        }
+
+        fn apply<'p>(
+            builder: &ArchiveBuilder,
+            dir: Dir<'p>,
+            names: &ahash::HashMap<u32, String>,
+        ) -> Dir<'p> {
+            // the renamed entries keep their place
+            let mut renamed = Dir::default();
+            for (name, node) in dir {
+                let new_name = builder
+                    .new_name(&name, names)
+                    .expect("names are validated")
+                    .to_owned();
+                let node = match node {
+                    Node::Dir(children) => Node::Dir(apply(builder, children, names)),
+                    file => file,
+                };
+                assert!(renamed.push(new_name, node), "names are validated");
+            }
+            renamed
+        }
+
+        // check everything before renaming, so nothing is changed in case of an error
+        let mut renamed = Vec::new();
+        plan(
+            self,
+            &self.root,
+            (Path::new(""), Path::new("")),
+            names,
+            &mut renamed,
+        )?;
+
+        let root = std::mem::take(&mut self.root);
+        self.root = apply(self, root, names);
+
+        Ok(renamed)
+    }
+
+    /// name of the entry after renaming it with [`ArchiveBuilder::rename_by_crc32`]
+    fn new_name<'a>(
+        &self,
+        name: &'a str,
+        names: &'a ahash::HashMap<u32, String>,
+    ) -> Result<&'a str, BuildError> {
+        let Some(new_name) = self.name_crc32(name).and_then(|crc32| names.get(&crc32)) else {
+            return Ok(name);
+        };
+
+        let valid = !new_name.is_empty()
+            && !new_name.contains(['/', '\\'])
+            && new_name != "."
+            && new_name != ".."
+            && self.name_crc32(new_name).is_some();
+
+        match valid {
+            true => Ok(new_name),
+            false => Err(BuildError::InvalidName(new_name.clone())),
+        }
+    }
+
+    /// add all the files inside the directory (recursively), paths inside the archive are relative to the directory
+    pub fn add_dir_all(&mut self, dir: impl AsRef<Path>) -> Result<(), BuildError> {
+        fn add(builder: &mut ArchiveBuilder, base: &Path, dir: &Path) -> Result<(), BuildError> {
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    add(builder, base, &path)?;
+                } else {
+                    let relative = path
+                        .strip_prefix(base)
+                        .expect("path is inside the base dir");
+                    builder.add_file(relative, UpdateKind::File(path.clone()))?;
+                }
+            }
+
+            Ok(())
+        }
+
+        let dir = dir.as_ref();
+        add(self, dir, dir)
+    }
+
+    /// metadata of the archive that will be built, the sizes are only known
+    /// after [`ArchiveBuilder::build`]
+    pub fn metadata(&self) -> Metadata {
+        fn count(dir: &Dir, metadata: &mut Metadata) {
+            for node in dir.values() {
+                match node {
+                    Node::File(_) => metadata.file_count += 1,
+                    Node::Dir(dir) => {
+                        metadata.dir_count += 1;
+                        count(dir, metadata);
+                    }
+                }
+            }
+        }
+
+        let endianness = match self.game {
+            Game::Obscure1 => Endianness::Big,
+            Game::Obscure2 | Game::FinalExam => self.endian.into(),
+        };
+
+        let mut metadata = Metadata::new(self.game, endianness);
+        count(&self.root, &mut metadata);
+        metadata
+    }
+
+    /// build the archive and write it to the given writer.
+    pub fn build<W: Write + Seek, P: RebuildProgress>(
+        &self,
+        writer: &mut W,
+        progress: P,
+    ) -> Result<Metadata, BuildError> {
+        if self.root.is_empty() {
+            return Err(BuildError::EmptyArchive);
+        }
+
+        progress.start(self.estimated_data_size().ok());
+
+        let start = writer.stream_position()?;
+        let (toc, raw_archive, end) = match self.game {
+            Game::Obscure1 => self.build_obscure1(writer, progress)?,
+            Game::Obscure2 => self.build_obscure2(writer, progress)?,
+            Game::FinalExam => self.build_final_exam(writer, progress)?,
+        };
+        writer.seek(SeekFrom::Start(end))?;
+
+        let mut metadata = self.metadata();
+        metadata.toc_size = toc.end - toc.start;
+        metadata.data_size = provider::data_end(&raw_archive).saturating_sub(toc.end);
+        metadata.archive_len = end - start;
+        Ok(metadata)
+    }
+
+    fn build_obscure1<W: Write + Seek, P: RebuildProgress>(
+        &self,
+        writer: &mut W,
+        progress: P,
+    ) -> Result<(Range<u64>, RawArchive, u64), BuildError> {
+        fn raw_entries(
+            builder: &ArchiveBuilder,
+            dir: &Dir,
+        ) -> io::Result<Vec<structures::obscure1::Entry>> {
+            dir.iter()
+                .map(|(name, node)| {
+                    let kind = match node {
+                        Node::File(data) => {
+                            let file = builder.raw_file(data)?;
+                            structures::obscure1::EntryKind::File(structures::obscure1::FileEntry {
+                                is_compressed: file.is_compressed,
+                                compressed_size: file.compressed_size,
+                                uncompressed_size: file.uncompressed_size,
+                                checksum: file.checksum,
+                                offset: 0,
+                                name: name.to_owned(),
+                            })
+                        }
+                        Node::Dir(dir) => structures::obscure1::EntryKind::Dir(
+                            structures::obscure1::DirEntry::new(
+                                name.to_owned(),
+                                raw_entries(builder, dir)?,
+                            ),
+                        ),
+                    };
+
+                    Ok(structures::obscure1::Entry::new(kind))
+                })
+                .collect()
+        }
+
+        let raw_entries = raw_entries(self, &self.root)?;
+        let entries = self.entries(&self.root, CompressionType::Zlib, self.files_endian())?;
+
+        let mut archive = structures::obscure1::HvpArchive {
+            header: structures::obscure1::Header::new(),
+            checksums: Some(structures::obscure1::Crc32 {
+                header: 0,
+                entries: 0,
+            }),
+            entries: raw_entries,
+        };
+
+        let toc_len = layout::plan_obscure1(&mut archive)?;
+        let toc = layout::skip_toc(writer, toc_len)?;
+        let offset = writer.stream_position()? as _;
+
+        let archive = obscure1::update_entries(
+            writer,
+            offset,
+            self.compression,
+            self.alignment,
+            archive,
+            &entries,
+            progress,
+        )?;
+        let end = writer.stream_position()?;
+
+        writer.seek(SeekFrom::Start(toc.start))?;
+        archive.write_be(writer)?;
+
+        Ok((toc, RawArchive::Obscure1(archive), end))
+    }
+
+    fn build_obscure2<W: Write + Seek, P: RebuildProgress>(
+        &self,
+        writer: &mut W,
+        progress: P,
+    ) -> Result<(Range<u64>, RawArchive, u64), BuildError> {
+        let table = flatten(&self.root);
+
+        let raw_entries = table
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                // root entry doesn't have any name
+                let name_crc32 = match idx {
+                    0 => 0,
+                    _ => obscure2::build_name_crc32(entry.name)
+                        .ok_or_else(|| BuildError::InvalidName(entry.name.to_owned()))?,
+                };
+
+                let kind = match entry.kind {
+                    FlatKind::File(data) => {
+                        let raw = self.raw_file(data)?;
+                        let file = structures::obscure2::FileEntry::new(
+                            raw.checksum,
+                            raw.uncompressed_size,
+                            0,
+                            raw.compressed_size,
+                        );
+                        match raw.is_compressed {
+                            true => structures::obscure2::EntryKind::FileCompressed(file),
+                            false => structures::obscure2::EntryKind::File(file),
+                        }
+                    }
+                    FlatKind::Dir { count, index } => structures::obscure2::EntryKind::Directory(
+                        structures::obscure2::DirEntry::new(count, index),
+                    ),
+                };
+
+                Ok(structures::obscure2::Entry { name_crc32, kind })
+            })
+            .collect::<Result<Vec<_>, BuildError>>()?;
+
+        let entries = self.entries(&self.root, CompressionType::Lzo, self.files_endian())?;
+        let mut archive = structures::obscure2::HvpArchive {
+            header: structures::obscure2::Header::new(self.endian),
+            entries: raw_entries,
+        };
+
+        // only used to show the names in the progress
+        let name_map = Obscure2NameMap::new(table.iter().skip(1).map(|e| e.name));
+
+        let toc_len = layout::plan_obscure2(&mut archive)?;
+        let toc = layout::skip_toc(writer, toc_len)?;
+        let offset = writer.stream_position()? as _;
+
+        let archive = obscure2::update_entries(
+            writer,
+            offset,
+            self.compression,
+            self.alignment,
+            archive,
+            &entries,
+            &name_map,
+            progress,
+        )?;
+        let end = writer.stream_position()?;
+
+        writer.seek(SeekFrom::Start(toc.start))?;
+        archive.write(writer)?;
+
+        Ok((toc, RawArchive::Obscure2(archive), end))
+    }
+
+    fn build_final_exam<W: Write + Seek, P: RebuildProgress>(
+        &self,
+        writer: &mut W,
+        progress: P,
+    ) -> Result<(Range<u64>, RawArchive, u64), BuildError> {
+        // names are stored once, in the order that they are first seen in a depth first walk
+        fn collect_names<'a>(
+            dir: &'a Dir,
+            bytes: &mut Vec<u8>,
+            offsets: &mut BTreeMap<&'a str, u32>,
+        ) {
+            for (name, node) in dir.iter() {
+                offsets.entry(name).or_insert_with(|| {
+                    let offset = bytes.len() as u32;
+                    bytes.extend_from_slice(name.as_bytes());
+                    bytes.push(0);
+                    offset
+                });
+
+                if let Node::Dir(dir) = node {
+                    collect_names(dir, bytes, offsets);
+                }
+            }
+        }
+
+        let mut names = Vec::new();
+        let mut name_offsets = BTreeMap::new();
+        collect_names(&self.root, &mut names, &mut name_offsets);
+        names.resize(names.len().next_multiple_of(4), 0);
+
+        let table = flatten(&self.root);
+
+        let raw_entries = table
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                // root entry doesn't have any name
+                let (name_crc32, name_offset) = match idx {
+                    0 => (0, 0),
+                    _ => (
+                        self.name_crc32(entry.name).expect("any name has a crc32"),
+                        name_offsets[entry.name],
+                    ),
+                };
+
+                let kind = match entry.kind {
+                    FlatKind::File(data) => {
+                        let raw = self.raw_file(data)?;
+                        let file = structures::final_exam::FileEntry {
+                            checksum: raw.checksum,
+                            uncompressed_size: raw.uncompressed_size,
+                            name_offset,
+                            offset: 0,
+                            compressed_size: raw.compressed_size,
+                        };
+                        match raw.is_compressed {
+                            true => structures::final_exam::EntryKind::FileCompressed(file),
+                            false => structures::final_exam::EntryKind::File(file),
+                        }
+                    }
+                    FlatKind::Dir { count, index } => structures::final_exam::EntryKind::Directory(
+                        structures::final_exam::DirEntry::new(name_offset, count, index),
+                    ),
+                };
+
+                Ok(structures::final_exam::Entry { name_crc32, kind })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        let entries = self.entries(&self.root, CompressionType::Lzo, self.files_endian())?;
+        let mut archive = structures::final_exam::HvpArchive {
+            header: structures::final_exam::Header::new(self.endian),
+            names: structures::final_exam::Names::new(names),
+            entries: raw_entries,
+        };
+
+        let toc_len = layout::plan_final_exam(&mut archive)?;
+        let toc = layout::skip_toc(writer, toc_len)?;
+        let offset = writer.stream_position()? as _;
+
+        let archive = final_exam::update_entries(
+            writer,
+            offset,
+            self.compression,
+            self.alignment,
+            archive.clone(),
+            &entries,
+            &archive.names,
+            progress,
+        )?;
+        let end = writer.stream_position()?;
+
+        writer.seek(SeekFrom::Start(toc.start))?;
+        archive.write(writer)?;
+
+        Ok((toc, RawArchive::FinalExam(archive), end))
+    }
+
+    /// create the mapped entries, all of them are marked as updated so the rebuild write their data
+    fn entries(
+        &self,
+        dir: &Dir<'p>,
+        compression_type: CompressionType,
+        endian: Endian,
+    ) -> io::Result<Vec<Entry<'p>>> {
+        dir.iter()
+            .map(|(name, node)| match node {
+                Node::File(FileData::Existing(file)) => Ok(Entry::File(FileEntry {
+                    // the file may be renamed, the updaters match the entries by their name
+                    name: name.to_owned(),
+                    checksum: existing_checksum(file, endian),
+                    endian,
+                    ..file.clone()
+                })),
+                Node::File(FileData::New { data, compress }) => {
+                    let size = data_size(data)?;
+                    Ok(Entry::File(FileEntry {
+                        name: name.to_owned(),
+                        compression_info: (*compress && !self.compression.skip && size > 0)
+                            .then_some(CompressionInfo {
+                                uncompressed_size: size,
+                                compression_type,
+                            }),
+                        checksum: 0,
+                        endian,
+                        raw_bytes: &[],
+                        update: Some(data.clone()),
+                        user_data: None,
+                    }))
+                }
+                Node::Dir(dir) => Ok(Entry::Dir(DirEntry {
+                    name: name.to_owned(),
+                    entries: self.entries(dir, compression_type, endian)?,
+                    user_data: None,
+                })),
+            })
+            .collect()
+    }
+}
+
+impl ArchiveBuilder<'_> {
+    /// endian used for the checksums of the files, obscure 1 is always little endian
+    fn files_endian(&self) -> Endian {
+        match self.game {
+            Game::Obscure1 => Endian::Little,
+            Game::Obscure2 | Game::FinalExam => self.endian,
+        }
+    }
+
+    /// number of data bytes that [`ArchiveBuilder::build`] write, new and updated files are counted
+    /// with their uncompressed size, so it's an upper bound when they are compressed
+    fn estimated_data_size(&self) -> io::Result<u64> {
+        fn size(builder: &ArchiveBuilder, dir: &Dir) -> io::Result<u64> {
+            dir.values().try_fold(0, |total, node| {
+                let node_size = match node {
+                    Node::File(data) => {
+                        let file = builder.raw_file(data)?;
+                        match file.compressed_size {
+                            0 => file.uncompressed_size as u64,
+                            size => size as u64,
+                        }
+                    }
+                    Node::Dir(dir) => size(builder, dir)?,
+                };
+                Ok(total + node_size)
+            })
+        }
+
+        size(self, &self.root)
+    }
+
+    /// fields of the raw entry of a file, the updaters fill the rest after writing the data
+    fn raw_file(&self, data: &FileData) -> io::Result<RawFile> {
+        match data {
+            FileData::New { data, compress } => {
+                let size = data_size(data)?;
+                Ok(RawFile {
+                    uncompressed_size: size,
+                    compressed_size: 0,
+                    checksum: 0,
+                    is_compressed: *compress && !self.compression.skip && size > 0,
+                })
+            }
+            // the checksum of the raw entry must match the mapped entry even if it's updated
+            FileData::Existing(file) => match &file.update {
+                Some(update) => Ok(RawFile {
+                    uncompressed_size: data_size(update)?,
+                    compressed_size: 0,
+                    checksum: file.checksum,
+                    is_compressed: !self.compression.skip && file.is_compressed(),
+                }),
+                None => Ok(RawFile {
+                    uncompressed_size: file
+                        .compression_info
+                        .map_or(file.raw_bytes.len() as _, |info| info.uncompressed_size),
+                    compressed_size: file.raw_bytes.len() as _,
+                    checksum: existing_checksum(file, self.files_endian()),
+                    is_compressed: file.is_compressed(),
+                }),
+            },
+        }
+    }
+}
+
+/// checksum of a file that is copied as is, the checksum is a sum of words so it
+/// must be calculated again when the endian of the archive changes
+fn existing_checksum(file: &FileEntry, endian: Endian) -> i32 {
+    match file.endian == endian {
+        true => file.checksum,
+        false => structures::checksum::bytes_sum(file.raw_bytes, endian),
+    }
+}
+
+/// order of the names in the original archives, by uppercase name
+fn sort_order(a: &str, b: &str) -> Ordering {
+    a.bytes()
+        .map(|c| c.to_ascii_uppercase())
+        .cmp(b.bytes().map(|c| c.to_ascii_uppercase()))
+        .then_with(|| a.cmp(b))
+}
+
+fn data_size(data: &UpdateKind) -> io::Result<u32> {
+    let size = match data {
+        UpdateKind::Bytes(bytes) => bytes.len() as u64,
+        UpdateKind::File(path) => fs::metadata(path)?.len(),
+    };
+
+    u32::try_from(size).map_err(|_| io::Error::other("file is too large to be stored in a hvp"))
+}
+
+/// a entry of the flat entry table that obscure 2 and final exam use
+struct FlatEntry<'a> {
+    name: &'a str,
+    kind: FlatKind<'a>,
+}
+
+enum FlatKind<'a> {
+    File(&'a FileData<'a>),
+    Dir { count: u32, index: u32 },
+}
+
+/// create the flat entry table, root directory is the first entry and children of each
+/// directory are placed together, the blocks are allocated in a depth first order
+fn flatten<'a>(root: &'a Dir) -> Vec<FlatEntry<'a>> {
+    fn allocate<'a>(table: &mut Vec<FlatEntry<'a>>, dir: &'a Dir) -> u32 {
+        let start = table.len();
+        let children: Vec<_> = dir.iter().collect();
+
+        table.extend(children.iter().map(|(name, node)| FlatEntry {
+            name,
+            kind: match node {
+                Node::File(data) => FlatKind::File(data),
+                Node::Dir(dir) => FlatKind::Dir {
+                    count: dir.len() as _,
+                    index: 0,
+                },
+            },
+        }));
+
+        for (idx, (_, node)) in children.iter().enumerate() {
+            if let Node::Dir(dir) = node {
+                let dir_index = allocate(table, dir);
+                if let FlatKind::Dir { index, .. } = &mut table[start + idx].kind {
+                    *index = dir_index;
+                }
+            }
+        }
+
+        start as _
+    }
+
+    let mut table = vec![FlatEntry {
+        name: "",
+        kind: FlatKind::Dir {
+            count: root.len() as _,
+            index: 1,
+        },
+    }];
+    allocate(&mut table, root);
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::test_utils::{EmptyProgress, for_each_fixture, reload};
+
+    fn names(entries: &[Entry]) -> Vec<String> {
+        entries.iter().map(|e| e.name().to_owned()).collect()
+    }
+
+    #[test]
+    fn from_archive_keep_custom_order() {
+        for_each_fixture(|_, provider| {
+            let mut archive = Archive::new(provider);
+            let (dir, original) = archive
+                .entries()
+                .iter()
+                .find_map(|e| match e {
+                    Entry::Dir(dir) if dir.entries.len() > 1 => {
+                        Some((dir.name.clone(), names(&dir.entries)))
+                    }
+                    _ => None,
+                })
+                .expect("fixture doesn't have a directory with multiple entries");
+
+            let reversed: Vec<_> = original.iter().rev().cloned().collect();
+            archive
+                .reorder(&dir, &reversed)
+                .expect("failed to reorder directory");
+
+            let mut builder = ArchiveBuilder::from_archive(&archive).unwrap();
+            builder
+                .add_file(
+                    Path::new(&dir).join("new_file.bin"),
+                    UpdateKind::Bytes(b"new file".to_vec()),
+                )
+                .unwrap();
+            let mut writer = Cursor::new(Vec::new());
+            builder.build(&mut writer, EmptyProgress).unwrap();
+
+            let provider = reload(writer.into_inner());
+            let built = Archive::new(&provider);
+            let Some(Entry::Dir(built_dir)) = built.entries().iter().find(|e| e.name() == dir)
+            else {
+                panic!("directory is missing");
+            };
+
+            let built_names = names(&built_dir.entries);
+            assert_eq!(built_names.len(), reversed.len() + 1);
+            let kept: Vec<_> = built_names
+                .into_iter()
+                .filter(|n| reversed.contains(n))
+                .collect();
+            assert_eq!(kept, reversed);
+        });
+    }
+
+    #[test]
+    fn from_archive_reject_duplicate_names() {
+        for_each_fixture(|_, provider| {
+            let mut archive = Archive::new(provider);
+            let first = archive.entries()[0].clone();
+            let name = first.name().to_owned();
+            archive.entries = archive.entries.iter().cloned().chain([first]).collect();
+
+            assert!(matches!(
+                ArchiveBuilder::from_archive(&archive),
+                Err(BuildError::DuplicateEntry(path)) if path == Path::new(&name)
+            ));
+        });
+    }
+}
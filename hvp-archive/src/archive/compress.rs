@@ -0,0 +1,89 @@
+//! compression of the updated files
+
+use flate2::{Compress, Compression, FlushCompress};
+
+/// how the updated files are compressed when the archive is rebuilt
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Compressor {
+    /// store the updated files without compression
+    pub skip: bool,
+    /// zlib or lzo level, `None` for the level that the game archives use
+    pub level: Option<u8>,
+}
+
+impl Compressor {
+    /// compress the bytes using zlib (obscure 1), levels above 9 are the same as 9
+    pub fn zlib(&self, bytes: &[u8]) -> Result<Vec<u8>, flate2::CompressError> {
+        let level = match self.level {
+            Some(level) => Compression::new(level.min(9) as _),
+            None => Compression::best(),
+        };
+
+        let mut compressed_buf = Vec::with_capacity(deflate_bound(bytes.len()));
+        Compress::new(level, true).compress_vec(
+            bytes,
+            &mut compressed_buf,
+            FlushCompress::Finish,
+        )?;
+
+        Ok(compressed_buf)
+    }
+
+    /// compress the bytes using lzo (obscure 2 and final exam), `default` is the level that the
+    /// game archives use. levels are clamped to 1-13
+    pub fn lzo(&self, bytes: &[u8], default: u8) -> Vec<u8> {
+        let level = lzo1x::CompressLevel::new(self.level.unwrap_or(default));
+        lzo1x::compress(bytes, level)
+    }
+}
+
+fn deflate_bound(source_len: usize) -> usize {
+    source_len + (source_len >> 12) + (source_len >> 14) + 11 - ((source_len >> 1) & 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        archive::{Archive, entry::UpdateKind},
+        test_utils::{for_each_fixture, rebuild, reload},
+    };
+
+    #[test]
+    fn rebuild_fixtures_with_compression_level() {
+        for_each_fixture(|_, provider| {
+            let mut archive = Archive::new(provider);
+
+            let contents: Vec<_> = archive
+                .files()
+                .map(|f| f.get_bytes().unwrap().into_owned())
+                .collect();
+            for (mut entry, bytes) in archive.files_mut().zip(&contents) {
+                entry.update(UpdateKind::Bytes(bytes.clone()));
+            }
+
+            // zlib levels start from 0 (stored) and lzo levels from 1
+            let mut sizes = Vec::new();
+            for level in [0, 13] {
+                archive.options.compression_level = Some(level);
+
+                let rebuilt = rebuild(&archive);
+                sizes.push(rebuilt.len());
+
+                let rebuilt_provider = reload(rebuilt);
+                let rebuilt = Archive::new(&rebuilt_provider);
+                for (file, bytes) in rebuilt.files().zip(&contents) {
+                    assert!(
+                        file.get_bytes().unwrap() == bytes.as_slice(),
+                        "level {level}: {} doesn't match",
+                        file.path.display()
+                    );
+                }
+            }
+
+            assert!(
+                sizes[1] <= sizes[0],
+                "higher level made a bigger archive {sizes:?}"
+            );
+        });
+    }
+}
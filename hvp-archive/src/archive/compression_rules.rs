@@ -0,0 +1,93 @@
+//! rules that decide if the new files of a directory are compressed
+//!
+//! console ports keep some directories (like the streamed sounds and videos) uncompressed, so
+//! the files that are added to them should be stored the same way to keep the layout.
+
+use std::path::{Path, PathBuf};
+
+use super::{Archive, entry::Entry};
+
+/// which new files are compressed, by the directory that they are in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionRules {
+    /// directory and whatever its files are compressed
+    rules: Vec<(PathBuf, bool)>,
+}
+
+impl CompressionRules {
+    /// rules without any directory, every new file is compressed
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the rules that the archive follow, every directory whose files are all stored without
+    /// compression (and that isn't inside such a directory) get a rule to store its new files
+    pub fn from_archive(archive: &Archive) -> Self {
+        /// `Some(true)` if every file inside the directory is stored, `None` if it has no file
+        fn all_stored(entries: &[Entry]) -> Option<bool> {
+            entries
+                .iter()
+                .filter_map(|entry| match entry {
+                    Entry::File(file) => Some(!file.is_compressed()),
+                    Entry::Dir(dir) => all_stored(&dir.entries),
+                })
+                .reduce(|a, b| a && b)
+        }
+
+        fn visit(rules: &mut CompressionRules, path: &Path, entries: &[Entry]) {
+            for entry in entries {
+                let Entry::Dir(dir) = entry else {
+                    continue;
+                };
+
+                let path = path.join(&dir.name);
+                match all_stored(&dir.entries) {
+                    Some(true) => rules.set(path, false),
+                    _ => visit(rules, &path, &dir.entries),
+                }
+            }
+        }
+
+        let mut rules = Self::new();
+        visit(&mut rules, Path::new(""), archive.entries());
+        rules
+    }
+
+    /// compress or store the new files inside `dir` and its subdirectories, a rule for
+    /// the same directory replace the old one
+    pub fn set(&mut self, dir: impl Into<PathBuf>, compress: bool) {
+        let dir = dir.into();
+        match self.rules.iter_mut().find(|(d, _)| *d == dir) {
+            Some((_, old)) => *old = compress,
+            None => self.rules.push((dir, compress)),
+        }
+    }
+
+    /// add the rules of `other`, they win over the current ones
+    pub fn extend(&mut self, other: CompressionRules) {
+        for (dir, compress) in other.rules {
+            self.set(dir, compress);
+        }
+    }
+
+    /// whatever a new file at `path` is compressed, the rule of the deepest directory that
+    /// contain it win. `None` when there isn't any rule for it
+    pub fn compress(&self, path: &Path) -> Option<bool> {
+        self.rules
+            .iter()
+            .filter(|(dir, _)| path.parent().is_some_and(|parent| parent.starts_with(dir)))
+            .max_by_key(|(dir, _)| dir.components().count())
+            .map(|(_, compress)| *compress)
+    }
+
+    /// the directories and whatever their new files are compressed
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, bool)> {
+        self.rules
+            .iter()
+            .map(|(dir, compress)| (dir.as_path(), *compress))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
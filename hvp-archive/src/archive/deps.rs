@@ -0,0 +1,256 @@
+//! hints about which entries reference other entries, found by looking for the names of the
+//! other entries inside script and level files. the formats of these files aren't parsed, so
+//! the graph can miss some references, or have a few false ones
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use ahash::HashMap;
+
+use super::Archive;
+
+/// extensions of the files that are scanned by default, they are text or binary files that
+/// store the paths or names of other assets (effect scripts, level maps, character setups, ...)
+pub const SCRIPT_EXTENSIONS: &[&str] = &["cbe", "dat", "hoe", "map", "txt", "xmc"];
+
+/// shortest name without extension that is matched with the names of the entries, shorter
+/// words are too common in scripts
+const MIN_STEM_LEN: usize = 4;
+
+/// which files to scan for references
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DependencyScan {
+    /// only files with one of the [`SCRIPT_EXTENSIONS`]
+    #[default]
+    Scripts,
+    /// every file of the archive
+    All,
+}
+
+/// references between the files of the archive, see [`Archive::dependencies`]
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// entries that each file reference
+    references: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+    /// files that reference each entry
+    referenced_by: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+    /// number of files that we scanned
+    pub files_scanned: usize,
+    /// files that couldn't be decompressed, so they weren't scanned
+    pub failed: Vec<PathBuf>,
+}
+
+impl DependencyGraph {
+    /// entries that the file reference
+    pub fn references(&self, path: impl AsRef<Path>) -> impl Iterator<Item = &Path> {
+        self.references
+            .get(path.as_ref())
+            .into_iter()
+            .flatten()
+            .map(PathBuf::as_path)
+    }
+
+    /// files that reference the entry, they may need to be updated when the entry is changed
+    pub fn referenced_by(&self, path: impl AsRef<Path>) -> impl Iterator<Item = &Path> {
+        self.referenced_by
+            .get(path.as_ref())
+            .into_iter()
+            .flatten()
+            .map(PathBuf::as_path)
+    }
+
+    /// every file that reference another entry, and the entries that it reference
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, impl Iterator<Item = &Path>)> {
+        self.references
+            .iter()
+            .map(|(path, refs)| (path.as_path(), refs.iter().map(PathBuf::as_path)))
+    }
+
+    /// everything that the entry reference directly or through other files, or with `reverse`
+    /// every file that reference it directly or through other files. the entry itself isn't included
+    pub fn transitive(&self, path: impl AsRef<Path>, reverse: bool) -> BTreeSet<&Path> {
+        let start = path.as_ref();
+        let mut found = BTreeSet::new();
+        let mut queue = vec![start];
+
+        while let Some(current) = queue.pop() {
+            let next: Vec<&Path> = match reverse {
+                true => self.referenced_by(current).collect(),
+                false => self.references(current).collect(),
+            };
+            for next in next {
+                if next != start && found.insert(next) {
+                    queue.push(next);
+                }
+            }
+        }
+
+        found
+    }
+
+    fn insert(&mut self, from: &Path, to: &Path) {
+        self.references
+            .entry(from.to_path_buf())
+            .or_default()
+            .insert(to.to_path_buf());
+        self.referenced_by
+            .entry(to.to_path_buf())
+            .or_default()
+            .insert(from.to_path_buf());
+    }
+}
+
+/// lowercase names of the files, so references can be found in constant time
+#[derive(Default)]
+struct PathIndex {
+    paths: HashMap<String, PathBuf>,
+    names: HashMap<String, Vec<PathBuf>>,
+    stems: HashMap<String, Vec<PathBuf>>,
+}
+
+impl PathIndex {
+    fn new<'a>(paths: impl Iterator<Item = &'a Path>) -> Self {
+        let mut index = Self::default();
+
+        for path in paths {
+            let key = normalize(&path.to_string_lossy());
+            index.paths.insert(key, path.to_path_buf());
+
+            if let Some(name) = path.file_name() {
+                index
+                    .names
+                    .entry(name.to_string_lossy().to_lowercase())
+                    .or_default()
+                    .push(path.to_path_buf());
+            }
+            if let Some(stem) = path.file_stem() {
+                index
+                    .stems
+                    .entry(stem.to_string_lossy().to_lowercase())
+                    .or_default()
+                    .push(path.to_path_buf());
+            }
+        }
+
+        index
+    }
+
+    /// entries that the token may point to, `dir` is the normalized directory of the file
+    /// that contain the token
+    fn resolve<'a>(&'a self, token: &str, dir: &str) -> Vec<&'a Path> {
+        let has_extension = Path::new(token).extension().is_some();
+
+        if !has_extension {
+            if token.len() < MIN_STEM_LEN || token.contains('/') {
+                return Vec::new();
+            }
+            return self
+                .stems
+                .get(token)
+                .into_iter()
+                .flatten()
+                .map(PathBuf::as_path)
+                .collect();
+        }
+
+        // relative to the file, and then relative to the root of the archive
+        let relative = match dir.is_empty() {
+            true => token.to_owned(),
+            false => format!("{dir}/{token}"),
+        };
+        if let Some(path) = self.paths.get(&relative).or_else(|| self.paths.get(token)) {
+            return vec![path];
+        }
+
+        // relative to some other directory, any entry whose path end with the token
+        let name = token.rsplit('/').next().unwrap_or(token);
+        let suffix = format!("/{token}");
+        self.names
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter(|path| {
+                !token.contains('/') || normalize(&path.to_string_lossy()).ends_with(&suffix)
+            })
+            .map(PathBuf::as_path)
+            .collect()
+    }
+}
+
+/// lowercase path with `/` as separator, without leading `./` and `/`
+fn normalize(path: &str) -> String {
+    let path = path.to_lowercase().replace('\\', "/");
+    let mut path = path.as_str();
+    loop {
+        match path.strip_prefix("./").or_else(|| path.strip_prefix('/')) {
+            Some(rest) => path = rest,
+            None => return path.trim_end_matches('.').to_owned(),
+        }
+    }
+}
+
+/// words that may be a path or name, the runs of ascii letters, digits and path characters
+fn tokens(bytes: &[u8]) -> impl Iterator<Item = String> + '_ {
+    fn is_token_char(b: &u8) -> bool {
+        b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'/' | b'\\')
+    }
+
+    bytes
+        .split(|b| !is_token_char(b))
+        .filter(|token| token.len() >= 3)
+        .map(|token| normalize(&String::from_utf8_lossy(token)))
+        .filter(|token| token.len() >= 3)
+}
+
+impl Archive<'_> {
+    /// look for references to other entries inside the files of the archive and build a graph of
+    /// them. a reference is a path (relative to the file, the root or any other directory), a file
+    /// name, or a file name without its extension
+    pub fn dependencies(&self, scan: DependencyScan) -> DependencyGraph {
+        let paths: Vec<PathBuf> = self.files().map(|f| f.path).collect();
+        let index = PathIndex::new(paths.iter().map(PathBuf::as_path));
+
+        let mut graph = DependencyGraph::default();
+
+        for file in self.files() {
+            let scanned = scan == DependencyScan::All
+                || file
+                    .path
+                    .extension()
+                    .is_some_and(|e| SCRIPT_EXTENSIONS.iter().any(|s| e.eq_ignore_ascii_case(s)));
+            if !scanned {
+                continue;
+            }
+
+            let Ok(bytes) = file.get_bytes() else {
+                graph.failed.push(file.path);
+                continue;
+            };
+            graph.files_scanned += 1;
+
+            let dir = file
+                .path
+                .parent()
+                .map(|p| normalize(&p.to_string_lossy()))
+                .unwrap_or_default();
+
+            let mut found = BTreeSet::new();
+            for token in tokens(&bytes) {
+                found.extend(
+                    index
+                        .resolve(&token, &dir)
+                        .into_iter()
+                        .filter(|path| *path != file.path),
+                );
+            }
+
+            for path in found {
+                graph.insert(&file.path, path);
+            }
+        }
+
+        graph
+    }
+}
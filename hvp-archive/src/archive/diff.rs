@@ -0,0 +1,124 @@
+//! compare the files of two archives
+
+use std::path::PathBuf;
+
+use super::Archive;
+use super::entry::FullFileEntry;
+
+/// a summary of a file that is used in the diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileSummary {
+    /// full path of the file inside the archive
+    pub path: PathBuf,
+    /// size of the file inside the archive (compressed size if the file is compressed)
+    pub stored_size: usize,
+    /// size of the file after decompression
+    pub uncompressed_size: usize,
+    /// checksum that is stored in the archive
+    pub checksum: i32,
+    /// whatever the file is compressed or not
+    pub compressed: bool,
+}
+
+impl From<&FullFileEntry<'_>> for FileSummary {
+    fn from(entry: &FullFileEntry<'_>) -> Self {
+        Self {
+            path: entry.path.clone(),
+            stored_size: entry.raw_bytes.len(),
+            uncompressed_size: entry.uncompressed_size(),
+            checksum: entry.stored_checksum(),
+            compressed: entry.is_compressed(),
+        }
+    }
+}
+
+/// a single difference between two archives
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffEntry {
+    /// file only exist in the new archive
+    Added(FileSummary),
+    /// file only exist in the old archive
+    Removed(FileSummary),
+    /// file exist in both archives but its content is different
+    Changed { old: FileSummary, new: FileSummary },
+}
+
+impl DiffEntry {
+    /// path of the file that this diff belongs to
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            DiffEntry::Added(file) | DiffEntry::Removed(file) => &file.path,
+            DiffEntry::Changed { new, .. } => &new.path,
+        }
+    }
+
+    /// how much the stored size of the file changed
+    pub fn size_delta(&self) -> i64 {
+        match self {
+            DiffEntry::Added(file) => file.stored_size as i64,
+            DiffEntry::Removed(file) => -(file.stored_size as i64),
+            DiffEntry::Changed { old, new } => new.stored_size as i64 - old.stored_size as i64,
+        }
+    }
+}
+
+/// result of comparing two archives
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveDiff {
+    /// list of differences, changed and added files come in the order of the new archive
+    /// and removed files come after them in the order of the old archive
+    pub entries: Vec<DiffEntry>,
+    /// number of files that are the same in both archives
+    pub unchanged: usize,
+}
+
+impl ArchiveDiff {
+    /// whatever the two archives have the same files
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// sum of the stored size changes of all the files
+    pub fn size_delta(&self) -> i64 {
+        self.entries.iter().map(DiffEntry::size_delta).sum()
+    }
+}
+
+impl Archive<'_> {
+    /// compare the files of this archive (as the old archive) with the other archive (as the new one).
+    ///
+    /// files are matched by their path and compared using their checksum, size and compression state.
+    pub fn diff(&self, other: &Archive) -> ArchiveDiff {
+        let mut old_files: ahash::HashMap<PathBuf, FileSummary> = self
+            .files()
+            .map(|f| (f.path.clone(), FileSummary::from(&f)))
+            .collect();
+
+        let mut diff = ArchiveDiff::default();
+
+        for file in other.files() {
+            let new = FileSummary::from(&file);
+
+            match old_files.remove(&new.path) {
+                Some(old)
+                    if old.checksum == new.checksum
+                        && old.stored_size == new.stored_size
+                        && old.compressed == new.compressed =>
+                {
+                    diff.unchanged += 1
+                }
+                Some(old) => diff.entries.push(DiffEntry::Changed { old, new }),
+                None => diff.entries.push(DiffEntry::Added(new)),
+            }
+        }
+
+        // keep the order of the old archive for removed files
+        diff.entries.extend(
+            self.files()
+                .filter_map(|f| old_files.remove(&f.path))
+                .map(DiffEntry::Removed),
+        );
+
+        diff
+    }
+}
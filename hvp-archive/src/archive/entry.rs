@@ -1,12 +1,16 @@
 use std::{
+    any::Any,
     borrow::Cow,
     fmt::Debug,
-    fs, io,
+    fs,
+    io::{self, Read},
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 use binrw::Endian;
 
+use super::{error::ReorderError, lzo::LzoReader};
 use crate::structures;
 
 /// you can just put the bytes that you want the archive to update from here
@@ -36,8 +40,16 @@ impl Debug for UpdateKind {
     }
 }
 
+/// data that a frontend attach to an entry, see [`FileEntry::set_user_data`]
+pub(crate) type UserData = Arc<dyn Any + Send + Sync>;
+
+#[inline]
+fn downcast<T: Any>(user_data: &Option<UserData>) -> Option<&T> {
+    user_data.as_deref()?.downcast_ref()
+}
+
 /// compression type
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CompressionType {
     /// used by obscure 1
     Zlib,
@@ -64,6 +76,7 @@ pub struct FileEntry<'p> {
     pub raw_bytes: &'p [u8],
     /// if this path is set we replace the entry data with file from this path
     pub update: Option<UpdateKind>,
+    pub(crate) user_data: Option<UserData>,
 }
 
 impl FileEntry<'_> {
@@ -77,6 +90,37 @@ impl FileEntry<'_> {
         self.compression_info.is_some()
     }
 
+    /// info about the compression, `None` if the entry isn't compressed
+    pub fn compression_info(&self) -> Option<CompressionInfo> {
+        self.compression_info
+    }
+
+    /// size of the entry after decompression
+    pub fn uncompressed_size(&self) -> usize {
+        match self.compression_info {
+            Some(info) => info.uncompressed_size as _,
+            None => self.raw_bytes.len(),
+        }
+    }
+
+    /// checksum that is stored in the archive, it cover the stored (maybe compressed)
+    /// bytes and not the content of the file
+    pub fn stored_checksum(&self) -> i32 {
+        self.checksum
+    }
+
+    /// checksum computed from the stored (maybe compressed) bytes, should be equal to [`Self::stored_checksum`]
+    pub fn computed_stored_checksum(&self) -> i32 {
+        structures::checksum::bytes_sum(self.raw_bytes, self.endian)
+    }
+
+    /// checksum computed from the decompressed content, using the same algorithm that
+    /// the games use. it isn't stored anywhere in the archive
+    pub fn content_checksum(&self) -> Result<i32, DecompressError> {
+        let bytes = self.get_bytes()?;
+        Ok(structures::checksum::bytes_sum(&bytes, self.endian))
+    }
+
     /// get the bytes of the entry. decompress if needed
     pub fn get_bytes(&self) -> Result<Cow<'_, [u8]>, DecompressError> {
         match self.compression_info {
@@ -85,9 +129,25 @@ impl FileEntry<'_> {
         }
     }
 
-    /// check whatever the checksum match
+    /// a reader over the content of the entry that decompress it while reading, see [`EntryReader`]
+    pub fn reader(&self) -> EntryReader<'_> {
+        EntryReader::new(self.raw_bytes, self.compression_info)
+    }
+
+    /// check whatever the stored checksum match the stored bytes
     pub fn checksum_match(&self) -> bool {
-        structures::checksum::bytes_sum(self.raw_bytes, self.endian) == self.checksum
+        self.computed_stored_checksum() == self.checksum
+    }
+
+    /// the attached user data, `None` if there isn't any or it isn't a `T`
+    pub fn get_user_data<T: Any>(&self) -> Option<&T> {
+        downcast(&self.user_data)
+    }
+
+    /// attach user data (like selection or annotations) to the entry, replacing the old one.
+    /// the archive never read it and clones of the entry share it
+    pub fn set_user_data<T: Any + Send + Sync>(&mut self, data: T) {
+        self.user_data = Some(Arc::new(data));
     }
 }
 
@@ -99,6 +159,7 @@ impl Debug for FileEntry<'_> {
             .field("checksum", &self.checksum)
             .field("raw_bytes", &format!("[u8; {}]", self.raw_bytes.len()))
             .field("update", &self.update)
+            .field("user_data", &self.user_data.is_some())
             .finish()
     }
 }
@@ -108,6 +169,27 @@ impl Debug for FileEntry<'_> {
 pub struct DirEntry<'p> {
     pub name: String,
     pub entries: Vec<Entry<'p>>,
+    pub(crate) user_data: Option<UserData>,
+}
+
+impl DirEntry<'_> {
+    /// move the entries with the given names to the start of the directory in the same order,
+    /// the other entries keep their order after them.
+    ///
+    /// [`super::Archive::rebuild`] and [`super::Archive::patch`] write the entries in this order
+    pub fn reorder<S: AsRef<str>>(&mut self, order: &[S]) -> Result<(), ReorderError> {
+        super::order::reorder_entries(&mut self.entries, order)
+    }
+
+    /// the attached user data, `None` if there isn't any or it isn't a `T`
+    pub fn get_user_data<T: Any>(&self) -> Option<&T> {
+        downcast(&self.user_data)
+    }
+
+    /// attach user data to the entry, see [`FileEntry::set_user_data`]
+    pub fn set_user_data<T: Any + Send + Sync>(&mut self, data: T) {
+        self.user_data = Some(Arc::new(data));
+    }
 }
 
 impl Debug for DirEntry<'_> {
@@ -115,6 +197,7 @@ impl Debug for DirEntry<'_> {
         f.debug_struct("DirEntry")
             .field("name", &self.name)
             .field("entries", &format!("[Entry; {}]", self.entries.len()))
+            .field("user_data", &self.user_data.is_some())
             .finish()
     }
 }
@@ -137,14 +220,50 @@ impl FullFileEntry<'_> {
         }
     }
 
+    /// a reader over the content of the entry that decompress it while reading, see [`EntryReader`]
+    pub fn reader(&self) -> EntryReader<'_> {
+        EntryReader::new(self.raw_bytes, self.compression_info)
+    }
+
     /// whatever the entry is compressed or not
     pub fn is_compressed(&self) -> bool {
         self.compression_info.is_some()
     }
 
-    /// check whatever the checksum match
+    /// info about the compression, `None` if the entry isn't compressed
+    pub fn compression_info(&self) -> Option<CompressionInfo> {
+        self.compression_info
+    }
+
+    /// size of the entry after decompression
+    pub fn uncompressed_size(&self) -> usize {
+        match self.compression_info {
+            Some(info) => info.uncompressed_size as _,
+            None => self.raw_bytes.len(),
+        }
+    }
+
+    /// checksum that is stored in the archive, it cover the stored (maybe compressed)
+    /// bytes and not the content of the file
+    pub fn stored_checksum(&self) -> i32 {
+        self.checksum
+    }
+
+    /// checksum computed from the stored (maybe compressed) bytes, should be equal to [`Self::stored_checksum`]
+    pub fn computed_stored_checksum(&self) -> i32 {
+        structures::checksum::bytes_sum(self.raw_bytes, self.endian)
+    }
+
+    /// checksum computed from the decompressed content, using the same algorithm that
+    /// the games use. it isn't stored anywhere in the archive
+    pub fn content_checksum(&self) -> Result<i32, DecompressError> {
+        let bytes = self.get_bytes()?;
+        Ok(structures::checksum::bytes_sum(&bytes, self.endian))
+    }
+
+    /// check whatever the stored checksum match the stored bytes
     pub fn checksum_match(&self) -> bool {
-        structures::checksum::bytes_sum(self.raw_bytes, self.endian) == self.checksum
+        self.computed_stored_checksum() == self.checksum
     }
 }
 
@@ -182,6 +301,12 @@ impl FullFileEntryMut<'_, '_> {
         self.entry.compression_info.is_some()
     }
 
+    /// checksum that is stored in the archive, it cover the stored (maybe compressed)
+    /// bytes and not the content of the file
+    pub fn stored_checksum(&self) -> i32 {
+        self.entry.checksum
+    }
+
     /// check whatever the checksum match
     pub fn checksum_match(&self) -> bool {
         structures::checksum::bytes_sum(self.entry.raw_bytes, self.entry.endian)
@@ -192,6 +317,16 @@ impl FullFileEntryMut<'_, '_> {
     pub fn update(&mut self, update: impl Into<Option<UpdateKind>>) {
         self.entry.update = update.into();
     }
+
+    /// the attached user data, see [`FileEntry::get_user_data`]
+    pub fn get_user_data<T: Any>(&self) -> Option<&T> {
+        self.entry.get_user_data()
+    }
+
+    /// attach user data to the entry, see [`FileEntry::set_user_data`]
+    pub fn set_user_data<T: Any + Send + Sync>(&mut self, data: T) {
+        self.entry.set_user_data(data);
+    }
 }
 
 impl Debug for FullFileEntryMut<'_, '_> {
@@ -211,6 +346,30 @@ pub enum Entry<'p> {
 }
 
 impl<'p> Entry<'p> {
+    /// name of the file or directory
+    pub fn name(&self) -> &str {
+        match self {
+            Entry::File(entry) => &entry.name,
+            Entry::Dir(entry) => &entry.name,
+        }
+    }
+
+    /// the attached user data of the file or directory, `None` if there isn't any or it isn't a `T`
+    pub fn get_user_data<T: Any>(&self) -> Option<&T> {
+        match self {
+            Entry::File(entry) => entry.get_user_data(),
+            Entry::Dir(entry) => entry.get_user_data(),
+        }
+    }
+
+    /// attach user data to the file or directory, replacing the old one
+    pub fn set_user_data<T: Any + Send + Sync>(&mut self, data: T) {
+        match self {
+            Entry::File(entry) => entry.set_user_data(data),
+            Entry::Dir(entry) => entry.set_user_data(data),
+        }
+    }
+
     /// flatten the entry to its files
     pub fn flatten_to_files(&self) -> Vec<FullFileEntry<'p>> {
         fn file<'p>(entry: &FileEntry<'p>, parent: Option<&Path>) -> FullFileEntry<'p> {
@@ -294,6 +453,46 @@ impl<'p> Entry<'p> {
     }
 }
 
+/// reader over the content of a file entry, the data is decompressed in chunks while reading,
+/// so big files don't need to be buffered
+pub struct EntryReader<'a> {
+    inner: ReaderKind<'a>,
+}
+
+enum ReaderKind<'a> {
+    Stored(&'a [u8]),
+    Zlib(io::Take<flate2::read::ZlibDecoder<&'a [u8]>>),
+    Lzo(LzoReader<'a>),
+}
+
+impl<'a> EntryReader<'a> {
+    fn new(input: &'a [u8], compression_info: Option<CompressionInfo>) -> Self {
+        let inner = match compression_info {
+            None => ReaderKind::Stored(input),
+            Some(info) => match info.compression_type {
+                CompressionType::Zlib => ReaderKind::Zlib(
+                    flate2::read::ZlibDecoder::new(input).take(info.uncompressed_size as _),
+                ),
+                CompressionType::Lzo => {
+                    ReaderKind::Lzo(LzoReader::new(input, info.uncompressed_size as _))
+                }
+            },
+        };
+
+        Self { inner }
+    }
+}
+
+impl Read for EntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.inner {
+            ReaderKind::Stored(input) => input.read(buf),
+            ReaderKind::Zlib(decoder) => decoder.read(buf),
+            ReaderKind::Lzo(decoder) => decoder.read(buf),
+        }
+    }
+}
+
 /// errors that can happen during decompression
 #[derive(Debug, thiserror::Error)]
 pub enum DecompressError {
@@ -327,3 +526,82 @@ fn decompress_buf(
     };
     Ok(output)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{archive::Archive, test_utils::for_each_fixture};
+
+    #[test]
+    fn user_data_follow_fixture_entries() {
+        #[derive(Debug, PartialEq)]
+        struct Selected(usize);
+
+        for_each_fixture(|_, provider| {
+            let mut archive = Archive::new(provider);
+
+            for (index, mut file) in archive.files_mut().enumerate() {
+                file.set_user_data(Selected(index));
+            }
+            let paths: Vec<_> = archive.files().map(|f| f.path).collect();
+
+            let Some(Entry::Dir(dir)) = archive.entries_mut().first_mut() else {
+                panic!("first entry isn't a directory");
+            };
+            dir.set_user_data("annotation");
+            let name = dir.name.clone();
+            let reversed: Vec<_> = dir
+                .entries
+                .iter()
+                .rev()
+                .map(|e| e.name().to_owned())
+                .collect();
+            dir.reorder(&reversed).expect("failed to reorder directory");
+
+            assert_eq!(
+                archive.entries()[0].get_user_data::<&str>(),
+                Some(&"annotation")
+            );
+            // another type isn't returned
+            assert_eq!(archive.entries()[0].get_user_data::<Selected>(), None);
+
+            // the data stay with its file after the reorder
+            let mut order = Vec::new();
+            for file in archive.files_mut() {
+                let Selected(index) = file.get_user_data().expect("user data is lost");
+                assert_eq!(file.path, paths[*index]);
+                order.push(*index);
+            }
+            if reversed.len() > 1 {
+                assert!(!order.is_sorted(), "{name}");
+            }
+        });
+    }
+
+    #[test]
+    fn entry_reader_match_fixture_bytes() {
+        for_each_fixture(|_, provider| {
+            for file in Archive::new(provider).files() {
+                let expected = file.get_bytes().unwrap();
+
+                let mut content = Vec::new();
+                file.reader()
+                    .read_to_end(&mut content)
+                    .expect("failed to read entry");
+                assert_eq!(content, *expected, "{}", file.path.display());
+
+                // small reads give the same content
+                let mut reader = file.reader();
+                let mut content = Vec::new();
+                let mut buf = [0; 7];
+                loop {
+                    match reader.read(&mut buf).expect("failed to read entry") {
+                        0 => break,
+                        n => content.extend_from_slice(&buf[..n]),
+                    }
+                }
+                assert_eq!(content, *expected, "{}", file.path.display());
+            }
+        });
+    }
+}
@@ -1,4 +1,7 @@
-use std::io;
+use std::{io, path::PathBuf};
+
+use super::entry::DecompressError;
+use crate::{Game, formats::Capability};
 
 /// errors that can happen during rebuilding of a archive
 #[derive(Debug, thiserror::Error)]
@@ -9,4 +12,89 @@ pub enum RebuildError {
     BinRW(#[from] binrw::Error),
     #[error("zlib compression failed")]
     ZlibCompressionFailed(#[from] flate2::CompressError),
+    #[error("archive is too large, offsets must fit in 32 bits")]
+    ArchiveTooLarge,
+    #[error("{0:?} isn't supported here, use the archive builder instead")]
+    UnsupportedOperation(Capability),
+    #[error("can't find {0} in its original directory, entries can only be reordered inside it")]
+    OrderMismatch(String),
+    #[error("archive was opened without the data of its files, it can't be rebuilt")]
+    NoData,
+    #[error("rebuild of the unchanged archive diverged from the source archive at offset {0:#x}")]
+    RoundtripDiverged(u64),
+}
+
+/// errors that can happen when reordering the entries of a directory
+#[derive(Debug, thiserror::Error)]
+pub enum ReorderError {
+    #[error("can't find directory {} in the archive", .0.display())]
+    MissingDir(PathBuf),
+    #[error("{0} isn't inside the directory")]
+    MissingEntry(String),
+    #[error("{0} is listed more than once")]
+    DuplicateEntry(String),
+}
+
+/// errors that can happen when applying a [`super::toc_patch::TocPatch`]
+#[derive(Debug, thiserror::Error)]
+pub enum TocPatchError {
+    #[error("can't find {} in the archive", .0.display())]
+    MissingFile(PathBuf),
+    #[error("{} is edited more than once", .0.display())]
+    DuplicateFile(PathBuf),
+    #[error("{} is compressed, but its uncompressed size isn't known", .0.display())]
+    MissingSize(PathBuf),
+    #[error("data of {} doesn't decompress to {uncompressed_size} bytes", path.display())]
+    InvalidCompression {
+        path: PathBuf,
+        uncompressed_size: u32,
+    },
+    #[error("{} isn't compressed, so its uncompressed size must be {expected}, found {found}", path.display())]
+    SizeMismatch {
+        path: PathBuf,
+        expected: u32,
+        found: u32,
+    },
+    #[error("checksum of {} must be {expected:#010x} to match its data, found {found:#010x}", path.display())]
+    ChecksumMismatch {
+        path: PathBuf,
+        expected: i32,
+        found: i32,
+    },
+}
+
+/// errors that can happen when making, reading or writing a patch file
+#[derive(Debug, thiserror::Error)]
+pub enum PatchFileError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Decompress(#[from] DecompressError),
+    #[error("invalid patch file: {0}")]
+    Invalid(&'static str),
+    #[error("unsupported patch file version {0}")]
+    UnsupportedVersion(u16),
+    #[error("patch is made for {patch:?}, but the archive is from {archive:?}")]
+    GameMismatch { patch: Game, archive: Game },
+    #[error("{} was added, patches can only change existing files", .0.display())]
+    FileAdded(PathBuf),
+    #[error("{} was removed, patches can only change existing files", .0.display())]
+    FileRemoved(PathBuf),
+    #[error("can't find {} in the archive", .0.display())]
+    MissingFile(PathBuf),
+    #[error(
+        "{} doesn't match the patch, expected checksum {expected_checksum:#010x} and size {expected_size}, found {found_checksum:#010x} and {found_size}",
+        path.display()
+    )]
+    OriginalMismatch {
+        path: PathBuf,
+        expected_checksum: i32,
+        expected_size: u32,
+        found_checksum: i32,
+        found_size: u32,
+    },
+    #[error("binary diff of {} is outside of the original file", .0.display())]
+    DeltaOutOfBounds(PathBuf),
+    #[error("content of {} doesn't match the patch after applying it", .0.display())]
+    ContentMismatch(PathBuf),
 }
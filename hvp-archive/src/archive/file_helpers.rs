@@ -169,3 +169,73 @@ impl<'a, 'p> ExactSizeIterator for FileIteratorMut<'a, 'p> {
         self.files_count - self.idx
     }
 }
+
+/// a entry that is visited by [`WalkIterator`]
+pub struct WalkEntry<'a, 'p> {
+    /// full path of the entry inside the archive
+    pub path: PathBuf,
+    /// number of directories above the entry, 0 for the entries of the root
+    pub depth: usize,
+    /// whatever it's the last entry of its directory
+    pub is_last: bool,
+    pub entry: &'a Entry<'p>,
+}
+
+struct WalkFrame<'a, 'p> {
+    entry: &'a Entry<'p>,
+    depth: usize,
+    is_last: bool,
+}
+
+/// a depth first iterator over every file and directory inside the archive,
+/// directories are visited before their entries
+pub struct WalkIterator<'a, 'p> {
+    stack: Vec<WalkFrame<'a, 'p>>,
+    path_stack: Vec<&'a str>,
+}
+
+impl<'a, 'p> WalkIterator<'a, 'p> {
+    pub(super) fn new(entries: &'a [Entry<'p>]) -> Self {
+        let mut walk = Self {
+            stack: Vec::with_capacity(entries.len()),
+            path_stack: Vec::new(),
+        };
+        walk.push_entries(entries, 0);
+        walk
+    }
+
+    fn push_entries(&mut self, entries: &'a [Entry<'p>], depth: usize) {
+        // reverse order, so they are visited in the original order
+        for (idx, entry) in entries.iter().enumerate().rev() {
+            self.stack.push(WalkFrame {
+                entry,
+                depth,
+                is_last: idx + 1 == entries.len(),
+            });
+        }
+    }
+}
+
+impl<'a, 'p> Iterator for WalkIterator<'a, 'p> {
+    type Item = WalkEntry<'a, 'p>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.stack.pop()?;
+        self.path_stack.truncate(frame.depth);
+
+        let mut path: PathBuf = self.path_stack.iter().collect();
+        path.push(frame.entry.name());
+
+        if let Entry::Dir(dir_entry) = frame.entry {
+            self.path_stack.push(&dir_entry.name);
+            self.push_entries(&dir_entry.entries, frame.depth + 1);
+        }
+
+        Some(WalkEntry {
+            path,
+            depth: frame.depth,
+            is_last: frame.is_last,
+            entry: frame.entry,
+        })
+    }
+}
@@ -1,22 +1,27 @@
-use std::io::{Read, Write};
+use std::borrow::Cow;
+use std::io::{Read, Seek, Write};
 use std::ops::Range;
 
 use binrw::Endian;
 
-use super::Metadata;
+use super::compress::Compressor;
 use super::entry::{CompressionInfo, CompressionType, DirEntry, Entry, FileEntry};
 use super::error::RebuildError;
-use super::rebuild_progress::RebuildProgress;
+use super::patch::Patcher;
+use super::rebuild_progress::{RebuildProgress, write_data};
+use super::{Metadata, ORPHANS_DIR, order};
 use crate::Game;
 use crate::provider::ArchiveProvider;
 use crate::structures::{checksum, final_exam};
 
-/// map the entries and return them plus the number of files
+/// map the entries and return them plus the number of files,
+/// entries at `orphans` indices are attached under a virtual [`ORPHANS_DIR`] directory
 pub fn map_entries<'p>(
     provider: &'p ArchiveProvider,
     entries: &[final_exam::Entry],
     endian: Endian,
     names: &final_exam::Names,
+    orphans: &[usize],
 ) -> (Vec<Entry<'p>>, Metadata) {
     // we ignore the root dir, because it really don't serve any purpose except adding one layer of nesting
     // we can manually add it when we are writing the entries back
@@ -36,19 +41,29 @@ pub fn map_entries<'p>(
         entries,
         endian,
         names,
-        metadata: Metadata {
-            dir_count: 0,
-            file_count: 0,
-            game: Game::FinalExam,
-        },
+        metadata: Metadata::new(Game::FinalExam, endian.into()),
     };
 
-    let entries = entries[1..1 + root_count]
+    let mut mapped: Vec<_> = entries[1..1 + root_count]
         .iter()
         .map(|entry| process.process_entry(entry))
         .collect();
 
-    (entries, process.metadata)
+    if !orphans.is_empty() {
+        let orphans = orphans
+            .iter()
+            .map(|&index| process.process_entry(&entries[index]))
+            .collect();
+
+        process.metadata.dir_count += 1;
+        mapped.push(Entry::Dir(DirEntry {
+            name: ORPHANS_DIR.to_owned(),
+            entries: orphans,
+            user_data: None,
+        }));
+    }
+
+    (mapped, process.metadata)
 }
 
 /// a helper for processing final exam entries
@@ -87,6 +102,7 @@ impl<'p> Process<'p, '_, '_> {
                 .provider
                 .get_bytes(entry.offset as _, entry.compressed_size as _),
             update: None,
+            user_data: None,
         })
     }
 
@@ -96,6 +112,7 @@ impl<'p> Process<'p, '_, '_> {
         let mut dir = DirEntry {
             name,
             entries: Vec::with_capacity(entry.count as usize),
+            user_data: None,
         };
 
         self.metadata.dir_count += 1;
@@ -119,10 +136,12 @@ impl<'p> Process<'p, '_, '_> {
 }
 
 /// update the archive entries based on the mapped entries
+#[allow(clippy::too_many_arguments)]
 pub fn update_entries<W: Write, P: RebuildProgress>(
     writer: &mut W,
     offset: u32,
-    skip_compression: bool,
+    compression: Compressor,
+    alignment: Option<u32>,
     mut archive: final_exam::HvpArchive,
     entries: &[Entry],
     names: &final_exam::Names,
@@ -145,13 +164,18 @@ pub fn update_entries<W: Write, P: RebuildProgress>(
         writer,
         progress,
         offset,
-        skip_compression,
+        compression,
         names,
         endian: archive.endian(),
+        alignment: alignment.unwrap_or(4).max(1),
     };
 
     updater.caculate_and_apply_padding()?;
 
+    order::sync_order(&mut archive.entries[1..1 + root_count], entries, |r, e| {
+        same_entry(names, r, e)
+    })?;
+
     let mut entries_iter = entries.iter();
     for o_entry_idx in 1..1 + root_count {
         let Some(u_entry) = entries_iter.next() else {
@@ -169,10 +193,11 @@ pub struct Updater<'a, 'n, W: Write, P: RebuildProgress> {
     writer: &'a mut W,
     progress: P,
     offset: u32,
-    skip_compression: bool,
+    compression: Compressor,
     names: &'n final_exam::Names,
-    // BigEndian version have 32 padding
     endian: Endian,
+    // the game archives have 4 padding
+    alignment: u32,
 }
 
 impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
@@ -191,7 +216,13 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
             Entry::File(u_entry),
         ) = (&mut entries[o_entry_idx].kind, u_entry)
         {
-            self.process_file(o_entry, u_entry)?;
+            let is_compressed = self.process_file(o_entry, u_entry)?;
+            if !is_compressed {
+                entries[o_entry_idx].kind.set_uncompressed();
+            }
+            if u_entry.update.is_none() {
+                sync_toc(&mut entries[o_entry_idx].kind, u_entry);
+            }
             self.caculate_and_apply_padding()?;
 
             Ok(())
@@ -204,16 +235,12 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
         }
     }
 
+    /// write the data of the file, return whatever the written data is compressed
     fn process_file(
         &mut self,
         o_entry: &mut final_exam::FileEntry,
         u_entry: &FileEntry,
-    ) -> Result<(), RebuildError> {
-        assert_eq!(
-            o_entry.checksum, u_entry.checksum,
-            "checksum original entry and updated entry doesn't match"
-        );
-
+    ) -> Result<bool, RebuildError> {
         let name = self
             .names
             .get_name_by_offset(o_entry.name_offset)
@@ -222,40 +249,40 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
         if o_entry.uncompressed_size == 0 {
             self.progress.inc(Some(format!("(skp) {name}")));
 
-            return Ok(());
+            return Ok(u_entry.is_compressed());
         }
 
         o_entry.offset = self.offset;
 
         let Some(update) = &u_entry.update else {
             self.progress.inc(Some(format!("(src) {name}")));
-            self.writer.write_all(u_entry.raw_bytes)?;
+            write_data(self.writer, &self.progress, u_entry.raw_bytes)?;
             self.offset += u_entry.raw_bytes.len() as u32;
-            return Ok(());
+            return Ok(u_entry.is_compressed());
         };
 
         let bytes = update.to_bytes()?;
 
         self.progress.inc(Some(format!("(upd) {name}")));
 
-        if self.skip_compression || !u_entry.is_compressed() {
-            self.writer.write_all(&bytes)?;
+        if self.compression.skip || !u_entry.is_compressed() {
+            write_data(self.writer, &self.progress, &bytes)?;
             self.offset += bytes.len() as u32;
             o_entry.compressed_size = bytes.len() as _;
             o_entry.uncompressed_size = bytes.len() as _;
             o_entry.checksum = checksum::bytes_sum(&bytes, self.endian);
-            return Ok(());
+            return Ok(false);
         }
 
-        let compressed_bytes = lzo1x::compress(&bytes, lzo1x::CompressLevel::new(12));
+        let compressed_bytes = self.compression.lzo(&bytes, 12);
 
-        self.writer.write_all(&compressed_bytes)?;
+        write_data(self.writer, &self.progress, &compressed_bytes)?;
         self.offset += compressed_bytes.len() as u32;
         o_entry.compressed_size = compressed_bytes.len() as _;
         o_entry.uncompressed_size = bytes.len() as _;
         o_entry.checksum = checksum::bytes_sum(&compressed_bytes, self.endian);
 
-        Ok(())
+        Ok(true)
     }
 
     fn process_dir(
@@ -264,6 +291,10 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
         range: Range<usize>,
         entries: &mut [final_exam::Entry],
     ) -> Result<(), RebuildError> {
+        order::sync_order(&mut entries[range.clone()], &u_entry.entries, |r, e| {
+            same_entry(self.names, r, e)
+        })?;
+
         let mut entries_iter = u_entry.entries.iter();
         for o_entry_idx in range {
             let Some(u_entry) = entries_iter.next() else {
@@ -278,8 +309,8 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
 
     #[inline]
     fn caculate_and_apply_padding(&mut self) -> std::io::Result<()> {
-        if !self.offset.is_multiple_of(4) {
-            let last_padding = 4 - (self.offset % 4);
+        if !self.offset.is_multiple_of(self.alignment) {
+            let last_padding = self.alignment - (self.offset % self.alignment);
             std::io::copy(&mut std::io::repeat(0).take(last_padding as _), self.writer)?;
             self.offset += last_padding;
         }
@@ -287,3 +318,143 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
         Ok(())
     }
 }
+
+/// patch the updated entries into the archive file and return the updated archive
+pub fn patch_entries<F: Write + Seek>(
+    patcher: &mut Patcher<F>,
+    compression: Compressor,
+    mut archive: final_exam::HvpArchive,
+    entries: &[Entry],
+) -> Result<final_exam::HvpArchive, RebuildError> {
+    let root_count = match &archive.entries[0] {
+        final_exam::Entry {
+            name_crc32: 0,
+            kind:
+                final_exam::EntryKind::Directory(final_exam::DirEntry {
+                    index: 1, count, ..
+                }),
+        } => *count as usize,
+        _ => unreachable!("found a hvp without valid root entry"),
+    };
+
+    fn patch_dir<F: Write + Seek>(
+        patcher: &mut Patcher<F>,
+        compression: Compressor,
+        endian: Endian,
+        names: &final_exam::Names,
+        range: Range<usize>,
+        u_entries: &[Entry],
+        o_entries: &mut [final_exam::Entry],
+    ) -> Result<(), RebuildError> {
+        order::sync_order(&mut o_entries[range.clone()], u_entries, |r, e| {
+            same_entry(names, r, e)
+        })?;
+
+        for (o_entry_idx, u_entry) in range.zip(u_entries) {
+            match (&mut o_entries[o_entry_idx].kind, u_entry) {
+                (
+                    final_exam::EntryKind::File(o_entry)
+                    | final_exam::EntryKind::FileCompressed(o_entry),
+                    Entry::File(u_entry),
+                ) => {
+                    let Some(update) = &u_entry.update else {
+                        sync_toc(&mut o_entries[o_entry_idx].kind, u_entry);
+                        continue;
+                    };
+
+                    if o_entry.uncompressed_size == 0 {
+                        continue;
+                    }
+
+                    let bytes = update.to_bytes()?;
+
+                    o_entry.uncompressed_size = bytes.len() as _;
+                    let is_compressed = !compression.skip && u_entry.is_compressed();
+                    let bytes = if is_compressed {
+                        Cow::Owned(compression.lzo(&bytes, 12))
+                    } else {
+                        bytes
+                    };
+
+                    o_entry.offset =
+                        patcher.place(o_entry.offset, o_entry.compressed_size, &bytes)?;
+                    o_entry.compressed_size = bytes.len() as _;
+                    o_entry.checksum = checksum::bytes_sum(&bytes, endian);
+
+                    if !is_compressed {
+                        o_entries[o_entry_idx].kind.set_uncompressed();
+                    }
+                }
+                (final_exam::EntryKind::Directory(o_entry), Entry::Dir(u_entry)) => {
+                    let range = o_entry.entries_range();
+                    patch_dir(
+                        patcher,
+                        compression,
+                        endian,
+                        names,
+                        range,
+                        &u_entry.entries,
+                        o_entries,
+                    )?;
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(())
+    }
+
+    let endian = archive.endian();
+    patch_dir(
+        patcher,
+        compression,
+        endian,
+        &archive.names,
+        1..1 + root_count,
+        entries,
+        &mut archive.entries,
+    )?;
+
+    Ok(archive)
+}
+
+/// copy the TOC fields of a file that its data isn't updated, they can only be changed
+/// using [`super::Archive::apply_toc_patch`]
+fn sync_toc(kind: &mut final_exam::EntryKind, u_entry: &FileEntry) {
+    let was_compressed = matches!(kind, final_exam::EntryKind::FileCompressed(_));
+    let (final_exam::EntryKind::File(o_entry) | final_exam::EntryKind::FileCompressed(o_entry)) =
+        kind
+    else {
+        unreachable!("only files have TOC fields");
+    };
+
+    o_entry.checksum = u_entry.checksum;
+    match u_entry.compression_info {
+        Some(info) => {
+            o_entry.uncompressed_size = info.uncompressed_size;
+            kind.set_compressed();
+        }
+        None => {
+            if was_compressed {
+                o_entry.uncompressed_size = u_entry.raw_bytes.len() as _;
+            }
+            kind.set_uncompressed();
+        }
+    }
+}
+
+/// whatever the raw entry is the one that the entry was read from
+pub(super) fn same_entry(
+    names: &final_exam::Names,
+    raw: &final_exam::Entry,
+    entry: &Entry,
+) -> bool {
+    let name_offset = match &raw.kind {
+        final_exam::EntryKind::File(file) | final_exam::EntryKind::FileCompressed(file) => {
+            file.name_offset
+        }
+        final_exam::EntryKind::Directory(dir) => dir.name_offset,
+    };
+
+    names.get_name_by_offset(name_offset) == entry.name()
+}
@@ -0,0 +1,120 @@
+//! resolved information about a single file of the archive
+
+use std::path::{Path, PathBuf};
+
+use super::{
+    Archive,
+    entry::{CompressionType, FullFileEntry},
+    obscure2,
+};
+use crate::Game;
+
+/// everything that we know about a single file of the archive
+#[derive(Debug, Clone)]
+pub struct FileInfo {
+    /// full path of the file inside the archive
+    pub path: PathBuf,
+    /// full path of the directory that contain the file, empty for files in the root
+    pub dir: PathBuf,
+    /// name of the file
+    pub name: String,
+    /// size of the data that is stored in the archive
+    pub stored_size: u64,
+    /// size of the file after decompression
+    pub uncompressed_size: u64,
+    /// compression type, `None` if the file isn't compressed
+    pub compression: Option<CompressionType>,
+    /// checksum that is stored in the archive, it cover the stored (maybe compressed) bytes
+    pub stored_checksum: i32,
+    /// whatever the stored checksum match the stored bytes, `None` if the archive is opened
+    /// without the data of its files
+    pub checksum_match: Option<bool>,
+    /// offset of the file data from the start of the archive
+    pub data_offset: u64,
+    /// crc32 of the name that is stored in the archive, only obscure 2 archives store one
+    pub name_crc32: Option<u32>,
+}
+
+impl<'p> Archive<'p> {
+    /// find a file by its full path and resolve everything that we know about it
+    pub fn file_info(&self, path: impl AsRef<Path>) -> Option<FileInfo> {
+        self.find_file(path).map(|entry| self.info_of(&entry))
+    }
+
+    /// resolve everything that we know about every file, in [`Archive::files`] order
+    pub fn files_info(&self) -> impl Iterator<Item = FileInfo> + '_ {
+        self.files().map(|entry| self.info_of(&entry))
+    }
+
+    fn info_of(&self, entry: &FullFileEntry<'p>) -> FileInfo {
+        let name = entry
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let name_crc32 = match self.metadata.game {
+            Game::Obscure2 => obscure2::build_name_crc32(&name),
+            Game::Obscure1 | Game::FinalExam => None,
+        };
+
+        FileInfo {
+            dir: entry
+                .path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            path: entry.path.clone(),
+            stored_size: entry.raw_bytes.len() as _,
+            uncompressed_size: entry.uncompressed_size() as _,
+            compression: entry.compression_info.map(|c| c.compression_type),
+            stored_checksum: entry.checksum,
+            checksum_match: self.provider.has_data().then(|| entry.checksum_match()),
+            data_offset: self.provider.offset_of(entry.raw_bytes),
+            name_crc32,
+            name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Format, for_each_fixture};
+
+    #[test]
+    fn fixtures_file_info() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            for file in archive.files() {
+                let info = archive.file_info(&file.path).unwrap();
+                let offset = info.data_offset as usize;
+
+                assert_eq!(info.path, file.path);
+                assert_eq!(Some(info.dir.as_path()), file.path.parent());
+                assert_eq!(info.stored_size as usize, file.raw_bytes.len());
+                assert_eq!(info.compression.is_some(), file.is_compressed());
+                assert_eq!(info.stored_checksum, file.stored_checksum());
+                assert_eq!(
+                    &fixture.bytes[offset..offset + file.raw_bytes.len()],
+                    file.raw_bytes,
+                    "{}",
+                    file.path.display()
+                );
+                assert_eq!(
+                    info.name_crc32.is_some(),
+                    fixture.format == Format::Obscure2
+                );
+            }
+
+            assert!(archive.file_info("does/not/exist.bin").is_none());
+
+            let infos: Vec<_> = archive.files_info().collect();
+            assert_eq!(infos.len(), archive.metadata().file_count);
+            for (info, file) in infos.iter().zip(archive.files()) {
+                assert_eq!(info.path, file.path);
+            }
+        });
+    }
+}
@@ -0,0 +1,115 @@
+//! plan the layout of a rebuilt archive
+//!
+//! the data of the files is written before the TOC, so the size of the serialized TOC must be
+//! known first. the fields of the TOC that depend on its size and content (like entry sizes,
+//! counts and obscure 1 data offset) are calculated again here, so the TOC can change freely.
+
+use std::{
+    io::{self, Cursor, Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use binrw::{BinResult, BinWrite};
+
+use crate::structures::{final_exam, obscure1, obscure2};
+
+/// fix the obscure 1 TOC and return its size
+pub(super) fn plan_obscure1(archive: &mut obscure1::HvpArchive) -> BinResult<u64> {
+    fn fix_entries(entries: &mut [obscure1::Entry], counts: &mut (u32, u32)) {
+        for entry in entries {
+            entry.update_entry_size();
+            match &mut entry.kind {
+                obscure1::EntryKind::Dir(dir) => {
+                    counts.0 += 1;
+                    fix_entries(&mut dir.entries, counts);
+                }
+                obscure1::EntryKind::File(_) => counts.1 += 1,
+            }
+        }
+    }
+
+    let mut counts = (0, 0);
+    fix_entries(&mut archive.entries, &mut counts);
+
+    // data offset in the header is the size of the entries
+    let entries_len = serialized_len(|w| archive.entries.write_be(w))?;
+
+    let header = &mut archive.header;
+    header.root_count = archive.entries.len() as _;
+    header.all_count = counts.0 + counts.1;
+    header.file_count = counts.1;
+    header.data_offset = entries_len as _;
+
+    serialized_len(|w| archive.write_be(w))
+}
+
+/// fix the obscure 2 TOC and return its size
+pub(super) fn plan_obscure2(archive: &mut obscure2::HvpArchive) -> BinResult<u64> {
+    archive.header.entries_count = archive.entries.len() as _;
+
+    serialized_len(|w| archive.write(w))
+}
+
+/// fix the final exam TOC and return its size
+pub(super) fn plan_final_exam(archive: &mut final_exam::HvpArchive) -> BinResult<u64> {
+    archive.header.entries_count = archive.entries.len() as _;
+
+    serialized_len(|w| archive.write(w))
+}
+
+/// skip the space of the TOC so it can be written after the data, return the range of the TOC
+pub(super) fn skip_toc<W: Write + Seek>(writer: &mut W, toc_len: u64) -> io::Result<Range<u64>> {
+    let start_pos = writer.stream_position()?;
+
+    if writer.seek(SeekFrom::Current(toc_len as _)).is_err() {
+        // in case of seek failing
+        io::copy(&mut io::repeat(0).take(toc_len), writer)?;
+    }
+
+    Ok(start_pos..start_pos + toc_len)
+}
+
+fn serialized_len(write: impl FnOnce(&mut Cursor<Vec<u8>>) -> BinResult<()>) -> BinResult<u64> {
+    let mut buf = Cursor::new(Vec::new());
+    write(&mut buf)?;
+    Ok(buf.get_ref().len() as _)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        archive::Archive,
+        test_utils::{for_each_fixture, rebuild, reload},
+    };
+
+    const ALIGNMENT: u32 = 2048;
+
+    #[test]
+    fn rebuild_fixtures_with_alignment() {
+        for_each_fixture(|_, provider| {
+            let mut archive = Archive::new(provider);
+            archive.options.alignment = Some(ALIGNMENT);
+
+            let rebuilt_provider = reload(rebuild(&archive));
+            let rebuilt = Archive::new(&rebuilt_provider);
+            assert!(rebuilt.verify().is_ok());
+
+            for info in rebuilt.files_info().filter(|info| info.stored_size > 0) {
+                assert_eq!(
+                    info.data_offset % ALIGNMENT as u64,
+                    0,
+                    "{} isn't aligned",
+                    info.path.display()
+                );
+            }
+
+            for (file, original) in rebuilt.files().zip(archive.files()) {
+                assert!(
+                    file.raw_bytes == original.raw_bytes,
+                    "{} doesn't match",
+                    file.path.display()
+                );
+            }
+        });
+    }
+}
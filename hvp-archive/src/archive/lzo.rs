@@ -0,0 +1,325 @@
+//! chunked lzo1x decompression for [`super::entry::EntryReader`]
+//!
+//! the format is described in <https://docs.kernel.org/staging/lzo.html>. the whole input is
+//! always in memory, so only the output is streamed: the last [`WINDOW`] decompressed bytes are
+//! kept for the back references and long literal runs and matches are copied in pieces.
+
+use std::io::{self, Read};
+
+use super::entry::DecompressError;
+
+/// the furthest back a match can point to
+const WINDOW: usize = 0xBFFF;
+/// the most bytes that are decompressed at once
+const CHUNK: usize = 0x4000;
+
+/// decompress lzo1x data while reading
+pub(crate) struct LzoReader<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// literals that were copied by the last instruction, 4 for 4 or more
+    state: usize,
+    first: bool,
+    done: bool,
+    /// pending match, (distance, length)
+    copy: (usize, usize),
+    /// pending literals, copied after the pending match
+    literals: usize,
+    /// the decompressed bytes, only the last [`WINDOW`] bytes that were read are kept
+    window: Vec<u8>,
+    /// bytes of window that were already read
+    read: usize,
+    /// decompressed bytes so far
+    total: usize,
+    uncompressed_size: usize,
+}
+
+impl<'a> LzoReader<'a> {
+    pub fn new(input: &'a [u8], uncompressed_size: usize) -> Self {
+        Self {
+            input,
+            pos: 0,
+            state: 0,
+            first: true,
+            done: false,
+            copy: (0, 0),
+            literals: 0,
+            window: Vec::with_capacity((WINDOW + 2 * CHUNK).min(uncompressed_size)),
+            read: 0,
+            total: 0,
+            uncompressed_size,
+        }
+    }
+
+    fn byte(&mut self) -> io::Result<usize> {
+        let byte = *self.input.get(self.pos).ok_or_else(invalid)?;
+        self.pos += 1;
+        Ok(byte as _)
+    }
+
+    fn le16(&mut self) -> io::Result<usize> {
+        Ok(self.byte()? | self.byte()? << 8)
+    }
+
+    /// length of a run that is stored as zero bytes (255 each) followed by a non zero byte
+    fn zero_run(&mut self) -> io::Result<usize> {
+        let mut len = 0_usize;
+        loop {
+            match self.byte()? {
+                0 => len = len.checked_add(255).ok_or_else(invalid)?,
+                b => return Ok(len + b),
+            }
+        }
+    }
+
+    /// make room for at least `len` more decompressed bytes
+    fn reserve(&mut self, len: usize) -> io::Result<()> {
+        self.total += len;
+        if self.total > self.uncompressed_size {
+            return Err(output_length());
+        }
+        Ok(())
+    }
+
+    /// decompress the next piece, or read the next instruction
+    fn step(&mut self) -> io::Result<()> {
+        let (distance, len) = self.copy;
+        if len != 0 {
+            let n = len.min(CHUNK);
+            self.reserve(n)?;
+            // the match can overlap with the bytes it write, so it's copied byte by byte
+            let start = self.window.len() - distance;
+            for i in start..start + n {
+                self.window.push(self.window[i]);
+            }
+            self.copy.1 -= n;
+            return Ok(());
+        }
+
+        if self.literals != 0 {
+            let n = self.literals.min(CHUNK);
+            let bytes = self.input.get(self.pos..self.pos + n).ok_or_else(invalid)?;
+            self.reserve(n)?;
+            self.window.extend_from_slice(bytes);
+            self.pos += n;
+            self.literals -= n;
+            return Ok(());
+        }
+
+        self.instruction()
+    }
+
+    /// read the next instruction and set the pending match and literals
+    fn instruction(&mut self) -> io::Result<()> {
+        let mut t = self.byte()?;
+
+        if std::mem::take(&mut self.first) && t > 17 {
+            self.literals = t - 17;
+            self.state = self.literals.min(4);
+            return Ok(());
+        }
+
+        let (distance, len, next) = match t {
+            0..=15 => match self.state {
+                0 => {
+                    if t == 0 {
+                        t = 15 + self.zero_run()?;
+                    }
+                    self.literals = t + 3;
+                    self.state = 4;
+                    return Ok(());
+                }
+                4 => (1 + 0x800 + (t >> 2) + (self.byte()? << 2), 3, t & 3),
+                _ => (1 + (t >> 2) + (self.byte()? << 2), 2, t & 3),
+            },
+            16..=31 => {
+                let len = match t & 7 {
+                    0 => 9 + self.zero_run()?,
+                    n => n + 2,
+                };
+                let v = self.le16()?;
+                let distance = ((t & 8) << 11) + (v >> 2);
+                if distance == 0 {
+                    self.done = true;
+                    return match self.total == self.uncompressed_size {
+                        true => Ok(()),
+                        false => Err(output_length()),
+                    };
+                }
+                (distance + 0x4000, len, v & 3)
+            }
+            32..=63 => {
+                let len = match t & 31 {
+                    0 => 33 + self.zero_run()?,
+                    n => n + 2,
+                };
+                let v = self.le16()?;
+                (1 + (v >> 2), len, v & 3)
+            }
+            _ => (
+                1 + ((t >> 2) & 7) + (self.byte()? << 3),
+                (t >> 5) + 1,
+                t & 3,
+            ),
+        };
+
+        if distance > self.window.len() {
+            return Err(invalid());
+        }
+
+        self.copy = (distance, len);
+        self.literals = next;
+        self.state = next;
+        Ok(())
+    }
+}
+
+impl Read for LzoReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read == self.window.len() {
+            if self.done || buf.is_empty() {
+                return Ok(0);
+            }
+
+            // drop the bytes that were read and can't be matched anymore
+            if self.read >= WINDOW + CHUNK {
+                self.window.drain(..self.read - WINDOW);
+                self.read = WINDOW;
+            }
+
+            self.step()?;
+        }
+
+        let n = buf.len().min(self.window.len() - self.read);
+        buf[..n].copy_from_slice(&self.window[self.read..self.read + n]);
+        self.read += n;
+        Ok(n)
+    }
+}
+
+fn invalid() -> io::Error {
+    io::Error::other(DecompressError::Lzo(lzo1x::DecompressError::InvalidInput))
+}
+
+fn output_length() -> io::Error {
+    io::Error::other(DecompressError::Lzo(lzo1x::DecompressError::OutputLength))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decompress(input: &[u8], size: usize, chunk: usize) -> io::Result<Vec<u8>> {
+        let mut reader = LzoReader::new(input, size);
+        let mut output = Vec::new();
+        let mut buf = vec![0; chunk];
+        loop {
+            match reader.read(&mut buf)? {
+                0 => return Ok(output),
+                n => output.extend_from_slice(&buf[..n]),
+            }
+        }
+    }
+
+    /// some text like data with repeats near and far
+    fn sample(len: usize) -> Vec<u8> {
+        let mut seed = 0x1234_5678_u32;
+        let words: [&[u8]; 6] = [
+            b"level",
+            b"script ",
+            b"\x00\x00\x00",
+            b"obscure",
+            b"\n",
+            b"hvp",
+        ];
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            match seed >> 29 {
+                0 => data.push((seed >> 8) as u8),
+                1 if data.len() > 60_000 => {
+                    let start = data.len() - 60_000 + (seed >> 20) as usize % 1000;
+                    data.extend_from_within(start..start + 40);
+                }
+                _ => data.extend_from_slice(words[(seed >> 16) as usize % words.len()]),
+            }
+        }
+        data.truncate(len);
+        data
+    }
+
+    #[test]
+    fn match_lzo1x() {
+        let inputs = [
+            vec![],
+            vec![0xAA],
+            vec![0xAA; 100],
+            vec![0; 1 << 20],
+            sample(300 * 1024),
+            (0..=255).cycle().take(70_000).collect(),
+        ];
+
+        for data in &inputs {
+            for level in [1, 3, 9] {
+                let compressed = lzo1x::compress(data, lzo1x::CompressLevel::new(level));
+                for chunk in [1, 7, 4096, 1 << 20] {
+                    let output = decompress(&compressed, data.len(), chunk).unwrap();
+                    assert!(output == *data, "len {} level {level}", data.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn long_runs_stay_chunked() {
+        // a single match of a few megabytes is decompressed in pieces
+        let data = vec![7; 8 << 20];
+        let compressed = lzo1x::compress(&data, lzo1x::CompressLevel::default());
+        let mut reader = LzoReader::new(&compressed, data.len());
+        let mut buf = [0; 16];
+        let mut total = 0;
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            assert!(buf[..n].iter().all(|&b| b == 7));
+            total += n;
+            assert!(reader.window.len() <= WINDOW + 2 * CHUNK);
+        }
+        assert_eq!(total, data.len());
+    }
+
+    #[test]
+    fn reject_broken_input() {
+        let data = sample(50_000);
+        let compressed = lzo1x::compress(&data, lzo1x::CompressLevel::default());
+
+        // wrong uncompressed size
+        assert!(decompress(&compressed, data.len() - 1, 4096).is_err());
+        assert!(decompress(&compressed, data.len() + 1, 4096).is_err());
+
+        // truncated input
+        for len in [0, 1, compressed.len() / 2, compressed.len() - 1] {
+            assert!(decompress(&compressed[..len], data.len(), 4096).is_err());
+        }
+
+        // a match before the start of the output
+        assert!(decompress(&[0x11 + 1, b'a', 0x40, 0x10], 10, 4096).is_err());
+
+        // endless zero run
+        assert!(decompress(&[0; 4096], usize::MAX, 4096).is_err());
+
+        // garbage never panic
+        let mut seed = 1_u32;
+        for _ in 0..2000 {
+            let garbage: Vec<u8> = (0..64)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                    (seed >> 16) as u8
+                })
+                .collect();
+            let _ = decompress(&garbage, 1024, 64);
+        }
+    }
+}
@@ -1,37 +1,191 @@
 //! a full abstraction over obscure 1 and 2 hvp archives
 
 use std::{
+    cmp::Ordering,
     fmt::Debug,
-    io::{Read, Seek, SeekFrom, Write},
+    io::{Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
 
 use crate::{
     Game,
+    formats::Capability,
     provider::{ArchiveProvider, RawArchive},
+    structures,
 };
 
 use binrw::BinWrite;
 
 pub use obscure2::Obscure2NameMap;
 
-use entry::Entry;
-use error::RebuildError;
-use file_helpers::{FileIterator, FileIteratorMut};
+use entry::{Entry, FullFileEntry};
+use error::{RebuildError, ReorderError};
+use file_helpers::{FileIterator, FileIteratorMut, WalkIterator};
+use raw_index::RawIndex;
 use rebuild_progress::RebuildProgress;
 
+pub mod analyze;
+pub mod apply;
+pub mod builder;
+mod compress;
+pub mod compression_rules;
+pub mod deps;
+pub mod diff;
 pub mod entry;
 pub mod error;
 pub mod file_helpers;
 mod final_exam;
+pub mod info;
+mod layout;
+mod lzo;
 mod obscure1;
 mod obscure2;
+mod order;
+mod orphans;
+pub mod patch;
+pub mod patch_file;
+pub mod plan;
+pub mod raw_index;
 pub mod rebuild_progress;
+mod roundtrip;
+#[cfg(feature = "textures")]
+pub mod thumbnail;
+pub mod toc_patch;
+pub mod verify;
 
 /// archive options
 #[derive(Debug, Default)]
 pub struct Options {
     pub obscure2_names: Obscure2NameMap,
     pub rebuild_skip_compression: bool,
+    /// compression level of the updated files, zlib (0-9) for obscure 1 and lzo (1-13) for
+    /// obscure 2 and final exam. `None` use the level that the game archives use
+    pub compression_level: Option<u8>,
+    /// alignment of the file data in the rebuilt archive, for example 2048 for sector aligned data.
+    /// `None` use the alignment that the game archives use (none for obscure 1 and little endian
+    /// obscure 2, 32 for big endian obscure 2 and 4 for final exam)
+    pub alignment: Option<u32>,
+    pub orphans: OrphanMode,
+    /// when no entry is updated or reordered, make sure that [`Archive::rebuild`] write the
+    /// source archive back byte for byte, and fail with [`RebuildError::RoundtripDiverged`] if not
+    pub guarantee_roundtrip: bool,
+    /// what [`Archive::patch`] do with the zero filled dead space between the files
+    pub dead_space: DeadSpacePolicy,
+}
+
+impl Options {
+    fn compressor(&self) -> compress::Compressor {
+        compress::Compressor {
+            skip: self.rebuild_skip_compression,
+            level: self.compression_level,
+        }
+    }
+}
+
+/// name of the virtual directory that orphan entries get attached to
+pub const ORPHANS_DIR: &str = "_orphans";
+
+/// what to do with entries that aren't reachable from the root directory,
+/// only obscure 2 and final exam archives can have them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OrphanMode {
+    /// ignore the orphan entries
+    #[default]
+    Drop,
+    /// attach the orphan entries under a virtual [`ORPHANS_DIR`] directory at the root.
+    ///
+    /// note: the virtual directory is ignored by [`Archive::rebuild`]
+    Attach,
+}
+
+/// what to do with the zero filled regions between the data of the files when patching an archive,
+/// see [`Archive::dead_space`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeadSpacePolicy {
+    /// leave the regions untouched, updated files that don't fit in place are appended
+    #[default]
+    Preserve,
+    /// write updated files that don't fit in place into the first region that is big enough
+    /// before appending them to the end of the archive
+    Reclaim,
+}
+
+/// a node of the entries tree in pre-order, used to detect structural changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ShapeNode {
+    name: String,
+    /// number of children if the node is a directory
+    children: Option<usize>,
+}
+
+/// the children of every directory are sorted by name, so reordering the entries doesn't change the shape
+fn shape(entries: &[Entry]) -> Vec<ShapeNode> {
+    fn walk(entries: &[Entry], shape: &mut Vec<ShapeNode>) {
+        let mut entries: Vec<_> = entries.iter().collect();
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+
+        for entry in entries {
+            match entry {
+                Entry::File(file) => shape.push(ShapeNode {
+                    name: file.name.clone(),
+                    children: None,
+                }),
+                Entry::Dir(dir) => {
+                    shape.push(ShapeNode {
+                        name: dir.name.clone(),
+                        children: Some(dir.entries.len()),
+                    });
+                    walk(&dir.entries, shape);
+                }
+            }
+        }
+    }
+
+    let mut shape = Vec::new();
+    walk(entries, &mut shape);
+    shape
+}
+
+/// crc32 of the names of the entries in pre-order, used to detect reordered entries
+fn order_crc32(entries: &[Entry]) -> u32 {
+    fn walk(entries: &[Entry], hasher: &mut crc32fast::Hasher) {
+        for entry in entries {
+            hasher.update(entry.name().as_bytes());
+            hasher.update(&[0]);
+            if let Entry::Dir(dir) = entry {
+                walk(&dir.entries, hasher);
+                hasher.update(&[0]);
+            }
+        }
+    }
+
+    let mut hasher = crc32fast::Hasher::new();
+    walk(entries, &mut hasher);
+    hasher.finalize()
+}
+
+/// whatever any file has an update
+fn has_updates(entries: &[Entry]) -> bool {
+    entries.iter().any(|entry| match entry {
+        Entry::File(file) => file.update.is_some(),
+        Entry::Dir(dir) => has_updates(&dir.entries),
+    })
+}
+
+/// byte order of the archive structures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+impl From<binrw::Endian> for Endianness {
+    fn from(endian: binrw::Endian) -> Self {
+        match endian {
+            binrw::Endian::Little => Self::Little,
+            binrw::Endian::Big => Self::Big,
+        }
+    }
 }
 
 /// metadata about the loaded archive
@@ -40,6 +194,32 @@ pub struct Metadata {
     pub dir_count: usize,
     pub file_count: usize,
     pub game: Game,
+    /// length of the header and TOC
+    pub toc_size: u64,
+    /// length of the data of the files, from the end of TOC to the end of the last file data
+    pub data_size: u64,
+    /// length of the whole archive file, including any padding at the end
+    pub archive_len: u64,
+    pub endianness: Endianness,
+    /// the archive store checksums of its files (and TOC for obscure 1),
+    /// old obscure 1 archives doesn't have them
+    pub has_checksums: bool,
+}
+
+impl Metadata {
+    /// metadata without any entries and sizes
+    pub(crate) fn new(game: Game, endianness: Endianness) -> Self {
+        Self {
+            dir_count: 0,
+            file_count: 0,
+            game,
+            toc_size: 0,
+            data_size: 0,
+            archive_len: 0,
+            endianness,
+            has_checksums: true,
+        }
+    }
 }
 
 /// ## archive abstraction over both obscure 1 and 2
@@ -49,6 +229,9 @@ pub struct Archive<'p> {
     provider: &'p ArchiveProvider,
     entries: Box<[Entry<'p>]>,
     metadata: Metadata,
+    orphans: Box<[usize]>,
+    shape: Box<[ShapeNode]>,
+    order: u32,
     pub options: Options,
 }
 
@@ -60,23 +243,60 @@ impl<'p> Archive<'p> {
 
     /// create a new archive with the given provider and options
     pub fn new_with_options(provider: &'p ArchiveProvider, options: Options) -> Self {
-        let (entries, metadata) = match &provider.raw_archive {
+        let mut orphans = Vec::new();
+
+        let (entries, mut metadata) = match &provider.raw_archive {
             RawArchive::Obscure1(hvp) => obscure1::map_entries(provider, &hvp.entries),
-            RawArchive::Obscure2(hvp) => obscure2::map_entries(
-                provider,
-                &hvp.entries,
-                hvp.endian(),
-                &options.obscure2_names,
-            ),
+            RawArchive::Obscure2(hvp) => {
+                let dir_range = |e: &structures::obscure2::Entry| e.kind.dir_range();
+                orphans = orphans::find(&hvp.entries, dir_range);
+                let attach = match options.orphans {
+                    OrphanMode::Drop => Vec::new(),
+                    OrphanMode::Attach => orphans::roots(&hvp.entries, &orphans, dir_range),
+                };
+
+                obscure2::map_entries(
+                    provider,
+                    &hvp.entries,
+                    hvp.endian(),
+                    &options.obscure2_names,
+                    &attach,
+                )
+            }
             RawArchive::FinalExam(hvp) => {
-                final_exam::map_entries(provider, &hvp.entries, hvp.endian(), &hvp.names)
+                let dir_range = |e: &structures::final_exam::Entry| e.kind.dir_range();
+                orphans = orphans::find(&hvp.entries, dir_range);
+                let attach = match options.orphans {
+                    OrphanMode::Drop => Vec::new(),
+                    OrphanMode::Attach => orphans::roots(&hvp.entries, &orphans, dir_range),
+                };
+
+                final_exam::map_entries(provider, &hvp.entries, hvp.endian(), &hvp.names, &attach)
             }
         };
 
+        let data_region = provider.data_region();
+        metadata.toc_size = provider.toc_len();
+        metadata.data_size = data_region.end - data_region.start;
+        metadata.archive_len = provider.file_len();
+        if let RawArchive::Obscure1(hvp) = &provider.raw_archive {
+            metadata.has_checksums = hvp.checksums.is_some();
+        }
+
+        if !orphans.is_empty() {
+            log::warn!(
+                "found {} entries that aren't reachable from the root directory",
+                orphans.len()
+            );
+        }
+
         Self {
             provider,
+            shape: shape(&entries).into_boxed_slice(),
+            order: order_crc32(&entries),
             entries: entries.into_boxed_slice(),
             metadata,
+            orphans: orphans.into_boxed_slice(),
             options,
         }
     }
@@ -106,6 +326,51 @@ impl<'p> Archive<'p> {
         FileIteratorMut::new(&mut self.entries, self.metadata.file_count)
     }
 
+    /// return a depth first iterator over every file and directory in the archive,
+    /// in the same order that they are stored
+    #[inline(always)]
+    pub fn walk(&self) -> WalkIterator<'_, 'p> {
+        WalkIterator::new(&self.entries)
+    }
+
+    /// reorder the entries of the directory at `dir` (empty for the root), see [`entry::DirEntry::reorder`].
+    ///
+    /// the attached [`ORPHANS_DIR`] always stay the last entry of the root
+    pub fn reorder<S: AsRef<str>>(
+        &mut self,
+        dir: impl AsRef<Path>,
+        order: &[S],
+    ) -> Result<(), ReorderError> {
+        let dir = dir.as_ref();
+
+        let mut entries = &mut self.entries[..];
+        if dir.as_os_str().is_empty()
+            && self.options.orphans == OrphanMode::Attach
+            && entries.last().is_some_and(|e| e.name() == ORPHANS_DIR)
+        {
+            let len = entries.len() - 1;
+            entries = &mut entries[..len];
+        }
+
+        for name in dir.iter() {
+            entries = entries
+                .iter_mut()
+                .find_map(|e| match e {
+                    Entry::Dir(d) if *d.name == *name => Some(&mut d.entries[..]),
+                    _ => None,
+                })
+                .ok_or_else(|| ReorderError::MissingDir(dir.to_owned()))?;
+        }
+
+        order::reorder_entries(entries, order)
+    }
+
+    /// find a file by its full path inside the archive
+    pub fn find_file(&self, path: impl AsRef<Path>) -> Option<FullFileEntry<'p>> {
+        let path = path.as_ref();
+        self.files().find(|f| f.path == path)
+    }
+
     /// check whatever checksum of all entries are valid or not.
     pub fn entries_checksum_match(&self) -> bool {
         fn check_entry(entry: &Entry) -> bool {
@@ -118,69 +383,153 @@ impl<'p> Archive<'p> {
         self.entries.iter().all(check_entry)
     }
 
+    /// index of the entries in the raw entry table that aren't reachable from the root directory,
+    /// always empty for obscure 1 archives
+    #[inline(always)]
+    pub fn orphans(&self) -> &[usize] {
+        &self.orphans
+    }
+
+    /// where every entry is stored in the entry table of the source archive, by its path and
+    /// in the same order as [`Archive::walk`].
+    ///
+    /// reordered entries keep their original index, entries that aren't in the source archive
+    /// (like renamed ones) and the virtual [`ORPHANS_DIR`] directory are left out
+    pub fn raw_index_map(&self) -> Vec<(PathBuf, RawIndex)> {
+        match &self.provider.raw_archive {
+            RawArchive::Obscure1(hvp) => raw_index::nested(
+                &self.entries,
+                &hvp.entries,
+                |e| match &e.kind {
+                    structures::obscure1::EntryKind::Dir(dir) => Some(&dir.entries),
+                    structures::obscure1::EntryKind::File(_) => None,
+                },
+                obscure1::same_entry,
+            ),
+            RawArchive::Obscure2(hvp) => raw_index::flat(
+                self,
+                &hvp.entries,
+                |e| e.kind.dir_range(),
+                obscure2::same_entry,
+            ),
+            RawArchive::FinalExam(hvp) => raw_index::flat(
+                self,
+                &hvp.entries,
+                |e| e.kind.dir_range(),
+                |r, e| final_exam::same_entry(&hvp.names, r, e),
+            ),
+        }
+    }
+
     /// get the metadata about the current loaded archive
     pub fn metadata(&self) -> Metadata {
         self.metadata
     }
 
+    /// operations that the format of this archive support, some of them are
+    /// only available using [`builder::ArchiveBuilder`]
+    pub fn capabilities(&self) -> &'static [Capability] {
+        self.metadata.game.format().capabilities
+    }
+
+    /// make sure that the entries tree has the same structure that it had when the archive was loaded,
+    /// [`Archive::rebuild`] and [`Archive::patch`] can only update the data of the files and the
+    /// order of the entries
+    fn check_shape(&self) -> Result<(), RebuildError> {
+        let current = shape(&self.entries);
+        if *current == *self.shape {
+            return Ok(());
+        }
+
+        let capability = match current.len().cmp(&self.shape.len()) {
+            Ordering::Greater => Capability::Add,
+            Ordering::Less => Capability::Remove,
+            Ordering::Equal => Capability::Rename,
+        };
+
+        Err(RebuildError::UnsupportedOperation(capability))
+    }
+
     /// rebuild the archive and write it to the given writer.
     pub fn rebuild<W: Write + Seek, P: RebuildProgress>(
         &self,
         writer: &mut W,
         progress: P,
     ) -> Result<(), RebuildError> {
-        let start_pos = writer.stream_position()?;
+        if !self.provider.has_data() {
+            return Err(RebuildError::NoData);
+        }
+        self.check_shape()?;
 
-        // we skip the size of entries, so we can write them back after
-        // writing the bytes
-        if writer
-            .seek(SeekFrom::Current(self.provider.entries_offset as _))
-            .is_err()
-        {
-            // in case of seek failing
-            std::io::copy(
-                &mut std::io::repeat(0).take(self.provider.entries_offset as _),
-                writer,
-            )?;
+        let unchanged = order_crc32(&self.entries) == self.order && !has_updates(&self.entries);
+        if self.options.guarantee_roundtrip && unchanged {
+            let mut writer = roundtrip::RoundtripWriter::new(writer, &self.provider.source)?;
+            self.write_entries(&mut writer, progress)?;
+            return writer.finish();
         }
 
-        let offset = writer.stream_position()? as _;
+        self.write_entries(writer, progress)
+    }
 
+    fn write_entries<W: Write + Seek, P: RebuildProgress>(
+        &self,
+        writer: &mut W,
+        progress: P,
+    ) -> Result<(), RebuildError> {
+        let compression = self.options.compressor();
+        progress.start(self.estimated_data_size());
+
+        // the TOC is written after the data, so we only skip its size here
         match &self.provider.raw_archive {
             RawArchive::Obscure1(archive) => {
+                let mut archive = archive.clone();
+                let toc = layout::skip_toc(writer, layout::plan_obscure1(&mut archive)?)?;
+                let offset = writer.stream_position()? as _;
+
                 let archive = obscure1::update_entries(
                     writer,
                     offset,
-                    self.options.rebuild_skip_compression,
-                    archive.clone(),
+                    compression,
+                    self.options.alignment,
+                    archive,
                     &self.entries,
                     progress,
                 )?;
 
                 // write the entries back
-                writer.seek(SeekFrom::Start(start_pos))?;
+                writer.seek(SeekFrom::Start(toc.start))?;
                 archive.write_be(writer)?;
             }
             RawArchive::Obscure2(archive) => {
+                let mut archive = archive.clone();
+                let toc = layout::skip_toc(writer, layout::plan_obscure2(&mut archive)?)?;
+                let offset = writer.stream_position()? as _;
+
                 let archive = obscure2::update_entries(
                     writer,
                     offset,
-                    self.options.rebuild_skip_compression,
-                    archive.clone(),
+                    compression,
+                    self.options.alignment,
+                    archive,
                     &self.entries,
                     &self.options.obscure2_names,
                     progress,
                 )?;
 
                 // write the entries back
-                writer.seek(SeekFrom::Start(start_pos))?;
+                writer.seek(SeekFrom::Start(toc.start))?;
                 archive.write(writer)?;
             }
             RawArchive::FinalExam(archive) => {
+                let mut archive = archive.clone();
+                let toc = layout::skip_toc(writer, layout::plan_final_exam(&mut archive)?)?;
+                let offset = writer.stream_position()? as _;
+
                 let archive = final_exam::update_entries(
                     writer,
                     offset,
-                    self.options.rebuild_skip_compression,
+                    compression,
+                    self.options.alignment,
                     archive.clone(),
                     &self.entries,
                     &archive.names,
@@ -188,7 +537,7 @@ impl<'p> Archive<'p> {
                 )?;
 
                 // write the entries back
-                writer.seek(SeekFrom::Start(start_pos))?;
+                writer.seek(SeekFrom::Start(toc.start))?;
                 archive.write(writer)?;
             }
         }
@@ -213,3 +562,126 @@ impl<'p> Debug for Archive<'p> {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::test_utils::{EmptyProgress, Format, for_each_fixture};
+
+    #[test]
+    fn find_every_fixture_file() {
+        for_each_fixture(|_, provider| {
+            let archive = Archive::new(provider);
+
+            for file in archive.files() {
+                let found = archive
+                    .find_file(&file.path)
+                    .unwrap_or_else(|| panic!("can't find {}", file.path.display()));
+                assert_eq!(found.raw_bytes, file.raw_bytes);
+            }
+
+            assert!(archive.find_file("not/in/archive.bin").is_none());
+        });
+    }
+
+    #[test]
+    fn fixtures_capabilities() {
+        for_each_fixture(|fixture, provider| {
+            let capabilities = Archive::new(provider).capabilities();
+
+            assert!(capabilities.contains(&Capability::Replace));
+            assert!(capabilities.contains(&Capability::Reorder));
+            assert_eq!(
+                capabilities.contains(&Capability::EndianConvert),
+                fixture.format != Format::Obscure1
+            );
+        });
+    }
+
+    #[test]
+    fn rebuild_rejects_structural_changes() {
+        fn first_dir<'a, 'p>(archive: &'a mut Archive<'p>) -> &'a mut Vec<Entry<'p>> {
+            archive
+                .entries_mut()
+                .iter_mut()
+                .find_map(|e| match e {
+                    Entry::Dir(dir) if dir.entries.len() > 1 => Some(&mut dir.entries),
+                    _ => None,
+                })
+                .expect("fixture doesn't have a directory with multiple entries")
+        }
+
+        type Change = fn(&mut Archive);
+
+        let changes: [(Capability, Change); 3] = [
+            (Capability::Add, |a| {
+                let dir = first_dir(a);
+                dir.push(dir[0].clone());
+            }),
+            (Capability::Remove, |a| {
+                first_dir(a).pop();
+            }),
+            (Capability::Rename, |a| {
+                let Some(Entry::Dir(dir)) = a
+                    .entries_mut()
+                    .iter_mut()
+                    .find(|e| matches!(e, Entry::Dir(_)))
+                else {
+                    unreachable!()
+                };
+                dir.name.push_str("_renamed");
+            }),
+        ];
+
+        for_each_fixture(|_, provider| {
+            for (capability, change) in &changes {
+                let mut archive = Archive::new(provider);
+                change(&mut archive);
+
+                let result = archive.rebuild(&mut Cursor::new(Vec::new()), EmptyProgress);
+                assert!(
+                    matches!(result, Err(RebuildError::UnsupportedOperation(c)) if c == *capability),
+                    "{capability:?} {result:?}"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn walk_visit_every_fixture_entry() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            let walked: Vec<_> = archive.walk().collect();
+            let dirs = walked
+                .iter()
+                .filter(|e| matches!(e.entry, Entry::Dir(_)))
+                .count();
+            assert_eq!(dirs, fixture.dir_count);
+
+            // files are visited in the same order as `files`
+            let files: Vec<_> = walked
+                .iter()
+                .filter(|e| matches!(e.entry, Entry::File(_)))
+                .map(|e| e.path.clone())
+                .collect();
+            let expected: Vec<_> = archive.files().map(|f| f.path).collect();
+            assert_eq!(files, expected);
+
+            for entry in &walked {
+                assert_eq!(
+                    entry.depth + 1,
+                    entry.path.components().count(),
+                    "{}",
+                    entry.path.display()
+                );
+            }
+
+            // the root and every directory have a single last entry
+            let last = walked.iter().filter(|e| e.is_last).count();
+            assert_eq!(last, dirs + 1);
+        });
+    }
+}
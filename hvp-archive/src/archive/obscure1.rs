@@ -1,12 +1,14 @@
-use std::io::Write;
+use std::borrow::Cow;
+use std::io::{Read, Seek, Write};
 
 use binrw::Endian;
-use flate2::{Compress, Compression, FlushCompress};
 
-use super::Metadata;
+use super::compress::Compressor;
 use super::entry::{CompressionInfo, CompressionType, DirEntry, Entry, FileEntry};
 use super::error::RebuildError;
-use super::rebuild_progress::RebuildProgress;
+use super::patch::Patcher;
+use super::rebuild_progress::{RebuildProgress, write_data};
+use super::{Endianness, Metadata, order};
 use crate::Game;
 use crate::provider::ArchiveProvider;
 use crate::structures::{checksum, obscure1};
@@ -18,11 +20,7 @@ pub fn map_entries<'p>(
 ) -> (Vec<Entry<'p>>, Metadata) {
     let mut process = Process {
         provider,
-        metadata: Metadata {
-            dir_count: 0,
-            file_count: 0,
-            game: Game::Obscure1,
-        },
+        metadata: Metadata::new(Game::Obscure1, Endianness::Big),
     };
 
     let entries = entries
@@ -68,6 +66,7 @@ impl<'p> Process<'p> {
             endian: Endian::Little,
             raw_bytes,
             update: None,
+            user_data: None,
         })
     }
 
@@ -83,6 +82,7 @@ impl<'p> Process<'p> {
         Entry::Dir(DirEntry {
             name: entry.name.clone(),
             entries,
+            user_data: None,
         })
     }
 }
@@ -91,7 +91,8 @@ impl<'p> Process<'p> {
 pub fn update_entries<W: Write, P: RebuildProgress>(
     writer: &mut W,
     offset: u32,
-    skip_compression: bool,
+    compression: Compressor,
+    alignment: Option<u32>,
     mut archive: obscure1::HvpArchive,
     entries: &[Entry],
     progress: P,
@@ -106,20 +107,12 @@ pub fn update_entries<W: Write, P: RebuildProgress>(
         writer,
         progress,
         offset,
-        skip_compression,
+        compression,
+        // the game archives don't align the data
+        alignment: alignment.unwrap_or(1).max(1),
     };
 
-    for (o, u) in archive.entries.iter_mut().zip(entries) {
-        match (&mut o.kind, u) {
-            (obscure1::EntryKind::Dir(o_entry), Entry::Dir(u_entry)) => {
-                updater.process_dir(o_entry, u_entry)?;
-            }
-            (obscure1::EntryKind::File(o_entry), Entry::File(u_entry)) => {
-                updater.process_file(o_entry, u_entry)?;
-            }
-            _ => unreachable!(),
-        }
-    }
+    updater.process_entries(&mut archive.entries, entries)?;
 
     Ok(archive)
 }
@@ -129,7 +122,8 @@ struct Updater<'a, W: Write, P: RebuildProgress> {
     writer: &'a mut W,
     progress: P,
     offset: u32,
-    skip_compression: bool,
+    compression: Compressor,
+    alignment: u32,
 }
 
 impl<W: Write, P: RebuildProgress> Updater<'_, W, P> {
@@ -138,16 +132,21 @@ impl<W: Write, P: RebuildProgress> Updater<'_, W, P> {
         o_entry: &mut obscure1::FileEntry,
         u_entry: &FileEntry,
     ) -> Result<(), RebuildError> {
+        if u_entry.update.is_none() {
+            sync_toc(o_entry, u_entry);
+        }
+
         if o_entry.uncompressed_size == 0 {
             self.progress.inc(Some(format!("(skp) {}", o_entry.name)));
             return Ok(());
         }
 
+        self.apply_padding()?;
         o_entry.offset = self.offset;
 
         let Some(update) = &u_entry.update else {
             self.progress.inc(Some(format!("(src) {}", o_entry.name)));
-            self.writer.write_all(u_entry.raw_bytes)?;
+            write_data(self.writer, &self.progress, u_entry.raw_bytes)?;
             self.offset += u_entry.raw_bytes.len() as u32;
             return Ok(());
         };
@@ -156,8 +155,8 @@ impl<W: Write, P: RebuildProgress> Updater<'_, W, P> {
 
         self.progress.inc(Some(format!("(upd) {}", o_entry.name)));
 
-        if self.skip_compression || !o_entry.is_compressed {
-            self.writer.write_all(&bytes)?;
+        if self.compression.skip || !o_entry.is_compressed {
+            write_data(self.writer, &self.progress, &bytes)?;
             self.offset += bytes.len() as u32;
             o_entry.compressed_size = bytes.len() as _;
             o_entry.uncompressed_size = bytes.len() as _;
@@ -166,14 +165,9 @@ impl<W: Write, P: RebuildProgress> Updater<'_, W, P> {
             return Ok(());
         }
 
-        let mut compressed_buf = Vec::with_capacity(deflate_bound(bytes.len()));
-        Compress::new(Compression::best(), true).compress_vec(
-            &bytes,
-            &mut compressed_buf,
-            FlushCompress::Finish,
-        )?;
+        let compressed_buf = self.compression.zlib(&bytes)?;
 
-        self.writer.write_all(&compressed_buf)?;
+        write_data(self.writer, &self.progress, &compressed_buf)?;
         self.offset += compressed_buf.len() as u32;
         o_entry.compressed_size = compressed_buf.len() as _;
         o_entry.uncompressed_size = bytes.len() as _;
@@ -182,15 +176,17 @@ impl<W: Write, P: RebuildProgress> Updater<'_, W, P> {
         Ok(())
     }
 
-    fn process_dir(
+    fn process_entries(
         &mut self,
-        o_entry: &mut obscure1::DirEntry,
-        u_entry: &DirEntry,
+        o_entries: &mut [obscure1::Entry],
+        u_entries: &[Entry],
     ) -> Result<(), RebuildError> {
-        for (o, u) in o_entry.entries.iter_mut().zip(&u_entry.entries) {
+        order::sync_order(o_entries, u_entries, same_entry)?;
+
+        for (o, u) in o_entries.iter_mut().zip(u_entries) {
             match (&mut o.kind, u) {
                 (obscure1::EntryKind::Dir(o_entry), Entry::Dir(u_entry)) => {
-                    self.process_dir(o_entry, u_entry)?;
+                    self.process_entries(&mut o_entry.entries, &u_entry.entries)?;
                 }
                 (obscure1::EntryKind::File(o_entry), Entry::File(u_entry)) => {
                     self.process_file(o_entry, u_entry)?;
@@ -201,8 +197,106 @@ impl<W: Write, P: RebuildProgress> Updater<'_, W, P> {
 
         Ok(())
     }
+
+    #[inline]
+    fn apply_padding(&mut self) -> std::io::Result<()> {
+        if !self.offset.is_multiple_of(self.alignment) {
+            let padding = self.alignment - (self.offset % self.alignment);
+            std::io::copy(&mut std::io::repeat(0).take(padding as _), self.writer)?;
+            self.offset += padding;
+        }
+
+        Ok(())
+    }
 }
 
-fn deflate_bound(source_len: usize) -> usize {
-    source_len + (source_len >> 12) + (source_len >> 14) + 11 - ((source_len >> 1) & 1)
+/// patch the updated entries into the archive file and return the updated archive
+pub fn patch_entries<F: Write + Seek>(
+    patcher: &mut Patcher<F>,
+    compression: Compressor,
+    mut archive: obscure1::HvpArchive,
+    entries: &[Entry],
+) -> Result<obscure1::HvpArchive, RebuildError> {
+    assert_eq!(
+        archive.entries.len(),
+        entries.len(),
+        "size of entries doesn't match"
+    );
+
+    fn patch<F: Write + Seek>(
+        patcher: &mut Patcher<F>,
+        compression: Compressor,
+        o_entries: &mut [obscure1::Entry],
+        u_entries: &[Entry],
+    ) -> Result<(), RebuildError> {
+        order::sync_order(o_entries, u_entries, same_entry)?;
+
+        for (o, u) in o_entries.iter_mut().zip(u_entries) {
+            match (&mut o.kind, u) {
+                (obscure1::EntryKind::Dir(o_entry), Entry::Dir(u_entry)) => {
+                    patch(patcher, compression, &mut o_entry.entries, &u_entry.entries)?;
+                }
+                (obscure1::EntryKind::File(o_entry), Entry::File(u_entry)) => {
+                    let Some(update) = &u_entry.update else {
+                        sync_toc(o_entry, u_entry);
+                        continue;
+                    };
+
+                    if o_entry.uncompressed_size == 0 {
+                        continue;
+                    }
+
+                    let bytes = update.to_bytes()?;
+
+                    o_entry.uncompressed_size = bytes.len() as _;
+                    let bytes = if compression.skip || !o_entry.is_compressed {
+                        o_entry.is_compressed = false;
+                        bytes
+                    } else {
+                        Cow::Owned(compression.zlib(&bytes)?)
+                    };
+
+                    o_entry.offset =
+                        patcher.place(o_entry.offset, o_entry.compressed_size, &bytes)?;
+                    o_entry.compressed_size = bytes.len() as _;
+                    o_entry.checksum = checksum::bytes_sum(&bytes, Endian::Little);
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(())
+    }
+
+    patch(patcher, compression, &mut archive.entries, entries)?;
+
+    Ok(archive)
+}
+
+/// copy the TOC fields of a file that its data isn't updated, they can only be changed
+/// using [`super::Archive::apply_toc_patch`]
+fn sync_toc(o_entry: &mut obscure1::FileEntry, u_entry: &FileEntry) {
+    o_entry.checksum = u_entry.checksum;
+    match u_entry.compression_info {
+        Some(info) => {
+            o_entry.uncompressed_size = info.uncompressed_size;
+            o_entry.is_compressed = true;
+        }
+        None => {
+            if o_entry.is_compressed {
+                o_entry.uncompressed_size = u_entry.raw_bytes.len() as _;
+            }
+            o_entry.is_compressed = false;
+        }
+    }
+}
+
+/// whatever the raw entry is the one that the entry was read from
+pub(super) fn same_entry(raw: &obscure1::Entry, entry: &Entry) -> bool {
+    let name = match &raw.kind {
+        obscure1::EntryKind::Dir(dir) => &dir.name,
+        obscure1::EntryKind::File(file) => &file.name,
+    };
+
+    *name == entry.name()
 }
@@ -1,22 +1,27 @@
-use std::io::{Read, Write};
+use std::borrow::Cow;
+use std::io::{Read, Seek, Write};
 use std::ops::Range;
 
 use binrw::Endian;
 
-use super::Metadata;
+use super::compress::Compressor;
 use super::entry::{CompressionInfo, CompressionType, DirEntry, Entry, FileEntry};
 use super::error::RebuildError;
-use super::rebuild_progress::RebuildProgress;
+use super::patch::Patcher;
+use super::rebuild_progress::{RebuildProgress, write_data};
+use super::{Metadata, ORPHANS_DIR, order};
 use crate::Game;
 use crate::provider::ArchiveProvider;
 use crate::structures::{checksum, obscure2};
 
-/// map the entries and return them plus the number of files
+/// map the entries and return them plus the number of files,
+/// entries at `orphans` indices are attached under a virtual [`ORPHANS_DIR`] directory
 pub fn map_entries<'p>(
     provider: &'p ArchiveProvider,
     entries: &[obscure2::Entry],
     endian: Endian,
     name_map: &Obscure2NameMap,
+    orphans: &[usize],
 ) -> (Vec<Entry<'p>>, Metadata) {
     // we ignore the root dir, because it really don't serve any purpose except adding one layer of nesting
     // we can manually add it when we are writing the entries back
@@ -36,19 +41,29 @@ pub fn map_entries<'p>(
         entries,
         endian,
         name_map,
-        metadata: Metadata {
-            dir_count: 0,
-            file_count: 0,
-            game: Game::Obscure2,
-        },
+        metadata: Metadata::new(Game::Obscure2, endian.into()),
     };
 
-    let entries = entries[1..1 + root_count]
+    let mut mapped: Vec<_> = entries[1..1 + root_count]
         .iter()
         .map(|entry| process.process_entry(entry))
         .collect();
 
-    (entries, process.metadata)
+    if !orphans.is_empty() {
+        let orphans = orphans
+            .iter()
+            .map(|&index| process.process_entry(&entries[index]))
+            .collect();
+
+        process.metadata.dir_count += 1;
+        mapped.push(Entry::Dir(DirEntry {
+            name: ORPHANS_DIR.to_owned(),
+            entries: orphans,
+            user_data: None,
+        }));
+    }
+
+    (mapped, process.metadata)
 }
 
 /// a helper for processing obscure 2 entries
@@ -103,6 +118,7 @@ impl<'p> Process<'p, '_, '_> {
                 .provider
                 .get_bytes(entry.offset as _, entry.compressed_size as _),
             update: None,
+            user_data: None,
         })
     }
 
@@ -124,6 +140,7 @@ impl<'p> Process<'p, '_, '_> {
         let mut dir = DirEntry {
             name,
             entries: Vec::with_capacity(entry.count as usize),
+            user_data: None,
         };
 
         self.metadata.dir_count += 1;
@@ -150,10 +167,12 @@ impl<'p> Process<'p, '_, '_> {
 }
 
 /// update the archive entries based on the mapped entries
+#[allow(clippy::too_many_arguments)]
 pub fn update_entries<W: Write, P: RebuildProgress>(
     writer: &mut W,
     offset: u32,
-    skip_compression: bool,
+    compression: Compressor,
+    alignment: Option<u32>,
     mut archive: obscure2::HvpArchive,
     entries: &[Entry],
     name_map: &Obscure2NameMap,
@@ -176,16 +195,23 @@ pub fn update_entries<W: Write, P: RebuildProgress>(
         writer,
         progress,
         offset,
-        skip_compression,
+        compression,
         name_map,
         endian: archive.endian(),
+        // BigEndian version have 32 padding
+        alignment: alignment
+            .unwrap_or(match archive.endian() {
+                Endian::Big => 32,
+                Endian::Little => 1,
+            })
+            .max(1),
         last_padding: None,
     };
 
-    if updater.endian == Endian::Big {
-        // we need to apply padding after the entris
-        updater.caculate_padding();
-    }
+    // we need to apply padding after the entris
+    updater.caculate_padding();
+
+    order::sync_order(&mut archive.entries[1..1 + root_count], entries, same_entry)?;
 
     let mut entries_iter = entries.iter();
     for o_entry_idx in 1..1 + root_count {
@@ -204,10 +230,10 @@ pub struct Updater<'a, 'n, W: Write, P: RebuildProgress> {
     writer: &'a mut W,
     progress: P,
     offset: u32,
-    skip_compression: bool,
+    compression: Compressor,
     name_map: &'n Obscure2NameMap,
-    // BigEndian version have 32 padding
     endian: Endian,
+    alignment: u32,
     // we do this because we don't want to apply padding to last
     // file, in this way each call to `apply_padding` will apply
     // last padding instead of current want and keep the current
@@ -232,15 +258,18 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
             Entry::File(u_entry),
         ) = (&mut entries[o_entry_idx].kind, u_entry)
         {
-            if self.endian == Endian::Big {
-                self.apply_padding()?;
-            }
-
-            self.process_file(entries[o_entry_idx].name_crc32, o_entry, u_entry)?;
+            self.apply_padding()?;
 
-            if self.endian == Endian::Big {
-                self.caculate_padding();
+            let is_compressed =
+                self.process_file(entries[o_entry_idx].name_crc32, o_entry, u_entry)?;
+            if !is_compressed {
+                entries[o_entry_idx].kind.set_uncompressed();
             }
+            if u_entry.update.is_none() {
+                sync_toc(&mut entries[o_entry_idx].kind, u_entry);
+            }
+
+            self.caculate_padding();
             Ok(())
         } else if let (obscure2::EntryKind::Directory(o_entry), Entry::Dir(u_entry)) =
             (&entries[o_entry_idx].kind, u_entry)
@@ -251,17 +280,13 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
         }
     }
 
+    /// write the data of the file, return whatever the written data is compressed
     fn process_file(
         &mut self,
         name_crc32: u32,
         o_entry: &mut obscure2::FileEntry,
         u_entry: &FileEntry,
-    ) -> Result<(), RebuildError> {
-        assert_eq!(
-            o_entry.checksum, u_entry.checksum,
-            "checksum original entry and updated entry doesn't match"
-        );
-
+    ) -> Result<bool, RebuildError> {
         let name = self
             .name_map
             .get_name(name_crc32)
@@ -271,40 +296,40 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
         if o_entry.uncompressed_size == 0 {
             self.progress.inc(Some(format!("(skp) {name}")));
 
-            return Ok(());
+            return Ok(u_entry.is_compressed());
         }
 
         o_entry.offset = self.offset;
 
         let Some(update) = &u_entry.update else {
             self.progress.inc(Some(format!("(src) {name}")));
-            self.writer.write_all(u_entry.raw_bytes)?;
+            write_data(self.writer, &self.progress, u_entry.raw_bytes)?;
             self.offset += u_entry.raw_bytes.len() as u32;
-            return Ok(());
+            return Ok(u_entry.is_compressed());
         };
 
         let bytes = update.to_bytes()?;
 
         self.progress.inc(Some(format!("(upd) {name}")));
 
-        if self.skip_compression || !u_entry.is_compressed() {
-            self.writer.write_all(&bytes)?;
+        if self.compression.skip || !u_entry.is_compressed() {
+            write_data(self.writer, &self.progress, &bytes)?;
             self.offset += bytes.len() as u32;
             o_entry.compressed_size = bytes.len() as _;
             o_entry.uncompressed_size = bytes.len() as _;
             o_entry.checksum = checksum::bytes_sum(&bytes, self.endian);
-            return Ok(());
+            return Ok(false);
         }
 
-        let compressed_bytes = lzo1x::compress(&bytes, lzo1x::CompressLevel::default());
+        let compressed_bytes = self.compression.lzo(&bytes, 3);
 
-        self.writer.write_all(&compressed_bytes)?;
+        write_data(self.writer, &self.progress, &compressed_bytes)?;
         self.offset += compressed_bytes.len() as u32;
         o_entry.compressed_size = compressed_bytes.len() as _;
         o_entry.uncompressed_size = bytes.len() as _;
         o_entry.checksum = checksum::bytes_sum(&compressed_bytes, self.endian);
 
-        Ok(())
+        Ok(true)
     }
 
     fn process_dir(
@@ -313,6 +338,8 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
         range: Range<usize>,
         entries: &mut [obscure2::Entry],
     ) -> Result<(), RebuildError> {
+        order::sync_order(&mut entries[range.clone()], &u_entry.entries, same_entry)?;
+
         let mut entries_iter = u_entry.entries.iter();
         for o_entry_idx in range {
             let Some(u_entry) = entries_iter.next() else {
@@ -327,8 +354,8 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
 
     #[inline]
     fn caculate_padding(&mut self) {
-        if !self.offset.is_multiple_of(32) {
-            self.last_padding = Some(32 - (self.offset % 32))
+        if !self.offset.is_multiple_of(self.alignment) {
+            self.last_padding = Some(self.alignment - (self.offset % self.alignment))
         }
     }
 
@@ -343,8 +370,102 @@ impl<W: Write, P: RebuildProgress> Updater<'_, '_, W, P> {
     }
 }
 
+/// patch the updated entries into the archive file and return the updated archive
+pub fn patch_entries<F: Write + Seek>(
+    patcher: &mut Patcher<F>,
+    compression: Compressor,
+    mut archive: obscure2::HvpArchive,
+    entries: &[Entry],
+) -> Result<obscure2::HvpArchive, RebuildError> {
+    let root_count = match &archive.entries[0] {
+        obscure2::Entry {
+            name_crc32: 0,
+            kind:
+                obscure2::EntryKind::Directory(obscure2::DirEntry {
+                    index: 1, count, ..
+                }),
+        } => *count as usize,
+        _ => unreachable!("found a hvp without valid root entry"),
+    };
+
+    fn patch_dir<F: Write + Seek>(
+        patcher: &mut Patcher<F>,
+        compression: Compressor,
+        endian: Endian,
+        range: Range<usize>,
+        u_entries: &[Entry],
+        o_entries: &mut [obscure2::Entry],
+    ) -> Result<(), RebuildError> {
+        order::sync_order(&mut o_entries[range.clone()], u_entries, same_entry)?;
+
+        for (o_entry_idx, u_entry) in range.zip(u_entries) {
+            match (&mut o_entries[o_entry_idx].kind, u_entry) {
+                (
+                    obscure2::EntryKind::File(o_entry)
+                    | obscure2::EntryKind::FileCompressed(o_entry),
+                    Entry::File(u_entry),
+                ) => {
+                    let Some(update) = &u_entry.update else {
+                        sync_toc(&mut o_entries[o_entry_idx].kind, u_entry);
+                        continue;
+                    };
+
+                    if o_entry.uncompressed_size == 0 {
+                        continue;
+                    }
+
+                    let bytes = update.to_bytes()?;
+
+                    o_entry.uncompressed_size = bytes.len() as _;
+                    let is_compressed = !compression.skip && u_entry.is_compressed();
+                    let bytes = if is_compressed {
+                        Cow::Owned(compression.lzo(&bytes, 3))
+                    } else {
+                        bytes
+                    };
+
+                    o_entry.offset =
+                        patcher.place(o_entry.offset, o_entry.compressed_size, &bytes)?;
+                    o_entry.compressed_size = bytes.len() as _;
+                    o_entry.checksum = checksum::bytes_sum(&bytes, endian);
+
+                    if !is_compressed {
+                        o_entries[o_entry_idx].kind.set_uncompressed();
+                    }
+                }
+                (obscure2::EntryKind::Directory(o_entry), Entry::Dir(u_entry)) => {
+                    let range = o_entry.entries_range();
+                    patch_dir(
+                        patcher,
+                        compression,
+                        endian,
+                        range,
+                        &u_entry.entries,
+                        o_entries,
+                    )?;
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(())
+    }
+
+    let endian = archive.endian();
+    patch_dir(
+        patcher,
+        compression,
+        endian,
+        1..1 + root_count,
+        entries,
+        &mut archive.entries,
+    )?;
+
+    Ok(archive)
+}
+
 /// obscure 2 name map (also used by alone in the dark 2008)
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Obscure2NameMap(ahash::HashMap<u32, String>);
 
 impl Obscure2NameMap {
@@ -366,11 +487,42 @@ impl Obscure2NameMap {
         Self(map)
     }
 
+    /// add more names to the map, names that are already in it are replaced
+    pub fn extend<I>(&mut self, names: I)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.0.extend(names.into_iter().map(|n| {
+            let name = n.as_ref().to_owned();
+            (get_name_crc32(&name), name)
+        }));
+    }
+
+    /// crc32 of a name in the same way that the game compute it, return `None`
+    /// if the name have a character that can't be stored in the archive
+    pub fn name_crc32(name: &str) -> Option<u32> {
+        name.chars()
+            .all(|c| c.is_ascii() || c == 'é')
+            .then(|| get_name_crc32(name))
+    }
+
+    /// crc32 that is stored in the archive for an entry with this name, names of unknown entries
+    /// (`unk_file_{crc32}.dat` and `unk_folder_{crc32}`) are turned back to their original crc32
+    pub fn stored_crc32(name: &str) -> Option<u32> {
+        build_name_crc32(name)
+    }
+
     /// get a name using crc32 of it
     pub fn get_name(&self, crc32: u32) -> Option<&str> {
         self.0.get(&crc32).map(String::as_str)
     }
 
+    /// whatever the name is inside the name map
+    pub fn contains_name(&self, name: &str) -> bool {
+        self.0.contains_key(&get_name_crc32(name))
+    }
+
     pub fn get_crc32_from_name(&self, name: &str) -> u32 {
         let crc32 = get_name_crc32(name);
 
@@ -383,6 +535,23 @@ impl Obscure2NameMap {
     }
 }
 
+/// crc32 of a name that is used when building a new archive.
+///
+/// names of unknown entries (`unk_file_{crc32}.dat` and `unk_folder_{crc32}`) are turned back to
+/// their original crc32, return `None` if the name can't be stored in the archive
+pub(super) fn build_name_crc32(name: &str) -> Option<u32> {
+    let unknown = name
+        .strip_prefix("unk_file_")
+        .and_then(|n| n.strip_suffix(".dat"))
+        .or_else(|| name.strip_prefix("unk_folder_"));
+
+    if let Some(crc32) = unknown.and_then(|n| n.parse().ok()) {
+        return Some(crc32);
+    }
+
+    Obscure2NameMap::name_crc32(name)
+}
+
 #[inline]
 fn get_name_crc32(name: &str) -> u32 {
     if name.contains('é') {
@@ -409,3 +578,32 @@ fn get_name_crc32(name: &str) -> u32 {
         crc32fast::hash(name.as_bytes())
     }
 }
+
+/// copy the TOC fields of a file that its data isn't updated, they can only be changed
+/// using [`super::Archive::apply_toc_patch`]
+fn sync_toc(kind: &mut obscure2::EntryKind, u_entry: &FileEntry) {
+    let was_compressed = matches!(kind, obscure2::EntryKind::FileCompressed(_));
+    let (obscure2::EntryKind::File(o_entry) | obscure2::EntryKind::FileCompressed(o_entry)) = kind
+    else {
+        unreachable!("only files have TOC fields");
+    };
+
+    o_entry.checksum = u_entry.checksum;
+    match u_entry.compression_info {
+        Some(info) => {
+            o_entry.uncompressed_size = info.uncompressed_size;
+            kind.set_compressed();
+        }
+        None => {
+            if was_compressed {
+                o_entry.uncompressed_size = u_entry.raw_bytes.len() as _;
+            }
+            kind.set_uncompressed();
+        }
+    }
+}
+
+/// whatever the raw entry is the one that the entry was read from
+pub(super) fn same_entry(raw: &obscure2::Entry, entry: &Entry) -> bool {
+    build_name_crc32(entry.name()) == Some(raw.name_crc32)
+}
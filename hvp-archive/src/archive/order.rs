@@ -0,0 +1,127 @@
+//! custom order of the entries inside directories
+
+use super::{
+    entry::Entry,
+    error::{RebuildError, ReorderError},
+};
+
+/// move the listed entries to the start of `entries` in the given order, the other entries keep
+/// their order after them
+pub(super) fn reorder_entries<S: AsRef<str>>(
+    entries: &mut [Entry],
+    order: &[S],
+) -> Result<(), ReorderError> {
+    for (idx, name) in order.iter().enumerate() {
+        let name = name.as_ref();
+        if order[..idx].iter().any(|n| n.as_ref() == name) {
+            return Err(ReorderError::DuplicateEntry(name.to_owned()));
+        }
+        if !entries.iter().any(|e| e.name() == name) {
+            return Err(ReorderError::MissingEntry(name.to_owned()));
+        }
+    }
+
+    // the sort is stable, so the entries that aren't listed keep their order
+    entries.sort_by_key(|e| {
+        order
+            .iter()
+            .position(|n| n.as_ref() == e.name())
+            .unwrap_or(order.len())
+    });
+
+    Ok(())
+}
+
+/// move the raw entries of a directory to the order of the loaded entries, `same` check whatever a
+/// raw entry is the one that a loaded entry was read from.
+///
+/// loaded entries after the raw ones are ignored, they can only be the virtual orphans directory
+pub(super) fn sync_order<T: Clone>(
+    raw: &mut [T],
+    entries: &[Entry],
+    same: impl Fn(&T, &Entry) -> bool,
+) -> Result<(), RebuildError> {
+    if raw.iter().zip(entries).all(|(r, e)| same(r, e)) {
+        return Ok(());
+    }
+
+    let mut original: Vec<_> = raw.iter().cloned().map(Some).collect();
+    for (slot, entry) in raw.iter_mut().zip(entries) {
+        *slot = original
+            .iter_mut()
+            .find(|r| r.as_ref().is_some_and(|r| same(r, entry)))
+            .and_then(Option::take)
+            .ok_or_else(|| RebuildError::OrderMismatch(entry.name().to_owned()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        archive::Archive,
+        test_utils::{for_each_fixture, rebuild, reload},
+    };
+
+    fn names(entries: &[Entry]) -> Vec<String> {
+        entries.iter().map(|e| e.name().to_owned()).collect()
+    }
+
+    #[test]
+    fn reorder_fixtures_then_rebuild_and_patch() {
+        for_each_fixture(|fixture, provider| {
+            let mut archive = Archive::new(provider);
+
+            let (dir, original) = archive
+                .entries()
+                .iter()
+                .find_map(|e| match e {
+                    Entry::Dir(dir) if dir.entries.len() > 1 => {
+                        Some((dir.name.clone(), names(&dir.entries)))
+                    }
+                    _ => None,
+                })
+                .expect("fixture doesn't have a directory with multiple entries");
+
+            let reversed: Vec<_> = original.iter().rev().cloned().collect();
+            archive
+                .reorder(&dir, &reversed)
+                .expect("failed to reorder directory");
+            archive
+                .reorder("", &[&dir])
+                .expect("failed to reorder root");
+
+            let rebuilt = rebuild(&archive);
+            let mut patched = Cursor::new(fixture.bytes.to_vec());
+            archive
+                .patch(&mut patched)
+                .expect("failed to patch reordered archive");
+
+            for (kind, bytes) in [("rebuilt", rebuilt), ("patched", patched.into_inner())] {
+                let provider = reload(bytes);
+                let reordered = Archive::new(&provider);
+
+                assert_eq!(reordered.entries()[0].name(), dir, "{kind}");
+                let Entry::Dir(first) = &reordered.entries()[0] else {
+                    panic!("{kind}: first entry isn't a directory");
+                };
+                assert_eq!(names(&first.entries), reversed, "{kind}");
+
+                assert!(reordered.verify().is_ok(), "{kind}");
+                for file in archive.files() {
+                    let entry = reordered.find_file(&file.path).unwrap();
+                    assert_eq!(
+                        entry.get_bytes().unwrap(),
+                        file.get_bytes().unwrap(),
+                        "{kind}: {}",
+                        file.path.display()
+                    );
+                }
+            }
+        });
+    }
+}
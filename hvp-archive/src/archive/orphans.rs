@@ -0,0 +1,64 @@
+//! detection of entries in flat tables (obscure 2 and final exam) that can't be reached from the root
+
+use std::ops::Range;
+
+/// find the index of every entry that isn't reachable from the root directory at index 0.
+///
+/// `dir_range` should return the children range of an entry if it's a directory
+pub fn find<T>(table: &[T], dir_range: impl Fn(&T) -> Option<Range<usize>>) -> Vec<usize> {
+    let mut visited = vec![false; table.len()];
+    let mut stack = Vec::new();
+
+    if !table.is_empty() {
+        visited[0] = true;
+        stack.push(0);
+    }
+
+    while let Some(index) = stack.pop() {
+        let Some(range) = dir_range(&table[index]) else {
+            continue;
+        };
+
+        for child in clamp(range, table.len()) {
+            if !std::mem::replace(&mut visited[child], true) {
+                stack.push(child);
+            }
+        }
+    }
+
+    visited
+        .iter()
+        .enumerate()
+        .filter(|(_, visited)| !**visited)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// only keep the orphans that aren't a child of another orphan directory,
+/// children of those are reached by walking their parent
+pub fn roots<T>(
+    table: &[T],
+    orphans: &[usize],
+    dir_range: impl Fn(&T) -> Option<Range<usize>>,
+) -> Vec<usize> {
+    let mut is_child = vec![false; table.len()];
+
+    for &index in orphans {
+        if let Some(range) = dir_range(&table[index]) {
+            for child in clamp(range, table.len()) {
+                is_child[child] = true;
+            }
+        }
+    }
+
+    orphans
+        .iter()
+        .copied()
+        .filter(|&index| !is_child[index])
+        .collect()
+}
+
+#[inline]
+fn clamp(range: Range<usize>, len: usize) -> Range<usize> {
+    range.start.min(len)..range.end.min(len)
+}
@@ -0,0 +1,236 @@
+//! patch updated files directly into an existing archive file
+
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use binrw::BinWrite;
+
+use super::error::RebuildError;
+use super::{Archive, DeadSpacePolicy};
+use crate::provider::RawArchive;
+
+/// alignment of the data that is appended to the end of the archive
+const APPEND_ALIGNMENT: u64 = 32;
+
+/// result of patching an archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PatchReport {
+    /// number of updated files that fit in place of their old data
+    pub in_place: usize,
+    /// number of updated files that were bigger than their old data and appended to the end of the archive
+    pub appended: usize,
+    /// number of updated files that were written into zero filled dead space,
+    /// see [`DeadSpacePolicy::Reclaim`]
+    pub reclaimed: usize,
+}
+
+impl PatchReport {
+    /// total number of patched files
+    pub fn patched(&self) -> usize {
+        self.in_place + self.appended + self.reclaimed
+    }
+}
+
+impl Archive<'_> {
+    /// write the updated files directly into `file`, which must be the same archive that this archive
+    /// was loaded from (or a copy of it), and then write the updated TOC. the updated data is read
+    /// from the provider while writing, so `file` shouldn't be the file that the provider map,
+    /// patch a copy and move it over the original instead.
+    ///
+    /// an updated file that fit in the space of its old data is written over it, a bigger one is
+    /// appended to the end of the archive. unchanged files are not touched, so this is much faster than
+    /// [`Archive::rebuild`] for small changes, but the space of the replaced data is never reclaimed.
+    /// with [`DeadSpacePolicy::Reclaim`] the bigger files go to the zero filled dead space of the
+    /// archive first, if there is a region that is big enough.
+    ///
+    /// after patching, the loaded provider doesn't match the file anymore and should be loaded again.
+    pub fn patch<F: Read + Write + Seek>(&self, file: &mut F) -> Result<PatchReport, RebuildError> {
+        self.check_shape()?;
+
+        let end = file.seek(SeekFrom::End(0))?;
+
+        let free = match self.options.dead_space {
+            DeadSpacePolicy::Preserve => Vec::new(),
+            DeadSpacePolicy::Reclaim => self
+                .dead_space()
+                .into_iter()
+                .filter(|region| region.zero_filled == Some(true))
+                .map(|region| region.range())
+                .collect(),
+        };
+
+        let mut patcher = Patcher {
+            file,
+            end,
+            free,
+            report: PatchReport::default(),
+        };
+
+        let compression = self.options.compressor();
+
+        match &self.provider.raw_archive {
+            RawArchive::Obscure1(archive) => {
+                let archive = super::obscure1::patch_entries(
+                    &mut patcher,
+                    compression,
+                    archive.clone(),
+                    &self.entries,
+                )?;
+
+                patcher.file.seek(SeekFrom::Start(0))?;
+                archive.write_be(patcher.file)?;
+            }
+            RawArchive::Obscure2(archive) => {
+                let archive = super::obscure2::patch_entries(
+                    &mut patcher,
+                    compression,
+                    archive.clone(),
+                    &self.entries,
+                )?;
+
+                patcher.file.seek(SeekFrom::Start(0))?;
+                archive.write(patcher.file)?;
+            }
+            RawArchive::FinalExam(archive) => {
+                let archive = super::final_exam::patch_entries(
+                    &mut patcher,
+                    compression,
+                    archive.clone(),
+                    &self.entries,
+                )?;
+
+                patcher.file.seek(SeekFrom::Start(0))?;
+                archive.write(patcher.file)?;
+            }
+        }
+
+        patcher.file.flush()?;
+
+        Ok(patcher.report)
+    }
+}
+
+/// a helper that place the updated data inside the archive file
+pub(super) struct Patcher<'a, F: Write + Seek> {
+    file: &'a mut F,
+    end: u64,
+    /// zero filled regions that the updated files can be written to
+    free: Vec<Range<u64>>,
+    report: PatchReport,
+}
+
+impl<F: Write + Seek> Patcher<'_, F> {
+    /// write the new data of an entry, return the offset that the data is written to
+    pub(super) fn place(
+        &mut self,
+        offset: u32,
+        old_size: u32,
+        bytes: &[u8],
+    ) -> Result<u32, RebuildError> {
+        if bytes.len() <= old_size as usize {
+            self.file.seek(SeekFrom::Start(offset as _))?;
+            self.file.write_all(bytes)?;
+            self.report.in_place += 1;
+            return Ok(offset);
+        }
+
+        if let Some(offset) = self.reclaim(bytes.len() as _) {
+            let new_offset = u32::try_from(offset).map_err(|_| RebuildError::ArchiveTooLarge)?;
+            self.file.seek(SeekFrom::Start(offset))?;
+            self.file.write_all(bytes)?;
+            self.report.reclaimed += 1;
+            return Ok(new_offset);
+        }
+
+        let offset = self.end.next_multiple_of(APPEND_ALIGNMENT);
+        let new_offset = u32::try_from(offset).map_err(|_| RebuildError::ArchiveTooLarge)?;
+
+        // fill the gap so the file doesn't end with a hole
+        self.file.seek(SeekFrom::Start(self.end))?;
+        std::io::copy(
+            &mut std::io::repeat(0).take(offset - self.end),
+            &mut self.file,
+        )?;
+        self.file.write_all(bytes)?;
+
+        self.end = offset + bytes.len() as u64;
+        self.report.appended += 1;
+
+        Ok(new_offset)
+    }
+
+    /// take `len` bytes from the first free region that is big enough, the data is aligned
+    /// the same as the appended data
+    fn reclaim(&mut self, len: u64) -> Option<u64> {
+        let idx = self.free.iter().position(|region| {
+            region.start.next_multiple_of(APPEND_ALIGNMENT) + len <= region.end
+        })?;
+
+        let region = self.free[idx].clone();
+        let offset = region.start.next_multiple_of(APPEND_ALIGNMENT);
+        self.free[idx] = offset + len..region.end;
+
+        Some(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        archive::entry::UpdateKind,
+        test_utils::{for_each_fixture, reload},
+    };
+
+    #[test]
+    fn patch_fixtures_in_place_and_append() {
+        for_each_fixture(|fixture, provider| {
+            let mut archive = Archive::new(provider);
+
+            // a small update that fit in place and a big random one that need to be appended
+            let small = b"patched".to_vec();
+            let mut seed = 0x1234_5678u32;
+            let big: Vec<u8> = (0..64 * 1024)
+                .map(|_| {
+                    seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    (seed >> 16) as u8
+                })
+                .collect();
+
+            let mut updated = Vec::new();
+            for (mut entry, bytes) in archive
+                .files_mut()
+                .filter(|f| f.raw_bytes().len() >= 64)
+                .zip([&small, &big])
+            {
+                entry.update(UpdateKind::Bytes(bytes.clone()));
+                updated.push((entry.path.clone(), bytes.clone()));
+            }
+
+            let mut patched = Cursor::new(fixture.bytes.to_vec());
+            let report = archive
+                .patch(&mut patched)
+                .expect("failed to patch archive");
+
+            assert_eq!(report.patched(), 2);
+            assert!(report.appended >= 1, "{report:?}");
+
+            let patched_provider = reload(patched.into_inner());
+            let patched_archive = Archive::new(&patched_provider);
+            assert!(patched_archive.verify().is_ok());
+
+            for (path, bytes) in &updated {
+                let entry = patched_archive.find_file(path).unwrap();
+                assert_eq!(&*entry.get_bytes().unwrap(), bytes.as_slice());
+            }
+
+            let diff = archive.diff(&patched_archive);
+            assert_eq!(diff.entries.len(), 2);
+            assert_eq!(diff.unchanged, fixture.file_count - 2);
+        });
+    }
+}
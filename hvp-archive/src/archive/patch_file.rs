@@ -0,0 +1,554 @@
+//! portable patches that only contain the changed files of an archive, so mods can be shared
+//! without the whole archive
+
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, read::ZlibDecoder, write::ZlibEncoder};
+
+use super::{
+    Archive,
+    diff::DiffEntry,
+    entry::{FullFileEntry, FullFileEntryMut, UpdateKind},
+    error::PatchFileError,
+};
+use crate::Game;
+
+/// magic at the start of patch files
+const MAGIC: &[u8; 8] = b"HVPPATCH";
+/// version of the patch file format
+const VERSION: u16 = 1;
+
+/// size of the blocks that are used to find matching data in the old file
+const BLOCK_SIZE: usize = 32;
+/// size of a [`DeltaOp`] without its data
+const OP_SIZE: usize = 9;
+
+/// a patch that contain the new content of the changed files of an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivePatch {
+    /// game of the archive that this patch is made for
+    pub game: Game,
+    pub entries: Vec<PatchEntry>,
+}
+
+/// a changed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatchEntry {
+    /// full path of the file inside the archive
+    pub path: PathBuf,
+    /// stored checksum of the file in the original archive
+    pub original_checksum: i32,
+    /// stored size of the file in the original archive
+    pub original_size: u32,
+    /// crc32 of the new content, used to check the content after applying the patch
+    pub content_crc32: u32,
+    pub data: PatchData,
+}
+
+/// new content of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchData {
+    /// the whole new content
+    Full(Vec<u8>),
+    /// the new content as a binary diff against the decompressed original content
+    Delta(Vec<DeltaOp>),
+}
+
+/// an operation of a binary diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeltaOp {
+    /// copy bytes from the original content
+    Copy { offset: u32, len: u32 },
+    /// insert new bytes
+    Insert(Vec<u8>),
+}
+
+impl ArchivePatch {
+    /// make a patch that turn `original` into `modified`, only the content of files can be changed.
+    ///
+    /// with `binary_diff` files are stored as a binary diff against the original file when it is smaller
+    pub fn generate(
+        original: &Archive,
+        modified: &Archive,
+        binary_diff: bool,
+    ) -> Result<Self, PatchFileError> {
+        let game = original.metadata().game;
+        if game != modified.metadata().game {
+            return Err(PatchFileError::GameMismatch {
+                patch: modified.metadata().game,
+                archive: game,
+            });
+        }
+
+        let diff = original.diff(modified);
+
+        let mut old_files: ahash::HashMap<PathBuf, FullFileEntry> =
+            original.files().map(|f| (f.path.clone(), f)).collect();
+        let mut new_files: ahash::HashMap<PathBuf, FullFileEntry> =
+            modified.files().map(|f| (f.path.clone(), f)).collect();
+
+        let mut entries = Vec::new();
+
+        for entry in diff.entries {
+            let path = match entry {
+                DiffEntry::Added(file) => return Err(PatchFileError::FileAdded(file.path)),
+                DiffEntry::Removed(file) => return Err(PatchFileError::FileRemoved(file.path)),
+                DiffEntry::Changed { new, .. } => new.path,
+            };
+
+            let old = old_files
+                .remove(&path)
+                .expect("changed file exist in original");
+            let new = new_files
+                .remove(&path)
+                .expect("changed file exist in modified");
+            let content = new.get_bytes()?;
+
+            let data = match binary_diff {
+                true => {
+                    let ops = delta(&old.get_bytes()?, &content);
+                    match delta_size(&ops) < content.len() {
+                        true => PatchData::Delta(ops),
+                        false => PatchData::Full(content.to_vec()),
+                    }
+                }
+                false => PatchData::Full(content.to_vec()),
+            };
+
+            entries.push(PatchEntry {
+                original_checksum: old.stored_checksum(),
+                original_size: old.raw_bytes.len() as _,
+                content_crc32: crc32fast::hash(&content),
+                data,
+                path,
+            });
+        }
+
+        Ok(Self { game, entries })
+    }
+
+    /// check that the archive is the original archive of this patch and update the changed files,
+    /// use [`Archive::rebuild`] to write the patched archive after it. return the number of updated files.
+    ///
+    /// every file is checked before updating anything, so on error the archive isn't changed
+    pub fn apply(&self, archive: &mut Archive) -> Result<usize, PatchFileError> {
+        let game = archive.metadata().game;
+        if game != self.game {
+            return Err(PatchFileError::GameMismatch {
+                patch: self.game,
+                archive: game,
+            });
+        }
+
+        let mut files: ahash::HashMap<PathBuf, FullFileEntryMut> =
+            archive.files_mut().map(|f| (f.path.clone(), f)).collect();
+
+        let mut contents = Vec::with_capacity(self.entries.len());
+
+        for entry in &self.entries {
+            let file = files
+                .get(&entry.path)
+                .ok_or_else(|| PatchFileError::MissingFile(entry.path.clone()))?;
+
+            let found_checksum = file.stored_checksum();
+            let found_size = file.raw_bytes().len() as u32;
+            if found_checksum != entry.original_checksum || found_size != entry.original_size {
+                return Err(PatchFileError::OriginalMismatch {
+                    path: entry.path.clone(),
+                    expected_checksum: entry.original_checksum,
+                    expected_size: entry.original_size,
+                    found_checksum,
+                    found_size,
+                });
+            }
+
+            let content = match &entry.data {
+                PatchData::Full(data) => data.clone(),
+                PatchData::Delta(ops) => apply_delta(&file.get_bytes()?, ops)
+                    .ok_or_else(|| PatchFileError::DeltaOutOfBounds(entry.path.clone()))?,
+            };
+
+            if crc32fast::hash(&content) != entry.content_crc32 {
+                return Err(PatchFileError::ContentMismatch(entry.path.clone()));
+            }
+
+            contents.push(content);
+        }
+
+        for (entry, content) in self.entries.iter().zip(contents) {
+            let file = files.get_mut(&entry.path).expect("file is checked above");
+            file.update(UpdateKind::Bytes(content));
+        }
+
+        Ok(self.entries.len())
+    }
+
+    /// read a patch that was written using [`ArchivePatch::write`]
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, PatchFileError> {
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(PatchFileError::Invalid("unknown magic"));
+        }
+
+        let version = u16::from_le_bytes(read_array(&mut reader)?);
+        if version != VERSION {
+            return Err(PatchFileError::UnsupportedVersion(version));
+        }
+
+        let mut reader = ZlibDecoder::new(reader);
+
+        let game = match read_u8(&mut reader)? {
+            0 => Game::Obscure1,
+            1 => Game::Obscure2,
+            2 => Game::FinalExam,
+            _ => return Err(PatchFileError::Invalid("unknown game")),
+        };
+
+        let count = read_u32(&mut reader)?;
+        let mut entries = Vec::new();
+
+        for _ in 0..count {
+            let path_len = u16::from_le_bytes(read_array(&mut reader)?);
+            let path = String::from_utf8(read_vec(&mut reader, path_len as _)?)
+                .map_err(|_| PatchFileError::Invalid("path isn't valid utf-8"))?;
+
+            let original_checksum = i32::from_le_bytes(read_array(&mut reader)?);
+            let original_size = read_u32(&mut reader)?;
+            let content_crc32 = read_u32(&mut reader)?;
+
+            let data = match read_u8(&mut reader)? {
+                0 => {
+                    let len = read_u32(&mut reader)?;
+                    PatchData::Full(read_vec(&mut reader, len as _)?)
+                }
+                1 => {
+                    let count = read_u32(&mut reader)?;
+                    let mut ops = Vec::new();
+                    for _ in 0..count {
+                        let op = match read_u8(&mut reader)? {
+                            0 => DeltaOp::Copy {
+                                offset: read_u32(&mut reader)?,
+                                len: read_u32(&mut reader)?,
+                            },
+                            1 => {
+                                let len = read_u32(&mut reader)?;
+                                DeltaOp::Insert(read_vec(&mut reader, len as _)?)
+                            }
+                            _ => return Err(PatchFileError::Invalid("unknown delta operation")),
+                        };
+                        ops.push(op);
+                    }
+                    PatchData::Delta(ops)
+                }
+                _ => return Err(PatchFileError::Invalid("unknown entry data kind")),
+            };
+
+            entries.push(PatchEntry {
+                path: path.split('/').collect(),
+                original_checksum,
+                original_size,
+                content_crc32,
+                data,
+            });
+        }
+
+        Ok(Self { game, entries })
+    }
+
+    /// write the patch, everything after the magic and version is compressed using zlib
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), PatchFileError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+
+        let mut writer = ZlibEncoder::new(writer, Compression::best());
+
+        let game: u8 = match self.game {
+            Game::Obscure1 => 0,
+            Game::Obscure2 => 1,
+            Game::FinalExam => 2,
+        };
+        writer.write_all(&[game])?;
+        write_len(&mut writer, self.entries.len())?;
+
+        for entry in &self.entries {
+            let path = path_to_string(&entry.path);
+            let path_len = u16::try_from(path.len())
+                .map_err(|_| PatchFileError::Invalid("path is too long"))?;
+            writer.write_all(&path_len.to_le_bytes())?;
+            writer.write_all(path.as_bytes())?;
+
+            writer.write_all(&entry.original_checksum.to_le_bytes())?;
+            writer.write_all(&entry.original_size.to_le_bytes())?;
+            writer.write_all(&entry.content_crc32.to_le_bytes())?;
+
+            match &entry.data {
+                PatchData::Full(data) => {
+                    writer.write_all(&[0])?;
+                    write_len(&mut writer, data.len())?;
+                    writer.write_all(data)?;
+                }
+                PatchData::Delta(ops) => {
+                    writer.write_all(&[1])?;
+                    write_len(&mut writer, ops.len())?;
+                    for op in ops {
+                        match op {
+                            DeltaOp::Copy { offset, len } => {
+                                writer.write_all(&[0])?;
+                                writer.write_all(&offset.to_le_bytes())?;
+                                writer.write_all(&len.to_le_bytes())?;
+                            }
+                            DeltaOp::Insert(data) => {
+                                writer.write_all(&[1])?;
+                                write_len(&mut writer, data.len())?;
+                                writer.write_all(data)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        writer.finish()?.flush()?;
+
+        Ok(())
+    }
+}
+
+/// patch paths always use `/` as separator, so they work on every platform
+fn path_to_string(path: &Path) -> String {
+    path.iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// find the parts of `new` that exist in `old` using a rolling hash over blocks of `old`
+fn delta(old: &[u8], new: &[u8]) -> Vec<DeltaOp> {
+    const PRIME: u64 = 0x100000001B3;
+
+    let mut ops = Vec::new();
+
+    if old.len() < BLOCK_SIZE || new.len() < BLOCK_SIZE {
+        if !new.is_empty() {
+            ops.push(DeltaOp::Insert(new.to_vec()));
+        }
+        return ops;
+    }
+
+    let hash = |block: &[u8]| {
+        block
+            .iter()
+            .fold(0u64, |h, &b| h.wrapping_mul(PRIME).wrapping_add(b as u64))
+    };
+    // weight of the byte that leave the window
+    let out_weight = (1..BLOCK_SIZE).fold(1u64, |w, _| w.wrapping_mul(PRIME));
+
+    let mut index: ahash::HashMap<u64, usize> = ahash::HashMap::default();
+    for offset in (0..=old.len() - BLOCK_SIZE).step_by(BLOCK_SIZE) {
+        index
+            .entry(hash(&old[offset..offset + BLOCK_SIZE]))
+            .or_insert(offset);
+    }
+
+    let mut insert_start = 0;
+    let mut pos = 0;
+    let mut h = hash(&new[..BLOCK_SIZE]);
+
+    while pos + BLOCK_SIZE <= new.len() {
+        let found = index
+            .get(&h)
+            .copied()
+            .filter(|&o| old[o..o + BLOCK_SIZE] == new[pos..pos + BLOCK_SIZE]);
+
+        let Some(mut offset) = found else {
+            if pos + BLOCK_SIZE < new.len() {
+                h = h
+                    .wrapping_sub((new[pos] as u64).wrapping_mul(out_weight))
+                    .wrapping_mul(PRIME)
+                    .wrapping_add(new[pos + BLOCK_SIZE] as u64);
+            }
+            pos += 1;
+            continue;
+        };
+
+        // extend the match in both directions
+        let mut start = pos;
+        while start > insert_start && offset > 0 && old[offset - 1] == new[start - 1] {
+            start -= 1;
+            offset -= 1;
+        }
+        let mut end = pos + BLOCK_SIZE;
+        while end < new.len()
+            && offset + (end - start) < old.len()
+            && old[offset + (end - start)] == new[end]
+        {
+            end += 1;
+        }
+
+        if insert_start < start {
+            ops.push(DeltaOp::Insert(new[insert_start..start].to_vec()));
+        }
+        ops.push(DeltaOp::Copy {
+            offset: offset as _,
+            len: (end - start) as _,
+        });
+
+        insert_start = end;
+        pos = end;
+        if pos + BLOCK_SIZE <= new.len() {
+            h = hash(&new[pos..pos + BLOCK_SIZE]);
+        }
+    }
+
+    if insert_start < new.len() {
+        ops.push(DeltaOp::Insert(new[insert_start..].to_vec()));
+    }
+
+    ops
+}
+
+/// rebuild the new content from the original content, `None` if a copy is outside of it
+fn apply_delta(old: &[u8], ops: &[DeltaOp]) -> Option<Vec<u8>> {
+    let mut content = Vec::new();
+
+    for op in ops {
+        match op {
+            DeltaOp::Copy { offset, len } => {
+                let start = *offset as usize;
+                content.extend_from_slice(old.get(start..start.checked_add(*len as usize)?)?);
+            }
+            DeltaOp::Insert(data) => content.extend_from_slice(data),
+        }
+    }
+
+    Some(content)
+}
+
+/// size of the delta operations when written to a patch
+fn delta_size(ops: &[DeltaOp]) -> usize {
+    ops.iter()
+        .map(|op| match op {
+            DeltaOp::Copy { .. } => OP_SIZE,
+            DeltaOp::Insert(data) => OP_SIZE - 4 + data.len(),
+        })
+        .sum()
+}
+
+fn write_len<W: Write>(writer: &mut W, len: usize) -> Result<(), PatchFileError> {
+    let len = u32::try_from(len).map_err(|_| PatchFileError::Invalid("data is too large"))?;
+    writer.write_all(&len.to_le_bytes())?;
+    Ok(())
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    read_array::<_, 1>(reader).map(|[b]| b)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    read_array(reader).map(u32::from_le_bytes)
+}
+
+fn read_vec<R: Read>(reader: &mut R, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.take(len as _).read_to_end(&mut buf)?;
+    if buf.len() != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{for_each_fixture, rebuild, reload};
+
+    #[test]
+    fn fixtures_patch_file_roundtrip() {
+        for_each_fixture(|_, provider| {
+            let mut archive = Archive::new(provider);
+
+            // a small change in the largest file and a full replacement of another one
+            let mut sizes: Vec<_> = archive
+                .files()
+                .map(|f| (f.uncompressed_size(), f.path))
+                .collect();
+            sizes.sort();
+            let (_, small_change) = sizes.pop().unwrap();
+            let (_, replaced) = sizes.pop().unwrap();
+
+            for mut entry in archive.files_mut() {
+                let mut bytes = entry.get_bytes().unwrap().into_owned();
+                if entry.path == small_change {
+                    let middle = bytes.len() / 2;
+                    bytes[middle..middle + 4].copy_from_slice(b"HVP!");
+                } else if entry.path == replaced {
+                    bytes
+                        .iter_mut()
+                        .for_each(|b| *b = b.wrapping_mul(31) ^ 0x5A);
+                } else {
+                    continue;
+                }
+                entry.update(UpdateKind::Bytes(bytes));
+            }
+
+            let original = Archive::new(provider);
+            let modified_provider = reload(rebuild(&archive));
+            let modified = Archive::new(&modified_provider);
+
+            let patch = ArchivePatch::generate(&original, &modified, true).unwrap();
+            assert_eq!(patch.entries.len(), 2);
+            for entry in &patch.entries {
+                assert_eq!(
+                    matches!(entry.data, PatchData::Delta(_)),
+                    entry.path == small_change,
+                    "{}",
+                    entry.path.display()
+                );
+            }
+
+            let mut bytes = Vec::new();
+            patch.write(&mut bytes).unwrap();
+            assert_eq!(ArchivePatch::read(&bytes[..]).unwrap(), patch);
+
+            let full = ArchivePatch::generate(&original, &modified, false).unwrap();
+            assert!(
+                full.entries
+                    .iter()
+                    .all(|e| matches!(e.data, PatchData::Full(_)))
+            );
+
+            // applying the patch to the original give the content of the modified archive
+            let mut patched = Archive::new(provider);
+            assert_eq!(patch.apply(&mut patched).unwrap(), 2);
+
+            let patched_provider = reload(rebuild(&patched));
+            let patched = Archive::new(&patched_provider);
+            for (file, expected) in patched.files().zip(modified.files()) {
+                assert_eq!(file.path, expected.path);
+                assert_eq!(
+                    file.get_bytes().unwrap(),
+                    expected.get_bytes().unwrap(),
+                    "{}",
+                    file.path.display()
+                );
+            }
+
+            // patch can't be applied to another archive
+            let mut modified_copy = Archive::new(&modified_provider);
+            assert!(matches!(
+                patch.apply(&mut modified_copy),
+                Err(PatchFileError::OriginalMismatch { .. })
+            ));
+        });
+    }
+}
@@ -0,0 +1,403 @@
+//! rebuild planning, in two phases.
+//!
+//! [`Archive::preview_rebuild`] is a cheap estimate of the output that doesn't compress anything,
+//! [`Archive::plan_rebuild`] is the exact list of writes that [`Archive::rebuild`] would do.
+//! the plan is recorded by running the same rebuild code with a writer that only keep track of
+//! the writes, so it always match the output of a real rebuild.
+
+use std::{
+    cell::Cell,
+    io::{self, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    Archive,
+    entry::{Entry, FileEntry, UpdateKind},
+    error::RebuildError,
+    rebuild_progress::RebuildProgress,
+};
+use crate::provider::ArchiveProvider;
+
+/// where the bytes of a write come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteSource {
+    /// stored bytes of a file that are copied as is from this range of the original archive
+    Original(Range<u64>),
+    /// new stored (maybe compressed) bytes of an updated file
+    Update(Vec<u8>),
+    /// bytes that are made by the rebuild, like the TOC and padding
+    Generated(Vec<u8>),
+}
+
+/// a single write of the rebuild
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOp {
+    /// where the bytes come from
+    pub source: WriteSource,
+    /// offset of the bytes in the rebuilt archive
+    pub target: u64,
+    /// index of the file in [`Archive::files`] order, `None` for the TOC and padding
+    pub entry: Option<usize>,
+}
+
+impl WriteOp {
+    /// number of bytes that are written
+    pub fn len(&self) -> u64 {
+        match &self.source {
+            WriteSource::Original(range) => range.end - range.start,
+            WriteSource::Update(bytes) | WriteSource::Generated(bytes) => bytes.len() as _,
+        }
+    }
+
+    /// whatever the write is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// range of the bytes in the rebuilt archive
+    pub fn target_range(&self) -> Range<u64> {
+        self.target..self.target + self.len()
+    }
+
+    /// the bytes that are written, `original` is the content of the original archive file
+    pub fn bytes<'a>(&'a self, original: &'a [u8]) -> &'a [u8] {
+        match &self.source {
+            WriteSource::Original(range) => &original[range.start as usize..range.end as usize],
+            WriteSource::Update(bytes) | WriteSource::Generated(bytes) => bytes,
+        }
+    }
+}
+
+impl<'p> Archive<'p> {
+    /// plan the rebuild without writing anything, the writes are sorted by their target offset.
+    ///
+    /// writing every op at its target produce the same output as [`Archive::rebuild`]
+    pub fn plan_rebuild(&self) -> Result<Vec<WriteOp>, RebuildError> {
+        let files = Cell::new(0);
+        let mut recorder = Recorder {
+            provider: self.provider,
+            files: &files,
+            written: 0,
+            position: 0,
+            ops: Vec::new(),
+        };
+
+        self.rebuild(&mut recorder, FileCounter(&files))?;
+
+        let mut ops = recorder.ops;
+        ops.sort_by_key(|op| op.target);
+
+        Ok(ops)
+    }
+}
+
+/// estimated result of a rebuild, see [`Archive::preview_rebuild`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RebuildPreview {
+    /// size of the original archive
+    pub original_size: u64,
+    /// estimated size of the rebuilt archive
+    pub projected_size: u64,
+    /// number of updated files that will be compressed during the rebuild
+    pub recompressions: usize,
+    /// updated files, in [`Archive::files`] order
+    pub entries: Vec<EntryPreview>,
+}
+
+impl RebuildPreview {
+    /// difference between the projected and the original size
+    pub fn size_delta(&self) -> i64 {
+        self.projected_size as i64 - self.original_size as i64
+    }
+}
+
+/// estimated change of a single updated file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryPreview {
+    /// index of the file in [`Archive::files`] order
+    pub entry: usize,
+    /// full path of the file inside the archive
+    pub path: PathBuf,
+    /// stored size of the file in the original archive
+    pub original_size: u64,
+    /// stored size of the file in the rebuilt archive
+    pub projected_size: u64,
+    /// whatever the projected size is an estimate, because the file will be compressed
+    pub estimated: bool,
+}
+
+impl EntryPreview {
+    /// difference between the projected and the original stored size
+    pub fn delta(&self) -> i64 {
+        self.projected_size as i64 - self.original_size as i64
+    }
+}
+
+impl<'p> Archive<'p> {
+    /// estimate the result of a rebuild without compressing or writing anything.
+    ///
+    /// unmodified files keep their original size, the size of updated files that will be
+    /// compressed is estimated using the compression ratio of the original file.
+    /// padding between the files isn't counted, so the real size can be a bit different
+    pub fn preview_rebuild(&self) -> Result<RebuildPreview, RebuildError> {
+        self.check_shape()?;
+
+        let mut files = Vec::with_capacity(self.metadata.file_count);
+        collect_files(&self.entries, Path::new(""), &mut files);
+
+        let original_size = self.provider.file_len();
+        let mut preview = RebuildPreview {
+            original_size,
+            projected_size: original_size,
+            recompressions: 0,
+            entries: Vec::new(),
+        };
+
+        for (entry, (path, file)) in files.into_iter().enumerate() {
+            let Some(update) = &file.update else {
+                continue;
+            };
+
+            let original_size = file.raw_bytes.len() as u64;
+            let uncompressed_size = file.uncompressed_size() as u64;
+
+            // the rebuild keep empty files as is
+            let (projected_size, estimated) = if uncompressed_size == 0 {
+                (original_size, false)
+            } else {
+                let content_size = match update {
+                    UpdateKind::Bytes(bytes) => bytes.len() as u64,
+                    UpdateKind::File(path) => std::fs::metadata(path)?.len(),
+                };
+
+                match file.is_compressed() && !self.options.rebuild_skip_compression {
+                    true => {
+                        preview.recompressions += 1;
+                        let ratio = original_size as f64 / uncompressed_size as f64;
+                        ((content_size as f64 * ratio).ceil() as u64, true)
+                    }
+                    false => (content_size, false),
+                }
+            };
+
+            preview.projected_size = (preview.projected_size + projected_size) - original_size;
+            preview.entries.push(EntryPreview {
+                entry,
+                path,
+                original_size,
+                projected_size,
+                estimated,
+            });
+        }
+
+        Ok(preview)
+    }
+}
+
+impl Archive<'_> {
+    /// estimated number of data bytes that [`Archive::rebuild`] write, see [`Archive::preview_rebuild`]
+    pub(super) fn estimated_data_size(&self) -> Option<u64> {
+        let preview = self.preview_rebuild().ok()?;
+        let stored: u64 = self.files().map(|f| f.raw_bytes.len() as u64).sum();
+        Some(stored.saturating_add_signed(preview.entries.iter().map(EntryPreview::delta).sum()))
+    }
+}
+
+/// every file of the archive with its full path, in [`Archive::files`] order
+fn collect_files<'a, 'p>(
+    entries: &'a [Entry<'p>],
+    parent: &Path,
+    files: &mut Vec<(PathBuf, &'a FileEntry<'p>)>,
+) {
+    for entry in entries {
+        match entry {
+            Entry::File(file) => files.push((parent.join(file.name()), file)),
+            Entry::Dir(dir) => collect_files(&dir.entries, &parent.join(&dir.name), files),
+        }
+    }
+}
+
+/// count the files that the rebuild has reached, so the writes can be linked to them
+struct FileCounter<'a>(&'a Cell<usize>);
+
+impl RebuildProgress for FileCounter<'_> {
+    fn inc(&self, _: Option<String>) {
+        self.0.set(self.0.get() + 1);
+    }
+
+    fn inc_n(&self, n: usize, _: Option<String>) {
+        self.0.set(self.0.get() + n);
+    }
+}
+
+/// a writer that record the writes instead of doing them
+struct Recorder<'a, 'p> {
+    provider: &'p ArchiveProvider,
+    files: &'a Cell<usize>,
+    // number of files that their data is already written
+    written: usize,
+    position: u64,
+    ops: Vec<WriteOp>,
+}
+
+impl Write for Recorder<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // the data of each file is written in one go right after the progress reach it,
+        // everything else is part of the TOC or padding
+        let files = self.files.get();
+        let (source, entry) = if files > self.written {
+            self.written = files;
+            let source = match self.provider.contains(buf) {
+                true => {
+                    let start = self.provider.offset_of(buf);
+                    WriteSource::Original(start..start + buf.len() as u64)
+                }
+                false => WriteSource::Update(buf.to_vec()),
+            };
+            (source, Some(files - 1))
+        } else {
+            (WriteSource::Generated(buf.to_vec()), None)
+        };
+
+        match (self.ops.last_mut(), &source) {
+            // TOC and padding are written in small pieces, keep them together
+            (
+                Some(WriteOp {
+                    source: WriteSource::Generated(last),
+                    target,
+                    ..
+                }),
+                WriteSource::Generated(bytes),
+            ) if *target + last.len() as u64 == self.position => last.extend_from_slice(bytes),
+            _ => self.ops.push(WriteOp {
+                source,
+                target: self.position,
+                entry,
+            }),
+        }
+
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Recorder<'_, '_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self
+                .ops
+                .iter()
+                .map(|op| op.target_range().end)
+                .max()
+                .unwrap_or(0)
+                .checked_add_signed(offset),
+        };
+
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{for_each_fixture, rebuild};
+
+    #[test]
+    fn fixtures_plan_match_rebuild() {
+        for_each_fixture(|fixture, provider| {
+            let mut archive = Archive::new(provider);
+
+            // update the first non empty file so the plan have every kind of source
+            let updated = archive
+                .files()
+                .position(|f| f.uncompressed_size() != 0)
+                .unwrap();
+            let mut file = archive.files_mut().nth(updated).unwrap();
+            let mut content = file.get_bytes().unwrap().into_owned();
+            content.extend_from_slice(b"plan");
+            file.update(UpdateKind::Bytes(content));
+
+            let plan = archive.plan_rebuild().expect("failed to plan rebuild");
+            let rebuilt = rebuild(&archive);
+
+            let mut planned = vec![0; rebuilt.len()];
+            let mut end = 0;
+            for op in &plan {
+                assert!(op.target >= end, "writes overlap");
+                end = op.target_range().end;
+                planned[op.target as usize..end as usize].copy_from_slice(op.bytes(fixture.bytes));
+
+                match &op.source {
+                    WriteSource::Update(_) => assert_eq!(op.entry, Some(updated)),
+                    WriteSource::Original(_) => assert!(op.entry.is_some_and(|e| e != updated)),
+                    WriteSource::Generated(_) => assert_eq!(op.entry, None),
+                }
+            }
+
+            assert_eq!(end as usize, rebuilt.len());
+            assert!(planned == rebuilt, "plan doesn't match rebuild");
+        });
+    }
+
+    #[test]
+    fn fixtures_preview_rebuild() {
+        for_each_fixture(|fixture, provider| {
+            let mut archive = Archive::new(provider);
+
+            let preview = archive.preview_rebuild().unwrap();
+            assert_eq!(preview.projected_size, fixture.bytes.len() as u64);
+            assert_eq!(preview.recompressions, 0);
+            assert!(preview.entries.is_empty());
+
+            let updated = archive
+                .files()
+                .position(|f| f.uncompressed_size() != 0)
+                .unwrap();
+            let mut file = archive.files_mut().nth(updated).unwrap();
+            let mut content = file.get_bytes().unwrap().into_owned();
+            content.extend_from_slice(b"preview");
+            let compressed = file.is_compressed();
+            file.update(UpdateKind::Bytes(content));
+
+            let preview = archive.preview_rebuild().unwrap();
+            assert_eq!(preview.recompressions, compressed as usize);
+            assert_eq!(preview.entries.len(), 1);
+
+            let entry = &preview.entries[0];
+            assert_eq!(entry.entry, updated);
+            assert_eq!(entry.estimated, compressed);
+            assert_eq!(preview.size_delta(), entry.delta());
+
+            let plan = archive.plan_rebuild().unwrap();
+            let update = plan
+                .iter()
+                .find(|op| matches!(op.source, WriteSource::Update(_)))
+                .unwrap();
+            if !compressed {
+                assert_eq!(entry.projected_size, update.len());
+                assert_eq!(entry.delta(), 7);
+            }
+
+            // only the padding around the updated file isn't counted
+            let rebuilt_size = plan.last().unwrap().target_range().end;
+            assert!(
+                rebuilt_size.abs_diff(preview.projected_size)
+                    <= entry.projected_size.abs_diff(update.len()) + 32
+            );
+        });
+    }
+}
@@ -0,0 +1,212 @@
+//! position of the loaded entries in the entry tables of the archive, see [`Archive::raw_index_map`]
+
+use std::{
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use super::{Archive, ORPHANS_DIR, OrphanMode, entry::Entry, orphans};
+
+/// where an entry is stored in the entry table of the archive
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RawIndex {
+    /// index in the flat table of obscure 2 and final exam archives, the root directory is 0
+    Flat(usize),
+    /// index of the entry and of every parent directory in the nested tables of obscure 1
+    /// archives, starting from the root
+    Nested(Vec<usize>),
+}
+
+impl Display for RawIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Flat(index) => write!(f, "{index}"),
+            Self::Nested(indices) => {
+                let indices: Vec<_> = indices.iter().map(usize::to_string).collect();
+                write!(f, "{}", indices.join("/"))
+            }
+        }
+    }
+}
+
+/// map the entries to the flat `table`, the children of every directory are looked up
+/// in its range by `same` so reordered entries still get their original index
+pub(super) fn flat<T>(
+    archive: &Archive,
+    table: &[T],
+    dir_range: impl Fn(&T) -> Option<Range<usize>> + Copy,
+    same: impl Fn(&T, &Entry) -> bool + Copy,
+) -> Vec<(PathBuf, RawIndex)> {
+    fn visit<T>(
+        map: &mut Vec<(PathBuf, RawIndex)>,
+        table: &[T],
+        path: &Path,
+        entries: &[Entry],
+        mut candidates: Vec<usize>,
+        dir_range: impl Fn(&T) -> Option<Range<usize>> + Copy,
+        same: impl Fn(&T, &Entry) -> bool + Copy,
+    ) {
+        for entry in entries {
+            let Some(position) = candidates.iter().position(|&i| same(&table[i], entry)) else {
+                continue;
+            };
+            let index = candidates.remove(position);
+
+            let path = path.join(entry.name());
+            map.push((path.clone(), RawIndex::Flat(index)));
+
+            if let (Entry::Dir(dir), Some(range)) = (entry, dir_range(&table[index])) {
+                let range = range.start.min(table.len())..range.end.min(table.len());
+                visit(
+                    map,
+                    table,
+                    &path,
+                    &dir.entries,
+                    range.collect(),
+                    dir_range,
+                    same,
+                );
+            }
+        }
+    }
+
+    let mut map = Vec::new();
+    let Some(root) = table.first().and_then(dir_range) else {
+        return map;
+    };
+
+    let mut entries = archive.entries();
+    let mut attached = Vec::new();
+    if archive.options.orphans == OrphanMode::Attach
+        && let Some((Entry::Dir(dir), rest)) = entries.split_last()
+        && dir.name == ORPHANS_DIR
+    {
+        entries = rest;
+
+        // the virtual directory itself isn't in the table, only its children
+        let roots = orphans::roots(table, archive.orphans(), dir_range);
+        let path = Path::new(ORPHANS_DIR);
+        visit(
+            &mut attached,
+            table,
+            path,
+            &dir.entries,
+            roots,
+            dir_range,
+            same,
+        );
+    }
+
+    let path = Path::new("");
+    visit(
+        &mut map,
+        table,
+        path,
+        entries,
+        root.collect(),
+        dir_range,
+        same,
+    );
+    map.extend(attached);
+    map
+}
+
+/// map the entries to the nested `tables`, `children` return the table of a directory
+pub(super) fn nested<'t, T>(
+    entries: &[Entry],
+    table: &'t [T],
+    children: impl Fn(&'t T) -> Option<&'t [T]> + Copy,
+    same: impl Fn(&T, &Entry) -> bool + Copy,
+) -> Vec<(PathBuf, RawIndex)> {
+    fn visit<'t, T>(
+        map: &mut Vec<(PathBuf, RawIndex)>,
+        path: &Path,
+        parents: &[usize],
+        entries: &[Entry],
+        table: &'t [T],
+        children: impl Fn(&'t T) -> Option<&'t [T]> + Copy,
+        same: impl Fn(&T, &Entry) -> bool + Copy,
+    ) {
+        let mut taken = vec![false; table.len()];
+        for entry in entries {
+            let Some(index) = (0..table.len()).find(|&i| !taken[i] && same(&table[i], entry))
+            else {
+                continue;
+            };
+            taken[index] = true;
+
+            let path = path.join(entry.name());
+            let mut indices = parents.to_vec();
+            indices.push(index);
+            map.push((path.clone(), RawIndex::Nested(indices.clone())));
+
+            if let (Entry::Dir(dir), Some(table)) = (entry, children(&table[index])) {
+                visit(map, &path, &indices, &dir.entries, table, children, same);
+            }
+        }
+    }
+
+    let mut map = Vec::new();
+    visit(&mut map, Path::new(""), &[], entries, table, children, same);
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{Format, for_each_fixture};
+
+    #[test]
+    fn fixtures_raw_index_map() {
+        for_each_fixture(|fixture, provider| {
+            let mut archive = Archive::new(provider);
+
+            let map = archive.raw_index_map();
+            let walked: Vec<_> = archive.walk().map(|e| e.path).collect();
+            let paths: Vec<_> = map.iter().map(|(path, _)| path.clone()).collect();
+            assert_eq!(paths, walked);
+
+            let mut indices: Vec<_> = map.iter().map(|(_, index)| index.clone()).collect();
+            for index in &indices {
+                match (fixture.format, index) {
+                    (Format::Obscure1, RawIndex::Nested(indices)) => assert!(!indices.is_empty()),
+                    // the root directory isn't a loaded entry
+                    (Format::Obscure2 | Format::FinalExam, RawIndex::Flat(index)) => {
+                        assert!(*index > 0)
+                    }
+                    _ => panic!("unexpected index {index:?}"),
+                }
+            }
+            indices.sort();
+            indices.dedup();
+            assert_eq!(indices.len(), map.len());
+
+            // reordered entries keep the index that they are stored at
+            let (dir, reversed) = archive
+                .walk()
+                .find_map(|e| match e.entry {
+                    Entry::Dir(dir) if dir.entries.len() > 1 => Some((
+                        e.path,
+                        dir.entries
+                            .iter()
+                            .rev()
+                            .map(|e| e.name().to_owned())
+                            .collect::<Vec<_>>(),
+                    )),
+                    _ => None,
+                })
+                .expect("fixture doesn't have a directory with multiple entries");
+            archive
+                .reorder(&dir, &reversed)
+                .expect("failed to reorder directory");
+
+            let mut reordered = archive.raw_index_map();
+            assert_ne!(reordered, map);
+            reordered.sort();
+            let mut map = map;
+            map.sort();
+            assert_eq!(reordered, map);
+        });
+    }
+}
@@ -1,7 +1,344 @@
+use std::{
+    io::{self, Write},
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
 /// a trait that can be used to share the rebuild progress with the user
 pub trait RebuildProgress {
+    /// called once before the data of the first file is written, with the number of data bytes
+    /// that will be written. it's an estimate when updated files are compressed again, `None`
+    /// when it isn't known
+    fn start(&self, total_bytes: Option<u64>) {
+        let _ = total_bytes;
+    }
     /// incress the progress by 1
     fn inc(&self, message: Option<String>);
     /// incress the progress by n
     fn inc_n(&self, n: usize, message: Option<String>);
+    /// called by [`watch`] when there was no progress for a while, `message` is the last
+    /// message that is passed to the progress, usually the name of the entry that is being processed
+    fn stalled(&self, message: Option<&str>, elapsed: Duration) {
+        let _ = (message, elapsed);
+    }
+    /// called while the data of a file is written to the new archive, with the number of bytes
+    /// that are written since the last call. big files are reported in chunks, see [`write_data`]
+    fn written(&self, bytes: u64) {
+        let _ = bytes;
+    }
+}
+
+impl<T: RebuildProgress + ?Sized> RebuildProgress for &T {
+    fn start(&self, total_bytes: Option<u64>) {
+        (**self).start(total_bytes)
+    }
+
+    fn inc(&self, message: Option<String>) {
+        (**self).inc(message)
+    }
+
+    fn inc_n(&self, n: usize, message: Option<String>) {
+        (**self).inc_n(n, message)
+    }
+
+    fn stalled(&self, message: Option<&str>, elapsed: Duration) {
+        (**self).stalled(message, elapsed)
+    }
+
+    fn written(&self, bytes: u64) {
+        (**self).written(bytes)
+    }
+}
+
+/// size of the chunks that [`write_data`] report
+const WRITE_CHUNK: usize = 1 << 20;
+
+/// write the data of a file and report every written chunk to [`RebuildProgress::written`],
+/// so a single big file doesn't freeze the progress until it's fully written
+pub fn write_data<W: Write + ?Sized>(
+    writer: &mut W,
+    progress: &impl RebuildProgress,
+    bytes: &[u8],
+) -> io::Result<()> {
+    for chunk in bytes.chunks(WRITE_CHUNK) {
+        writer.write_all(chunk)?;
+        progress.written(chunk.len() as u64);
+    }
+    Ok(())
+}
+
+/// a progress that is watched by [`watch`], it forward everything to the inner progress
+pub struct Watched<P> {
+    progress: P,
+    state: Mutex<WatchState>,
+    changed: Condvar,
+}
+
+struct WatchState {
+    last_progress: Instant,
+    message: Option<String>,
+    done: bool,
+}
+
+impl<P: RebuildProgress> Watched<P> {
+    /// mark the start of the work on an entry without increasing the progress,
+    /// so a stall report can name it
+    pub fn working_on(&self, message: String) {
+        self.state.lock().unwrap().message = Some(message);
+    }
+
+    fn progressed(&self, message: Option<&String>) {
+        let mut state = self.state.lock().unwrap();
+        state.last_progress = Instant::now();
+        if let Some(message) = message {
+            state.message = Some(message.clone());
+        }
+    }
+
+    /// wait until the work is done, and report every `timeout` without any progress
+    fn run(&self, timeout: Duration) {
+        let mut state = self.state.lock().unwrap();
+        let mut reported = None;
+
+        while !state.done {
+            let since = reported
+                .unwrap_or(state.last_progress)
+                .max(state.last_progress);
+            let elapsed = since.elapsed();
+
+            if elapsed < timeout {
+                state = self
+                    .changed
+                    .wait_timeout(state, timeout - elapsed)
+                    .unwrap()
+                    .0;
+                continue;
+            }
+
+            let message = state.message.clone();
+            let elapsed = state.last_progress.elapsed();
+            reported = Some(Instant::now());
+
+            // don't hold the lock while reporting, so the work isn't blocked by it
+            drop(state);
+            self.progress.stalled(message.as_deref(), elapsed);
+            state = self.state.lock().unwrap();
+        }
+    }
+}
+
+impl<P: RebuildProgress> RebuildProgress for Watched<P> {
+    fn start(&self, total_bytes: Option<u64>) {
+        self.progress.start(total_bytes);
+    }
+
+    fn inc(&self, message: Option<String>) {
+        self.progressed(message.as_ref());
+        self.progress.inc(message);
+    }
+
+    fn inc_n(&self, n: usize, message: Option<String>) {
+        self.progressed(message.as_ref());
+        self.progress.inc_n(n, message);
+    }
+
+    fn stalled(&self, message: Option<&str>, elapsed: Duration) {
+        self.progress.stalled(message, elapsed);
+    }
+
+    fn written(&self, bytes: u64) {
+        // a big file that is still being written isn't a stall
+        self.progressed(None);
+        self.progress.written(bytes);
+    }
+}
+
+/// run `work` while watching its progress, [`RebuildProgress::stalled`] is called every `timeout`
+/// without any progress. this can tell a slow disk apart from a file that take forever to (de)compress.
+///
+/// with `None` as timeout nothing is watched and the progress is only forwarded
+pub fn watch<P, R>(progress: P, timeout: Option<Duration>, work: impl FnOnce(&Watched<P>) -> R) -> R
+where
+    P: RebuildProgress + Sync,
+{
+    let watched = Watched {
+        progress,
+        state: Mutex::new(WatchState {
+            last_progress: Instant::now(),
+            message: None,
+            done: false,
+        }),
+        changed: Condvar::new(),
+    };
+
+    let Some(timeout) = timeout else {
+        return work(&watched);
+    };
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| watched.run(timeout));
+
+        // stop the watcher even if the work panic, otherwise the scope never end
+        let _finish = Finish(&watched);
+        work(&watched)
+    })
+}
+
+struct Finish<'a, P>(&'a Watched<P>);
+
+impl<P> Drop for Finish<'_, P> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.0.state.lock() {
+            state.done = true;
+        }
+        self.0.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    use super::*;
+    use crate::{
+        archive::{Archive, builder::ArchiveBuilder},
+        test_utils::for_each_fixture,
+    };
+
+    #[test]
+    fn watch_report_stalled_fixtures_rebuild() {
+        /// take a long time on the first file, like a file that is very slow to compress
+        #[derive(Default)]
+        struct SlowProgress {
+            calls: Mutex<usize>,
+            stalls: Mutex<Vec<Option<String>>>,
+        }
+
+        impl RebuildProgress for SlowProgress {
+            fn inc(&self, _: Option<String>) {
+                let mut calls = self.calls.lock().unwrap();
+                *calls += 1;
+                if *calls == 1 {
+                    std::thread::sleep(Duration::from_millis(200));
+                }
+            }
+
+            fn inc_n(&self, n: usize, _: Option<String>) {
+                *self.calls.lock().unwrap() += n;
+            }
+
+            fn stalled(&self, message: Option<&str>, elapsed: Duration) {
+                assert!(elapsed >= Duration::from_millis(20));
+                self.stalls.lock().unwrap().push(message.map(str::to_owned));
+            }
+        }
+
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+            let progress = SlowProgress::default();
+
+            let mut writer = Cursor::new(Vec::new());
+            watch(&progress, Some(Duration::from_millis(20)), |progress| {
+                archive.rebuild(&mut writer, progress)
+            })
+            .expect("failed to rebuild archive");
+
+            assert_eq!(crc32fast::hash(writer.get_ref()), fixture.archive_crc32);
+
+            let stalls = progress.stalls.lock().unwrap();
+            assert!(!stalls.is_empty(), "stall wasn't reported");
+            assert!(
+                stalls.iter().all(|m| m.is_some()),
+                "stall without the entry name"
+            );
+
+            drop(stalls);
+
+            // without a timeout nothing is watched
+            progress.stalls.lock().unwrap().clear();
+            *progress.calls.lock().unwrap() = 0;
+            watch(&progress, None, |progress| {
+                archive.rebuild(&mut Cursor::new(Vec::new()), progress)
+            })
+            .expect("failed to rebuild archive");
+            assert!(progress.stalls.lock().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn fixtures_rebuild_report_written_bytes() {
+        #[derive(Default)]
+        struct BytesProgress {
+            total: Mutex<Option<Option<u64>>>,
+            written: AtomicU64,
+        }
+
+        impl RebuildProgress for BytesProgress {
+            fn start(&self, total_bytes: Option<u64>) {
+                let previous = self.total.lock().unwrap().replace(total_bytes);
+                assert!(previous.is_none(), "start is called more than once");
+            }
+
+            fn inc(&self, _: Option<String>) {}
+
+            fn inc_n(&self, _: usize, _: Option<String>) {}
+
+            fn written(&self, bytes: u64) {
+                self.written.fetch_add(bytes, Ordering::Relaxed);
+            }
+        }
+
+        for_each_fixture(|_, provider| {
+            let archive = Archive::new(provider);
+
+            // without any update the data of every file is copied as is, so the total is exact
+            let expected: u64 = archive.files().map(|f| f.raw_bytes.len() as u64).sum();
+
+            let progress = BytesProgress::default();
+            archive
+                .rebuild(&mut Cursor::new(Vec::new()), &progress)
+                .expect("failed to rebuild archive");
+            assert_eq!(progress.written.load(Ordering::Relaxed), expected);
+            assert_eq!(*progress.total.lock().unwrap(), Some(Some(expected)));
+
+            let progress = BytesProgress::default();
+            ArchiveBuilder::from_archive(&archive)
+                .unwrap()
+                .build(&mut Cursor::new(Vec::new()), &progress)
+                .expect("failed to build archive");
+            assert_eq!(
+                *progress.total.lock().unwrap(),
+                Some(Some(progress.written.load(Ordering::Relaxed))),
+                "builder"
+            );
+        });
+    }
+
+    #[test]
+    fn write_data_report_chunks() {
+        #[derive(Default)]
+        struct ChunksProgress(Mutex<Vec<u64>>);
+
+        impl RebuildProgress for ChunksProgress {
+            fn inc(&self, _: Option<String>) {}
+
+            fn inc_n(&self, _: usize, _: Option<String>) {}
+
+            fn written(&self, bytes: u64) {
+                self.0.lock().unwrap().push(bytes);
+            }
+        }
+
+        // a big file is reported while it's written, not only once at the end
+        let bytes = vec![7; (5 << 20) / 2];
+        let progress = ChunksProgress::default();
+        let mut out = Vec::new();
+        write_data(&mut out, &progress, &bytes).unwrap();
+
+        assert_eq!(out, bytes);
+        assert_eq!(*progress.0.lock().unwrap(), [1 << 20, 1 << 20, 1 << 19]);
+    }
 }
@@ -0,0 +1,171 @@
+//! checking that a rebuild of an unchanged archive is byte identical to the source archive
+
+use std::{
+    io::{self, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use super::error::RebuildError;
+
+/// writer that compare everything written to it with the source archive at the same offset.
+///
+/// the TOC is written after the data, so the written bytes are compared in place instead of
+/// hashing them in the order that they are written
+pub(super) struct RoundtripWriter<'a, W> {
+    inner: &'a mut W,
+    source: &'a [u8],
+    /// position of the inner writer when the rebuild started
+    start: u64,
+    /// position relative to `start`
+    pos: u64,
+    /// ranges that were written, adjacent writes are merged
+    written: Vec<Range<u64>>,
+    /// first offset where the output is different from the source
+    diverged_at: Option<u64>,
+}
+
+impl<'a, W: Write + Seek> RoundtripWriter<'a, W> {
+    pub(super) fn new(inner: &'a mut W, source: &'a [u8]) -> io::Result<Self> {
+        let start = inner.stream_position()?;
+        Ok(Self {
+            inner,
+            source,
+            start,
+            pos: 0,
+            written: Vec::new(),
+            diverged_at: None,
+        })
+    }
+
+    fn check(&mut self, buf: &[u8]) {
+        let range = self.pos..self.pos + buf.len() as u64;
+
+        let source = self.source.get(range.start as usize..).unwrap_or_default();
+        let diverged = buf
+            .iter()
+            .zip(source)
+            .position(|(a, b)| a != b)
+            .or((buf.len() > source.len()).then_some(source.len()));
+        if let Some(idx) = diverged {
+            let offset = range.start + idx as u64;
+            self.diverged_at = Some(self.diverged_at.map_or(offset, |o| o.min(offset)));
+        }
+
+        match self.written.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => self.written.push(range),
+        }
+    }
+
+    /// make sure that the whole source was written back and nothing diverged
+    pub(super) fn finish(mut self) -> Result<(), RebuildError> {
+        self.written.sort_by_key(|r| r.start);
+
+        // first offset that wasn't written
+        let mut covered = 0;
+        for range in &self.written {
+            if range.start > covered {
+                break;
+            }
+            covered = covered.max(range.end);
+        }
+
+        let len = self.source.len() as u64;
+        let missing = (covered < len).then_some(covered);
+
+        // writes past the end of the source are already diverged
+        match [self.diverged_at, missing].into_iter().flatten().min() {
+            Some(offset) => Err(RebuildError::RoundtripDiverged(offset)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<W: Write + Seek> Write for RoundtripWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.check(&buf[..written]);
+        self.pos += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for RoundtripWriter<'_, W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => SeekFrom::Start(self.start + offset),
+            pos => pos,
+        };
+        let new = self.inner.seek(pos)?;
+        self.pos = new.checked_sub(self.start).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before the start of the archive",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        archive::{
+            Archive,
+            entry::{Entry, UpdateKind},
+        },
+        test_utils::{EmptyProgress, for_each_fixture},
+    };
+
+    #[test]
+    fn fixtures_rebuild_guarantee_roundtrip() {
+        for_each_fixture(|fixture, provider| {
+            let mut archive = Archive::new(provider);
+            archive.options.guarantee_roundtrip = true;
+
+            // the archive doesn't have to be written at the start of the writer
+            let mut writer = Cursor::new(b"prefix".to_vec());
+            writer.set_position(6);
+            archive
+                .rebuild(&mut writer, EmptyProgress)
+                .expect("failed to rebuild archive");
+            assert!(
+                writer.into_inner()[6..] == *fixture.bytes,
+                "rebuild isn't byte identical"
+            );
+
+            // change the data of a file without updating it, so the output diverge
+            let Some(Entry::File(file)) = archive
+                .entries_mut()
+                .iter_mut()
+                .find(|e| matches!(e, Entry::File(f) if !f.raw_bytes.is_empty()))
+            else {
+                return;
+            };
+            let mut raw_bytes = file.raw_bytes.to_vec();
+            raw_bytes[0] ^= 0xff;
+            file.raw_bytes = Vec::leak(raw_bytes);
+
+            let result = archive.rebuild(&mut Cursor::new(Vec::new()), EmptyProgress);
+            assert!(
+                matches!(result, Err(RebuildError::RoundtripDiverged(_))),
+                "{result:?}"
+            );
+
+            // updated archives aren't checked
+            let mut file = archive.files_mut().next().unwrap();
+            let bytes = file.get_bytes().unwrap().into_owned();
+            file.update(UpdateKind::Bytes(bytes));
+            archive
+                .rebuild(&mut Cursor::new(Vec::new()), EmptyProgress)
+                .expect("failed to rebuild updated archive");
+        });
+    }
+}
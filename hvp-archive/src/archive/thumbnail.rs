@@ -0,0 +1,624 @@
+//! small rgba previews of the textures inside the archives
+//!
+//! only the formats that can be recognized are decoded: windows bitmaps (`.bmp`) and truevision
+//! targa images (`.tga`), stored or run length encoded. the game specific texture formats like
+//! `.hvt` aren't documented, so they don't have a preview.
+
+use std::path::{Path, PathBuf};
+
+use super::{Archive, entry::DecompressError};
+
+/// the most pixels of a texture that are decoded, the games don't have textures this large
+const MAX_PIXELS: usize = 1 << 24;
+
+/// the texture formats that have a thumbnail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Bmp,
+    Tga,
+}
+
+impl TextureFormat {
+    /// format of the file, bitmaps are recognized by their content and targa images (that don't
+    /// have a magic number) by their extension
+    pub fn detect(path: &Path, bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"BM") {
+            return Some(Self::Bmp);
+        }
+
+        path.extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("tga"))
+            .then_some(Self::Tga)
+    }
+}
+
+/// errors that can happen when creating a thumbnail
+#[derive(Debug, thiserror::Error)]
+pub enum ThumbnailError {
+    #[error("can't find {} in the archive", .0.display())]
+    NotFound(PathBuf),
+    #[error("{} isn't a texture that can be previewed", .0.display())]
+    Unsupported(PathBuf),
+    #[error(transparent)]
+    Decompress(#[from] DecompressError),
+    #[error("invalid {format:?} image: {reason}")]
+    Invalid {
+        format: TextureFormat,
+        reason: &'static str,
+    },
+}
+
+/// a small rgba preview of a texture, see [`Archive::thumbnail`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub width: u32,
+    pub height: u32,
+    /// 4 bytes per pixel (red, green, blue and alpha), the rows are from top to bottom
+    pub pixels: Vec<u8>,
+}
+
+impl Thumbnail {
+    /// rgba of the pixel, `(0, 0)` is the top left one
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let idx = (y as usize * self.width as usize + x as usize) * 4;
+        self.pixels[idx..idx + 4].try_into().unwrap()
+    }
+
+    /// decode the texture and scale it down so its width and height are at most `max_dim`,
+    /// smaller textures keep their size
+    pub fn decode(
+        format: TextureFormat,
+        bytes: &[u8],
+        max_dim: u32,
+    ) -> Result<Self, ThumbnailError> {
+        let image = match format {
+            TextureFormat::Bmp => decode_bmp(bytes),
+            TextureFormat::Tga => decode_tga(bytes),
+        }
+        .map_err(|reason| ThumbnailError::Invalid { format, reason })?;
+
+        Ok(image.scale_down(max_dim.max(1)))
+    }
+
+    /// average the blocks of pixels that are merged, so thin lines don't disappear
+    fn scale_down(self, max_dim: u32) -> Self {
+        let largest = self.width.max(self.height);
+        if largest <= max_dim {
+            return self;
+        }
+
+        let scaled = |dim: u32| (dim as u64 * max_dim as u64 / largest as u64).max(1) as u32;
+        let (width, height) = (scaled(self.width), scaled(self.height));
+        // first and last source pixel (exclusive) of each target pixel
+        let range = |idx: u32, target: u32, source: u32| {
+            let start = idx as u64 * source as u64 / target as u64;
+            let end = (idx as u64 + 1) * source as u64 / target as u64;
+            start as u32..(end as u32).max(start as u32 + 1)
+        };
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            let rows = range(y, height, self.height);
+            for x in 0..width {
+                let columns = range(x, width, self.width);
+                let mut sum = [0_u64; 4];
+                for sy in rows.clone() {
+                    for sx in columns.clone() {
+                        for (total, channel) in sum.iter_mut().zip(self.pixel(sx, sy)) {
+                            *total += channel as u64;
+                        }
+                    }
+                }
+                let count = rows.len() as u64 * columns.len() as u64;
+                pixels.extend(sum.map(|total| (total / count) as u8));
+            }
+        }
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+impl Archive<'_> {
+    /// a small rgba preview of a texture of the archive, whose width and height are at most
+    /// `max_dim`. see [`TextureFormat`] for the formats that have a preview
+    pub fn thumbnail(
+        &self,
+        path: impl AsRef<Path>,
+        max_dim: u32,
+    ) -> Result<Thumbnail, ThumbnailError> {
+        let path = path.as_ref();
+        let file = self
+            .find_file(path)
+            .ok_or_else(|| ThumbnailError::NotFound(path.to_path_buf()))?;
+        let bytes = file.get_bytes()?;
+        let format = TextureFormat::detect(path, &bytes)
+            .ok_or_else(|| ThumbnailError::Unsupported(path.to_path_buf()))?;
+
+        Thumbnail::decode(format, &bytes, max_dim)
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// size of the decoded image, or an error if it's empty or too large
+fn pixel_count(width: u32, height: u32) -> Result<usize, &'static str> {
+    match width as usize * height as usize {
+        0 => Err("image is empty"),
+        count if count > MAX_PIXELS => Err("image is too large"),
+        count => Ok(count),
+    }
+}
+
+/// rgba of a 15 or 16 bits `ARRRRRGG GGGBBBBB` pixel
+fn rgb555(value: u16, alpha: bool) -> [u8; 4] {
+    let channel = |shift: u16| {
+        let c = ((value >> shift) & 0x1F) as u8;
+        (c << 3) | (c >> 2)
+    };
+    let a = match alpha && value & 0x8000 == 0 {
+        true => 0,
+        false => 0xFF,
+    };
+    [channel(10), channel(5), channel(0), a]
+}
+
+/// uncompressed bitmaps with 1, 4, 8, 16, 24 or 32 bits per pixel
+fn decode_bmp(bytes: &[u8]) -> Result<Thumbnail, &'static str> {
+    const TRUNCATED: &str = "file is truncated";
+
+    let data_offset = u32_at(bytes, 10).ok_or(TRUNCATED)? as usize;
+    let header_size = u32_at(bytes, 14).ok_or(TRUNCATED)? as usize;
+    if header_size < 40 {
+        return Err("only bitmaps with a BITMAPINFOHEADER or newer header are supported");
+    }
+
+    let width = u32_at(bytes, 18).ok_or(TRUNCATED)? as i32;
+    let height = u32_at(bytes, 22).ok_or(TRUNCATED)? as i32;
+    let bits = u16_at(bytes, 28).ok_or(TRUNCATED)?;
+    let compression = u32_at(bytes, 30).ok_or(TRUNCATED)?;
+    let colors_used = u32_at(bytes, 46).ok_or(TRUNCATED)? as usize;
+
+    // compression 3 (bit fields) is only accepted with the default masks
+    let default_masks = match (compression, bits) {
+        (0, _) => true,
+        (3, 16) => u32_at(bytes, 54) == Some(0x7C00) && u32_at(bytes, 58) == Some(0x03E0),
+        (3, 32) => u32_at(bytes, 54) == Some(0xFF_0000) && u32_at(bytes, 58) == Some(0xFF00),
+        _ => false,
+    };
+    if !default_masks {
+        return Err("compressed bitmaps aren't supported");
+    }
+    if width <= 0 || height == 0 || height == i32::MIN {
+        return Err("invalid image size");
+    }
+
+    // the rows are stored from the bottom unless the height is negative
+    let top_down = height < 0;
+    let (width, height) = (width as u32, height.unsigned_abs());
+    pixel_count(width, height)?;
+
+    let palette = match bits {
+        1 | 4 | 8 => {
+            let count = match colors_used {
+                0 => 1 << bits,
+                count => count.min(1 << bits),
+            };
+            let start = 14 + header_size;
+            let palette = bytes.get(start..start + count * 4).ok_or(TRUNCATED)?;
+            palette
+                .chunks_exact(4)
+                .map(|c| [c[2], c[1], c[0], 0xFF])
+                .collect()
+        }
+        16 | 24 | 32 => Vec::new(),
+        _ => return Err("unsupported bits per pixel"),
+    };
+
+    let stride = (width as usize * bits as usize).div_ceil(32) * 4;
+    let data = bytes
+        .get(data_offset..)
+        .and_then(|data| data.get(..stride * height as usize))
+        .ok_or(TRUNCATED)?;
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for y in 0..height as usize {
+        let row = match top_down {
+            true => y,
+            false => height as usize - 1 - y,
+        };
+        let row = &data[row * stride..][..stride];
+        for x in 0..width as usize {
+            let pixel = match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits as usize;
+                    let shift = 8 - bits as usize - bit % 8;
+                    let idx = (row[bit / 8] >> shift) as usize & ((1 << bits) - 1);
+                    *palette.get(idx).ok_or("color isn't in the palette")?
+                }
+                16 => rgb555(u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]), false),
+                // the fourth byte of 32 bits pixels isn't used
+                _ => {
+                    let p = &row[x * bits as usize / 8..];
+                    [p[2], p[1], p[0], 0xFF]
+                }
+            };
+            pixels.extend(pixel);
+        }
+    }
+
+    Ok(Thumbnail {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// color mapped, true color and grayscale targa images, stored or run length encoded
+fn decode_tga(bytes: &[u8]) -> Result<Thumbnail, &'static str> {
+    const TRUNCATED: &str = "file is truncated";
+
+    let header = bytes.get(..18).ok_or(TRUNCATED)?;
+    let id_len = header[0] as usize;
+    let color_map_type = header[1];
+    let image_type = header[2];
+    let map_first = u16_at(header, 3).unwrap() as usize;
+    let map_len = u16_at(header, 5).unwrap() as usize;
+    let map_bits = header[7];
+    let width = u16_at(header, 12).unwrap() as u32;
+    let height = u16_at(header, 14).unwrap() as u32;
+    let bits = header[16];
+    let descriptor = header[17];
+    let alpha_bits = descriptor & 0x0F;
+    let count = pixel_count(width, height)?;
+
+    let rle = image_type & 8 != 0;
+    // rgba of a stored pixel value
+    let color = |p: &[u8], bits: u8| -> [u8; 4] {
+        match bits {
+            8 => [p[0], p[0], p[0], 0xFF],
+            15 | 16 => rgb555(u16::from_le_bytes([p[0], p[1]]), alpha_bits > 0),
+            24 => [p[2], p[1], p[0], 0xFF],
+            // the alpha is ignored when the descriptor say that there isn't any
+            _ => [p[2], p[1], p[0], if alpha_bits > 0 { p[3] } else { 0xFF }],
+        }
+    };
+
+    let mut pos = 18 + id_len;
+    let palette: Vec<[u8; 4]> = match color_map_type {
+        0 => Vec::new(),
+        1 => {
+            if !matches!(map_bits, 15 | 16 | 24 | 32) {
+                return Err("unsupported color map entry size");
+            }
+            let entry_len = (map_bits as usize).div_ceil(8);
+            let map = bytes.get(pos..pos + map_len * entry_len).ok_or(TRUNCATED)?;
+            pos += map.len();
+            map.chunks_exact(entry_len)
+                .map(|c| color(c, map_bits))
+                .collect()
+        }
+        _ => return Err("unsupported color map type"),
+    };
+
+    let pixel_len = match (image_type & !8, bits) {
+        (1, 8) if !palette.is_empty() => 1,
+        (2, 15 | 16) => 2,
+        (2, 24) => 3,
+        (2, 32) => 4,
+        (3, 8) => 1,
+        (1..=3, _) => return Err("unsupported bits per pixel"),
+        _ => return Err("unsupported image type"),
+    };
+    let to_rgba = |p: &[u8]| -> Result<[u8; 4], &'static str> {
+        match image_type & !8 {
+            1 => palette
+                .get((p[0] as usize).wrapping_sub(map_first))
+                .copied()
+                .ok_or("color isn't in the color map"),
+            _ => Ok(color(p, bits)),
+        }
+    };
+
+    let mut decoded = Vec::with_capacity(count * 4);
+    let data = bytes.get(pos..).unwrap_or_default();
+    match rle {
+        false => {
+            let data = data.get(..count * pixel_len).ok_or(TRUNCATED)?;
+            for p in data.chunks_exact(pixel_len) {
+                decoded.extend(to_rgba(p)?);
+            }
+        }
+        true => {
+            let mut pos = 0;
+            while decoded.len() < count * 4 {
+                let packet = *data.get(pos).ok_or(TRUNCATED)?;
+                let len = (packet & 0x7F) as usize + 1;
+                pos += 1;
+                if packet & 0x80 != 0 {
+                    let p = data.get(pos..pos + pixel_len).ok_or(TRUNCATED)?;
+                    let pixel = to_rgba(p)?;
+                    for _ in 0..len {
+                        decoded.extend(pixel);
+                    }
+                    pos += pixel_len;
+                } else {
+                    let run = data.get(pos..pos + len * pixel_len).ok_or(TRUNCATED)?;
+                    for p in run.chunks_exact(pixel_len) {
+                        decoded.extend(to_rgba(p)?);
+                    }
+                    pos += run.len();
+                }
+            }
+            // a packet can't cross the end of the image, but some writers let it
+            decoded.truncate(count * 4);
+        }
+    }
+
+    // the rows are stored from the bottom unless bit 5 of the descriptor is set, and from the
+    // left unless bit 4 is set
+    let row_len = width as usize * 4;
+    if descriptor & 0x20 == 0 {
+        let rows: Vec<_> = decoded
+            .chunks_exact(row_len)
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        decoded = rows;
+    }
+    if descriptor & 0x10 != 0 {
+        for row in decoded.chunks_exact_mut(row_len) {
+            row.reverse();
+            for pixel in row.chunks_exact_mut(4) {
+                pixel.reverse();
+            }
+        }
+    }
+
+    Ok(Thumbnail {
+        width,
+        height,
+        pixels: decoded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        Game,
+        archive::{builder::ArchiveBuilder, entry::UpdateKind},
+        test_utils::{EmptyProgress, reload},
+    };
+
+    const RED: [u8; 4] = [0xFF, 0, 0, 0xFF];
+    const GREEN: [u8; 4] = [0, 0xFF, 0, 0xFF];
+    const BLUE: [u8; 4] = [0, 0, 0xFF, 0xFF];
+    const WHITE: [u8; 4] = [0xFF; 4];
+
+    /// 2x2 image, red and green on the top row and blue and white on the bottom one
+    fn expected() -> Thumbnail {
+        Thumbnail {
+            width: 2,
+            height: 2,
+            pixels: [RED, GREEN, BLUE, WHITE].concat(),
+        }
+    }
+
+    fn bmp(bits: u16, palette: &[[u8; 4]], rows: &[&[u8]]) -> Vec<u8> {
+        let data_offset = 54 + palette.len() as u32 * 4;
+        let mut bmp = b"BM".to_vec();
+        bmp.extend(0_u32.to_le_bytes());
+        bmp.extend(0_u32.to_le_bytes());
+        bmp.extend(data_offset.to_le_bytes());
+        bmp.extend(40_u32.to_le_bytes());
+        bmp.extend(2_i32.to_le_bytes());
+        bmp.extend(2_i32.to_le_bytes());
+        bmp.extend(1_u16.to_le_bytes());
+        bmp.extend(bits.to_le_bytes());
+        bmp.extend([0; 16]);
+        bmp.extend((palette.len() as u32).to_le_bytes());
+        bmp.extend([0; 4]);
+        for [r, g, b, _] in palette {
+            bmp.extend([*b, *g, *r, 0]);
+        }
+        // bottom row first
+        for row in rows.iter().rev() {
+            bmp.extend(*row);
+        }
+        bmp
+    }
+
+    fn tga(image_type: u8, bits: u8, descriptor: u8, data: &[u8]) -> Vec<u8> {
+        let mut tga = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0];
+        tga.extend([bits, descriptor]);
+        tga.extend(data);
+        tga
+    }
+
+    fn decode(format: TextureFormat, bytes: &[u8]) -> Thumbnail {
+        Thumbnail::decode(format, bytes, 64).unwrap()
+    }
+
+    #[test]
+    fn decode_bitmaps() {
+        // rows are padded to 4 bytes
+        let bmp24 = bmp(
+            24,
+            &[],
+            &[
+                &[0, 0, 0xFF, 0, 0xFF, 0, 0, 0],
+                &[0xFF, 0, 0, 0xFF, 0xFF, 0xFF, 0, 0],
+            ],
+        );
+        assert_eq!(decode(TextureFormat::Bmp, &bmp24), expected());
+
+        let bmp32 = bmp(
+            32,
+            &[],
+            &[
+                &[0, 0, 0xFF, 0, 0, 0xFF, 0, 0],
+                &[0xFF, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0],
+            ],
+        );
+        assert_eq!(decode(TextureFormat::Bmp, &bmp32), expected());
+
+        let palette = [WHITE, RED, GREEN, BLUE];
+        let bmp8 = bmp(8, &palette, &[&[1, 2, 0, 0], &[3, 0, 0, 0]]);
+        assert_eq!(decode(TextureFormat::Bmp, &bmp8), expected());
+
+        let bmp4 = bmp(4, &palette, &[&[0x12, 0, 0, 0], &[0x30, 0, 0, 0]]);
+        assert_eq!(decode(TextureFormat::Bmp, &bmp4), expected());
+
+        let bmp16 = bmp(16, &[], &[&[0, 0x7C, 0xE0, 0x03], &[0x1F, 0, 0xFF, 0x7F]]);
+        assert_eq!(decode(TextureFormat::Bmp, &bmp16), expected());
+    }
+
+    #[test]
+    fn decode_targa_images() {
+        // bottom row first, unless bit 5 of the descriptor is set
+        let tga24 = tga(
+            2,
+            24,
+            0,
+            &[0xFF, 0, 0, 0xFF, 0xFF, 0xFF, 0, 0, 0xFF, 0, 0xFF, 0],
+        );
+        assert_eq!(decode(TextureFormat::Tga, &tga24), expected());
+
+        let tga32 = tga(
+            2,
+            32,
+            0x28,
+            &[
+                0, 0, 0xFF, 0xFF, 0, 0xFF, 0, 0xFF, 0xFF, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+        );
+        assert_eq!(decode(TextureFormat::Tga, &tga32), expected());
+
+        // a run of two white pixels and two stored ones, from the bottom right
+        let rle = tga(
+            10,
+            24,
+            0x10,
+            &[0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0x81, 0, 0xFF, 0],
+        );
+        let mut expected_rle = expected();
+        expected_rle.pixels = [GREEN, GREEN, BLUE, WHITE].concat();
+        assert_eq!(decode(TextureFormat::Tga, &rle), expected_rle);
+
+        let gray = tga(3, 8, 0x20, &[0, 0x80, 0xFF, 0x10]);
+        assert_eq!(
+            decode(TextureFormat::Tga, &gray).pixels,
+            [
+                0, 0, 0, 0xFF, 0x80, 0x80, 0x80, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x10, 0x10, 0x10,
+                0xFF
+            ]
+        );
+
+        // 24 bits color map of 3 colors starting at index 1
+        let mut mapped = vec![0, 1, 1, 1, 0, 3, 0, 24, 0, 0, 0, 0, 2, 0, 2, 0, 8, 0x20];
+        mapped.extend([0, 0, 0xFF, 0, 0xFF, 0, 0xFF, 0, 0]);
+        mapped.extend([1, 2, 3, 1]);
+        let mut expected_mapped = expected();
+        expected_mapped.pixels = [RED, GREEN, BLUE, RED].concat();
+        assert_eq!(decode(TextureFormat::Tga, &mapped), expected_mapped);
+    }
+
+    #[test]
+    fn scale_down_average_pixels() {
+        let big = Thumbnail {
+            width: 4,
+            height: 2,
+            pixels: [RED, RED, BLUE, WHITE, RED, RED, BLUE, WHITE].concat(),
+        };
+        let small = big.clone().scale_down(2);
+        assert_eq!((small.width, small.height), (2, 1));
+        assert_eq!(small.pixel(0, 0), RED);
+        assert_eq!(small.pixel(1, 0), [0x7F, 0x7F, 0xFF, 0xFF]);
+
+        assert_eq!(big.clone().scale_down(4), big);
+        assert_eq!(big.scale_down(1).width, 1);
+    }
+
+    #[test]
+    fn reject_invalid_images() {
+        let bmp24 = bmp(24, &[], &[&[0; 8], &[0; 8]]);
+        for len in [0, 2, 20, 40, bmp24.len() - 1] {
+            assert!(Thumbnail::decode(TextureFormat::Bmp, &bmp24[..len], 8).is_err());
+        }
+
+        let mut compressed = bmp24.clone();
+        compressed[30] = 1;
+        assert!(Thumbnail::decode(TextureFormat::Bmp, &compressed, 8).is_err());
+
+        let tga24 = tga(10, 24, 0, &[0x83, 0, 0, 0]);
+        for len in [0, 10, 18, tga24.len() - 1] {
+            assert!(Thumbnail::decode(TextureFormat::Tga, &tga24[..len], 8).is_err());
+        }
+        assert!(Thumbnail::decode(TextureFormat::Tga, &tga(2, 24, 0, &[0; 11]), 8).is_err());
+        assert!(Thumbnail::decode(TextureFormat::Tga, &tga(4, 24, 0, &[0; 12]), 8).is_err());
+
+        // a huge image with a few bytes of run length encoded data
+        let mut huge = tga(10, 32, 0, &[0xFF; 32]);
+        huge[12..16].copy_from_slice(&[0xFF; 4]);
+        assert!(Thumbnail::decode(TextureFormat::Tga, &huge, 8).is_err());
+    }
+
+    #[test]
+    fn archive_thumbnails() {
+        let bmp24 = bmp(
+            24,
+            &[],
+            &[
+                &[0, 0, 0xFF, 0, 0xFF, 0, 0, 0],
+                &[0xFF, 0, 0, 0xFF, 0xFF, 0xFF, 0, 0],
+            ],
+        );
+        let mut builder = ArchiveBuilder::new(Game::FinalExam);
+        for (path, bytes) in [
+            ("textures/image.bmp", bmp24),
+            ("textures/image.tga", tga(2, 24, 0x20, &[0; 12])),
+            ("textures/image.hvt", vec![0; 16]),
+        ] {
+            builder.add_file(path, UpdateKind::Bytes(bytes)).unwrap();
+        }
+        let mut writer = Cursor::new(Vec::new());
+        builder.build(&mut writer, EmptyProgress).unwrap();
+        let provider = reload(writer.into_inner());
+        let archive = Archive::new(&provider);
+
+        assert_eq!(
+            archive.thumbnail("textures/image.bmp", 64).unwrap(),
+            expected()
+        );
+        assert_eq!(archive.thumbnail("textures/image.bmp", 1).unwrap().width, 1);
+        assert_eq!(
+            archive.thumbnail("textures/image.tga", 64).unwrap().pixels,
+            [0, 0, 0, 0xFF].repeat(4)
+        );
+        assert!(matches!(
+            archive.thumbnail("textures/image.hvt", 64),
+            Err(ThumbnailError::Unsupported(_))
+        ));
+        assert!(matches!(
+            archive.thumbnail("textures/missing.bmp", 64),
+            Err(ThumbnailError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,329 @@
+//! edits of the TOC fields of files (compression flag, checksum and uncompressed size), like the
+//! ones that are made by external analysis tools using the raw structures.
+//!
+//! the edits are validated against the stored data of the files before anything is changed, so the
+//! archive can still be rebuilt using [`Archive::rebuild`] or [`Archive::patch`]
+
+use std::path::PathBuf;
+
+use flate2::{Decompress, FlushDecompress, Status};
+
+use super::{
+    Archive,
+    entry::{CompressionInfo, CompressionType, FullFileEntryMut},
+    error::TocPatchError,
+};
+use crate::Game;
+
+/// a set of edits of the TOC fields of files
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TocPatch {
+    pub edits: Vec<TocEdit>,
+}
+
+/// new TOC fields of a single file, fields that are `None` are left as they are.
+/// the stored data of the file isn't changed
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TocEdit {
+    /// full path of the file inside the archive
+    pub path: PathBuf,
+    /// whatever the stored data is compressed, using the compression of the format
+    pub compressed: Option<bool>,
+    /// checksum of the stored data
+    pub checksum: Option<i32>,
+    /// size of the file after decompression
+    pub uncompressed_size: Option<u32>,
+}
+
+impl Archive<'_> {
+    /// apply the edits to the files and return the number of files that changed.
+    ///
+    /// every edit is checked before any file is changed: the checksum must match the stored data,
+    /// compressed data must decompress to exactly the uncompressed size and the uncompressed size
+    /// of a file without compression must be the size of its data
+    pub fn apply_toc_patch(&mut self, patch: &TocPatch) -> Result<usize, TocPatchError> {
+        let compression_type = match self.metadata.game {
+            Game::Obscure1 => CompressionType::Zlib,
+            Game::Obscure2 | Game::FinalExam => CompressionType::Lzo,
+        };
+
+        let mut files: ahash::HashMap<PathBuf, FullFileEntryMut> =
+            self.files_mut().map(|f| (f.path.clone(), f)).collect();
+
+        let mut changes = Vec::with_capacity(patch.edits.len());
+
+        for (idx, edit) in patch.edits.iter().enumerate() {
+            if patch.edits[..idx].iter().any(|e| e.path == edit.path) {
+                return Err(TocPatchError::DuplicateFile(edit.path.clone()));
+            }
+
+            let file = files
+                .get(&edit.path)
+                .ok_or_else(|| TocPatchError::MissingFile(edit.path.clone()))?;
+            let entry = &*file.entry;
+            let stored_size = entry.raw_bytes.len() as u32;
+
+            let compression_info = match edit.compressed.unwrap_or(entry.is_compressed()) {
+                true => {
+                    let uncompressed_size = edit
+                        .uncompressed_size
+                        .or(entry.compression_info.map(|c| c.uncompressed_size))
+                        .ok_or_else(|| TocPatchError::MissingSize(edit.path.clone()))?;
+                    let info = CompressionInfo {
+                        uncompressed_size,
+                        compression_type,
+                    };
+
+                    if !decompress_exactly(entry.raw_bytes, info) {
+                        return Err(TocPatchError::InvalidCompression {
+                            path: edit.path.clone(),
+                            uncompressed_size,
+                        });
+                    }
+
+                    Some(info)
+                }
+                false => {
+                    if let Some(size) = edit.uncompressed_size
+                        && size != stored_size
+                    {
+                        return Err(TocPatchError::SizeMismatch {
+                            path: edit.path.clone(),
+                            expected: stored_size,
+                            found: size,
+                        });
+                    }
+
+                    None
+                }
+            };
+
+            let checksum = edit.checksum.unwrap_or(entry.checksum);
+            let computed = entry.computed_stored_checksum();
+            if checksum != computed {
+                return Err(TocPatchError::ChecksumMismatch {
+                    path: edit.path.clone(),
+                    expected: computed,
+                    found: checksum,
+                });
+            }
+
+            let changed = checksum != entry.checksum
+                || compression_info.map(|c| (c.uncompressed_size, c.compression_type))
+                    != entry
+                        .compression_info
+                        .map(|c| (c.uncompressed_size, c.compression_type));
+            if changed {
+                changes.push((edit.path.clone(), compression_info, checksum));
+            }
+        }
+
+        let changed = changes.len();
+        for (path, compression_info, checksum) in changes {
+            let file = files.get_mut(&path).expect("file is checked above");
+            file.entry.compression_info = compression_info;
+            file.entry.checksum = checksum;
+        }
+
+        Ok(changed)
+    }
+
+    /// set the checksum of every file to the checksum of its stored data and return the paths of
+    /// the files that changed. the crc32s of the header are always computed again when the TOC
+    /// is written, so [`Archive::patch`] without any updated file only repair the TOC
+    pub fn repair_checksums(&mut self) -> Vec<PathBuf> {
+        self.files_mut()
+            .filter_map(|file| {
+                let computed = file.entry.computed_stored_checksum();
+                (computed != file.entry.checksum).then(|| {
+                    file.entry.checksum = computed;
+                    file.path
+                })
+            })
+            .collect()
+    }
+}
+
+/// whatever the data decompress to exactly the uncompressed size
+fn decompress_exactly(raw: &[u8], info: CompressionInfo) -> bool {
+    let size = info.uncompressed_size as usize;
+    match info.compression_type {
+        CompressionType::Zlib => {
+            let mut output = Vec::with_capacity(size + 1);
+            matches!(
+                Decompress::new(true).decompress_vec(raw, &mut output, FlushDecompress::Finish),
+                Ok(Status::StreamEnd)
+            ) && output.len() == size
+        }
+        CompressionType::Lzo => lzo1x::decompress(raw, &mut vec![0; size]).is_ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Cursor};
+
+    use super::*;
+    use crate::{
+        provider::ArchiveProvider,
+        test_utils::{for_each_fixture, rebuild, reload},
+    };
+
+    #[test]
+    fn fixtures_toc_patch_roundtrip() {
+        for_each_fixture(|fixture, provider| {
+            let mut archive = Archive::new(provider);
+
+            let file = archive
+                .files()
+                .find(|f| f.is_compressed() && f.uncompressed_size() > 0)
+                .expect("fixture doesn't have a compressed file");
+            let stored_size = file.raw_bytes.len() as u32;
+            let uncompressed_size = file.uncompressed_size() as u32;
+            let content = file.get_bytes().unwrap().into_owned();
+
+            // invalid edits don't change anything
+            let invalid = [
+                (
+                    TocEdit {
+                        path: "missing.bin".into(),
+                        ..Default::default()
+                    },
+                    "missing",
+                ),
+                (
+                    TocEdit {
+                        path: file.path.clone(),
+                        checksum: Some(file.stored_checksum().wrapping_add(1)),
+                        ..Default::default()
+                    },
+                    "checksum",
+                ),
+                (
+                    TocEdit {
+                        path: file.path.clone(),
+                        uncompressed_size: Some(uncompressed_size + 1),
+                        ..Default::default()
+                    },
+                    "compression",
+                ),
+                (
+                    TocEdit {
+                        path: file.path.clone(),
+                        compressed: Some(false),
+                        uncompressed_size: Some(uncompressed_size),
+                        ..Default::default()
+                    },
+                    "size",
+                ),
+            ];
+            for (edit, kind) in invalid {
+                let result = archive.apply_toc_patch(&TocPatch { edits: vec![edit] });
+                let expected = match kind {
+                    "missing" => matches!(result, Err(TocPatchError::MissingFile(_))),
+                    "checksum" => matches!(result, Err(TocPatchError::ChecksumMismatch { .. })),
+                    "compression" => {
+                        matches!(result, Err(TocPatchError::InvalidCompression { .. }))
+                    }
+                    _ => matches!(result, Err(TocPatchError::SizeMismatch { .. })),
+                };
+                assert!(expected, "{kind}: {result:?}");
+            }
+
+            // store the compressed data as a file without compression
+            let patch = TocPatch {
+                edits: vec![TocEdit {
+                    path: file.path.clone(),
+                    compressed: Some(false),
+                    ..Default::default()
+                }],
+            };
+            assert_eq!(archive.apply_toc_patch(&patch).unwrap(), 1);
+
+            let rebuilt = rebuild(&archive);
+            let mut patched = Cursor::new(fixture.bytes.to_vec());
+            archive.patch(&mut patched).unwrap();
+
+            for (kind, bytes) in [("rebuilt", rebuilt), ("patched", patched.into_inner())] {
+                let provider = reload(bytes);
+                let mut edited = Archive::new(&provider);
+
+                let entry = edited.find_file(&file.path).unwrap();
+                assert!(!entry.is_compressed(), "{kind}");
+                assert_eq!(entry.uncompressed_size() as u32, stored_size, "{kind}");
+                assert!(edited.verify().is_ok(), "{kind}");
+
+                // and back to the original fields
+                let patch = TocPatch {
+                    edits: vec![TocEdit {
+                        path: file.path.clone(),
+                        compressed: Some(true),
+                        uncompressed_size: Some(uncompressed_size),
+                        checksum: None,
+                    }],
+                };
+                edited.apply_toc_patch(&patch).unwrap();
+
+                let provider = reload(rebuild(&edited));
+                let restored = Archive::new(&provider);
+
+                let entry = restored.find_file(&file.path).unwrap();
+                assert!(entry.is_compressed(), "{kind}");
+                assert_eq!(&*entry.get_bytes().unwrap(), &content[..], "{kind}");
+            }
+        });
+    }
+
+    #[test]
+    fn repair_fixtures_checksums() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+            let toc_len = provider.toc_len() as usize;
+
+            // change the first byte of a file and the stored crc32 of the entries
+            let mut bytes = fixture.bytes.to_vec();
+            let info = archive.files_info().next().unwrap();
+            bytes[info.data_offset as usize] ^= 0xff;
+
+            let crc32 = archive.stored_header_crcs().unwrap().entries;
+            let crc32_offset = (0..toc_len - 4)
+                .find(|&i| {
+                    let field = &bytes[i..i + 4];
+                    field == crc32.to_le_bytes() || field == crc32.to_be_bytes()
+                })
+                .expect("can't find the crc32 of the entries");
+            bytes[crc32_offset] ^= 0xff;
+
+            let path = std::env::temp_dir().join(format!("hvp_repair_{}.hvp", fixture.name));
+            std::fs::write(&path, &bytes).unwrap();
+            assert!(
+                ArchiveProvider::new(File::open(&path).unwrap(), None).is_err(),
+                "stale crc32 should fail to load"
+            );
+            let broken_provider = ArchiveProvider::new_lenient(File::open(&path).unwrap(), None)
+                .expect("failed to load broken archive");
+            let mut broken = Archive::new(&broken_provider);
+            assert!(!broken.verify().is_ok());
+            assert_eq!(broken.repair_checksums(), vec![info.path]);
+
+            let mut repaired = Cursor::new(bytes.clone());
+            let report = broken
+                .patch(&mut repaired)
+                .expect("failed to repair archive");
+            assert_eq!(report.patched(), 0);
+
+            // only the TOC is written again
+            let repaired = repaired.into_inner();
+            assert_eq!(repaired[toc_len..], bytes[toc_len..]);
+
+            let repaired_provider = reload(repaired);
+            let mut repaired_archive = Archive::new(&repaired_provider);
+            assert!(repaired_archive.verify().is_ok());
+            assert!(repaired_archive.repair_checksums().is_empty());
+
+            drop(broken);
+            drop(broken_provider);
+            let _ = std::fs::remove_file(path);
+        });
+    }
+}
@@ -0,0 +1,632 @@
+//! detailed verification of archive checksums and structure
+
+use std::{fmt::Display, io, ops::Range, path::PathBuf};
+
+use binrw::Endian;
+
+use super::Archive;
+use super::analyze::Anomaly;
+use super::entry::{CompressionInfo, CompressionType};
+use crate::provider::RawArchive;
+use crate::structures::{checksum, common, final_exam, obscure1, obscure2};
+
+/// crc32 of a part of the archive header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderCrc {
+    /// which part of the archive this crc32 belongs to
+    pub name: &'static str,
+    /// crc32 that is stored in the archive
+    pub stored: u32,
+    /// crc32 that we computed from the archive
+    pub computed: u32,
+}
+
+impl HeaderCrc {
+    /// whatever the stored crc32 match the computed one
+    pub fn is_match(&self) -> bool {
+        self.stored == self.computed
+    }
+}
+
+/// crc32s that cover the header and the entry table of the archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderCrcs {
+    /// crc32 of the header, only obscure 1 archives have one
+    pub header: Option<u32>,
+    /// crc32 of the entry table
+    pub entries: u32,
+}
+
+/// a file entry which its checksum doesn't match
+#[derive(Debug, Clone)]
+pub struct ChecksumMismatch {
+    /// full path of the file inside the archive
+    pub path: PathBuf,
+    /// checksum that is stored in the archive, it cover the stored (maybe compressed) bytes
+    pub expected: i32,
+    /// checksum that we computed from the stored (maybe compressed) bytes of the file
+    pub computed: i32,
+}
+
+/// checksums of a file, see [`Archive::content_checksums`]
+#[derive(Debug, Clone)]
+pub struct ContentChecksum {
+    /// full path of the file inside the archive
+    pub path: PathBuf,
+    /// checksum that is stored in the archive, it cover the stored (maybe compressed) bytes
+    pub stored: i32,
+    /// checksum of the decompressed content, `None` if the file can't be decompressed
+    pub content: Option<i32>,
+}
+
+/// result of verifying a archive
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// crc32s of the header, empty if the archive doesn't have any
+    pub header_crcs: Vec<HeaderCrc>,
+    /// number of files that we checked
+    pub files_checked: usize,
+    /// files that their checksum doesn't match
+    pub mismatches: Vec<ChecksumMismatch>,
+}
+
+impl VerifyReport {
+    /// whatever everything in the archive is valid
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty() && self.header_crcs.iter().all(HeaderCrc::is_match)
+    }
+}
+
+impl Archive<'_> {
+    /// check checksum of every file and crc32s of the header and return a detailed report
+    pub fn verify(&self) -> VerifyReport {
+        let mut report = VerifyReport {
+            header_crcs: header_crcs(self),
+            ..Default::default()
+        };
+
+        for file in self.files() {
+            report.files_checked += 1;
+
+            let computed = checksum::bytes_sum(file.raw_bytes, file.endian);
+            if computed != file.checksum {
+                report.mismatches.push(ChecksumMismatch {
+                    path: file.path,
+                    expected: file.checksum,
+                    computed,
+                });
+            }
+        }
+
+        report
+    }
+
+    /// crc32s that are stored in the archive, `None` if the archive doesn't store any (old obscure 1 archives)
+    pub fn stored_header_crcs(&self) -> Option<HeaderCrcs> {
+        match &self.provider.raw_archive {
+            RawArchive::Obscure1(archive) => archive.checksums.as_ref().map(|crcs| HeaderCrcs {
+                header: Some(crcs.header),
+                entries: crcs.entries,
+            }),
+            RawArchive::Obscure2(archive) => Some(HeaderCrcs {
+                header: None,
+                entries: archive.header.entries_crc32,
+            }),
+            RawArchive::FinalExam(archive) => Some(HeaderCrcs {
+                header: None,
+                entries: archive.header.entries_crc32,
+            }),
+        }
+    }
+
+    /// crc32s computed from the header and entry table of the loaded archive, they should be
+    /// equal to [`Archive::stored_header_crcs`]. changes to the entries aren't included until
+    /// the archive is rebuilt
+    pub fn expected_header_crcs(&self) -> HeaderCrcs {
+        // we generate the crc32 from parsed structures, so writing them
+        // shouldn't ever fail
+        const MSG: &str = "failed to generate crc32 of parsed structure";
+
+        match &self.provider.raw_archive {
+            RawArchive::Obscure1(archive) => HeaderCrcs {
+                header: Some(common::generate_crc32(&archive.header, Endian::Big).expect(MSG)),
+                entries: common::generate_crc32(&archive.entries, Endian::Big).expect(MSG),
+            },
+            RawArchive::Obscure2(archive) => HeaderCrcs {
+                header: None,
+                entries: common::generate_crc32(&archive.entries, archive.endian()).expect(MSG),
+            },
+            RawArchive::FinalExam(archive) => HeaderCrcs {
+                header: None,
+                entries: common::generate_crc32(&archive.entries, archive.endian()).expect(MSG),
+            },
+        }
+    }
+
+    /// decompress every file and compute the checksum of its content.
+    ///
+    /// note: the checksum that is stored in the archive cover the stored bytes, so
+    /// the content checksum of compressed files is different from it
+    pub fn content_checksums(&self) -> Vec<ContentChecksum> {
+        self.files()
+            .map(|file| ContentChecksum {
+                stored: file.checksum,
+                content: file.content_checksum().ok(),
+                path: file.path,
+            })
+            .collect()
+    }
+}
+
+/// stored and computed crc32s of the header, empty if the archive doesn't store any
+fn header_crcs(archive: &Archive) -> Vec<HeaderCrc> {
+    let Some(stored) = archive.stored_header_crcs() else {
+        return Vec::new();
+    };
+    let computed = archive.expected_header_crcs();
+
+    let header = stored
+        .header
+        .zip(computed.header)
+        .map(|(stored, computed)| HeaderCrc {
+            name: "header",
+            stored,
+            computed,
+        });
+    let entries = HeaderCrc {
+        name: "entries",
+        stored: stored.entries,
+        computed: computed.entries,
+    };
+
+    header.into_iter().chain([entries]).collect()
+}
+
+/// a structural problem that is found by [`Archive::cross_check`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructureIssue {
+    /// file is marked as compressed, but its data can't be decompressed
+    NotDecompressable { path: PathBuf },
+    /// size of the file data doesn't match the stored uncompressed size,
+    /// `actual` is `None` if the size can't be found (lzo only report the mismatch)
+    SizeMismatch {
+        path: PathBuf,
+        stored: u32,
+        actual: Option<usize>,
+    },
+    /// a count in the header doesn't match the entries
+    CountMismatch {
+        name: &'static str,
+        stored: u32,
+        actual: u32,
+    },
+    /// directory entries are outside of the entry table
+    DirOutOfBounds { index: usize, range: Range<usize> },
+    /// entry is a child of more than one directory
+    SharedEntry { index: usize },
+    /// entry isn't a child of any directory
+    OrphanEntry { index: usize },
+}
+
+impl Display for StructureIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotDecompressable { path } => {
+                write!(
+                    f,
+                    "{}: marked as compressed but can't be decompressed",
+                    path.display()
+                )
+            }
+            Self::SizeMismatch {
+                path,
+                stored,
+                actual: Some(actual),
+            } => write!(
+                f,
+                "{}: uncompressed size is {stored}, but data size is {actual}",
+                path.display()
+            ),
+            Self::SizeMismatch {
+                path,
+                stored,
+                actual: None,
+            } => write!(
+                f,
+                "{}: uncompressed size {stored} doesn't match the data size",
+                path.display()
+            ),
+            Self::CountMismatch {
+                name,
+                stored,
+                actual,
+            } => write!(f, "header {name} is {stored}, but archive have {actual}"),
+            Self::DirOutOfBounds { index, range } => write!(
+                f,
+                "directory at index {index} point to entries {range:?} outside of the entry table"
+            ),
+            Self::SharedEntry { index } => {
+                write!(
+                    f,
+                    "entry at index {index} is a child of more than one directory"
+                )
+            }
+            Self::OrphanEntry { index } => {
+                write!(f, "entry at index {index} isn't a child of any directory")
+            }
+        }
+    }
+}
+
+/// result of cross checking a archive
+#[derive(Debug, Clone, Default)]
+pub struct CrossCheckReport {
+    /// report of the checksums
+    pub verify: VerifyReport,
+    /// structural problems of the archive
+    pub issues: Vec<StructureIssue>,
+    /// unusual compression that is found by [`Archive::analyze`], it doesn't make the report fail
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl CrossCheckReport {
+    /// whatever everything in the archive is valid
+    pub fn is_ok(&self) -> bool {
+        self.verify.is_ok() && self.issues.is_empty()
+    }
+}
+
+impl Archive<'_> {
+    /// a complete structural audit of the archive, on top of [`Archive::verify`] it check the
+    /// compression flag and size of every file against its data and make sure that directories
+    /// cover the whole entry table without any orphan or shared entry
+    pub fn cross_check(&self) -> CrossCheckReport {
+        let mut issues = Vec::new();
+
+        let sizes = raw_file_sizes(&self.provider.raw_archive, &mut issues);
+
+        // both of them walk the entries in the same depth first order
+        for (file, size) in self.files().zip(sizes) {
+            // empty files don't have any data
+            if size.uncompressed == 0 {
+                continue;
+            }
+
+            let issue = match file.compression_info {
+                Some(info) => check_compressed_data(file.raw_bytes, info)
+                    .err()
+                    .map(|actual| match actual {
+                        DataIssue::NotDecompressable => StructureIssue::NotDecompressable {
+                            path: file.path.clone(),
+                        },
+                        DataIssue::SizeMismatch(actual) => StructureIssue::SizeMismatch {
+                            path: file.path.clone(),
+                            stored: size.uncompressed,
+                            actual,
+                        },
+                    }),
+                None => (size.stored != size.uncompressed).then(|| StructureIssue::SizeMismatch {
+                    path: file.path.clone(),
+                    stored: size.uncompressed,
+                    actual: Some(size.stored as _),
+                }),
+            };
+
+            issues.extend(issue);
+        }
+
+        CrossCheckReport {
+            verify: self.verify(),
+            issues,
+            anomalies: self.analyze().anomalies,
+        }
+    }
+}
+
+/// sizes and data offset of a file in the raw entry table
+pub(super) struct RawSize {
+    pub(super) uncompressed: u32,
+    pub(super) stored: u32,
+    pub(super) offset: u32,
+}
+
+enum DataIssue {
+    NotDecompressable,
+    SizeMismatch(Option<usize>),
+}
+
+fn check_compressed_data(raw_bytes: &[u8], info: CompressionInfo) -> Result<(), DataIssue> {
+    let size = info.uncompressed_size as usize;
+
+    match info.compression_type {
+        CompressionType::Zlib => {
+            let mut decoder = flate2::read::ZlibDecoder::new(raw_bytes);
+            match io::copy(&mut decoder, &mut io::sink()) {
+                Ok(actual) if actual as usize == size => Ok(()),
+                Ok(actual) => Err(DataIssue::SizeMismatch(Some(actual as _))),
+                Err(_) => Err(DataIssue::NotDecompressable),
+            }
+        }
+        CompressionType::Lzo => {
+            let mut buf = vec![0_u8; size];
+            match lzo1x::decompress(raw_bytes, &mut buf) {
+                Ok(()) => Ok(()),
+                Err(lzo1x::DecompressError::OutputLength) => Err(DataIssue::SizeMismatch(None)),
+                Err(lzo1x::DecompressError::InvalidInput) => Err(DataIssue::NotDecompressable),
+            }
+        }
+    }
+}
+
+/// kind of a entry in a flat entry table
+enum FlatKind {
+    File(RawSize),
+    Dir(Range<usize>),
+}
+
+/// walk the raw entries and return the sizes of the files in a depth first order, structural
+/// problems of the entry table are added to `issues`
+pub(super) fn raw_file_sizes(
+    raw_archive: &RawArchive,
+    issues: &mut Vec<StructureIssue>,
+) -> Vec<RawSize> {
+    match raw_archive {
+        RawArchive::Obscure1(archive) => {
+            fn walk(entries: &[obscure1::Entry], sizes: &mut Vec<RawSize>, dirs: &mut u32) {
+                for entry in entries {
+                    match &entry.kind {
+                        obscure1::EntryKind::Dir(dir) => {
+                            *dirs += 1;
+                            walk(&dir.entries, sizes, dirs);
+                        }
+                        obscure1::EntryKind::File(file) => sizes.push(RawSize {
+                            uncompressed: file.uncompressed_size,
+                            stored: file.compressed_size,
+                            offset: file.offset,
+                        }),
+                    }
+                }
+            }
+
+            let mut sizes = Vec::new();
+            let mut dirs = 0;
+            walk(&archive.entries, &mut sizes, &mut dirs);
+
+            let header = &archive.header;
+            let counts = [
+                (
+                    "root count",
+                    header.root_count,
+                    archive.entries.len() as u32,
+                ),
+                ("all count", header.all_count, dirs + sizes.len() as u32),
+                ("file count", header.file_count, sizes.len() as u32),
+            ];
+            for (name, stored, actual) in counts {
+                if stored != actual {
+                    issues.push(StructureIssue::CountMismatch {
+                        name,
+                        stored,
+                        actual,
+                    });
+                }
+            }
+
+            sizes
+        }
+        RawArchive::Obscure2(archive) => {
+            let table = archive
+                .entries
+                .iter()
+                .map(|e| match &e.kind {
+                    obscure2::EntryKind::File(file) | obscure2::EntryKind::FileCompressed(file) => {
+                        FlatKind::File(RawSize {
+                            uncompressed: file.uncompressed_size,
+                            stored: file.compressed_size,
+                            offset: file.offset,
+                        })
+                    }
+                    obscure2::EntryKind::Directory(dir) => FlatKind::Dir(dir.entries_range()),
+                })
+                .collect::<Vec<_>>();
+
+            flat_file_sizes(&table, issues)
+        }
+        RawArchive::FinalExam(archive) => {
+            let table = archive
+                .entries
+                .iter()
+                .map(|e| match &e.kind {
+                    final_exam::EntryKind::File(file)
+                    | final_exam::EntryKind::FileCompressed(file) => FlatKind::File(RawSize {
+                        uncompressed: file.uncompressed_size,
+                        stored: file.compressed_size,
+                        offset: file.offset,
+                    }),
+                    final_exam::EntryKind::Directory(dir) => FlatKind::Dir(dir.entries_range()),
+                })
+                .collect::<Vec<_>>();
+
+            flat_file_sizes(&table, issues)
+        }
+    }
+}
+
+/// walk a flat entry table from the root directory at index 0
+fn flat_file_sizes(table: &[FlatKind], issues: &mut Vec<StructureIssue>) -> Vec<RawSize> {
+    fn walk(
+        table: &[FlatKind],
+        index: usize,
+        range: &Range<usize>,
+        visited: &mut [bool],
+        sizes: &mut Vec<RawSize>,
+        issues: &mut Vec<StructureIssue>,
+    ) {
+        if range.end > table.len() {
+            issues.push(StructureIssue::DirOutOfBounds {
+                index,
+                range: range.clone(),
+            });
+            return;
+        }
+
+        for child in range.clone() {
+            if std::mem::replace(&mut visited[child], true) {
+                issues.push(StructureIssue::SharedEntry { index: child });
+                continue;
+            }
+
+            match &table[child] {
+                FlatKind::File(size) => sizes.push(RawSize {
+                    uncompressed: size.uncompressed,
+                    stored: size.stored,
+                    offset: size.offset,
+                }),
+                FlatKind::Dir(range) => walk(table, child, range, visited, sizes, issues),
+            }
+        }
+    }
+
+    let mut sizes = Vec::new();
+    let mut visited = vec![false; table.len()];
+
+    if let Some(FlatKind::Dir(range)) = table.first() {
+        visited[0] = true;
+        walk(table, 0, range, &mut visited, &mut sizes, issues);
+    }
+
+    issues.extend(
+        visited
+            .iter()
+            .enumerate()
+            .filter(|(_, visited)| !**visited)
+            .map(|(index, _)| StructureIssue::OrphanEntry { index }),
+    );
+
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        archive::entry::UpdateKind,
+        test_utils::{Format, for_each_fixture, rebuild, reload},
+    };
+
+    #[test]
+    fn verify_fixtures() {
+        for_each_fixture(|fixture, provider| {
+            let report = Archive::new(provider).verify();
+
+            assert_eq!(report.files_checked, fixture.file_count);
+            assert!(report.is_ok(), "{report:?}");
+        });
+    }
+
+    #[test]
+    fn cross_check_fixtures_and_rebuilt() {
+        for_each_fixture(|_, provider| {
+            let mut archive = Archive::new(provider);
+
+            let report = archive.cross_check();
+            assert!(report.is_ok(), "{report:?}");
+
+            // rebuilt output, both compressed and with every file updated without compression
+            for skip_compression in [false, true] {
+                archive.options.rebuild_skip_compression = skip_compression;
+                for mut entry in archive.files_mut() {
+                    let bytes = entry.get_bytes().unwrap().into_owned();
+                    entry.update(UpdateKind::Bytes(bytes));
+                }
+
+                let rebuilt_provider = reload(rebuild(&archive));
+                let report = Archive::new(&rebuilt_provider).cross_check();
+                assert!(
+                    report.is_ok(),
+                    "skip compression {skip_compression}: {report:?}"
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn cross_check_corrupted_fixtures() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            let file = archive
+                .files()
+                .find(|f| f.is_compressed() && f.raw_bytes.len() >= 64)
+                .expect("fixture doesn't have a compressed file");
+
+            let mut bytes = fixture.bytes.to_vec();
+            let offset = fixture
+                .bytes
+                .windows(file.raw_bytes.len())
+                .position(|w| w == file.raw_bytes)
+                .expect("can't find file data");
+            bytes[offset..offset + file.raw_bytes.len()].fill(0xFF);
+
+            let corrupted_provider = reload(bytes);
+            let report = Archive::new(&corrupted_provider).cross_check();
+
+            assert!(!report.is_ok());
+            assert_eq!(report.verify.mismatches.len(), 1);
+            assert!(
+                matches!(
+                    &report.issues[..],
+                    [StructureIssue::NotDecompressable { path } | StructureIssue::SizeMismatch { path, .. }]
+                        if *path == file.path
+                ),
+                "{:?}",
+                report.issues
+            );
+        });
+    }
+
+    #[test]
+    fn fixtures_content_checksums() {
+        for_each_fixture(|_, provider| {
+            let archive = Archive::new(provider);
+            let checksums = archive.content_checksums();
+
+            assert_eq!(checksums.len(), archive.metadata().file_count);
+
+            for (file, checksum) in archive.files().zip(&checksums) {
+                assert_eq!(checksum.path, file.path);
+                assert_eq!(checksum.stored, file.stored_checksum());
+                assert_eq!(file.computed_stored_checksum(), file.stored_checksum());
+                assert_eq!(checksum.content, Some(file.content_checksum().unwrap()));
+
+                // without compression both checksums cover the same bytes
+                if !file.is_compressed() {
+                    assert_eq!(checksum.content, Some(checksum.stored));
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn fixtures_header_crcs() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            let expected = archive.expected_header_crcs();
+            assert_eq!(archive.stored_header_crcs(), Some(expected));
+
+            // only obscure 1 have a crc32 for the header itself
+            assert_eq!(
+                expected.header.is_some(),
+                fixture.format == Format::Obscure1
+            );
+
+            let report = archive.verify();
+            assert_eq!(
+                report.header_crcs.len(),
+                1 + expected.header.is_some() as usize
+            );
+            assert!(report.header_crcs.iter().all(|c| c.is_match()));
+        });
+    }
+}
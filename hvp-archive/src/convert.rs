@@ -0,0 +1,426 @@
+//! conversion between hvp archives and zip files, or between the formats of the games.
+//!
+//! hvp specific fields that zip files can't hold are kept in a [`MANIFEST_FILE`] inside the zip,
+//! it is also used to only update the files that changed when converting back to hvp.
+
+use std::{
+    fmt::Write as _,
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    Game,
+    archive::{
+        Archive,
+        builder::{ArchiveBuilder, BuildError},
+        entry::{CompressionType, DecompressError, UpdateKind},
+        error::RebuildError,
+        rebuild_progress::RebuildProgress,
+    },
+    zip::{ZipError, ZipReader, ZipWriter},
+};
+
+/// name of the manifest file inside the zip
+pub const MANIFEST_FILE: &str = "hvp_manifest.txt";
+
+/// errors that can happen during conversion
+#[derive(Debug, thiserror::Error)]
+pub enum ConvertError {
+    #[error(transparent)]
+    Zip(#[from] ZipError),
+    #[error(transparent)]
+    Rebuild(#[from] RebuildError),
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error("failed to decompress {}", .0.display())]
+    Decompress(PathBuf, #[source] DecompressError),
+    #[error("invalid manifest at line {line}: {reason}")]
+    InvalidManifest { line: usize, reason: &'static str },
+    #[error("zip is made from a {zip:?} archive, but the template is from {template:?}")]
+    GameMismatch { zip: Game, template: Game },
+    #[error("{} isn't in the template archive, only existing files can be converted back", .0.display())]
+    UnknownFile(PathBuf),
+}
+
+/// hvp specific fields of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    /// full path of the file inside the archive
+    pub path: PathBuf,
+    /// compression type of the file, `None` if it isn't compressed
+    pub compression: Option<CompressionType>,
+    /// checksum that is stored in the archive
+    pub stored_checksum: i32,
+    /// crc32 of the decompressed content, same as the crc32 of the zip entry
+    pub content_crc32: u32,
+}
+
+/// manifest of a zip that is made from a hvp archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Manifest {
+    /// game of the archive that the zip is made from
+    pub game: Game,
+    pub files: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// one line per field, tab separated so it is easy to read and edit
+    pub fn to_text(&self) -> String {
+        let mut text = String::from(
+            "# hvp manifest, one file per line: path, compression, stored checksum, content crc32\n",
+        );
+        let _ = writeln!(text, "game\t{:?}", self.game);
+
+        for file in &self.files {
+            let compression = match file.compression {
+                Some(compression) => format!("{compression:?}"),
+                None => "None".to_owned(),
+            };
+            let _ = writeln!(
+                text,
+                "file\t{}\t{compression}\t{:#010x}\t{:#010x}",
+                zip_name(&file.path),
+                file.stored_checksum,
+                file.content_crc32
+            );
+        }
+
+        text
+    }
+
+    /// parse a manifest that is written using [`Manifest::to_text`]
+    pub fn parse(text: &str) -> Result<Self, ConvertError> {
+        let mut game = None;
+        let mut files = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let error = |reason| ConvertError::InvalidManifest {
+                line: i + 1,
+                reason,
+            };
+
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[..] {
+                ["game", name] => {
+                    game = Some(match name {
+                        "Obscure1" => Game::Obscure1,
+                        "Obscure2" => Game::Obscure2,
+                        "FinalExam" => Game::FinalExam,
+                        _ => return Err(error("unknown game")),
+                    })
+                }
+                ["file", path, compression, stored_checksum, content_crc32] => {
+                    let compression = match compression {
+                        "None" => None,
+                        "Zlib" => Some(CompressionType::Zlib),
+                        "Lzo" => Some(CompressionType::Lzo),
+                        _ => return Err(error("unknown compression")),
+                    };
+
+                    files.push(ManifestEntry {
+                        path: path.split('/').collect(),
+                        compression,
+                        stored_checksum: parse_hex(stored_checksum)
+                            .ok_or_else(|| error("invalid stored checksum"))?
+                            as i32,
+                        content_crc32: parse_hex(content_crc32)
+                            .ok_or_else(|| error("invalid content crc32"))?,
+                    });
+                }
+                _ => return Err(error("unknown line")),
+            }
+        }
+
+        let game = game.ok_or(ConvertError::InvalidManifest {
+            line: 0,
+            reason: "missing game",
+        })?;
+
+        Ok(Self { game, files })
+    }
+}
+
+/// result of a conversion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConvertReport {
+    /// number of files that are written to the output
+    pub converted: usize,
+    /// number of files that didn't change according to the manifest, only used by [`zip_to_hvp`]
+    pub unchanged: usize,
+}
+
+/// write every file of the archive decompressed into a zip, along with a [`MANIFEST_FILE`]
+pub fn hvp_to_zip<W: Write>(archive: &Archive, writer: W) -> Result<ConvertReport, ConvertError> {
+    let mut zip = ZipWriter::new(writer);
+    let mut manifest = Manifest {
+        game: archive.metadata().game,
+        files: Vec::with_capacity(archive.metadata().file_count),
+    };
+
+    for file in archive.files() {
+        let content = file
+            .get_bytes()
+            .map_err(|e| ConvertError::Decompress(file.path.clone(), e))?;
+
+        zip.add(&zip_name(&file.path), &content)?;
+
+        manifest.files.push(ManifestEntry {
+            compression: file.compression_info().map(|c| c.compression_type),
+            stored_checksum: file.stored_checksum(),
+            content_crc32: crc32fast::hash(&content),
+            path: file.path,
+        });
+    }
+
+    zip.add(MANIFEST_FILE, manifest.to_text().as_bytes())?;
+    zip.finish()?;
+
+    Ok(ConvertReport {
+        converted: manifest.files.len(),
+        unchanged: 0,
+    })
+}
+
+/// update the files of `template` with the files of the zip and rebuild it into `writer`.
+///
+/// if the zip have a [`MANIFEST_FILE`], files that have the same crc32 as the manifest are
+/// left untouched. every file of the zip must exist in the template
+pub fn zip_to_hvp<R, W, P>(
+    reader: R,
+    template: &mut Archive,
+    writer: &mut W,
+    progress: P,
+) -> Result<ConvertReport, ConvertError>
+where
+    R: Read + Seek,
+    W: Write + Seek,
+    P: RebuildProgress,
+{
+    let mut zip = ZipReader::new(reader)?;
+
+    let manifest_path = Path::new(MANIFEST_FILE);
+    let manifest = match zip.find(manifest_path).cloned() {
+        Some(entry) => {
+            let text = zip.read(&entry)?;
+            let manifest = Manifest::parse(&String::from_utf8_lossy(&text))?;
+
+            let game = template.metadata().game;
+            if manifest.game != game {
+                return Err(ConvertError::GameMismatch {
+                    zip: manifest.game,
+                    template: game,
+                });
+            }
+
+            manifest
+                .files
+                .into_iter()
+                .map(|f| (f.path, f.content_crc32))
+                .collect()
+        }
+        None => ahash::HashMap::default(),
+    };
+
+    let paths: ahash::HashSet<PathBuf> = template.files().map(|f| f.path).collect();
+
+    let mut report = ConvertReport::default();
+    let mut updates = ahash::HashMap::default();
+
+    for entry in zip.entries().to_vec() {
+        let path = entry.path();
+        if path == manifest_path {
+            continue;
+        }
+
+        if !paths.contains(&path) {
+            return Err(ConvertError::UnknownFile(path));
+        }
+
+        if manifest.get(&path) == Some(&entry.crc32) {
+            report.unchanged += 1;
+            continue;
+        }
+
+        updates.insert(path, zip.read(&entry)?);
+    }
+
+    for mut file in template.files_mut() {
+        if let Some(content) = updates.remove(&file.path) {
+            file.update(UpdateKind::Bytes(content));
+            report.converted += 1;
+        }
+    }
+
+    template.rebuild(writer, progress)?;
+
+    Ok(report)
+}
+
+/// zip names always use `/` as separator
+fn zip_name(path: &Path) -> String {
+    path.iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    u32::from_str_radix(s.strip_prefix("0x")?, 16).ok()
+}
+
+/// make a builder for the archive format of `game` with every file of the archive, so the same
+/// file tree can be written for another game.
+///
+/// the files are decompressed and compressed again using the compression of the new format, files
+/// that aren't compressed stay uncompressed. empty directories aren't kept
+pub fn hvp_to_game<'p>(archive: &Archive, game: Game) -> Result<ArchiveBuilder<'p>, ConvertError> {
+    let mut builder = ArchiveBuilder::new(game);
+
+    for file in archive.files() {
+        let content = file
+            .get_bytes()
+            .map_err(|e| ConvertError::Decompress(file.path.clone(), e))?;
+        let data = UpdateKind::Bytes(content.into_owned());
+
+        match file.is_compressed() {
+            true => builder.add_file(&file.path, data)?,
+            false => builder.add_file_uncompressed(&file.path, data)?,
+        }
+    }
+
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        archive::{Obscure2NameMap, Options},
+        provider::ArchiveProvider,
+        test_utils::{EmptyProgress, for_each_fixture, reload},
+    };
+
+    #[test]
+    fn fixtures_zip_roundtrip() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            let mut zip = Vec::new();
+            let report = hvp_to_zip(&archive, &mut zip).unwrap();
+            assert_eq!(report.converted, fixture.file_count);
+
+            // nothing changed, so the rebuilt archive is the same as the original
+            let mut template = Archive::new(provider);
+            let mut writer = Cursor::new(Vec::new());
+            let report =
+                zip_to_hvp(Cursor::new(&zip), &mut template, &mut writer, EmptyProgress).unwrap();
+            assert_eq!(report.converted, 0);
+            assert_eq!(report.unchanged, fixture.file_count);
+            assert_eq!(crc32fast::hash(&writer.into_inner()), fixture.archive_crc32);
+
+            // change a single file inside the zip
+            let mut reader = ZipReader::new(Cursor::new(&zip)).unwrap();
+            let changed = archive.files().next().unwrap().path;
+            let mut modified = ZipWriter::new(Vec::new());
+            for entry in reader.entries().to_vec() {
+                let mut data = reader.read(&entry).unwrap();
+                if entry.path() == changed {
+                    data.extend_from_slice(b"modified");
+                }
+                modified.add(&entry.name, &data).unwrap();
+            }
+            let modified = modified.finish().unwrap();
+            assert!(
+                ZipReader::new(Cursor::new(&modified))
+                    .unwrap()
+                    .find(MANIFEST_FILE.as_ref())
+                    .is_some()
+            );
+
+            let mut template = Archive::new(provider);
+            let mut writer = Cursor::new(Vec::new());
+            let report = zip_to_hvp(
+                Cursor::new(&modified),
+                &mut template,
+                &mut writer,
+                EmptyProgress,
+            )
+            .unwrap();
+            assert_eq!(report.converted, 1);
+
+            let rebuilt_provider = reload(writer.into_inner());
+            let rebuilt = Archive::new(&rebuilt_provider);
+            let file = rebuilt.find_file(&changed).unwrap();
+            assert!(file.get_bytes().unwrap().ends_with(b"modified"));
+        });
+    }
+
+    #[test]
+    fn convert_fixtures_between_games() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            // obscure 2 only store the crc32 of the names
+            let names: Vec<String> = archive
+                .files()
+                .flat_map(|f| {
+                    f.path
+                        .iter()
+                        .map(|c| c.to_string_lossy().into_owned())
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+
+            for game in [Game::Obscure1, Game::Obscure2, Game::FinalExam] {
+                let builder = hvp_to_game(&archive, game).unwrap();
+                let mut writer = Vec::new();
+                builder
+                    .build(&mut Cursor::new(&mut writer), EmptyProgress)
+                    .unwrap_or_else(|e| panic!("to {game:?}: {e}"));
+
+                let converted_provider = ArchiveProvider::from_bytes(writer, Some(game)).unwrap();
+                let converted = Archive::new_with_options(
+                    &converted_provider,
+                    Options {
+                        obscure2_names: Obscure2NameMap::new(&names),
+                        ..Default::default()
+                    },
+                );
+
+                assert_eq!(converted.metadata().game, game);
+                assert_eq!(
+                    converted.metadata().file_count,
+                    fixture.file_count,
+                    "to {game:?}"
+                );
+                assert!(converted.verify().is_ok(), "to {game:?}");
+
+                for file in archive.files() {
+                    let entry = converted
+                        .find_file(&file.path)
+                        .unwrap_or_else(|| panic!("to {game:?}: {}", file.path.display()));
+                    assert_eq!(
+                        entry.get_bytes().unwrap(),
+                        file.get_bytes().unwrap(),
+                        "to {game:?}: {}",
+                        file.path.display()
+                    );
+                    assert_eq!(
+                        entry.is_compressed(),
+                        file.is_compressed(),
+                        "to {game:?}: {}",
+                        file.path.display()
+                    );
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,140 @@
+//! description of the supported archive formats, so frontends can check what
+//! each format support without trying the operations
+
+use crate::Game;
+
+/// an operation that can be done on an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// read and extract the files
+    Extract,
+    /// replace the data of existing files and rebuild the archive
+    Replace,
+    /// add new files, see [`crate::archive::builder::ArchiveBuilder::add_file`]
+    Add,
+    /// remove files or directories, see [`crate::archive::builder::ArchiveBuilder::remove`]
+    Remove,
+    /// rename or move files or directories, see [`crate::archive::builder::ArchiveBuilder::rename`]
+    Rename,
+    /// build a new archive without an original archive, see [`crate::archive::builder::ArchiveBuilder::new`]
+    Build,
+    /// write the entries of a directory in a custom order, see [`crate::archive::entry::DirEntry::reorder`]
+    Reorder,
+    /// convert between little and big endian variants, see [`crate::archive::builder::ArchiveBuilder::big_endian`]
+    EndianConvert,
+}
+
+/// a variant of a format, each platform may use a different magic and endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatVariant {
+    /// platforms that use this variant
+    pub platforms: &'static str,
+    /// magic at the start of the archive
+    pub magic: &'static [u8],
+    pub big_endian: bool,
+}
+
+/// description of a supported archive format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatDescriptor {
+    pub game: Game,
+    pub name: &'static str,
+    pub variants: &'static [FormatVariant],
+    /// names are only stored as crc32, the name maps are needed to read them back
+    pub hashed_names: bool,
+    pub capabilities: &'static [Capability],
+}
+
+impl FormatDescriptor {
+    /// check whatever the format support the given operation
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability)
+    }
+}
+
+const SINGLE_ENDIAN_CAPABILITIES: &[Capability] = &[
+    Capability::Extract,
+    Capability::Replace,
+    Capability::Add,
+    Capability::Remove,
+    Capability::Rename,
+    Capability::Build,
+    Capability::Reorder,
+];
+
+const CAPABILITIES: &[Capability] = &[
+    Capability::Extract,
+    Capability::Replace,
+    Capability::Add,
+    Capability::Remove,
+    Capability::Rename,
+    Capability::Build,
+    Capability::Reorder,
+    Capability::EndianConvert,
+];
+
+static FORMATS: [FormatDescriptor; 3] = [
+    FormatDescriptor {
+        game: Game::Obscure1,
+        name: "Obscure 1",
+        variants: &[FormatVariant {
+            platforms: "PC, PS2, XBOX",
+            magic: b"HV PackF",
+            big_endian: true,
+        }],
+        hashed_names: false,
+        capabilities: SINGLE_ENDIAN_CAPABILITIES,
+    },
+    FormatDescriptor {
+        game: Game::Obscure2,
+        name: "Obscure 2",
+        variants: &[
+            FormatVariant {
+                platforms: "PC, PS2, PSP (and alone in the dark 2008 on PS2)",
+                magic: &[0, 0, 4, 0],
+                big_endian: false,
+            },
+            FormatVariant {
+                platforms: "Wii (and alone in the dark 2008 on Wii)",
+                magic: &[0, 4, 0, 0],
+                big_endian: true,
+            },
+        ],
+        hashed_names: true,
+        capabilities: CAPABILITIES,
+    },
+    FormatDescriptor {
+        game: Game::FinalExam,
+        name: "Final Exam",
+        variants: &[
+            FormatVariant {
+                platforms: "PC",
+                magic: &[0, 0, 5, 0],
+                big_endian: false,
+            },
+            FormatVariant {
+                platforms: "probably PS3 and Xbox 360, untested",
+                magic: &[0, 5, 0, 0],
+                big_endian: true,
+            },
+        ],
+        hashed_names: false,
+        capabilities: CAPABILITIES,
+    },
+];
+
+/// every format that this crate can read and write
+pub fn supported_formats() -> &'static [FormatDescriptor] {
+    &FORMATS
+}
+
+impl Game {
+    /// description of the format used by this game
+    pub fn format(self) -> &'static FormatDescriptor {
+        match self {
+            Game::Obscure1 => &FORMATS[0],
+            Game::Obscure2 => &FORMATS[1],
+            Game::FinalExam => &FORMATS[2],
+        }
+    }
+}
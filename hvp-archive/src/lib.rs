@@ -1,7 +1,14 @@
+pub use formats::supported_formats;
 pub use utils::try_detect_game;
 
 pub mod archive;
+pub mod convert;
+pub mod formats;
 pub mod provider;
+pub mod zip;
+
+#[cfg(feature = "vfs")]
+pub mod vfs;
 
 #[cfg(feature = "raw_structure")]
 pub mod structures;
@@ -10,6 +17,9 @@ mod structures;
 
 mod utils;
 
+#[cfg(test)]
+mod test_utils;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Game {
     Obscure1,
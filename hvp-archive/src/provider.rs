@@ -1,9 +1,12 @@
 use std::{
     fs::File,
-    io::{self, Seek, SeekFrom},
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    ops::{Deref, Range},
+    path::Path,
 };
 
 use binrw::{BinRead, io::BufReader};
+#[cfg(feature = "mmap")]
 use memmap2::{Mmap, MmapOptions};
 
 use crate::structures::{final_exam, obscure1, obscure2};
@@ -18,8 +21,42 @@ pub enum ProviderError {
     UnknownArchive,
     #[error("failed to load archive")]
     ArchiveLoadFailed(#[from] binrw::Error),
+    #[error(
+        "archive looks like {} but it was opened as {}, open it as {0} or autodetect the game",
+        .detected.format().name,
+        .specified.format().name
+    )]
+    GameMismatch { detected: Game, specified: Game },
+    #[error(
+        "file doesn't look like a hvp archive and failed to load it as {}",
+        .specified.format().name
+    )]
+    NotAnArchive {
+        specified: Game,
+        #[source]
+        source: binrw::Error,
+    },
     #[error("entry offset or size doesn't fit in archive")]
     EntryOffsetOrSizeDoesntFit,
+    #[error(
+        "entries range {range:?} of directory at index {index} goes past the end of the table ({len} entries)"
+    )]
+    DirRangeOutOfBounds {
+        index: usize,
+        range: Range<usize>,
+        len: usize,
+    },
+    #[error("entries range {range:?} of directory at index {index} include the root entry")]
+    DirRangeIncludeRoot { index: usize, range: Range<usize> },
+    #[error(
+        "entries range {range:?} of directory at index {index} overlap with range {other_range:?} of directory at index {other}"
+    )]
+    DirRangeOverlap {
+        index: usize,
+        range: Range<usize>,
+        other: usize,
+        other_range: Range<usize>,
+    },
 }
 
 /// hold the underlying raw archive
@@ -38,6 +75,50 @@ pub enum RawArchive {
     FinalExam(final_exam::HvpArchive),
 }
 
+/// how the mapped archive is going to be read, the os use it to decide how much to read ahead
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    #[default]
+    Normal,
+    /// from the start to the end, like when extracting every file
+    Sequential,
+    /// in random order, like when reading a few files
+    Random,
+}
+
+/// how the archive file is read, see [`ArchiveProvider::new_with_options`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderOptions {
+    /// how the mapped archive is going to be read, only used on unix
+    pub access: Access,
+    /// read the whole file when mapping it, so reading the files later doesn't wait on the disk.
+    /// only used on linux
+    pub populate: bool,
+    /// read the file into memory instead of mapping it. reading a mapped file that is changed or
+    /// gone (like on a removed drive or a dropped network share) crash the program with SIGBUS.
+    /// always the case without the `mmap` feature
+    pub buffered: bool,
+}
+
+/// the bytes of the archive, mapped from a file or read into memory
+pub(crate) enum Source {
+    #[cfg(feature = "mmap")]
+    Mapped(Mmap),
+    Buffered(Box<[u8]>),
+}
+
+impl Deref for Source {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(mmap) => mmap,
+            Self::Buffered(bytes) => bytes,
+        }
+    }
+}
+
 /// archive provider is the main type that load the hvp archives
 ///
 /// it support both obscure 1 and 2 and can also autodetect the game
@@ -46,48 +127,139 @@ pub enum RawArchive {
 /// it also validate the entries to make sure that the loaded archive isn't broken.
 pub struct ArchiveProvider {
     pub(crate) raw_archive: RawArchive,
-    pub(crate) mmap: Mmap,
+    pub(crate) source: Source,
     pub(crate) entries_offset: usize,
+    /// `false` when the archive is opened with [`ArchiveProvider::open_toc_only`]
+    has_data: bool,
 }
 
 impl ArchiveProvider {
     /// create a new provider from the given file, optionally you can pass the game that the
     /// archive is belong to, if not passed we'll try to autodetect it using [`crate::try_detect_game`].
+    ///
+    /// the file is mapped, or read into memory when the `mmap` feature is disabled. see
+    /// [`ArchiveProvider::new_with_options`] to read it into memory anyway
     pub fn new(file: File, game: Option<Game>) -> Result<Self, ProviderError> {
-        let mut reader = BufReader::new(file);
+        Self::new_with_options(file, game, ProviderOptions::default())
+    }
 
-        let game = match game {
-            Some(game) => game,
-            None => {
-                log::debug!("trying to autodetect game based on archive");
-                let game = try_detect_game(&mut reader)?.ok_or(ProviderError::UnknownArchive)?;
-                log::info!("autodetected game: {game:?}");
-                game
-            }
-        };
+    /// like [`ArchiveProvider::new`], with control over how the file is mapped or read
+    pub fn new_with_options(
+        file: File,
+        game: Option<Game>,
+        options: ProviderOptions,
+    ) -> Result<Self, ProviderError> {
+        Self::load(file, game, false, false, options)
+    }
 
-        let raw_archive = match game {
-            Game::Obscure1 => RawArchive::Obscure1(obscure1::HvpArchive::read_be(&mut reader)?),
-            Game::Obscure2 => RawArchive::Obscure2(obscure2::HvpArchive::read(&mut reader)?),
-            Game::FinalExam => RawArchive::FinalExam(final_exam::HvpArchive::read(&mut reader)?),
-        };
+    /// like [`ArchiveProvider::new`], but the crc32 of the entries isn't validated, so an archive
+    /// with stale crc32s can still be loaded and repaired. the other validations are still done
+    pub fn new_lenient(file: File, game: Option<Game>) -> Result<Self, ProviderError> {
+        Self::load(file, game, true, false, ProviderOptions::default())
+    }
 
-        let entries_offset = reader.stream_position()? as usize;
-        log::debug!("entries offest: {entries_offset}");
+    /// open only the header and TOC of the archive, the file isn't mapped so scanning many
+    /// big archives stay cheap. the data of the files read as zeros, so only what the TOC
+    /// store (paths, sizes, offsets, compression and stored checksums) can be used, and
+    /// the archive can't be rebuilt. see [`ArchiveProvider::has_data`]
+    pub fn open_toc_only(
+        path: impl AsRef<Path>,
+        game: Option<Game>,
+    ) -> Result<Self, ProviderError> {
+        Self::load(
+            File::open(path)?,
+            game,
+            false,
+            true,
+            ProviderOptions::default(),
+        )
+    }
+
+    /// create a new provider from any reader, like a network stream, an archive inside another
+    /// archive or a [`Cursor`]. the archive start at the current position of the reader, so an
+    /// archive embedded at an offset can be read by seeking to it first. the entries borrow their
+    /// data from the provider, so the rest of the stream is read into memory, use
+    /// [`ArchiveProvider::new`] to map files instead
+    pub fn from_reader<R: Read + Seek>(
+        mut reader: R,
+        game: Option<Game>,
+    ) -> Result<Self, ProviderError> {
+        // the offsets in the TOC are relative to the start of the archive
+        let base = reader.stream_position()?;
+        log::debug!("reading archive from offset {base}");
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(bytes, game)
+    }
+
+    /// create a new provider from the bytes of an archive that is already in memory, like an
+    /// embedded or downloaded archive. it doesn't need the `mmap` feature
+    pub fn from_bytes(
+        bytes: impl Into<Box<[u8]>>,
+        game: Option<Game>,
+    ) -> Result<Self, ProviderError> {
+        let bytes = bytes.into();
+        let (raw_archive, entries_offset) = read_raw(&mut Cursor::new(&bytes), game, false)?;
+        Self::validate(raw_archive, Source::Buffered(bytes), entries_offset, true)
+    }
+
+    fn load(
+        file: File,
+        game: Option<Game>,
+        skip_crc32: bool,
+        toc_only: bool,
+        options: ProviderOptions,
+    ) -> Result<Self, ProviderError> {
+        let mut reader = BufReader::new(file);
+        let (raw_archive, entries_offset) = read_raw(&mut reader, game, skip_crc32)?;
         let mut file = reader.into_inner();
         file.seek(SeekFrom::Start(0))?;
 
-        let mmap = unsafe { MmapOptions::new().map(&file)? };
+        let source = match (toc_only, options.buffered || cfg!(not(feature = "mmap"))) {
+            #[cfg(feature = "mmap")]
+            // the entries still need slices with the size and offset of their data, an anonymous
+            // mapping that is never touched give them that without reading or reserving anything
+            (true, _) => Source::Mapped(
+                MmapOptions::new()
+                    .len(file.metadata()?.len() as _)
+                    .no_reserve_swap()
+                    .map_anon()?
+                    .make_read_only()?,
+            ),
+            #[cfg(not(feature = "mmap"))]
+            (true, _) => Source::Buffered(vec![0; file.metadata()?.len() as _].into_boxed_slice()),
+            #[cfg(feature = "mmap")]
+            (false, false) => Source::Mapped(map(&file, options)?),
+            (false, _) => {
+                let mut bytes = Vec::new();
+                file.read_to_end(&mut bytes)?;
+                Source::Buffered(bytes.into_boxed_slice())
+            }
+        };
 
+        Self::validate(raw_archive, source, entries_offset, !toc_only)
+    }
+
+    /// make sure that the entries fit in the source before creating the provider
+    fn validate(
+        raw_archive: RawArchive,
+        source: Source,
+        entries_offset: usize,
+        has_data: bool,
+    ) -> Result<Self, ProviderError> {
         log::info!("validating entries offset and sizes");
-        if !validate_entries(&raw_archive, &mmap) {
+        if !validate_entries(&raw_archive, &source) {
             return Err(ProviderError::EntryOffsetOrSizeDoesntFit);
         }
 
+        log::info!("validating directory ranges");
+        validate_dir_ranges(&raw_archive)?;
+
         Ok(Self {
             raw_archive,
-            mmap,
+            source,
             entries_offset,
+            has_data,
         })
     }
 
@@ -100,19 +272,60 @@ impl ArchiveProvider {
         }
     }
 
+    /// whatever the data of the files can be read, `false` for [`ArchiveProvider::open_toc_only`]
+    pub fn has_data(&self) -> bool {
+        self.has_data
+    }
+
+    /// length of the archive header and TOC, the data of the files come after it
+    pub fn toc_len(&self) -> u64 {
+        self.entries_offset as _
+    }
+
+    /// range of the archive that hold the data of the files, from the end of TOC to the end of
+    /// the last file data. anything after it (like padding) isn't part of the range
+    pub fn data_region(&self) -> Range<u64> {
+        let start = self.toc_len();
+        start..data_end(&self.raw_archive).max(start)
+    }
+
+    /// length of the archive file
+    pub fn file_len(&self) -> u64 {
+        self.source.len() as _
+    }
+
+    /// size of the biggest file in the archive, the bigger one of stored or uncompressed size
+    pub fn largest_file_size(&self) -> u64 {
+        largest_file_size(&self.raw_archive)
+    }
+
     /// get bytes from the given offset.
     /// ### SAFETY:
     /// because we validate archive before this call, it should be safe to call with any **valid** entry offset and size.
     pub(crate) fn get_bytes(&self, offset: usize, size: usize) -> &[u8] {
-        debug_assert!(offset + size <= self.mmap.len());
+        debug_assert!(offset + size <= self.source.len());
         log::debug!("getting bytes from offset {offset} with size {size}");
-        &self.mmap[offset..offset + size]
+        &self.source[offset..offset + size]
+    }
+
+    /// offset of a slice that was returned by [`ArchiveProvider::get_bytes`] from the start of the archive
+    pub(crate) fn offset_of(&self, bytes: &[u8]) -> u64 {
+        let offset = bytes.as_ptr() as usize - self.source.as_ptr() as usize;
+        debug_assert!(offset + bytes.len() <= self.source.len());
+        offset as _
+    }
+
+    /// whatever the slice point inside the archive, like the ones returned by [`ArchiveProvider::get_bytes`]
+    pub(crate) fn contains(&self, bytes: &[u8]) -> bool {
+        let range = self.source.as_ptr_range();
+        let bytes = bytes.as_ptr_range();
+        !self.source.is_empty() && range.start <= bytes.start && bytes.end <= range.end
     }
 
     /// a simple function to get a slice from buffer with size 0
     pub(crate) fn get_empty_bytes(&self) -> &[u8] {
         log::debug!("getting a zero sized slice");
-        &self.mmap[0..0]
+        &self.source[0..0]
     }
 
     /// retuturn a reference the underlying raw archive
@@ -122,6 +335,178 @@ impl ArchiveProvider {
     }
 }
 
+/// map the file with the given options
+#[cfg(feature = "mmap")]
+fn map(file: &File, options: ProviderOptions) -> io::Result<Mmap> {
+    let mut mmap_options = MmapOptions::new();
+    if options.populate {
+        mmap_options.populate();
+    }
+    let mmap = unsafe { mmap_options.map(file)? };
+
+    #[cfg(unix)]
+    match options.access {
+        Access::Normal => (),
+        Access::Sequential => mmap.advise(memmap2::Advice::Sequential)?,
+        Access::Random => mmap.advise(memmap2::Advice::Random)?,
+    }
+
+    Ok(mmap)
+}
+
+/// detect the game (or make sure that it match the given one) and read the header and TOC,
+/// return them plus the offset of the end of TOC
+fn read_raw<R: Read + Seek>(
+    reader: &mut R,
+    game: Option<Game>,
+    skip_crc32: bool,
+) -> Result<(RawArchive, usize), ProviderError> {
+    log::debug!("trying to detect game based on archive");
+    let detected = try_detect_game(reader)?;
+    let game = match (game, detected) {
+        (Some(specified), Some(detected)) if specified != detected => {
+            return Err(ProviderError::GameMismatch {
+                detected,
+                specified,
+            });
+        }
+        (Some(game), _) => game,
+        (None, Some(game)) => {
+            log::info!("autodetected game: {game:?}");
+            game
+        }
+        (None, None) => return Err(ProviderError::UnknownArchive),
+    };
+
+    let raw_archive = match game {
+        Game::Obscure1 => {
+            obscure1::HvpArchive::read_be_args(reader, (skip_crc32,)).map(RawArchive::Obscure1)
+        }
+        Game::Obscure2 => {
+            obscure2::HvpArchive::read_args(reader, (skip_crc32,)).map(RawArchive::Obscure2)
+        }
+        Game::FinalExam => {
+            final_exam::HvpArchive::read_args(reader, (skip_crc32,)).map(RawArchive::FinalExam)
+        }
+    }
+    .map_err(|source| match detected {
+        // the magic is unknown, so the game was forced and the file is likely something else
+        None => ProviderError::NotAnArchive {
+            specified: game,
+            source,
+        },
+        Some(_) => ProviderError::ArchiveLoadFailed(source),
+    })?;
+
+    let entries_offset = reader.stream_position()? as usize;
+    log::debug!("entries offest: {entries_offset}");
+
+    Ok((raw_archive, entries_offset))
+}
+
+/// end of the data of the last file in the archive
+pub(crate) fn data_end(raw_archive: &RawArchive) -> u64 {
+    data_ranges(raw_archive)
+        .iter()
+        .map(|r| r.end)
+        .max()
+        .unwrap_or(0)
+}
+
+/// ranges of the archive that hold the data of the files, in the order of the entries.
+/// entries that aren't reachable from the root are included too, files without stored data aren't
+pub(crate) fn data_ranges(raw_archive: &RawArchive) -> Vec<Range<u64>> {
+    fn range(offset: u32, size: u32) -> Option<Range<u64>> {
+        (size != 0).then(|| offset as u64..offset as u64 + size as u64)
+    }
+
+    match raw_archive {
+        RawArchive::Obscure1(archive) => {
+            fn entry_ranges(e: &obscure1::Entry, ranges: &mut Vec<Range<u64>>) {
+                match &e.kind {
+                    obscure1::EntryKind::Dir(e) => {
+                        e.entries.iter().for_each(|e| entry_ranges(e, ranges))
+                    }
+                    // entries with uncompressed size zero have invalid compressed sizes
+                    obscure1::EntryKind::File(e) if e.uncompressed_size == 0 => (),
+                    obscure1::EntryKind::File(e) => {
+                        ranges.extend(range(e.offset, e.compressed_size))
+                    }
+                }
+            }
+
+            let mut ranges = Vec::new();
+            archive
+                .entries
+                .iter()
+                .for_each(|e| entry_ranges(e, &mut ranges));
+            ranges
+        }
+        RawArchive::Obscure2(archive) => archive
+            .entries
+            .iter()
+            .filter_map(|e| match &e.kind {
+                obscure2::EntryKind::File(file) | obscure2::EntryKind::FileCompressed(file) => {
+                    range(file.offset, file.compressed_size)
+                }
+                _ => None,
+            })
+            .collect(),
+        RawArchive::FinalExam(archive) => archive
+            .entries
+            .iter()
+            .filter_map(|e| match &e.kind {
+                final_exam::EntryKind::File(file) | final_exam::EntryKind::FileCompressed(file) => {
+                    range(file.offset, file.compressed_size)
+                }
+                _ => None,
+            })
+            .collect(),
+    }
+}
+
+/// size of the biggest file in the archive
+fn largest_file_size(raw_archive: &RawArchive) -> u64 {
+    match raw_archive {
+        RawArchive::Obscure1(archive) => {
+            fn entry_size(e: &obscure1::Entry) -> u64 {
+                match &e.kind {
+                    obscure1::EntryKind::Dir(e) => {
+                        e.entries.iter().map(entry_size).max().unwrap_or(0)
+                    }
+                    // entries with uncompressed size zero have invalid compressed sizes
+                    obscure1::EntryKind::File(e) if e.uncompressed_size == 0 => 0,
+                    obscure1::EntryKind::File(e) => e.uncompressed_size.max(e.compressed_size) as _,
+                }
+            }
+
+            archive.entries.iter().map(entry_size).max().unwrap_or(0)
+        }
+        RawArchive::Obscure2(archive) => archive
+            .entries
+            .iter()
+            .filter_map(|e| match &e.kind {
+                obscure2::EntryKind::File(file) | obscure2::EntryKind::FileCompressed(file) => {
+                    Some(file.uncompressed_size.max(file.compressed_size) as u64)
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0),
+        RawArchive::FinalExam(archive) => archive
+            .entries
+            .iter()
+            .filter_map(|e| match &e.kind {
+                final_exam::EntryKind::File(file) | final_exam::EntryKind::FileCompressed(file) => {
+                    Some(file.uncompressed_size.max(file.compressed_size) as u64)
+                }
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0),
+    }
+}
+
 #[inline]
 fn validate_entries(raw_archive: &RawArchive, mmap: &[u8]) -> bool {
     match raw_archive {
@@ -153,3 +538,230 @@ fn validate_entries(raw_archive: &RawArchive, mmap: &[u8]) -> bool {
         }),
     }
 }
+
+/// make sure the children range of every directory in the flat entry table (obscure 2 and final exam)
+/// is inside the table, doesn't include the root entry and doesn't overlap with other directories.
+/// empty directories don't have any children, so only the bounds of their range are checked
+fn validate_dir_ranges(raw_archive: &RawArchive) -> Result<(), ProviderError> {
+    let (mut dirs, len): (Vec<_>, _) = match raw_archive {
+        RawArchive::Obscure1(_) => return Ok(()),
+        RawArchive::Obscure2(archive) => (
+            archive
+                .entries
+                .iter()
+                .enumerate()
+                .filter_map(|(index, e)| Some((index, e.kind.dir_range()?)))
+                .collect(),
+            archive.entries.len(),
+        ),
+        RawArchive::FinalExam(archive) => (
+            archive
+                .entries
+                .iter()
+                .enumerate()
+                .filter_map(|(index, e)| Some((index, e.kind.dir_range()?)))
+                .collect(),
+            archive.entries.len(),
+        ),
+    };
+
+    for (index, range) in &dirs {
+        if range.end > len {
+            return Err(ProviderError::DirRangeOutOfBounds {
+                index: *index,
+                range: range.clone(),
+                len,
+            });
+        }
+
+        if range.start == 0 && !range.is_empty() {
+            return Err(ProviderError::DirRangeIncludeRoot {
+                index: *index,
+                range: range.clone(),
+            });
+        }
+    }
+
+    // after sorting by start, only neighbours can overlap
+    dirs.retain(|(_, range)| !range.is_empty());
+    dirs.sort_by_key(|(_, range)| range.start);
+    for pair in dirs.windows(2) {
+        let [(other, other_range), (index, range)] = pair else {
+            unreachable!()
+        };
+
+        if range.start < other_range.end {
+            return Err(ProviderError::DirRangeOverlap {
+                index: *index,
+                range: range.clone(),
+                other: *other,
+                other_range: other_range.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        archive::{
+            Archive,
+            builder::{ArchiveBuilder, BuildError},
+            entry::Entry,
+            error::RebuildError,
+        },
+        test_utils::{EmptyProgress, for_each_fixture, game, rebuild, reload},
+    };
+
+    #[test]
+    fn fixtures_data_region_inside_file() {
+        for_each_fixture(|fixture, provider| {
+            assert_eq!(provider.file_len(), fixture.bytes.len() as u64);
+
+            let region = provider.data_region();
+            assert!(provider.toc_len() > 0);
+            assert_eq!(region.start, provider.toc_len());
+            assert!(region.start < region.end);
+            assert!(region.end <= provider.file_len());
+        });
+    }
+
+    #[test]
+    fn fixtures_largest_file_size() {
+        for_each_fixture(|_, provider| {
+            let largest = Archive::new(provider)
+                .files()
+                .map(|f| f.raw_bytes.len().max(f.get_bytes().unwrap().len()))
+                .max()
+                .unwrap();
+
+            assert_eq!(provider.largest_file_size(), largest as u64);
+        });
+    }
+
+    #[test]
+    fn open_fixtures_toc_only() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            let toc_provider = ArchiveProvider::open_toc_only(fixture.path, None)
+                .expect("failed to load hvp archive toc");
+            assert!(provider.has_data() && !toc_provider.has_data());
+            assert_eq!(toc_provider.game(), provider.game());
+            assert_eq!(toc_provider.toc_len(), provider.toc_len());
+
+            let toc_archive = Archive::new(&toc_provider);
+            assert_eq!(toc_archive.metadata(), archive.metadata());
+
+            for (info, toc_info) in archive.files_info().zip(toc_archive.files_info()) {
+                assert_eq!(toc_info.path, info.path);
+                assert_eq!(toc_info.stored_size, info.stored_size);
+                assert_eq!(toc_info.data_offset, info.data_offset);
+                assert_eq!(info.checksum_match, Some(true));
+                assert_eq!(toc_info.checksum_match, None);
+            }
+
+            let result = toc_archive.rebuild(&mut Cursor::new(Vec::new()), EmptyProgress);
+            assert!(matches!(result, Err(RebuildError::NoData)), "{result:?}");
+            assert!(matches!(
+                ArchiveBuilder::from_archive(&toc_archive),
+                Err(BuildError::Rebuild(RebuildError::NoData))
+            ));
+        });
+    }
+
+    #[test]
+    fn open_fixtures_from_reader() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            // the archive is embedded after some other data, and read from the current position
+            let mut embedded = vec![0xAA; 100];
+            embedded.extend_from_slice(fixture.bytes);
+            let mut reader = Cursor::new(embedded);
+            reader.set_position(100);
+            let buffered = ArchiveProvider::from_reader(reader, None)
+                .expect("failed to load hvp archive from reader");
+            let buffered = Archive::new(&buffered);
+
+            assert_eq!(buffered.metadata(), archive.metadata());
+            assert!(buffered.verify().is_ok());
+            for (a, b) in archive.files().zip(buffered.files()) {
+                assert_eq!(a.path, b.path);
+                assert_eq!(a.get_bytes().unwrap(), b.get_bytes().unwrap());
+            }
+
+            assert_eq!(rebuild(&buffered), fixture.bytes);
+
+            let game = Some(game(fixture.format));
+            assert!(ArchiveProvider::from_bytes(fixture.bytes.to_vec(), game).is_ok());
+        });
+    }
+
+    #[test]
+    fn open_fixtures_with_options() {
+        for_each_fixture(|fixture, provider| {
+            let archive = Archive::new(provider);
+
+            for options in [
+                ProviderOptions {
+                    buffered: true,
+                    ..Default::default()
+                },
+                ProviderOptions {
+                    access: Access::Sequential,
+                    populate: true,
+                    ..Default::default()
+                },
+                ProviderOptions {
+                    access: Access::Random,
+                    ..Default::default()
+                },
+            ] {
+                let file = File::open(fixture.path).unwrap();
+                let other = ArchiveProvider::new_with_options(file, None, options)
+                    .expect("failed to load hvp archive with options");
+                let other = Archive::new(&other);
+
+                assert_eq!(other.metadata(), archive.metadata(), "{options:?}");
+                for (a, b) in archive.files().zip(other.files()) {
+                    assert_eq!(a.raw_bytes, b.raw_bytes, "{options:?}");
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn open_archive_with_empty_dir() {
+        for_each_fixture(|_, provider| {
+            let mut archive = Archive::new(provider);
+            let name = archive
+                .entries_mut()
+                .iter_mut()
+                .find_map(|e| match e {
+                    Entry::Dir(dir) if !dir.entries.is_empty() => {
+                        dir.entries.clear();
+                        Some(dir.name.clone())
+                    }
+                    _ => None,
+                })
+                .expect("fixture doesn't have a directory");
+
+            let mut writer = Cursor::new(Vec::new());
+            ArchiveBuilder::from_archive(&archive)
+                .unwrap()
+                .build(&mut writer, EmptyProgress)
+                .unwrap();
+
+            let provider = reload(writer.into_inner());
+            let archive = Archive::new(&provider);
+            assert!(archive.entries().iter().any(|e| matches!(
+                e,
+                Entry::Dir(dir) if dir.name == name && dir.entries.is_empty()
+            )));
+        });
+    }
+}
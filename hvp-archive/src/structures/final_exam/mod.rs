@@ -18,12 +18,13 @@ const BIG_ENDIAN_MAGIC: [u8; 4] = [0, 5, 0, 0];
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
 #[br(stream = r, is_big = is_magic_big_endian(r)?)]
+#[br(import(skip_crc32: bool))]
 #[bw(is_big = self.endian() == Endian::Big)]
 pub struct HvpArchive {
     #[bw(args(entries))]
     pub header: Header,
     pub names: Names,
-    #[br(args(header.entries_count as _, Some(header.entries_crc32)))]
+    #[br(args(header.entries_count as _, (!skip_crc32).then_some(header.entries_crc32)))]
     #[br(parse_with = common::read_entries_with_validation)]
     #[br(assert(have_root_entry(&entries), "invalid final exam hvp, archive should start with a root directory entry"))]
     #[br(assert(names.validate_name_offsets(&entries), "invalid name offsets in the archive"))]
@@ -52,6 +53,22 @@ pub struct Header {
     pub entries_crc32: u32,
 }
 
+impl Header {
+    /// create a new header, crc32 of the entries is calculated when writing and the
+    /// count is filled when the layout of the archive is planned
+    pub(crate) fn new(endian: Endian) -> Self {
+        Self {
+            magic: match endian {
+                Endian::Big => BIG_ENDIAN_MAGIC,
+                Endian::Little => LITTLE_ENDIAN_MAGIC,
+            },
+            zero: 0,
+            entries_count: 0,
+            entries_crc32: 0,
+        }
+    }
+}
+
 #[binrw]
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
@@ -64,6 +81,10 @@ pub struct Names {
 }
 
 impl Names {
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
     fn validate_name_offsets(&self, entries: &[Entry]) -> bool {
         for entry in entries {
             let offset = match &entry.kind {
@@ -122,6 +143,30 @@ pub enum EntryKind {
     Directory(DirEntry),
 }
 
+impl EntryKind {
+    /// range of the children entries if this entry is a directory
+    pub(crate) fn dir_range(&self) -> Option<Range<usize>> {
+        match self {
+            Self::Directory(dir) => Some(dir.entries_range()),
+            Self::File(_) | Self::FileCompressed(_) => None,
+        }
+    }
+
+    /// turn a compressed file entry into a uncompressed one
+    pub(crate) fn set_uncompressed(&mut self) {
+        if let Self::FileCompressed(file) = self {
+            *self = Self::File(file.clone());
+        }
+    }
+
+    /// turn a uncompressed file entry into a compressed one
+    pub(crate) fn set_compressed(&mut self) {
+        if let Self::File(file) = self {
+            *self = Self::FileCompressed(file.clone());
+        }
+    }
+}
+
 #[binrw]
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
@@ -142,12 +187,22 @@ pub struct DirEntry {
     #[br(assert(zero2 == 0))]
     zero2: u32,
     pub name_offset: u32,
-    #[br(assert(count > 0, "invalid archive, directory can't have zero entries"))]
+    /// empty directories are allowed, the index of them isn't used
     pub count: u32,
     pub index: u32,
 }
 
 impl DirEntry {
+    pub(crate) fn new(name_offset: u32, count: u32, index: u32) -> Self {
+        Self {
+            zero1: 0,
+            zero2: 0,
+            name_offset,
+            count,
+            index,
+        }
+    }
+
     pub fn entries_range(&self) -> Range<usize> {
         let start = self.index as usize;
         let end = start + self.count as usize;
@@ -7,4 +7,4 @@ pub mod obscure1;
 pub mod obscure2;
 
 pub(crate) mod checksum;
-mod common;
+pub(crate) mod common;
@@ -11,12 +11,13 @@ use super::common;
 #[binrw]
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
+#[br(import(skip_crc32: bool))]
 pub struct HvpArchive {
     pub header: Header,
     #[br(if(header.minor_version == 1))]
     #[bw(args(header, entries))]
     pub checksums: Option<Crc32>,
-    #[br(args(header.root_count as _, checksums.as_ref().map(|c| c.entries)))]
+    #[br(args(header.root_count as _, checksums.as_ref().filter(|_| !skip_crc32).map(|c| c.entries)))]
     #[br(parse_with = common::read_entries_with_validation)]
     pub entries: Vec<Entry>,
 }
@@ -38,6 +39,21 @@ pub struct Header {
     pub data_offset: u32,
 }
 
+impl Header {
+    /// create a new header, with the version that include the crc32 checksums.
+    /// counts and data offset are filled when the layout of the archive is planned
+    pub(crate) fn new() -> Self {
+        Self {
+            major_version: 3,
+            minor_version: 1,
+            root_count: 0,
+            all_count: 0,
+            file_count: 0,
+            data_offset: 0,
+        }
+    }
+}
+
 #[binrw]
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
@@ -58,6 +74,27 @@ pub struct Entry {
     pub kind: EntryKind,
 }
 
+impl Entry {
+    /// create a new entry, entry size is calculated from the kind
+    pub(crate) fn new(kind: EntryKind) -> Self {
+        let mut entry = Self {
+            entry_size: 0,
+            kind,
+        };
+        entry.update_entry_size();
+        entry
+    }
+
+    /// calculate the entry size again, needed after changing the name
+    pub(crate) fn update_entry_size(&mut self) {
+        // size of the fixed fields plus the name, children are not included
+        self.entry_size = match &self.kind {
+            EntryKind::Dir(dir) => 17 + dir.name.len(),
+            EntryKind::File(file) => 29 + file.name.len(),
+        } as u32;
+    }
+}
+
 #[binrw]
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
@@ -99,3 +136,13 @@ pub struct DirEntry {
     #[br(count = count)]
     pub entries: Vec<Entry>,
 }
+
+impl DirEntry {
+    pub(crate) fn new(name: String, entries: Vec<Entry>) -> Self {
+        Self {
+            zero: 0,
+            name,
+            entries,
+        }
+    }
+}
@@ -19,11 +19,12 @@ const BIG_ENDIAN_MAGIC: [u8; 4] = [0, 4, 0, 0];
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
 #[br(stream = r, is_big = is_magic_big_endian(r)?)]
+#[br(import(skip_crc32: bool))]
 #[bw(is_big = self.endian() == Endian::Big)]
 pub struct HvpArchive {
     #[bw(args(entries))]
     pub header: Header,
-    #[br(args(header.entries_count as _, Some(header.entries_crc32)))]
+    #[br(args(header.entries_count as _, (!skip_crc32).then_some(header.entries_crc32)))]
     #[br(parse_with = common::read_entries_with_validation)]
     #[br(assert(have_root_entry(&entries), "invalid obscure 2 hvp, archive should start with a root directory entry"))]
     pub entries: Vec<Entry>,
@@ -51,6 +52,22 @@ pub struct Header {
     pub entries_crc32: u32,
 }
 
+impl Header {
+    /// create a new header, crc32 of the entries is calculated when writing and the
+    /// count is filled when the layout of the archive is planned
+    pub(crate) fn new(endian: Endian) -> Self {
+        Self {
+            magic: match endian {
+                Endian::Big => BIG_ENDIAN_MAGIC,
+                Endian::Little => LITTLE_ENDIAN_MAGIC,
+            },
+            zero: 0,
+            entries_count: 0,
+            entries_crc32: 0,
+        }
+    }
+}
+
 #[binrw]
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
@@ -71,6 +88,30 @@ pub enum EntryKind {
     Directory(DirEntry),
 }
 
+impl EntryKind {
+    /// range of the children entries if this entry is a directory
+    pub(crate) fn dir_range(&self) -> Option<Range<usize>> {
+        match self {
+            Self::Directory(dir) => Some(dir.entries_range()),
+            Self::File(_) | Self::FileCompressed(_) => None,
+        }
+    }
+
+    /// turn a compressed file entry into a uncompressed one
+    pub(crate) fn set_uncompressed(&mut self) {
+        if let Self::FileCompressed(file) = self {
+            *self = Self::File(file.clone());
+        }
+    }
+
+    /// turn a uncompressed file entry into a compressed one
+    pub(crate) fn set_compressed(&mut self) {
+        if let Self::File(file) = self {
+            *self = Self::FileCompressed(file.clone());
+        }
+    }
+}
+
 #[binrw]
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
@@ -83,6 +124,23 @@ pub struct FileEntry {
     pub compressed_size: u32,
 }
 
+impl FileEntry {
+    pub(crate) fn new(
+        checksum: i32,
+        uncompressed_size: u32,
+        offset: u32,
+        compressed_size: u32,
+    ) -> Self {
+        Self {
+            zero: 0,
+            checksum,
+            uncompressed_size,
+            offset,
+            compressed_size,
+        }
+    }
+}
+
 #[binrw]
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "raw_structure", derive(serde::Serialize))]
@@ -93,12 +151,22 @@ pub struct DirEntry {
     zero2: u32,
     #[br(assert(zero3 == 0))]
     zero3: u32,
-    #[br(assert(count > 0, "invalid archive, directory can't have zero entries"))]
+    /// empty directories are allowed, the index of them isn't used
     pub count: u32,
     pub index: u32,
 }
 
 impl DirEntry {
+    pub(crate) fn new(count: u32, index: u32) -> Self {
+        Self {
+            zero1: 0,
+            zero2: 0,
+            zero3: 0,
+            count,
+            index,
+        }
+    }
+
     pub fn entries_range(&self) -> Range<usize> {
         let start = self.index as usize;
         let end = start + self.count as usize;
@@ -0,0 +1,66 @@
+//! helpers for the tests that run over every archive of `hvp-test-fixtures`
+
+use std::{fs::File, io::Cursor};
+
+pub use hvp_test_fixtures::{Fixture, Format};
+
+use crate::{
+    Game,
+    archive::{Archive, rebuild_progress::RebuildProgress},
+    provider::ArchiveProvider,
+};
+
+/// progress that ignore everything
+pub struct EmptyProgress;
+
+impl RebuildProgress for EmptyProgress {
+    fn inc(&self, _: Option<String>) {}
+    fn inc_n(&self, _: usize, _: Option<String>) {}
+}
+
+/// game of the fixture format
+pub fn game(format: Format) -> Game {
+    match format {
+        Format::Obscure1 => Game::Obscure1,
+        Format::Obscure2 => Game::Obscure2,
+        Format::FinalExam => Game::FinalExam,
+    }
+}
+
+/// load the archive of the fixture, the game is detected
+pub fn load(fixture: &Fixture) -> ArchiveProvider {
+    let file = File::open(fixture.path).expect("failed to open fixture");
+    ArchiveProvider::new(file, None).expect("failed to load fixture")
+}
+
+/// load an archive that the test wrote
+pub fn reload(bytes: Vec<u8>) -> ArchiveProvider {
+    ArchiveProvider::from_bytes(bytes, None).expect("failed to load the written archive")
+}
+
+/// rebuild the archive into memory
+pub fn rebuild(archive: &Archive) -> Vec<u8> {
+    let mut writer = Cursor::new(Vec::new());
+    archive
+        .rebuild(&mut writer, EmptyProgress)
+        .expect("failed to rebuild archive");
+    writer.into_inner()
+}
+
+/// run the test with every fixture, a failure also print which fixture it happened with
+pub fn for_each_fixture(mut test: impl FnMut(&Fixture, &ArchiveProvider)) {
+    for fixture in hvp_test_fixtures::ALL {
+        let _guard = FixtureGuard(fixture.name);
+        test(fixture, &load(fixture));
+    }
+}
+
+struct FixtureGuard(&'static str);
+
+impl Drop for FixtureGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            eprintln!("failed with the {} fixture", self.0);
+        }
+    }
+}
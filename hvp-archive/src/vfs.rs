@@ -0,0 +1,178 @@
+//! a read only [`vfs::FileSystem`] over an hvp archive, so tools that use the `vfs` crate can
+//! read the files of the archive like any other file system.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use hvp_archive::{provider::ArchiveProvider, vfs::HvpFs};
+//!
+//! let provider = ArchiveProvider::new(std::fs::File::open("game_data.hvp")?, None)?;
+//! let root = vfs::VfsPath::new(HvpFs::new(provider));
+//! let config = root.join("common/platform_utility/config.xmc")?.read_to_string()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    fmt::Debug,
+    io::{self, Cursor},
+    path::Path,
+};
+
+use ahash::HashMap;
+use vfs::{
+    FileSystem, SeekAndRead, SeekAndWrite, VfsError, VfsFileType, VfsMetadata, VfsResult,
+    error::VfsErrorKind,
+};
+
+use crate::{
+    archive::{
+        Archive, Options,
+        entry::{Entry, FullFileEntry},
+    },
+    provider::ArchiveProvider,
+};
+
+type FileList<'p> = Vec<FullFileEntry<'p>>;
+
+self_cell::self_cell!(
+    /// the provider and the files of the archive, that borrow from it
+    struct Files {
+        owner: ArchiveProvider,
+        #[covariant]
+        dependent: FileList,
+    }
+);
+
+/// read only file system over an hvp archive, every method that change the
+/// file system return [`VfsErrorKind::NotSupported`].
+///
+/// the files are decompressed in memory when they are opened
+pub struct HvpFs {
+    files: Files,
+    /// the names of the entries of every directory by the directory path, the root is `""`
+    dirs: HashMap<String, Vec<String>>,
+    /// index in `files` by the file path
+    paths: HashMap<String, usize>,
+}
+
+impl HvpFs {
+    /// create a file system over the archive with default options
+    pub fn new(provider: ArchiveProvider) -> Self {
+        Self::new_with_options(provider, Options::default())
+    }
+
+    /// create a file system over the archive with the given options, for example
+    /// to use the obscure 2 name map
+    pub fn new_with_options(provider: ArchiveProvider, options: Options) -> Self {
+        let mut dirs: HashMap<String, Vec<String>> = HashMap::default();
+        dirs.insert(String::new(), Vec::new());
+
+        let files = Files::new(provider, |provider| {
+            let archive = Archive::new_with_options(provider, options);
+            for walk in archive.walk() {
+                let path = vfs_path(&walk.path);
+                let parent = path[..path.rfind('/').unwrap_or(0)].to_owned();
+                dirs.entry(parent)
+                    .or_default()
+                    .push(walk.entry.name().to_owned());
+                if let Entry::Dir(_) = walk.entry {
+                    dirs.entry(path).or_default();
+                }
+            }
+            archive.files().collect()
+        });
+
+        let paths = files
+            .borrow_dependent()
+            .iter()
+            .enumerate()
+            .map(|(idx, file)| (vfs_path(&file.path), idx))
+            .collect();
+
+        Self { files, dirs, paths }
+    }
+
+    /// the provider of the archive
+    pub fn provider(&self) -> &ArchiveProvider {
+        self.files.borrow_owner()
+    }
+
+    fn file(&self, path: &str) -> Option<&FullFileEntry<'_>> {
+        let idx = *self.paths.get(path)?;
+        Some(&self.files.borrow_dependent()[idx])
+    }
+}
+
+impl Debug for HvpFs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HvpFs")
+            .field("game", &self.provider().game())
+            .field("files", &self.paths.len())
+            .field("dirs", &self.dirs.len())
+            .finish()
+    }
+}
+
+impl FileSystem for HvpFs {
+    fn read_dir(&self, path: &str) -> VfsResult<Box<dyn Iterator<Item = String> + Send>> {
+        match self.dirs.get(path) {
+            Some(names) => Ok(Box::new(names.clone().into_iter())),
+            None => Err(VfsErrorKind::FileNotFound.into()),
+        }
+    }
+
+    fn create_dir(&self, _path: &str) -> VfsResult<()> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+
+    fn open_file(&self, path: &str) -> VfsResult<Box<dyn SeekAndRead + Send>> {
+        let file = self.file(path).ok_or(VfsErrorKind::FileNotFound)?;
+        let bytes = file.get_bytes().map_err(io::Error::other)?;
+        Ok(Box::new(Cursor::new(bytes.into_owned())))
+    }
+
+    fn create_file(&self, _path: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+
+    fn append_file(&self, _path: &str) -> VfsResult<Box<dyn SeekAndWrite + Send>> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+
+    fn metadata(&self, path: &str) -> VfsResult<VfsMetadata> {
+        let (file_type, len) = match self.file(path) {
+            Some(file) => (VfsFileType::File, file.uncompressed_size() as u64),
+            None if self.dirs.contains_key(path) => (VfsFileType::Directory, 0),
+            None => return Err(VfsError::from(VfsErrorKind::FileNotFound)),
+        };
+
+        Ok(VfsMetadata {
+            file_type,
+            len,
+            created: None,
+            modified: None,
+            accessed: None,
+        })
+    }
+
+    fn exists(&self, path: &str) -> VfsResult<bool> {
+        Ok(self.paths.contains_key(path) || self.dirs.contains_key(path))
+    }
+
+    fn remove_file(&self, _path: &str) -> VfsResult<()> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+
+    fn remove_dir(&self, _path: &str) -> VfsResult<()> {
+        Err(VfsErrorKind::NotSupported.into())
+    }
+}
+
+/// the path of an entry as `vfs` expect it, `/` followed by the components joined by `/`
+fn vfs_path(path: &Path) -> String {
+    path.iter().fold(String::new(), |mut path, name| {
+        path.push('/');
+        path.push_str(&name.to_string_lossy());
+        path
+    })
+}
@@ -0,0 +1,427 @@
+//! a minimal zip reader and writer, only support stored and deflate entries without zip64 and encryption.
+//! this is enough for zip files that people usually share for mods.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{Compression, write::DeflateEncoder};
+
+const EOCD_SIGNATURE: u32 = 0x06054B50;
+const CENTRAL_DIR_SIGNATURE: u32 = 0x02014B50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034B50;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATE: u16 = 8;
+
+/// errors that can happen when reading a zip file
+#[derive(Debug, thiserror::Error)]
+pub enum ZipError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("invalid zip file: {0}")]
+    Invalid(&'static str),
+    #[error("unsupported zip file: {0}")]
+    Unsupported(&'static str),
+    #[error("entry {0} use unsupported compression method {1}")]
+    UnsupportedMethod(String, u16),
+    #[error("crc32 of entry {0} doesn't match")]
+    Crc32Mismatch(String),
+}
+
+/// a file inside the zip
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    /// name of the entry inside the zip, always use `/` as separator
+    pub name: String,
+    /// crc32 of uncompressed content
+    pub crc32: u32,
+    pub uncompressed_size: u32,
+    compressed_size: u32,
+    method: u16,
+    header_offset: u32,
+}
+
+impl ZipEntry {
+    /// entry name as a relative path
+    pub fn path(&self) -> PathBuf {
+        self.name.split('/').filter(|c| !c.is_empty()).collect()
+    }
+}
+
+/// a zip archive opened for reading
+pub struct ZipReader<R = BufReader<File>> {
+    reader: R,
+    entries: Vec<ZipEntry>,
+}
+
+impl ZipReader {
+    /// open the zip archive and read its central directory
+    pub fn open(path: &Path) -> Result<Self, ZipError> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> ZipReader<R> {
+    /// read the central directory of the zip archive
+    pub fn new(mut reader: R) -> Result<Self, ZipError> {
+        let entries = read_central_dir(&mut reader)?;
+
+        Ok(Self { reader, entries })
+    }
+
+    /// file entries inside the zip, directories are ignored
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// find a entry by its path
+    pub fn find(&self, path: &Path) -> Option<&ZipEntry> {
+        self.entries.iter().find(|e| e.path() == path)
+    }
+
+    /// read and decompress content of a entry
+    pub fn read(&mut self, entry: &ZipEntry) -> Result<Vec<u8>, ZipError> {
+        self.reader
+            .seek(SeekFrom::Start(entry.header_offset as _))?;
+
+        let mut header = [0u8; 30];
+        self.reader.read_exact(&mut header)?;
+        if u32_at(&header, 0) != LOCAL_HEADER_SIGNATURE {
+            return Err(ZipError::Invalid("bad local file header signature"));
+        }
+
+        let name_len = u16_at(&header, 26) as i64;
+        let extra_len = u16_at(&header, 28) as i64;
+        self.reader.seek_relative(name_len + extra_len)?;
+
+        let mut data = Vec::with_capacity(entry.uncompressed_size as _);
+        let compressed = (&mut self.reader).take(entry.compressed_size as _);
+
+        match entry.method {
+            METHOD_STORED => compressed
+                .take(entry.uncompressed_size as _)
+                .read_to_end(&mut data)?,
+            METHOD_DEFLATE => {
+                flate2::read::DeflateDecoder::new(compressed).read_to_end(&mut data)?
+            }
+            method => return Err(ZipError::UnsupportedMethod(entry.name.clone(), method)),
+        };
+
+        if crc32fast::hash(&data) != entry.crc32 {
+            return Err(ZipError::Crc32Mismatch(entry.name.clone()));
+        }
+
+        Ok(data)
+    }
+}
+
+/// a zip archive opened for writing, entries are deflated when it make them smaller
+pub struct ZipWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    central_dir: Vec<u8>,
+    count: usize,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            offset: 0,
+            central_dir: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// add a file to the zip, `name` should use `/` as separator
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<(), ZipError> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let deflated = encoder.finish()?;
+
+        let (method, stored) = match deflated.len() < data.len() {
+            true => (METHOD_DEFLATE, &deflated[..]),
+            false => (METHOD_STORED, data),
+        };
+
+        let too_large = || ZipError::Unsupported("zip64 archives aren't supported");
+        let header_offset = u32::try_from(self.offset).map_err(|_| too_large())?;
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let name_len =
+            u16::try_from(name.len()).map_err(|_| ZipError::Invalid("name is too long"))?;
+
+        // version needed, flags (utf-8 names), method, time, date (1980-01-01), crc32 and sizes
+        let mut common = Vec::with_capacity(26);
+        common.extend_from_slice(&20u16.to_le_bytes());
+        common.extend_from_slice(&0x0800u16.to_le_bytes());
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&0x21u16.to_le_bytes());
+        common.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        common.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&name_len.to_le_bytes());
+
+        self.writer
+            .write_all(&LOCAL_HEADER_SIGNATURE.to_le_bytes())?;
+        self.writer.write_all(&common)?;
+        self.writer.write_all(&0u16.to_le_bytes())?; // extra length
+        self.writer.write_all(name.as_bytes())?;
+        self.writer.write_all(stored)?;
+
+        let cd = &mut self.central_dir;
+        cd.extend_from_slice(&CENTRAL_DIR_SIGNATURE.to_le_bytes());
+        cd.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        cd.extend_from_slice(&common);
+        // extra, comment, disk number, internal and external attributes
+        cd.extend_from_slice(&[0; 12]);
+        cd.extend_from_slice(&header_offset.to_le_bytes());
+        cd.extend_from_slice(name.as_bytes());
+
+        self.offset += 30 + name.len() as u64 + stored.len() as u64;
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// write the central directory and return the inner writer
+    pub fn finish(mut self) -> Result<W, ZipError> {
+        let too_large = || ZipError::Unsupported("zip64 archives aren't supported");
+        let count = u16::try_from(self.count)
+            .ok()
+            .filter(|&c| c != u16::MAX)
+            .ok_or_else(too_large)?;
+        let cd_offset = u32::try_from(self.offset)
+            .ok()
+            .filter(|&o| o != u32::MAX)
+            .ok_or_else(too_large)?;
+
+        self.writer.write_all(&self.central_dir)?;
+
+        let mut eocd = Vec::with_capacity(22);
+        eocd.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        eocd.extend_from_slice(&[0; 4]); // disk numbers
+        eocd.extend_from_slice(&count.to_le_bytes());
+        eocd.extend_from_slice(&count.to_le_bytes());
+        eocd.extend_from_slice(&(self.central_dir.len() as u32).to_le_bytes());
+        eocd.extend_from_slice(&cd_offset.to_le_bytes());
+        eocd.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.writer.write_all(&eocd)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+fn read_central_dir<R: Read + Seek>(reader: &mut R) -> Result<Vec<ZipEntry>, ZipError> {
+    // end of central directory is at least 22 bytes and can have a comment up to 64kb
+    let len = reader.seek(SeekFrom::End(0))?;
+    let tail_len = len.min(22 + u16::MAX as u64);
+    reader.seek(SeekFrom::Start(len - tail_len))?;
+    let mut tail = vec![0u8; tail_len as usize];
+    reader.read_exact(&mut tail)?;
+
+    let eocd = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| u32_at(&tail, i) == EOCD_SIGNATURE)
+        .map(|i| &tail[i..])
+        .ok_or(ZipError::Invalid("can't find end of central directory"))?;
+
+    let count = u16_at(eocd, 10);
+    let cd_size = u32_at(eocd, 12);
+    let cd_offset = u32_at(eocd, 16);
+
+    if count == u16::MAX || cd_offset == u32::MAX {
+        return Err(ZipError::Unsupported("zip64 archives aren't supported"));
+    }
+
+    reader.seek(SeekFrom::Start(cd_offset as _))?;
+    let mut cd = vec![0u8; cd_size as usize];
+    reader.read_exact(&mut cd)?;
+
+    let mut entries = Vec::with_capacity(count as _);
+    let mut pos = 0;
+    for _ in 0..count {
+        let header = cd
+            .get(pos..pos + 46)
+            .ok_or(ZipError::Invalid("central directory is truncated"))?;
+        if u32_at(header, 0) != CENTRAL_DIR_SIGNATURE {
+            return Err(ZipError::Invalid("bad central directory signature"));
+        }
+
+        let flags = u16_at(header, 8);
+        let name_len = u16_at(header, 28) as usize;
+        let extra_len = u16_at(header, 30) as usize;
+        let comment_len = u16_at(header, 32) as usize;
+
+        let name = cd
+            .get(pos + 46..pos + 46 + name_len)
+            .ok_or(ZipError::Invalid("central directory is truncated"))?;
+        let name = String::from_utf8_lossy(name).replace('\\', "/");
+
+        let entry = ZipEntry {
+            name,
+            crc32: u32_at(header, 16),
+            compressed_size: u32_at(header, 20),
+            uncompressed_size: u32_at(header, 24),
+            method: u16_at(header, 10),
+            header_offset: u32_at(header, 42),
+        };
+
+        pos += 46 + name_len + extra_len + comment_len;
+
+        if entry.name.ends_with('/') {
+            continue;
+        }
+
+        if flags & 1 != 0 {
+            return Err(ZipError::Unsupported("encrypted entries aren't supported"));
+        }
+
+        entries.push(entry);
+    }
+
+    Ok(entries)
+}
+
+#[inline]
+fn u16_at(buf: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([buf[pos], buf[pos + 1]])
+}
+
+#[inline]
+fn u32_at(buf: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// made by info-zip with `zip -r`, the mod folder has a deflated text file, a stored binary
+    /// file and an empty folder, the entries have extra fields and the zip has a comment
+    const INFO_ZIP: &[u8] = include_bytes!("../tests/data/info-zip.zip");
+    /// the same folder made by info-zip writing to a pipe, so the entries use data descriptors
+    const INFO_ZIP_STREAMED: &[u8] = include_bytes!("../tests/data/info-zip-streamed.zip");
+
+    const CONFIG_CRC32: u32 = 0xB482E8D0;
+    const INTRO_CRC32: u32 = 0x2D57DE13;
+
+    fn open(bytes: &[u8]) -> Result<ZipReader<Cursor<&[u8]>>, ZipError> {
+        ZipReader::new(Cursor::new(bytes))
+    }
+
+    #[test]
+    fn read_info_zip() {
+        for bytes in [INFO_ZIP, INFO_ZIP_STREAMED] {
+            let mut zip = open(bytes).unwrap();
+
+            // directories are skipped
+            let names: Vec<_> = zip.entries().iter().map(|e| e.name.as_str()).collect();
+            assert_eq!(names, ["mod/sound/intro.bin", "mod/config.txt"]);
+
+            let config = zip.find(Path::new("mod/config.txt")).unwrap().clone();
+            assert_eq!(
+                (config.crc32, config.uncompressed_size),
+                (CONFIG_CRC32, 380)
+            );
+            assert_eq!(
+                zip.read(&config).unwrap(),
+                "language = english\n".repeat(20).as_bytes()
+            );
+
+            let intro = zip
+                .find(&Path::new("mod").join("sound").join("intro.bin"))
+                .unwrap()
+                .clone();
+            assert_eq!((intro.crc32, intro.uncompressed_size), (INTRO_CRC32, 300));
+            assert_eq!(zip.read(&intro).unwrap().len(), 300);
+        }
+
+        assert_eq!(open(INFO_ZIP).unwrap().entries()[0].method, METHOD_STORED);
+        assert_eq!(
+            open(INFO_ZIP_STREAMED).unwrap().entries()[0].method,
+            METHOD_DEFLATE
+        );
+    }
+
+    #[test]
+    fn writer_roundtrip() {
+        let files: [(&str, Vec<u8>); 4] = [
+            ("empty.txt", Vec::new()),
+            ("text/config.txt", b"subtitles = 1\n".repeat(50)),
+            (
+                "data/noise.bin",
+                std::iter::successors(Some(0x2545_F491_u32), |x| {
+                    let x = x ^ x << 13;
+                    let x = x ^ x >> 17;
+                    Some(x ^ x << 5)
+                })
+                .map(|x| x as u8)
+                .take(2000)
+                .collect(),
+            ),
+            ("text/ménu.txt", "é".repeat(10).into_bytes()),
+        ];
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in &files {
+            writer.add(name, data).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut zip = open(&bytes).unwrap();
+        let methods: Vec<_> = zip.entries().iter().map(|e| e.method).collect();
+        assert_eq!(
+            methods,
+            [METHOD_STORED, METHOD_DEFLATE, METHOD_STORED, METHOD_DEFLATE]
+        );
+
+        for (entry, (name, data)) in zip.entries().to_vec().iter().zip(&files) {
+            assert_eq!(entry.name, *name);
+            assert_eq!(entry.crc32, crc32fast::hash(data));
+            assert_eq!(zip.read(entry).unwrap(), *data);
+        }
+    }
+
+    #[test]
+    fn invalid_zips() {
+        assert!(matches!(
+            open(b"not a zip file at all, not even close"),
+            Err(ZipError::Invalid(_))
+        ));
+        assert!(open(&[]).is_err());
+        assert!(open(&INFO_ZIP[..INFO_ZIP.len() - 40]).is_err());
+
+        // a changed byte in the stored file fail the crc32 check
+        let mut bytes = INFO_ZIP.to_vec();
+        let mut zip = open(INFO_ZIP).unwrap();
+        let intro = zip.entries()[0].clone();
+        let content = zip.read(&intro).unwrap();
+        let pos = bytes
+            .windows(content.len())
+            .position(|w| w == content)
+            .unwrap();
+        bytes[pos] ^= 0xFF;
+        let mut zip = open(&bytes).unwrap();
+        assert!(
+            matches!(zip.read(&intro), Err(ZipError::Crc32Mismatch(name)) if name == intro.name)
+        );
+
+        // encrypted entries are refused when the central directory is read
+        let mut bytes = INFO_ZIP.to_vec();
+        let cd = (0..bytes.len())
+            .find(|&i| {
+                u32_at(&bytes, i) == CENTRAL_DIR_SIGNATURE
+                    && bytes[i + 46..].starts_with(b"mod/sound/intro.bin")
+            })
+            .unwrap();
+        bytes[cd + 8] |= 1;
+        assert!(matches!(open(&bytes), Err(ZipError::Unsupported(_))));
+    }
+}
@@ -2,12 +2,12 @@ use std::{fs::File, io::Cursor};
 
 use hvp_archive::{Game, try_detect_game};
 
-mod constants;
+use hvp_test_fixtures as fixtures;
 
 #[test]
 fn autodetect_obscure1() {
     let obscure1 = {
-        let mut file = File::open(constants::OBSCURE1_HVP).expect("failed to open file");
+        let mut file = File::open(fixtures::OBSCURE1.path).expect("failed to open file");
         try_detect_game(&mut file).expect("failed to parse obscure1 archive")
     };
 
@@ -21,7 +21,7 @@ fn autodetect_obscure1() {
 #[test]
 fn autodetect_obscure2() {
     let obscure2 = {
-        let mut file = File::open(constants::OBSCURE2_HVP).expect("failed to open file");
+        let mut file = File::open(fixtures::OBSCURE2.path).expect("failed to open file");
         try_detect_game(&mut file).expect("failed to parse obscure2 archive")
     };
 
@@ -35,7 +35,7 @@ fn autodetect_obscure2() {
 #[test]
 fn autodetect_obscure2_wii() {
     let obscure2_wii = {
-        let mut file = File::open(constants::OBSCURE2_WII_HVP).expect("failed to open file");
+        let mut file = File::open(fixtures::OBSCURE2_WII.path).expect("failed to open file");
         try_detect_game(&mut file).expect("failed to parse obscure2 wii archive")
     };
 
@@ -49,7 +49,7 @@ fn autodetect_obscure2_wii() {
 #[test]
 fn autodetect_final_exam() {
     let final_exam = {
-        let mut file = File::open(constants::FINAL_EXAM_HVP).expect("failed to open file");
+        let mut file = File::open(fixtures::FINAL_EXAM.path).expect("failed to open file");
         try_detect_game(&mut file).expect("failed to parse final exam archive")
     };
 
@@ -69,3 +69,106 @@ fn autodetect_invalid() {
 
     assert_eq!(invalid, None, "the input should be detected as invalid");
 }
+
+#[test]
+fn supported_formats_cover_fixtures() {
+    use hvp_archive::{formats::Capability, supported_formats};
+
+    for game in [Game::Obscure1, Game::Obscure2, Game::FinalExam] {
+        let format = game.format();
+        assert_eq!(format.game, game);
+        assert!(supported_formats().contains(format), "{game:?}");
+        assert!(format.supports(Capability::Extract), "{game:?}");
+    }
+
+    for fixture in fixtures::ALL {
+        let detected = {
+            let mut file = File::open(fixture.path).expect("failed to open file");
+            try_detect_game(&mut file).unwrap().unwrap()
+        };
+
+        let variant = detected
+            .format()
+            .variants
+            .iter()
+            .find(|v| fixture.bytes.starts_with(v.magic))
+            .unwrap_or_else(|| panic!("{}: magic doesn't match any variant", fixture.name));
+
+        // obscure 1 only have a big endian variant
+        if detected != Game::Obscure1 {
+            assert_eq!(variant.big_endian, fixture.big_endian, "{}", fixture.name);
+        }
+    }
+}
+
+#[test]
+fn game_mismatch() {
+    use hvp_archive::provider::{ArchiveProvider, ProviderError};
+
+    for fixture in fixtures::ALL {
+        let detected = try_detect_game(&mut File::open(fixture.path).unwrap())
+            .unwrap()
+            .unwrap();
+
+        for specified in [Game::Obscure1, Game::Obscure2, Game::FinalExam] {
+            let result = ArchiveProvider::new(File::open(fixture.path).unwrap(), Some(specified));
+            match specified == detected {
+                true => assert!(result.is_ok(), "{}", fixture.name),
+                false => assert!(
+                    matches!(
+                        result,
+                        Err(ProviderError::GameMismatch { detected: d, specified: s })
+                            if d == detected && s == specified
+                    ),
+                    "{} opened as {specified:?}",
+                    fixture.name
+                ),
+            }
+        }
+    }
+}
+
+#[test]
+fn not_an_archive() {
+    use hvp_archive::provider::{ArchiveProvider, ProviderError};
+
+    let path = std::env::temp_dir().join("hvp_not_an_archive.hvp");
+    std::fs::write(&path, [0xAB; 256]).unwrap();
+    let result = ArchiveProvider::new(File::open(&path).unwrap(), Some(Game::Obscure2));
+    let _ = std::fs::remove_file(path);
+
+    assert!(matches!(
+        result,
+        Err(ProviderError::NotAnArchive {
+            specified: Game::Obscure2,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn autodetect_from_bytes() {
+    use hvp_archive::provider::{ArchiveProvider, ProviderError};
+
+    for fixture in fixtures::ALL {
+        let detected = try_detect_game(&mut Cursor::new(fixture.bytes))
+            .unwrap()
+            .unwrap();
+
+        // the embedded bytes are loaded without touching the file
+        let provider = ArchiveProvider::from_bytes(fixture.bytes, None)
+            .expect("failed to load hvp archive from bytes");
+        assert_eq!(provider.game(), detected, "{}", fixture.name);
+        assert_eq!(
+            provider.file_len(),
+            fixture.bytes.len() as u64,
+            "{}",
+            fixture.name
+        );
+    }
+
+    assert!(matches!(
+        ArchiveProvider::from_bytes(vec![0xAA; 64], None),
+        Err(ProviderError::UnknownArchive)
+    ));
+}
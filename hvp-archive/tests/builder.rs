@@ -0,0 +1,942 @@
+use std::{
+    fs::File,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use hvp_archive::{
+    Game,
+    archive::{
+        Archive, Obscure2NameMap, Options,
+        analyze::Anomaly,
+        builder::{ArchiveBuilder, BuildError},
+        compression_rules::CompressionRules,
+        entry::UpdateKind,
+    },
+    provider::ArchiveProvider,
+};
+
+use hvp_test_fixtures::{Fixture, Format};
+
+mod common;
+
+use common::{EmptyProgress, for_each_fixture, game, load};
+
+/// obscure 2 name maps of the repository plus the extra names, without the real names
+/// the order of the entries can't match the original archive
+fn name_map(extra: &[&str]) -> Obscure2NameMap {
+    let hashes = concat!(env!("CARGO_MANIFEST_DIR"), "/../hashes");
+    let mut names: Vec<String> = std::fs::read_dir(hashes)
+        .expect("failed to read hashes dir")
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "txt"))
+        .flat_map(|p| {
+            std::fs::read_to_string(p)
+                .unwrap()
+                .lines()
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    names.extend(extra.iter().map(|n| n.to_string()));
+
+    Obscure2NameMap::new(names)
+}
+
+/// load the archive with the obscure 2 name maps of the repository
+fn open<'p>(provider: &'p ArchiveProvider) -> Archive<'p> {
+    Archive::new_with_options(
+        provider,
+        Options {
+            obscure2_names: name_map(&[]),
+            ..Default::default()
+        },
+    )
+}
+
+/// build a new archive from the files of the fixture and write it to a temp file
+fn build_from_fixture(fixture: &Fixture, skip_compression: bool) -> std::path::PathBuf {
+    let provider = load(fixture);
+    let archive = open(&provider);
+
+    let mut builder = ArchiveBuilder::new(game(fixture.format))
+        .big_endian(fixture.big_endian)
+        .skip_compression(skip_compression);
+
+    for file in archive.files() {
+        let bytes = file.get_bytes().expect("failed to decompress file");
+        builder
+            .add_file(&file.path, UpdateKind::Bytes(bytes.into_owned()))
+            .expect("failed to add file");
+    }
+
+    let metadata = builder.metadata();
+    assert_eq!(metadata.dir_count, fixture.dir_count);
+    assert_eq!(metadata.file_count, fixture.file_count);
+
+    let mut writer = Cursor::new(Vec::new());
+    builder
+        .build(&mut writer, EmptyProgress)
+        .expect("failed to build archive");
+
+    let path = std::env::temp_dir().join(format!(
+        "hvp_builder_{}_{skip_compression}.hvp",
+        fixture.name
+    ));
+    std::fs::write(&path, writer.into_inner()).expect("failed to write built archive");
+    path
+}
+
+#[test]
+fn build_fixtures_from_scratch() {
+    for_each_fixture(|fixture, provider| {
+        for skip_compression in [false, true] {
+            let path = build_from_fixture(fixture, skip_compression);
+
+            let archive = open(provider);
+
+            let built_provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+                .expect("failed to load built archive");
+            let built = open(&built_provider);
+
+            // the sizes depend on the compression, so only the layout of entries is compared
+            let (built_metadata, metadata) = (built.metadata(), archive.metadata());
+            assert_eq!(
+                (
+                    built_metadata.dir_count,
+                    built_metadata.file_count,
+                    built_metadata.game,
+                    built_metadata.endianness
+                ),
+                (
+                    metadata.dir_count,
+                    metadata.file_count,
+                    metadata.game,
+                    metadata.endianness
+                ),
+                "metadata doesn't match"
+            );
+            assert!(
+                built.cross_check().is_ok(),
+                "built archive failed verification"
+            );
+
+            // same order and same content
+            for (original, built) in archive.files().zip(built.files()) {
+                assert_eq!(original.path, built.path);
+                assert_eq!(built.is_compressed(), !skip_compression);
+                assert_eq!(
+                    original.get_bytes().unwrap(),
+                    built.get_bytes().unwrap(),
+                    "{} content doesn't match",
+                    original.path.display()
+                );
+            }
+
+            let _ = std::fs::remove_file(path);
+        }
+    });
+}
+
+#[cfg(feature = "raw_structure")]
+#[test]
+fn build_fixtures_match_expected_toc_layout() {
+    use hvp_archive::provider::RawArchive;
+    use serde_json::Value;
+
+    /// remove the fields that depend on the data and compression of the files
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for field in ["offset", "compressed_size", "checksum", "is_compressed"] {
+                    map.remove(field);
+                }
+                if let Some(file) = map.remove("FileCompressed") {
+                    map.insert("File".to_owned(), file);
+                }
+                map.values_mut().for_each(strip);
+            }
+            Value::Array(values) => values.iter_mut().for_each(strip),
+            _ => (),
+        }
+    }
+
+    for_each_fixture(|fixture, _| {
+        let path = build_from_fixture(fixture, false);
+        let provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+            .expect("failed to load built archive");
+
+        let mut toc = match provider.raw_archive() {
+            RawArchive::Obscure1(archive) => serde_json::to_value(&archive.entries),
+            RawArchive::Obscure2(archive) => serde_json::to_value(&archive.entries),
+            RawArchive::FinalExam(archive) => serde_json::to_value(&archive.entries),
+        }
+        .expect("failed to serialize entries");
+
+        let mut expected: Value =
+            serde_json::from_str(fixture.toc_json).expect("failed to parse expected toc");
+
+        strip(&mut toc);
+        strip(&mut expected);
+
+        assert_eq!(toc, expected, "toc layout doesn't match");
+
+        let _ = std::fs::remove_file(path);
+    });
+}
+
+#[test]
+fn build_from_archive_without_changes() {
+    for_each_fixture(|fixture, provider| {
+        let archive = open(provider);
+
+        let builder = ArchiveBuilder::from_archive(&archive).expect("failed to create builder");
+        let expected = archive.metadata();
+        let metadata = builder.metadata();
+        assert_eq!(
+            (metadata.dir_count, metadata.file_count, metadata.game),
+            (expected.dir_count, expected.file_count, expected.game)
+        );
+        assert_eq!(metadata.endianness, expected.endianness);
+
+        let mut writer = Cursor::new(Vec::new());
+        let metadata = builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+        assert_eq!(metadata, expected);
+
+        assert_eq!(
+            crc32fast::hash(writer.get_ref()),
+            fixture.archive_crc32,
+            "built archive doesn't match the original"
+        );
+    });
+}
+
+#[test]
+fn build_from_archive_with_swapped_endian() {
+    for_each_fixture(|fixture, provider| {
+        if fixture.format == Format::Obscure1 {
+            return;
+        }
+
+        let archive = open(provider);
+
+        let mut writer = Cursor::new(Vec::new());
+        ArchiveBuilder::from_archive(&archive)
+            .expect("failed to create builder")
+            .big_endian(!fixture.big_endian)
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_{}_swapped.hvp", fixture.name));
+        std::fs::write(&path, writer.into_inner()).expect("failed to write swapped archive");
+        let swapped_provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+            .expect("failed to load swapped archive");
+        let swapped = open(&swapped_provider);
+        assert_ne!(swapped.metadata().endianness, archive.metadata().endianness);
+        assert!(
+            swapped.cross_check().is_ok(),
+            "swapped archive failed verification"
+        );
+
+        for (file, swapped_file) in archive.files().zip(swapped.files()) {
+            assert_eq!(file.path, swapped_file.path);
+            assert_eq!(
+                file.get_bytes().unwrap(),
+                swapped_file.get_bytes().unwrap(),
+                "{}",
+                file.path.display()
+            );
+        }
+
+        // swapping it again must give back the original archive
+        let mut writer = Cursor::new(Vec::new());
+        ArchiveBuilder::from_archive(&swapped)
+            .expect("failed to create builder")
+            .big_endian(fixture.big_endian)
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+
+        assert_eq!(
+            crc32fast::hash(writer.get_ref()),
+            fixture.archive_crc32,
+            "archive doesn't match the original after swapping back"
+        );
+    });
+}
+
+#[test]
+fn build_from_archive_with_new_files() {
+    for_each_fixture(|fixture, provider| {
+        let archive = open(provider);
+
+        let existing_dir = archive
+            .files()
+            .find_map(|f| {
+                f.path
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .map(|p| p.to_path_buf())
+            })
+            .expect("fixture doesn't have any directory");
+
+        let new_files = [
+            (existing_dir.join("new_file.bin"), b"new file".to_vec()),
+            (
+                "new_dir/nested/new_file.bin".into(),
+                b"nested new file".to_vec(),
+            ),
+        ];
+
+        let mut builder = ArchiveBuilder::from_archive(&archive).expect("failed to create builder");
+        for (path, bytes) in &new_files {
+            builder
+                .add_file(path, UpdateKind::Bytes(bytes.clone()))
+                .expect("failed to add file");
+        }
+
+        let mut writer = Cursor::new(Vec::new());
+        let metadata = builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+        assert_eq!(metadata.file_count, fixture.file_count + 2);
+        assert_eq!(metadata.dir_count, fixture.dir_count + 2);
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_add_{}.hvp", fixture.name));
+        std::fs::write(&path, writer.into_inner()).unwrap();
+
+        let built_provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+            .expect("failed to load built archive");
+        let built = Archive::new_with_options(
+            &built_provider,
+            Options {
+                obscure2_names: name_map(&["new_file.bin", "new_dir", "nested"]),
+                ..Default::default()
+            },
+        );
+
+        assert!(built.verify().is_ok());
+
+        for file in archive.files() {
+            let built_file = built.find_file(&file.path).expect("file is missing");
+            assert_eq!(file.raw_bytes, built_file.raw_bytes);
+        }
+
+        for (path, bytes) in &new_files {
+            let file = built.find_file(path).expect("new file is missing");
+            assert_eq!(&file.get_bytes().unwrap()[..], &bytes[..]);
+        }
+
+        let _ = std::fs::remove_file(path);
+    });
+}
+
+#[test]
+fn build_from_archive_with_removed_entries() {
+    for_each_fixture(|fixture, provider| {
+        let archive = open(provider);
+
+        let removed_file = archive.files().next().unwrap().path;
+        let removed_dir = archive
+            .files()
+            .last()
+            .and_then(|f| f.path.parent().map(PathBuf::from))
+            .filter(|p| !p.as_os_str().is_empty())
+            .expect("last file of fixture isn't inside a directory");
+
+        let mut builder = ArchiveBuilder::from_archive(&archive).expect("failed to create builder");
+        assert!(builder.remove(&removed_file));
+        assert!(builder.remove(&removed_dir));
+        assert!(!builder.remove(&removed_dir));
+        assert!(!builder.remove("not/in/archive.bin"));
+
+        let mut writer = Cursor::new(Vec::new());
+        builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_remove_{}.hvp", fixture.name));
+        std::fs::write(&path, writer.into_inner()).unwrap();
+
+        let built_provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+            .expect("failed to load built archive");
+        let built = open(&built_provider);
+        assert!(built.verify().is_ok());
+
+        let expected: Vec<_> = archive
+            .files()
+            .filter(|f| f.path != removed_file && !f.path.starts_with(&removed_dir))
+            .map(|f| (f.path, f.raw_bytes))
+            .collect();
+        let files: Vec<_> = built.files().map(|f| (f.path, f.raw_bytes)).collect();
+        assert_eq!(files, expected);
+
+        let _ = std::fs::remove_file(path);
+    });
+}
+
+#[test]
+fn build_from_archive_with_renamed_entries() {
+    for_each_fixture(|fixture, provider| {
+        let archive = open(provider);
+
+        let file = archive.files().next().unwrap();
+        let dir = archive
+            .files()
+            .last()
+            .and_then(|f| f.path.parent().map(PathBuf::from))
+            .filter(|p| !p.as_os_str().is_empty())
+            .expect("last file of fixture isn't inside a directory");
+
+        let renamed_file = PathBuf::from("a_renamed_file_with_a_longer_name.bin");
+        let renamed_dir = PathBuf::from("renamed_dir");
+
+        let mut builder = ArchiveBuilder::from_archive(&archive).expect("failed to create builder");
+        builder.rename(&file.path, &renamed_file).unwrap();
+        builder.rename(&dir, &renamed_dir).unwrap();
+
+        assert!(matches!(
+            builder.rename(&file.path, "other.bin"),
+            Err(BuildError::NotFound(_))
+        ));
+        assert!(matches!(
+            builder.rename(&renamed_dir, renamed_dir.join("inside")),
+            Err(BuildError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            builder.rename(&renamed_dir, &renamed_file),
+            Err(BuildError::PathConflict(_))
+        ));
+        assert!(matches!(
+            builder.rename(&renamed_dir, renamed_file.join("dir")),
+            Err(BuildError::PathConflict(_))
+        ));
+        assert_eq!(builder.metadata().file_count, fixture.file_count);
+
+        let mut writer = Cursor::new(Vec::new());
+        builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_rename_{}.hvp", fixture.name));
+        std::fs::write(&path, writer.into_inner()).unwrap();
+
+        let built_provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+            .expect("failed to load built archive");
+        let built = Archive::new_with_options(
+            &built_provider,
+            Options {
+                obscure2_names: name_map(&["a_renamed_file_with_a_longer_name.bin", "renamed_dir"]),
+                ..Default::default()
+            },
+        );
+        assert!(built.cross_check().is_ok());
+
+        for original in archive.files() {
+            let new_path = match original.path.strip_prefix(&dir) {
+                _ if original.path == file.path => renamed_file.clone(),
+                Ok(rest) => renamed_dir.join(rest),
+                Err(_) => original.path.clone(),
+            };
+
+            let built_file = built
+                .find_file(&new_path)
+                .unwrap_or_else(|| panic!("{} is missing", new_path.display()));
+            assert_eq!(original.raw_bytes, built_file.raw_bytes);
+        }
+
+        let _ = std::fs::remove_file(path);
+    });
+}
+
+/// crc32 of the name that is used by [`ArchiveBuilder::rename_by_crc32`]
+fn stored_name_crc32(game: Game, name: &str) -> u32 {
+    match game {
+        Game::Obscure2 => Obscure2NameMap::stored_crc32(name).unwrap(),
+        Game::Obscure1 | Game::FinalExam => crc32fast::hash(name.as_bytes()),
+    }
+}
+
+#[test]
+fn build_from_archive_with_renamed_crc32s() {
+    for_each_fixture(|fixture, provider| {
+        let game = game(fixture.format);
+        let archive = open(provider);
+
+        let file = archive.files().next().unwrap();
+        let dir = archive
+            .files()
+            .last()
+            .and_then(|f| f.path.parent().map(PathBuf::from))
+            .filter(|p| !p.as_os_str().is_empty())
+            .expect("last file of fixture isn't inside a directory");
+        let dir_name = dir.file_name().unwrap().to_str().unwrap();
+
+        let names: ahash::HashMap<u32, String> = [
+            (
+                stored_name_crc32(game, file.path.file_name().unwrap().to_str().unwrap()),
+                "crc_renamed_file.bin",
+            ),
+            (stored_name_crc32(game, dir_name), "crc_renamed_dir"),
+            // no entry has this name
+            (
+                stored_name_crc32(game, "missing_name.bin"),
+                "never_used.bin",
+            ),
+        ]
+        .into_iter()
+        .map(|(crc32, name)| (crc32, name.to_owned()))
+        .collect();
+
+        let mut builder = ArchiveBuilder::from_archive(&archive).expect("failed to create builder");
+        let renamed = builder.rename_by_crc32(&names).unwrap();
+
+        // every entry with the same name is renamed, not just the ones that we picked
+        let expected = |path: &std::path::Path| -> PathBuf {
+            path.iter()
+                .map(|name| {
+                    let name = name.to_str().unwrap();
+                    names
+                        .get(&stored_name_crc32(game, name))
+                        .map_or(name, String::as_str)
+                        .to_owned()
+                })
+                .collect()
+        };
+        assert!(renamed.contains(&(file.path.clone(), expected(&file.path))));
+        assert!(renamed.contains(&(dir.clone(), expected(&dir))));
+
+        let mut writer = Cursor::new(Vec::new());
+        builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_crc32s_{}.hvp", fixture.name));
+        std::fs::write(&path, writer.into_inner()).unwrap();
+
+        let built_provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+            .expect("failed to load built archive");
+        let built = Archive::new_with_options(
+            &built_provider,
+            Options {
+                obscure2_names: name_map(&["crc_renamed_file.bin", "crc_renamed_dir"]),
+                ..Default::default()
+            },
+        );
+        assert!(built.cross_check().is_ok());
+        assert_eq!(built.files().count(), fixture.file_count);
+
+        for original in archive.files() {
+            let new_path = expected(&original.path);
+            let built_file = built
+                .find_file(&new_path)
+                .unwrap_or_else(|| panic!("{} is missing", new_path.display()));
+            assert_eq!(original.raw_bytes, built_file.raw_bytes);
+        }
+
+        let _ = std::fs::remove_file(path);
+    });
+}
+
+#[test]
+fn build_rename_crc32s_with_collisions() {
+    for_each_fixture(|fixture, provider| {
+        let game = game(fixture.format);
+        let archive = open(provider);
+
+        // two files of the same directory
+        let mut files = archive.files();
+        let first = files.next().unwrap();
+        let second = files
+            .find(|f| f.path.parent() == first.path.parent())
+            .expect("fixture doesn't have two files in the same directory");
+
+        let mut builder = ArchiveBuilder::from_archive(&archive).expect("failed to create builder");
+
+        let names = [(
+            stored_name_crc32(game, first.path.file_name().unwrap().to_str().unwrap()),
+            second
+                .path
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_owned(),
+        )]
+        .into_iter()
+        .collect();
+        assert!(matches!(
+            builder.rename_by_crc32(&names),
+            Err(BuildError::NameCollision(..))
+        ));
+
+        let names = [(
+            stored_name_crc32(game, first.path.file_name().unwrap().to_str().unwrap()),
+            "a/b".to_owned(),
+        )]
+        .into_iter()
+        .collect();
+        assert!(matches!(
+            builder.rename_by_crc32(&names),
+            Err(BuildError::InvalidName(_))
+        ));
+
+        // nothing is renamed after an error
+        let mut writer = Cursor::new(Vec::new());
+        builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_collision_{}.hvp", fixture.name));
+        std::fs::write(&path, writer.into_inner()).unwrap();
+
+        let built_provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+            .expect("failed to load built archive");
+        let built = open(&built_provider);
+        assert!(built.find_file(&first.path).is_some());
+        assert!(built.find_file(&second.path).is_some());
+
+        let _ = std::fs::remove_file(path);
+    });
+}
+
+#[cfg(feature = "raw_structure")]
+#[test]
+fn build_obscure1_header_match_new_toc() {
+    use hvp_archive::provider::RawArchive;
+
+    let provider = load(&hvp_test_fixtures::OBSCURE1);
+    let archive = open(&provider);
+
+    let mut builder = ArchiveBuilder::from_archive(&archive).expect("failed to create builder");
+    builder
+        .add_file(
+            "a_new_directory_with_a_long_name/and_a_new_file_with_a_long_name.bin",
+            UpdateKind::Bytes(b"new file".to_vec()),
+        )
+        .unwrap();
+
+    let mut writer = Cursor::new(Vec::new());
+    let metadata = builder
+        .build(&mut writer, EmptyProgress)
+        .expect("failed to build archive");
+
+    let path = std::env::temp_dir().join("hvp_builder_obscure1_header.hvp");
+    std::fs::write(&path, writer.into_inner()).unwrap();
+
+    let built_provider = ArchiveProvider::new(File::open(&path).unwrap(), None)
+        .expect("failed to load built archive");
+    let RawArchive::Obscure1(raw) = built_provider.raw_archive() else {
+        panic!("built archive isn't obscure 1");
+    };
+
+    // magic, header and crc32s come before the entries
+    assert_eq!(raw.header.data_offset as u64 + 40, built_provider.toc_len());
+    assert_eq!(
+        raw.header.all_count as usize,
+        metadata.dir_count + metadata.file_count
+    );
+    assert_eq!(raw.header.file_count as usize, metadata.file_count);
+    assert_eq!(built_provider.data_region().start, built_provider.toc_len());
+
+    let _ = std::fs::remove_file(path);
+}
+
+#[test]
+fn build_from_dir() {
+    let dir = std::env::temp_dir().join("hvp_builder_dir");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("b/c")).unwrap();
+    std::fs::write(dir.join("Z.txt"), b"z").unwrap();
+    std::fs::write(dir.join("b/empty.bin"), b"").unwrap();
+    std::fs::write(dir.join("b/c/a_file.bin"), b"hello hvp").unwrap();
+    std::fs::write(dir.join("b/c/afile.bin"), b"hello again").unwrap();
+
+    for game in [Game::Obscure1, Game::Obscure2, Game::FinalExam] {
+        let mut builder = ArchiveBuilder::new(game);
+        builder.add_dir_all(&dir).expect("failed to add dir");
+
+        let mut writer = Cursor::new(Vec::new());
+        let metadata = builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+        assert_eq!((metadata.dir_count, metadata.file_count), (2, 4));
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_dir_{game:?}.hvp"));
+        std::fs::write(&path, writer.into_inner()).unwrap();
+
+        let provider = ArchiveProvider::new(File::open(&path).unwrap(), Some(game))
+            .expect("failed to load built archive");
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names: Obscure2NameMap::new([
+                    "b",
+                    "c",
+                    "Z.txt",
+                    "empty.bin",
+                    "a_file.bin",
+                    "afile.bin",
+                ]),
+                ..Default::default()
+            },
+        );
+
+        let files: Vec<_> = archive
+            .files()
+            .map(|f| (f.path.clone(), f.get_bytes().unwrap().into_owned()))
+            .collect();
+
+        // entries are sorted by their uppercase name, so `_` come after letters
+        assert_eq!(
+            files,
+            [
+                ("b/c/afile.bin".into(), b"hello again".to_vec()),
+                ("b/c/a_file.bin".into(), b"hello hvp".to_vec()),
+                ("b/empty.bin".into(), Vec::new()),
+                ("Z.txt".into(), b"z".to_vec()),
+            ],
+            "{game:?}"
+        );
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[test]
+fn build_invalid_paths() {
+    let mut builder = ArchiveBuilder::new(Game::Obscure2);
+
+    assert!(matches!(
+        builder.build(&mut Cursor::new(Vec::new()), EmptyProgress),
+        Err(BuildError::EmptyArchive)
+    ));
+
+    builder
+        .add_file("a/b.bin", UpdateKind::Bytes(vec![1]))
+        .unwrap();
+
+    assert!(matches!(
+        builder.add_file("a/b.bin/c.bin", UpdateKind::Bytes(vec![1])),
+        Err(BuildError::PathConflict(_))
+    ));
+    assert!(matches!(
+        builder.add_file("a", UpdateKind::Bytes(vec![1])),
+        Err(BuildError::PathConflict(_))
+    ));
+    assert!(matches!(
+        builder.add_file("../a.bin", UpdateKind::Bytes(vec![1])),
+        Err(BuildError::InvalidPath(_))
+    ));
+    assert!(matches!(
+        builder.add_file("", UpdateKind::Bytes(vec![1])),
+        Err(BuildError::InvalidPath(_))
+    ));
+    assert!(matches!(
+        builder.add_file("名前.bin", UpdateKind::Bytes(vec![1])),
+        Err(BuildError::InvalidName(_))
+    ));
+}
+
+#[test]
+fn build_with_uncompressed_files() {
+    let data = b"some text that is repeated, some text that is repeated".repeat(16);
+
+    for game in [Game::Obscure1, Game::Obscure2, Game::FinalExam] {
+        let mut builder = ArchiveBuilder::new(game);
+        builder
+            .add_file("data/compressed.bin", UpdateKind::Bytes(data.clone()))
+            .unwrap();
+        builder
+            .add_file_uncompressed("data/stored.bin", UpdateKind::Bytes(data.clone()))
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_uncompressed_{game:?}.hvp"));
+        let mut writer = Cursor::new(Vec::new());
+        builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+        std::fs::write(&path, writer.into_inner()).unwrap();
+
+        let provider = ArchiveProvider::new(File::open(&path).unwrap(), Some(game))
+            .expect("failed to load built archive");
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names: Obscure2NameMap::new(["data", "compressed.bin", "stored.bin"]),
+                ..Default::default()
+            },
+        );
+
+        let compressed = archive.find_file("data/compressed.bin").unwrap();
+        let stored = archive.find_file("data/stored.bin").unwrap();
+        assert!(compressed.is_compressed(), "{game:?}");
+        assert!(!stored.is_compressed(), "{game:?}");
+        assert_eq!(*stored.get_bytes().unwrap(), data, "{game:?}");
+        assert_eq!(*compressed.get_bytes().unwrap(), data, "{game:?}");
+
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[test]
+fn build_with_compression_rules() {
+    let data = b"some text that is repeated, some text that is repeated".repeat(16);
+    let names = [
+        "music", "menu", "sound", "textures", "a.wav", "b.wav", "c.wav", "d.tex", "e.wav", "f.tex",
+    ];
+
+    for game in [Game::Obscure1, Game::Obscure2, Game::FinalExam] {
+        let path = std::env::temp_dir().join(format!("hvp_builder_rules_{game:?}.hvp"));
+        let build = |builder: ArchiveBuilder| {
+            let mut writer = Cursor::new(Vec::new());
+            builder
+                .build(&mut writer, EmptyProgress)
+                .expect("failed to build archive");
+            std::fs::write(&path, writer.into_inner()).unwrap();
+            ArchiveProvider::new(File::open(&path).unwrap(), Some(game))
+                .expect("failed to load built archive")
+        };
+        let open = |provider| {
+            Archive::new_with_options(
+                provider,
+                Options {
+                    obscure2_names: Obscure2NameMap::new(names),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let mut rules = CompressionRules::new();
+        rules.set("music", false);
+        rules.set("music/menu", true);
+        rules.set("sound", false);
+
+        let mut builder = ArchiveBuilder::new(game).compression_rules(rules);
+        for path in [
+            "music/a.wav",
+            "music/menu/b.wav",
+            "sound/c.wav",
+            "textures/d.tex",
+        ] {
+            builder
+                .add_file(path, UpdateKind::Bytes(data.clone()))
+                .unwrap();
+        }
+        let provider = build(builder);
+        let archive = open(&provider);
+        let compressed = |path| archive.find_file(path).unwrap().is_compressed();
+
+        // the deepest rule win
+        assert!(!compressed("music/a.wav"), "{game:?}");
+        assert!(compressed("music/menu/b.wav"), "{game:?}");
+        assert!(!compressed("sound/c.wav"), "{game:?}");
+        assert!(compressed("textures/d.tex"), "{game:?}");
+
+        // only the directories whose files are all stored get a rule
+        let rules = CompressionRules::from_archive(&archive);
+        assert_eq!(
+            rules.iter().collect::<Vec<_>>(),
+            [(Path::new("sound"), false)],
+            "{game:?}"
+        );
+
+        // so the new files of a rebuilt archive keep the layout
+        let mut builder = ArchiveBuilder::from_archive(&archive).unwrap();
+        for path in ["sound/e.wav", "textures/f.tex"] {
+            builder
+                .add_file(path, UpdateKind::Bytes(data.clone()))
+                .unwrap();
+        }
+        let provider = build(builder);
+        let archive = open(&provider);
+        assert!(!archive.find_file("sound/e.wav").unwrap().is_compressed());
+        assert!(archive.find_file("textures/f.tex").unwrap().is_compressed());
+
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[test]
+fn build_with_compression_anomalies() {
+    let text = b"some text that is repeated, some text that is repeated".repeat(16);
+    // xorshift bytes, zlib and lzo can't make them smaller
+    let mut state = 0x2545_f491_u32;
+    let noise: Vec<u8> = (0..text.len())
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+
+    let mut names = vec![
+        "data".to_owned(),
+        "empty.txt".to_owned(),
+        "noise.txt".to_owned(),
+    ];
+    names.extend((0..8).map(|i| format!("text{i}.txt")));
+
+    for game in [Game::Obscure1, Game::Obscure2, Game::FinalExam] {
+        let mut builder = ArchiveBuilder::new(game);
+        for name in names.iter().filter(|n| n.starts_with("text")) {
+            builder
+                .add_file(format!("data/{name}"), UpdateKind::Bytes(text.clone()))
+                .unwrap();
+        }
+        builder
+            .add_file("data/noise.txt", UpdateKind::Bytes(noise.clone()))
+            .unwrap();
+        builder
+            .add_file("data/empty.txt", UpdateKind::Bytes(Vec::new()))
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!("hvp_builder_anomalies_{game:?}.hvp"));
+        let mut writer = Cursor::new(Vec::new());
+        builder
+            .build(&mut writer, EmptyProgress)
+            .expect("failed to build archive");
+        std::fs::write(&path, writer.into_inner()).unwrap();
+
+        let provider = ArchiveProvider::new(File::open(&path).unwrap(), Some(game))
+            .expect("failed to load built archive");
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names: Obscure2NameMap::new(names.clone()),
+                ..Default::default()
+            },
+        );
+
+        let report = archive.analyze();
+        assert_eq!(report.files_checked, 10, "{game:?}");
+
+        let noise_path = PathBuf::from("data/noise.txt");
+        let mut kinds: Vec<_> = report
+            .anomalies
+            .iter()
+            .map(|anomaly| match anomaly {
+                Anomaly::CompressedLarger { path, .. } => (path, "larger"),
+                Anomaly::ZeroSize { path, .. } => (path, "zero"),
+                Anomaly::RatioOutlier { path, .. } => (path, "ratio"),
+            })
+            .collect();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            [(&noise_path, "larger"), (&noise_path, "ratio")],
+            "{game:?}"
+        );
+
+        // anomalies don't fail the cross check
+        let cross = archive.cross_check();
+        assert!(cross.is_ok(), "{game:?}");
+        assert_eq!(cross.anomalies, report.anomalies, "{game:?}");
+
+        let _ = std::fs::remove_file(path);
+    }
+}
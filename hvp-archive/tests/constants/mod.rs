@@ -1,6 +0,0 @@
-#![allow(unused)]
-
-pub const OBSCURE1_HVP: &str = "tests/resources/obscure1.hvp";
-pub const OBSCURE2_HVP: &str = "tests/resources/obscure2.hvp";
-pub const OBSCURE2_WII_HVP: &str = "tests/resources/obscure2_wii.hvp";
-pub const FINAL_EXAM_HVP: &str = "tests/resources/final_exam_pc.hvp";
@@ -5,14 +5,14 @@ use std::{
 
 use hvp_archive::{
     Game,
-    archive::{Archive, Metadata, rebuild_progress::RebuildProgress},
+    archive::{Archive, rebuild_progress::RebuildProgress},
     provider::ArchiveProvider,
 };
 
-mod constants;
+use hvp_test_fixtures as fixtures;
 
 fn load() -> ArchiveProvider {
-    let file = File::open(constants::FINAL_EXAM_HVP).expect("failed to open file");
+    let file = File::open(fixtures::FINAL_EXAM.path).expect("failed to open file");
     ArchiveProvider::new(file, Some(Game::FinalExam))
         .expect("failed to load hvp archive using provider")
 }
@@ -24,13 +24,10 @@ fn load_and_check_final_exam() {
 
     // check archive metadata
 
+    let metadata = archive.metadata();
     assert_eq!(
-        archive.metadata(),
-        Metadata {
-            dir_count: 4,
-            file_count: 13,
-            game: Game::FinalExam
-        },
+        (metadata.dir_count, metadata.file_count, metadata.game),
+        (4, 13, Game::FinalExam),
         "archive metadata doesn't match with the expected metadata"
     );
 
@@ -47,7 +44,7 @@ fn rebuild_final_exam() {
     let provider = load();
     let archive = Archive::new(&provider);
 
-    let org_archive = std::fs::read(constants::FINAL_EXAM_HVP).expect("failed to open file");
+    let org_archive = std::fs::read(fixtures::FINAL_EXAM.path).expect("failed to open file");
     let mut writer = Cursor::new(Vec::with_capacity(org_archive.len()));
     archive
         .rebuild(&mut writer, EmptyProgress)
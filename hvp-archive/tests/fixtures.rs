@@ -0,0 +1,73 @@
+use std::io::Cursor;
+
+use hvp_archive::archive::{Archive, Endianness};
+use hvp_test_fixtures::Format;
+
+mod common;
+
+use common::{EmptyProgress, for_each_fixture, game};
+
+#[test]
+fn fixtures_match_expected_metadata() {
+    for_each_fixture(|fixture, provider| {
+        let metadata = Archive::new(provider).metadata();
+
+        assert_eq!(metadata.game, game(fixture.format));
+        assert_eq!(metadata.dir_count, fixture.dir_count);
+        assert_eq!(metadata.file_count, fixture.file_count);
+
+        assert_eq!(metadata.archive_len, fixture.bytes.len() as u64);
+        assert_eq!(metadata.toc_size, provider.toc_len());
+        assert!(metadata.toc_size + metadata.data_size <= metadata.archive_len);
+        assert!(metadata.has_checksums);
+
+        // obscure 1 archives are always big endian
+        let endianness = match fixture.big_endian || fixture.format == Format::Obscure1 {
+            true => Endianness::Big,
+            false => Endianness::Little,
+        };
+        assert_eq!(metadata.endianness, endianness);
+    });
+}
+
+#[test]
+fn fixtures_rebuild_match_expected_crc32() {
+    for_each_fixture(|fixture, provider| {
+        assert_eq!(
+            crc32fast::hash(fixture.bytes),
+            fixture.archive_crc32,
+            "bytes doesn't match the expected crc32"
+        );
+
+        let mut writer = Cursor::new(Vec::with_capacity(fixture.bytes.len()));
+        Archive::new(provider)
+            .rebuild(&mut writer, EmptyProgress)
+            .expect("failed to rebuild archive");
+
+        assert_eq!(
+            crc32fast::hash(&writer.into_inner()),
+            fixture.archive_crc32,
+            "rebuild doesn't match the expected crc32"
+        );
+    });
+}
+
+#[cfg(feature = "raw_structure")]
+#[test]
+fn fixtures_match_expected_toc() {
+    use hvp_archive::provider::RawArchive;
+
+    for_each_fixture(|fixture, provider| {
+        let toc = match provider.raw_archive() {
+            RawArchive::Obscure1(archive) => serde_json::to_value(&archive.entries),
+            RawArchive::Obscure2(archive) => serde_json::to_value(&archive.entries),
+            RawArchive::FinalExam(archive) => serde_json::to_value(&archive.entries),
+        }
+        .expect("failed to serialize entries");
+
+        let expected: serde_json::Value =
+            serde_json::from_str(fixture.toc_json).expect("failed to parse expected toc");
+
+        assert_eq!(toc, expected, "toc doesn't match");
+    });
+}
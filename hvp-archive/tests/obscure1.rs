@@ -5,14 +5,14 @@ use std::{
 
 use hvp_archive::{
     Game,
-    archive::{Archive, Metadata, rebuild_progress::RebuildProgress},
+    archive::{Archive, diff::DiffEntry, entry::UpdateKind, rebuild_progress::RebuildProgress},
     provider::ArchiveProvider,
 };
 
-mod constants;
+use hvp_test_fixtures as fixtures;
 
 fn load() -> ArchiveProvider {
-    let file = File::open(constants::OBSCURE1_HVP).expect("failed to open file");
+    let file = File::open(fixtures::OBSCURE1.path).expect("failed to open file");
     ArchiveProvider::new(file, Some(Game::Obscure1))
         .expect("failed to load hvp archive using provider")
 }
@@ -24,13 +24,10 @@ fn load_and_check_obscure1() {
 
     // check archive metadata
 
+    let metadata = archive.metadata();
     assert_eq!(
-        archive.metadata(),
-        Metadata {
-            dir_count: 34,
-            file_count: 284,
-            game: Game::Obscure1
-        },
+        (metadata.dir_count, metadata.file_count, metadata.game),
+        (34, 284, Game::Obscure1),
         "archive metadata doesn't match with the expected metadata"
     );
 
@@ -47,7 +44,7 @@ fn rebuild_obscure1() {
     let provider = load();
     let archive = Archive::new(&provider);
 
-    let org_archive = std::fs::read(constants::OBSCURE1_HVP).expect("failed to open file");
+    let org_archive = std::fs::read(fixtures::OBSCURE1.path).expect("failed to open file");
     let mut writer = Cursor::new(Vec::with_capacity(org_archive.len()));
     archive
         .rebuild(&mut writer, EmptyProgress)
@@ -62,6 +59,90 @@ fn rebuild_obscure1() {
     );
 }
 
+#[test]
+fn diff_obscure1() {
+    let provider = load();
+    let mut archive = Archive::new(&provider);
+
+    assert!(
+        archive.diff(&archive).is_empty(),
+        "archive shouldn't have any difference with itself"
+    );
+
+    // update one file and compare the rebuilt archive with the original one
+    let path = {
+        let mut entry = archive
+            .files_mut()
+            .nth(3)
+            .expect("archive doesn't have enough files");
+        entry.update(UpdateKind::Bytes(b"updated content".to_vec()));
+        entry.path.clone()
+    };
+
+    let rebuild_path = std::env::temp_dir().join("hvp_diff_obscure1.hvp");
+    let mut writer = File::create(&rebuild_path).expect("failed to create file");
+    archive
+        .rebuild(&mut writer, EmptyProgress)
+        .expect("failed to rebuild archive");
+    drop(writer);
+
+    let rebuild_provider = ArchiveProvider::new(
+        File::open(&rebuild_path).expect("failed to open file"),
+        Some(Game::Obscure1),
+    )
+    .expect("failed to load rebuilt archive");
+    let rebuild_archive = Archive::new(&rebuild_provider);
+
+    let diff = Archive::new(&provider).diff(&rebuild_archive);
+
+    assert_eq!(diff.entries.len(), 1, "expected exactly one changed file");
+    assert!(
+        matches!(&diff.entries[0], DiffEntry::Changed { new, .. } if new.path == path && new.uncompressed_size == 15),
+        "unexpected diff entry: {:?}",
+        diff.entries[0]
+    );
+    assert_eq!(diff.unchanged, fixtures::OBSCURE1.file_count - 1);
+
+    drop(rebuild_archive);
+    drop(rebuild_provider);
+    let _ = std::fs::remove_file(rebuild_path);
+}
+
+#[test]
+fn verify_obscure1_corrupted_header_crc() {
+    // the crc32 of the header is stored right after the 32 bytes of the header
+    let mut bytes = fixtures::OBSCURE1.bytes.to_vec();
+    bytes[32] ^= 0xFF;
+
+    let path = std::env::temp_dir().join("hvp_verify_obscure1_corrupted_header.hvp");
+    std::fs::write(&path, &bytes).expect("failed to write corrupted archive");
+
+    let file = File::open(&path).expect("failed to open file");
+    let provider = ArchiveProvider::new(file, Some(Game::Obscure1))
+        .expect("failed to load hvp archive using provider");
+    let archive = Archive::new(&provider);
+
+    let stored = archive.stored_header_crcs().expect("archive have crc32s");
+    let expected = archive.expected_header_crcs();
+
+    assert_ne!(
+        stored.header, expected.header,
+        "header crc32 should mismatch"
+    );
+    assert_eq!(
+        stored.entries, expected.entries,
+        "entries crc32 should match"
+    );
+
+    let report = archive.verify();
+    assert!(!report.is_ok(), "corrupted archive passed verification");
+    assert!(report.mismatches.is_empty(), "file checksums should match");
+
+    drop(archive);
+    drop(provider);
+    let _ = std::fs::remove_file(path);
+}
+
 struct EmptyProgress;
 
 impl RebuildProgress for EmptyProgress {
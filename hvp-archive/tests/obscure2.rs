@@ -5,20 +5,22 @@ use std::{
 
 use hvp_archive::{
     Game,
-    archive::{Archive, Metadata, rebuild_progress::RebuildProgress},
-    provider::ArchiveProvider,
+    archive::{
+        Archive, Obscure2NameMap, Options, deps::DependencyScan, rebuild_progress::RebuildProgress,
+    },
+    provider::{ArchiveProvider, ProviderError},
 };
 
-mod constants;
+use hvp_test_fixtures as fixtures;
 
 fn load() -> ArchiveProvider {
-    let file = File::open(constants::OBSCURE2_HVP).expect("failed to open file");
+    let file = File::open(fixtures::OBSCURE2.path).expect("failed to open file");
     ArchiveProvider::new(file, Some(Game::Obscure2))
         .expect("failed to load hvp archive using provider")
 }
 
 fn load_wii() -> ArchiveProvider {
-    let file = File::open(constants::OBSCURE2_WII_HVP).expect("failed to open file");
+    let file = File::open(fixtures::OBSCURE2_WII.path).expect("failed to open file");
     ArchiveProvider::new(file, Some(Game::Obscure2))
         .expect("failed to load hvp archive using provider")
 }
@@ -30,13 +32,10 @@ fn load_and_check_obscure2() {
 
     // check archive metadata
 
+    let metadata = archive.metadata();
     assert_eq!(
-        archive.metadata(),
-        Metadata {
-            dir_count: 55,
-            file_count: 478,
-            game: Game::Obscure2
-        },
+        (metadata.dir_count, metadata.file_count, metadata.game),
+        (55, 478, Game::Obscure2),
         "archive metadata doesn't match with the expected metadata"
     );
 
@@ -55,7 +54,7 @@ fn rebuild_obscure2() {
 
     // rebuild the archive as is without any changes
 
-    let org_archive = std::fs::read(constants::OBSCURE2_HVP).expect("failed to open file");
+    let org_archive = std::fs::read(fixtures::OBSCURE2.path).expect("failed to open file");
     let mut writer = Cursor::new(Vec::with_capacity(org_archive.len()));
     archive
         .rebuild(&mut writer, EmptyProgress)
@@ -77,13 +76,10 @@ fn load_and_check_obscure2_wii() {
 
     // check archive metadata
 
+    let metadata = archive.metadata();
     assert_eq!(
-        archive.metadata(),
-        Metadata {
-            dir_count: 12,
-            file_count: 83,
-            game: Game::Obscure2
-        },
+        (metadata.dir_count, metadata.file_count, metadata.game),
+        (12, 83, Game::Obscure2),
         "archive metadata doesn't match with the expected metadata"
     );
 
@@ -102,7 +98,7 @@ fn rebuild_obscure2_wii() {
 
     // rebuild the archive as is without any changes
 
-    let org_archive = std::fs::read(constants::OBSCURE2_WII_HVP).expect("failed to open file");
+    let org_archive = std::fs::read(fixtures::OBSCURE2_WII.path).expect("failed to open file");
     let mut writer = Cursor::new(Vec::with_capacity(org_archive.len()));
     archive
         .rebuild(&mut writer, EmptyProgress)
@@ -117,9 +113,300 @@ fn rebuild_obscure2_wii() {
     );
 }
 
+#[test]
+fn verify_obscure2_corrupted_entry() {
+    // flip the last byte of the archive, which belongs to the data of the last file
+    let mut bytes = fixtures::OBSCURE2.bytes.to_vec();
+    *bytes.last_mut().unwrap() ^= 0xFF;
+
+    let path = std::env::temp_dir().join("hvp_verify_obscure2_corrupted.hvp");
+    std::fs::write(&path, &bytes).expect("failed to write corrupted archive");
+
+    let file = File::open(&path).expect("failed to open file");
+    let provider = ArchiveProvider::new(file, Some(Game::Obscure2))
+        .expect("failed to load hvp archive using provider");
+    let archive = Archive::new(&provider);
+
+    let report = archive.verify();
+
+    assert!(!report.is_ok(), "corrupted archive passed verification");
+    assert!(
+        report.header_crcs.iter().all(|c| c.is_match()),
+        "header crc32 shouldn't be affected by data corruption"
+    );
+    assert_eq!(report.mismatches.len(), 1, "expected exactly one mismatch");
+    assert_ne!(report.mismatches[0].expected, report.mismatches[0].computed);
+
+    drop(archive);
+    drop(provider);
+    let _ = std::fs::remove_file(path);
+}
+
+/// patch the directory entries in the table of an obscure 2 fixture,
+/// the entries crc32 in the header is updated so the archive still loads
+struct TablePatcher {
+    bytes: Vec<u8>,
+    big_endian: bool,
+}
+
+impl TablePatcher {
+    const ENTRY_SIZE: usize = 24;
+    const DIR_COUNT: usize = 16;
+    const DIR_INDEX: usize = 20;
+
+    fn new(fixture: &fixtures::Fixture) -> Self {
+        Self {
+            bytes: fixture.bytes.to_vec(),
+            big_endian: fixture.big_endian,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.read(8) as usize
+    }
+
+    fn is_dir(&self, index: usize) -> bool {
+        let at = Self::field(index, 4);
+        let b = [self.bytes[at], self.bytes[at + 1]];
+        let kind = match self.big_endian {
+            true => u16::from_be_bytes(b),
+            false => u16::from_le_bytes(b),
+        };
+        kind == 4
+    }
+
+    fn field(index: usize, offset: usize) -> usize {
+        16 + index * Self::ENTRY_SIZE + offset
+    }
+
+    fn read(&self, at: usize) -> u32 {
+        let b: [u8; 4] = self.bytes[at..at + 4].try_into().unwrap();
+        match self.big_endian {
+            true => u32::from_be_bytes(b),
+            false => u32::from_le_bytes(b),
+        }
+    }
+
+    fn write(&mut self, at: usize, value: u32) {
+        let b = match self.big_endian {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        };
+        self.bytes[at..at + 4].copy_from_slice(&b);
+    }
+
+    fn dir_count(&self, index: usize) -> u32 {
+        self.read(Self::field(index, Self::DIR_COUNT))
+    }
+
+    fn set_dir_count(&mut self, index: usize, count: u32) {
+        self.write(Self::field(index, Self::DIR_COUNT), count);
+    }
+
+    fn set_dir_index(&mut self, index: usize, start: u32) {
+        self.write(Self::field(index, Self::DIR_INDEX), start);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let entries_len = self.len() * Self::ENTRY_SIZE;
+        let crc32 = crc32fast::hash(&self.bytes[16..16 + entries_len]);
+        self.write(12, crc32);
+        self.bytes
+    }
+}
+
+/// detach the last child of the root directory by lowering the root count
+fn detach_last_root_entry(fixture: &fixtures::Fixture) -> Vec<u8> {
+    let mut patcher = TablePatcher::new(fixture);
+    let root_count = patcher.dir_count(0);
+    assert!(root_count > 1, "{} root only have one entry", fixture.name);
+    patcher.set_dir_count(0, root_count - 1);
+    patcher.finish()
+}
+
+fn load_bytes(name: &str, bytes: &[u8]) -> Result<ArchiveProvider, ProviderError> {
+    let path = std::env::temp_dir().join(format!("hvp_{name}.hvp"));
+    std::fs::write(&path, bytes).unwrap();
+    let provider = ArchiveProvider::new(File::open(&path).unwrap(), None);
+    let _ = std::fs::remove_file(path);
+    provider
+}
+
+#[test]
+fn invalid_dir_ranges_obscure2() {
+    for fixture in [fixtures::OBSCURE2, fixtures::OBSCURE2_WII] {
+        let name = fixture.name;
+        let len = TablePatcher::new(&fixture).len();
+
+        // root range that goes past the table
+        let mut patcher = TablePatcher::new(&fixture);
+        patcher.set_dir_count(0, len as u32);
+        let result = load_bytes(&format!("{name}_out_of_bounds"), &patcher.finish());
+        assert!(
+            matches!(result, Err(ProviderError::DirRangeOutOfBounds { index: 0, range, len: l }) if range == (1..len + 1) && l == len),
+            "{name}"
+        );
+
+        // first sub directory that point back to the root
+        let mut patcher = TablePatcher::new(&fixture);
+        let dir = (1..len).find(|&i| patcher.is_dir(i)).unwrap();
+        patcher.set_dir_index(dir, 0);
+        let result = load_bytes(&format!("{name}_include_root"), &patcher.finish());
+        assert!(
+            matches!(result, Err(ProviderError::DirRangeIncludeRoot { index, .. }) if index == dir),
+            "{name}"
+        );
+
+        // root range that grab the first child of another directory
+        let mut patcher = TablePatcher::new(&fixture);
+        let root_count = patcher.dir_count(0);
+        patcher.set_dir_count(0, root_count + 1);
+        let result = load_bytes(&format!("{name}_overlap"), &patcher.finish());
+        assert!(
+            matches!(result, Err(ProviderError::DirRangeOverlap { other: 0, .. })),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn orphans_obscure2_drop_and_attach() {
+    use hvp_archive::archive::{
+        ORPHANS_DIR, Options, OrphanMode, entry::Entry, raw_index::RawIndex,
+    };
+
+    for fixture in [fixtures::OBSCURE2, fixtures::OBSCURE2_WII] {
+        let provider = ArchiveProvider::new(File::open(fixture.path).unwrap(), None).unwrap();
+        let original = Archive::new(&provider);
+        assert!(original.orphans().is_empty(), "{}", fixture.name);
+
+        let path = std::env::temp_dir().join(format!("hvp_orphans_{}.hvp", fixture.name));
+        std::fs::write(&path, detach_last_root_entry(&fixture)).unwrap();
+        let corrupted = ArchiveProvider::new(File::open(&path).unwrap(), None)
+            .expect("failed to load archive with orphan entries");
+
+        let root_count = original.entries().len();
+
+        let dropped = Archive::new(&corrupted);
+        assert!(!dropped.orphans().is_empty(), "{}", fixture.name);
+        assert_eq!(dropped.entries().len(), root_count - 1, "{}", fixture.name);
+        assert!(!dropped.cross_check().is_ok(), "{}", fixture.name);
+
+        let attached = Archive::new_with_options(
+            &corrupted,
+            Options {
+                orphans: OrphanMode::Attach,
+                ..Default::default()
+            },
+        );
+        assert_eq!(attached.orphans(), dropped.orphans(), "{}", fixture.name);
+        assert_eq!(
+            attached.metadata().file_count,
+            original.metadata().file_count,
+            "{}",
+            fixture.name
+        );
+
+        let Some(Entry::Dir(orphans)) = attached.entries().last() else {
+            panic!("{}: orphans aren't attached", fixture.name);
+        };
+        assert_eq!(orphans.name, ORPHANS_DIR);
+        assert_eq!(orphans.entries.len(), 1, "{}", fixture.name);
+
+        let orphan_files: Vec<_> = attached
+            .files()
+            .filter(|f| f.path.starts_with(ORPHANS_DIR))
+            .collect();
+        assert!(!orphan_files.is_empty(), "{}", fixture.name);
+
+        // the attached entries keep their index in the table, the virtual directory has none
+        for (path, index) in attached.raw_index_map() {
+            assert_ne!(path.as_os_str(), ORPHANS_DIR, "{}", fixture.name);
+            if path.starts_with(ORPHANS_DIR) && path.components().count() == 2 {
+                let RawIndex::Flat(index) = index else {
+                    panic!("{}: expected a flat index", fixture.name);
+                };
+                assert!(attached.orphans().contains(&index), "{}", fixture.name);
+            }
+        }
+
+        for file in orphan_files {
+            let original_path = file.path.strip_prefix(ORPHANS_DIR).unwrap();
+            let original_file = original.find_file(original_path).unwrap();
+            assert_eq!(file.raw_bytes, original_file.raw_bytes, "{}", fixture.name);
+        }
+
+        drop(dropped);
+        drop(attached);
+        drop(corrupted);
+        let _ = std::fs::remove_file(path);
+    }
+}
+
 struct EmptyProgress;
 
 impl RebuildProgress for EmptyProgress {
     fn inc(&self, _: Option<String>) {}
     fn inc_n(&self, _: usize, _: Option<String>) {}
 }
+
+#[test]
+fn name_crc32_obscure2() {
+    assert_eq!(Obscure2NameMap::name_crc32("o2.db"), Some(0x179ce296));
+    // 'é' is hashed using its windows-1250 byte
+    assert_eq!(
+        Obscure2NameMap::name_crc32("menué.tga"),
+        Some(crc32fast::hash(b"menu\xE9.tga"))
+    );
+    assert_eq!(Obscure2NameMap::name_crc32("menü.tga"), None);
+}
+
+#[test]
+fn dependencies_obscure2() {
+    let provider = load();
+    let archive = Archive::new_with_options(
+        &provider,
+        Options {
+            obscure2_names: Obscure2NameMap::new([
+                "characters",
+                "cbox.xmc",
+                "shom",
+                "shom.cbe",
+                "ogre",
+                "ogre.cbe",
+            ]),
+            ..Default::default()
+        },
+    );
+
+    let graph = archive.dependencies(DependencyScan::Scripts);
+    assert!(graph.files_scanned > 0);
+    assert!(graph.failed.is_empty());
+
+    // `cbox.xmc` reference `shom\shom.cbe`, relative to its own directory
+    let references: Vec<_> = graph.references("characters/cbox.xmc").collect();
+    for expected in ["characters/shom/shom.cbe", "characters/ogre/ogre.cbe"] {
+        assert!(
+            references
+                .iter()
+                .any(|p| *p == std::path::Path::new(expected)),
+            "{expected} isn't referenced by cbox.xmc"
+        );
+    }
+
+    assert!(
+        graph
+            .referenced_by("characters/shom/shom.cbe")
+            .any(|p| p == std::path::Path::new("characters/cbox.xmc"))
+    );
+    assert!(
+        graph
+            .transitive("characters/shom/shom.cbe", true)
+            .contains(std::path::Path::new("characters/cbox.xmc"))
+    );
+    assert!(
+        !graph
+            .transitive("characters/cbox.xmc", false)
+            .contains(std::path::Path::new("characters/cbox.xmc"))
+    );
+}
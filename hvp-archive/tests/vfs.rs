@@ -0,0 +1,63 @@
+#![cfg(feature = "vfs")]
+
+use std::io::Read;
+
+use hvp_archive::{archive::Archive, vfs::HvpFs};
+use vfs::{VfsFileType, VfsPath};
+
+mod common;
+
+use common::{for_each_fixture, load};
+
+#[test]
+fn fixtures_read_through_vfs() {
+    for_each_fixture(|fixture, provider| {
+        let archive = Archive::new(provider);
+        let root = VfsPath::new(HvpFs::new(load(fixture)));
+
+        let walked: Vec<VfsPath> = root
+            .walk_dir()
+            .expect("failed to walk the root")
+            .collect::<Result<_, _>>()
+            .expect("failed to walk the archive");
+        let dirs = walked.iter().filter(|p| p.is_dir().unwrap()).count();
+        assert_eq!(
+            (dirs, walked.len() - dirs),
+            (fixture.dir_count, fixture.file_count),
+            "entries that are visible through vfs don't match"
+        );
+
+        for file in archive.files() {
+            let path = root
+                .join(file.path.to_str().unwrap().replace('\\', "/"))
+                .unwrap();
+            let metadata = path.metadata().expect("failed to get file metadata");
+            assert_eq!(metadata.file_type, VfsFileType::File);
+            assert_eq!(metadata.len, file.uncompressed_size() as u64);
+
+            let mut content = Vec::new();
+            path.open_file()
+                .expect("failed to open file")
+                .read_to_end(&mut content)
+                .unwrap();
+            assert!(
+                content == *file.get_bytes().unwrap(),
+                "content of {} doesn't match",
+                file.path.display()
+            );
+        }
+    });
+}
+
+#[test]
+fn vfs_is_read_only() {
+    let root = VfsPath::new(HvpFs::new(load(&hvp_test_fixtures::OBSCURE1)));
+    let first = root.read_dir().unwrap().next().unwrap();
+
+    assert!(!root.join("missing.txt").unwrap().exists().unwrap());
+    assert!(root.join("missing.txt").unwrap().open_file().is_err());
+    assert!(root.join("new.txt").unwrap().create_file().is_err());
+    assert!(root.join("new").unwrap().create_dir().is_err());
+    assert!(first.remove_file().is_err() && first.remove_dir().is_err());
+    assert!(first.exists().unwrap());
+}
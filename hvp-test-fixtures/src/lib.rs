@@ -0,0 +1,69 @@
+//! conformance test vectors for hvp archives
+//!
+//! this crate ship the same small archives that `hvp-archive` use in its own tests,
+//! together with the expected TOC (as json) and a few expected values about each archive.
+//! other implementations (C#, Python, ...) can use the files inside `resources` and
+//! `expected` directories directly, rust code can use the constants below.
+
+/// format of a fixture archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Obscure1,
+    Obscure2,
+    FinalExam,
+}
+
+/// a single test vector
+#[derive(Debug, Clone, Copy)]
+pub struct Fixture {
+    /// short name of the fixture, same as file stem of the archive
+    pub name: &'static str,
+    /// format of the archive
+    pub format: Format,
+    /// whatever the archive use big endian layout or not
+    pub big_endian: bool,
+    /// absolute path to the archive on disk
+    pub path: &'static str,
+    /// raw bytes of the archive
+    pub bytes: &'static [u8],
+    /// expected raw entries of the archive, serialized as json
+    ///
+    /// this is the same output that `obscure-hvp dump` generate
+    pub toc_json: &'static str,
+    /// expected number of directories (without the root directory of obscure 2 and final exam)
+    pub dir_count: usize,
+    /// expected number of files
+    pub file_count: usize,
+    /// crc32 of the whole archive, a rebuild without any change should produce the same value
+    pub archive_crc32: u32,
+}
+
+macro_rules! fixture {
+    ($name:literal, $format:expr, $big_endian:expr, $dir_count:expr, $file_count:expr, $crc32:expr) => {
+        Fixture {
+            name: $name,
+            format: $format,
+            big_endian: $big_endian,
+            path: concat!(env!("CARGO_MANIFEST_DIR"), "/resources/", $name, ".hvp"),
+            bytes: include_bytes!(concat!("../resources/", $name, ".hvp")),
+            toc_json: include_str!(concat!("../expected/", $name, ".json")),
+            dir_count: $dir_count,
+            file_count: $file_count,
+            archive_crc32: $crc32,
+        }
+    };
+}
+
+/// obscure 1 (PC) archive
+pub const OBSCURE1: Fixture = fixture!("obscure1", Format::Obscure1, false, 34, 284, 0x0EDC625D);
+/// obscure 2 (PC) archive
+pub const OBSCURE2: Fixture = fixture!("obscure2", Format::Obscure2, false, 55, 478, 0xFEE743C1);
+/// obscure 2 (Wii) archive, big endian
+pub const OBSCURE2_WII: Fixture =
+    fixture!("obscure2_wii", Format::Obscure2, true, 12, 83, 0x40F9CE5D);
+/// final exam (PC) archive
+pub const FINAL_EXAM: Fixture =
+    fixture!("final_exam_pc", Format::FinalExam, false, 4, 13, 0x8360F871);
+
+/// all the available fixtures
+pub const ALL: &[Fixture] = &[OBSCURE1, OBSCURE2, OBSCURE2_WII, FINAL_EXAM];
@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeSet,
+    ffi::OsStr,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Options, builder::ArchiveBuilder, entry::UpdateKind},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{is_sidecar, name_maps_for_game, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path to folder of the new files, paths inside the archive are relative to it
+    #[arg(value_hint = ValueHint::DirPath, value_parser = utils::is_dir)]
+    pub input_folder: PathBuf,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    pub output: Option<PathBuf>,
+    /// skip compression of the new files
+    #[arg(long, short = 'c', default_value_t = false, required = false)]
+    pub skip_compression: bool,
+    /// file with the directories whose new files are stored or compressed, they are added on
+    /// top of the directories that the archive already store without compression
+    #[arg(long, value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub compression_rules: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                rebuild_skip_compression: self.skip_compression,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let files: Vec<PathBuf> = utils::list_files(&self.input_folder, true)
+            .into_iter()
+            .filter(|p| !is_sidecar(p))
+            .collect();
+
+        if files.is_empty() {
+            anyhow::bail!("no file found in input folder")
+        }
+
+        println!(
+            "{} found {} files in input folder",
+            "[+]".green(),
+            files.len()
+        );
+
+        let mut builder =
+            ArchiveBuilder::from_archive(&archive).context("failed to read archive entries")?;
+        if let Some(path) = &self.compression_rules {
+            builder = builder.compression_rules(utils::load_compression_rules(path)?);
+        }
+
+        for path in &files {
+            if archive.find_file(path).is_some() {
+                anyhow::bail!(
+                    "{} already exists in the archive, use create command to replace it",
+                    path.display()
+                );
+            }
+
+            builder
+                .add_file(path, UpdateKind::File(self.input_folder.join(path)))
+                .with_context(|| format!("failed to add {} to the archive", path.display()))?;
+        }
+
+        if archive.metadata().game == Game::Obscure2 {
+            // obscure 2 only store the crc32 of names, so new names can't be read back without name maps
+            let unknown_names: BTreeSet<_> = files
+                .iter()
+                .flat_map(|p| p.iter())
+                .filter_map(OsStr::to_str)
+                .filter(|n| !archive.options.obscure2_names.contains_name(n))
+                .collect();
+
+            if !unknown_names.is_empty() {
+                println!(
+                    "{} these names are not in the name maps, add them to a txt file inside hashes directory to be able to see them after extraction: {}",
+                    "[!]".yellow(),
+                    unknown_names.into_iter().collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let metadata = builder.metadata();
+        let pb = utils::progress_bar(metadata.file_count as _);
+        let metadata = utils::watch_progress(&pb, |progress| builder.build(&mut writer, progress))
+            .context("failed to build the archive")?;
+
+        pb.finish_with_message(
+            "rebuild finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} added {} files, new archive has {} dirs and {} files",
+            "[+]".green(),
+            files.len(),
+            metadata.dir_count,
+            metadata.file_count,
+        );
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            metadata,
+            json!({ "added_files": files.len() }),
+        );
+
+        Ok(())
+    }
+}
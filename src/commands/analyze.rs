@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{
+        Archive, Options,
+        analyze::{Anomaly, DeadSpace},
+    },
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    size, utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        println!(
+            "{} analyzing compression of the files and dead space",
+            "[+]".green()
+        );
+
+        let report = archive.analyze();
+
+        for anomaly in &report.anomalies {
+            println!("{} {anomaly}", "[?]".yellow());
+        }
+
+        println!(
+            "{} analyzed {} files, found {} anomalies",
+            "[+]".green(),
+            report.files_checked,
+            report.anomalies.len(),
+        );
+
+        let (zero_filled, leftover): (Vec<_>, Vec<_>) = report
+            .dead_space
+            .iter()
+            .partition(|region| region.zero_filled == Some(true));
+        for region in &leftover {
+            println!(
+                "{} dead space at {:#x} ({}) isn't zero filled",
+                "[?]".yellow(),
+                region.offset,
+                size::format(region.len),
+            );
+        }
+
+        let total = |regions: &[&DeadSpace]| regions.iter().map(|r| r.len).sum::<u64>();
+        println!(
+            "{} dead space: {} zero filled regions ({}), {} regions with leftover data ({})",
+            "[+]".green(),
+            zero_filled.len(),
+            size::format(total(&zero_filled)),
+            leftover.len(),
+            size::format(total(&leftover)),
+        );
+
+        output::report(
+            "analyze",
+            json!({
+                "files_checked": report.files_checked,
+                "anomalies": report.anomalies.iter().map(anomaly_json).collect::<Vec<_>>(),
+                "dead_space": report.dead_space.iter().map(|region| json!({
+                    "offset": region.offset,
+                    "size": region.len,
+                    "zero_filled": region.zero_filled,
+                })).collect::<Vec<_>>(),
+            }),
+        );
+
+        Ok(())
+    }
+}
+
+fn anomaly_json(anomaly: &Anomaly) -> Value {
+    match anomaly {
+        Anomaly::CompressedLarger {
+            path,
+            stored,
+            uncompressed,
+        } => json!({
+            "kind": "CompressedLarger",
+            "path": path,
+            "stored_size": stored,
+            "uncompressed_size": uncompressed,
+        }),
+        Anomaly::ZeroSize {
+            path,
+            offset,
+            stored,
+            uncompressed,
+        } => json!({
+            "kind": "ZeroSize",
+            "path": path,
+            "offset": offset,
+            "stored_size": stored,
+            "uncompressed_size": uncompressed,
+        }),
+        Anomaly::RatioOutlier {
+            path,
+            extension,
+            ratio,
+            median,
+        } => json!({
+            "kind": "RatioOutlier",
+            "path": path,
+            "extension": extension,
+            "ratio": ratio,
+            "median": median,
+        }),
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options, patch_file::ArchivePatch},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{name_maps_for_game, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to the original hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path to the patch file that is made using make-patch
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub patch: PathBuf,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+    /// skip compression of the patched files
+    #[arg(long, short = 'c', default_value_t = false, required = false)]
+    pub skip_compression: bool,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let file = File::open(&self.patch).context("failed to open patch file")?;
+        let patch =
+            ArchivePatch::read(BufReader::new(file)).context("failed to read patch file")?;
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let mut archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                rebuild_skip_compression: self.skip_compression,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        println!(
+            "{} applying {} changed files from {}",
+            "[+]".green(),
+            patch.entries.len(),
+            self.patch.display()
+        );
+
+        let patched_files = patch
+            .apply(&mut archive)
+            .context("failed to apply patch, is this the original archive of the patch?")?;
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(archive.metadata().file_count as _);
+        utils::watch_progress(&pb, |progress| archive.rebuild(&mut writer, progress))
+            .context("failed to rebuild the archive")?;
+
+        pb.finish_with_message(
+            "rebuild finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!("{} patched {patched_files} files", "[+]".green());
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            archive.metadata(),
+            json!({ "patched_files": patched_files }),
+        );
+
+        Ok(())
+    }
+}
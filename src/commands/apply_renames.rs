@@ -0,0 +1,291 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Obscure2NameMap, Options, builder::ArchiveBuilder},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    HASHES_FILE, MANIFEST_FILE, hashes,
+    manifest::{Manifest, path_str},
+    name_maps_for_game,
+    output::println,
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// csv file with a `crc32,name` row per name, every file and directory whose name crc32 match
+    /// is renamed. the crc32 is the stored name crc32 for obscure 2 (the one in `unk_file_{crc32}.dat`),
+    /// and the crc32 of the name for the other games. it can be decimal or hex (`0x179ce296`)
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub renames: PathBuf,
+    /// folder that the archive was extracted to, its files, hashes.json and manifest.json are
+    /// renamed as well so it can still be used with `create`
+    #[arg(long, short = 'e', value_hint = ValueHint::DirPath, value_parser = utils::is_dir)]
+    pub extracted: Option<PathBuf>,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+/// new names by their crc32, empty lines and lines that start with `#` are ignored, and the
+/// first row is skipped if it's a header
+fn parse_csv(csv: &str) -> anyhow::Result<ahash::HashMap<u32, String>> {
+    let mut names = ahash::HashMap::default();
+    let mut first_row = true;
+
+    for (idx, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let header = std::mem::replace(&mut first_row, false);
+
+        let (crc32, name) = line
+            .split_once(',')
+            .with_context(|| format!("line {}: expected `crc32,name`", idx + 1))?;
+        let (crc32, name) = (crc32.trim(), name.trim().trim_matches('"'));
+
+        let crc32 = match crc32
+            .strip_prefix("0x")
+            .or_else(|| crc32.strip_prefix("0X"))
+        {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => crc32.parse().ok(),
+        };
+        let Some(crc32) = crc32 else {
+            // like `crc32,name`
+            if header {
+                continue;
+            }
+            anyhow::bail!("line {}: invalid crc32 in {line:?}", idx + 1);
+        };
+
+        match names.insert(crc32, name.to_owned()) {
+            Some(old) if old != name => {
+                anyhow::bail!(
+                    "line {}: crc32 {crc32:#010x} is both {old:?} and {name:?}",
+                    idx + 1
+                )
+            }
+            _ => (),
+        }
+    }
+
+    Ok(names)
+}
+
+/// new path of the entry, using the deepest renamed entry that contain it
+fn new_path(path: &Path, renamed: &ahash::HashMap<&Path, &Path>) -> PathBuf {
+    path.ancestors()
+        .find_map(|parent| {
+            let new = renamed.get(parent)?;
+            Some(new.join(path.strip_prefix(parent).expect("parent of the path")))
+        })
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let csv = std::fs::read_to_string(&self.renames).context("failed to read renames csv")?;
+        let names = parse_csv(&csv).context("failed to parse renames csv")?;
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let mut builder =
+            ArchiveBuilder::from_archive(&archive).context("failed to read archive entries")?;
+
+        let renamed = builder
+            .rename_by_crc32(&names)
+            .context("failed to rename entries")?;
+
+        if renamed.is_empty() {
+            println!(
+                "{} no entry has a name crc32 that is in the csv, nothing to rename",
+                "[!]".yellow()
+            );
+            return Ok(());
+        }
+
+        for (old, new) in &renamed {
+            println!("{} {} -> {}", "[+]".green(), old.display(), new.display());
+        }
+
+        if archive.metadata().game == Game::Obscure2 {
+            // obscure 2 only store the crc32 of names, so new names can't be read back without name maps
+            let mut unknown_names: Vec<_> = renamed
+                .iter()
+                .filter_map(|(_, new)| new.file_name().and_then(OsStr::to_str))
+                .filter(|n| !archive.options.obscure2_names.contains_name(n))
+                .collect();
+            unknown_names.sort_unstable();
+            unknown_names.dedup();
+
+            if !unknown_names.is_empty() {
+                println!(
+                    "{} these names are not in the name maps, add them to a txt file inside hashes directory to be able to see them after extraction: {}",
+                    "[!]".yellow(),
+                    unknown_names.join(", ")
+                );
+            }
+        }
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(builder.metadata().file_count as _);
+        let metadata = utils::watch_progress(&pb, |progress| builder.build(&mut writer, progress))
+            .context("failed to build the archive")?;
+
+        pb.finish_with_message(
+            "rebuild finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!("{} renamed {} entries", "[+]".green(), renamed.len());
+
+        let extracted = match &self.extracted {
+            Some(folder) => Some(
+                rename_extracted(&archive, folder, &renamed)
+                    .context("failed to rename the extracted files")?,
+            ),
+            None => None,
+        };
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            metadata,
+            json!({
+                "renamed": renamed
+                    .iter()
+                    .map(|(old, new)| json!({ "from": path_str(old), "to": path_str(new) }))
+                    .collect::<Vec<_>>(),
+                "extracted_renamed": extracted,
+            }),
+        );
+
+        Ok(())
+    }
+}
+
+/// rename the entries inside the extracted folder and move their hashes and manifest entries,
+/// return the number of renamed files and directories. entries that weren't extracted are skipped
+fn rename_extracted(
+    archive: &Archive,
+    folder: &Path,
+    renamed: &[(PathBuf, PathBuf)],
+) -> anyhow::Result<usize> {
+    // children are renamed before their parents, so their old parent still exists
+    let moves: Vec<_> = renamed
+        .iter()
+        .rev()
+        .map(|(old, new)| {
+            let name = new.file_name().expect("renamed entries have a name");
+            (folder.join(old), folder.join(old).with_file_name(name))
+        })
+        .filter(|(old, _)| old.exists())
+        .collect();
+
+    // check everything before renaming, so the folder isn't left half renamed
+    for (_, new) in &moves {
+        if new.exists() {
+            anyhow::bail!("{} already exists", new.display());
+        }
+    }
+
+    for (old, new) in &moves {
+        std::fs::rename(old, new)
+            .with_context(|| format!("failed to rename {} to {}", old.display(), new.display()))?;
+    }
+
+    let renamed_paths: ahash::HashMap<&Path, &Path> = renamed
+        .iter()
+        .map(|(old, new)| (old.as_path(), new.as_path()))
+        .collect();
+    let renamed_files: Vec<_> = archive
+        .files()
+        .map(|file| {
+            let new = new_path(&file.path, &renamed_paths);
+            (file.path, new)
+        })
+        .filter(|(old, new)| old != new)
+        .collect();
+
+    let hashes_file = folder.join(HASHES_FILE);
+    if hashes_file.is_file() {
+        let bytes = std::fs::read(&hashes_file).context("failed to read hashes.json")?;
+        let bytes = hashes::rename(&bytes, &renamed_files)
+            .context("failed to rename hashes.json entries")?;
+        std::fs::write(&hashes_file, bytes).context("failed to write hashes.json")?;
+    }
+
+    if let Some(mut manifest) = Manifest::load(&folder.join(MANIFEST_FILE))? {
+        for (old, new) in &renamed_files {
+            let Some(mut entry) = manifest.files.remove(old) else {
+                continue;
+            };
+            // the name crc32 is only recorded for obscure 2
+            if entry.name_crc32.is_some() {
+                entry.name_crc32 = new
+                    .file_name()
+                    .and_then(OsStr::to_str)
+                    .and_then(Obscure2NameMap::stored_crc32);
+            }
+            manifest.files.insert(new.clone(), entry);
+        }
+
+        let writer = BufWriter::new(
+            File::create(folder.join(MANIFEST_FILE))
+                .context("failed to create manifest.json file")?,
+        );
+        serde_json::to_writer_pretty(writer, &manifest.to_json())
+            .context("failed to serialize manifest")?;
+    }
+
+    println!(
+        "{} renamed {} entries inside {}",
+        "[+]".green(),
+        moves.len(),
+        folder.display()
+    );
+
+    Ok(moves.len())
+}
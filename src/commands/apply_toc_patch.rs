@@ -0,0 +1,154 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{
+        Archive, Options,
+        toc_patch::{TocEdit, TocPatch},
+    },
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+
+use super::{name_maps_for_game, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// json file with a list of edits, like `[{"path": "dir/file.bin", "compressed": false, "checksum": "0x1234abcd"}]`.
+    /// `checksum` and `uncompressed_size` can be numbers or hex strings
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub patch: PathBuf,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+/// a number that is written as a json number or a hex string
+fn number(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => {
+            let hex = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X"))?;
+            i64::from_str_radix(hex, 16).ok()
+        }
+        _ => None,
+    }
+}
+
+fn parse_patch(bytes: &[u8]) -> anyhow::Result<TocPatch> {
+    let value: Value = serde_json::from_slice(bytes).context("invalid json")?;
+
+    let edits = value
+        .as_array()
+        .context("expected a list of edits")?
+        .iter()
+        .map(|edit| {
+            let path = edit
+                .get("path")
+                .and_then(Value::as_str)
+                .context("edit without path")?;
+            // checksums are signed, but tools usually write them as unsigned
+            let field = |name: &str, min: i64| -> anyhow::Result<Option<u32>> {
+                edit.get(name)
+                    .filter(|v| !v.is_null())
+                    .map(|v| {
+                        number(v)
+                            .filter(|n| (min..=u32::MAX as i64).contains(n))
+                            .map(|n| n as u32)
+                            .with_context(|| format!("invalid {name} of {path}"))
+                    })
+                    .transpose()
+            };
+
+            Ok(TocEdit {
+                path: path.split('/').collect(),
+                compressed: match edit.get("compressed") {
+                    None | Some(Value::Null) => None,
+                    Some(v) => Some(
+                        v.as_bool()
+                            .with_context(|| format!("invalid compressed of {path}"))?,
+                    ),
+                },
+                checksum: field("checksum", i32::MIN as i64)?.map(|c| c as i32),
+                uncompressed_size: field("uncompressed_size", 0)?,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(TocPatch { edits })
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let bytes = std::fs::read(&self.patch).context("failed to read toc patch")?;
+        let patch = parse_patch(&bytes).context("failed to load toc patch")?;
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let mut archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        println!(
+            "{} applying {} toc edits from {}",
+            "[+]".green(),
+            patch.edits.len(),
+            self.patch.display()
+        );
+
+        let changed = archive
+            .apply_toc_patch(&patch)
+            .context("failed to apply toc patch, nothing is changed")?;
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(archive.metadata().file_count as _);
+        utils::watch_progress(&pb, |progress| archive.rebuild(&mut writer, progress))
+            .context("failed to rebuild the archive")?;
+
+        pb.finish_with_message(
+            "rebuild finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!("{} changed the toc of {changed} files", "[+]".green());
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            archive.metadata(),
+            json!({ "edits": patch.edits.len(), "changed_files": changed }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! a minimal standard base64 (with padding) encoder and decoder
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// encode the bytes as base64
+pub fn encode(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len().div_ceil(3) * 4);
+
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = u32::from_be_bytes([0, b[0], b[1], b[2]]);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+
+    output
+}
+
+/// decode base64 text, whitespaces are ignored
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let text: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+
+    if !text.len().is_multiple_of(4) {
+        return Err("base64 length must be a multiple of 4".to_owned());
+    }
+
+    let mut output = Vec::with_capacity(text.len() / 4 * 3);
+
+    for (i, chunk) in text.chunks(4).enumerate() {
+        let is_last = i == text.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|b| **b == b'=').count();
+        if padding > 2 || (padding > 0 && !is_last) {
+            return Err("invalid base64 padding".to_owned());
+        }
+
+        let mut n = 0u32;
+        for &b in &chunk[..4 - padding] {
+            let value = ALPHABET
+                .iter()
+                .position(|a| *a == b)
+                .ok_or_else(|| format!("invalid base64 character: {:?}", b as char))?;
+            n = (n << 6) | value as u32;
+        }
+        n <<= 6 * padding as u32;
+
+        output.extend_from_slice(&n.to_be_bytes()[1..4 - padding]);
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// test vectors from RFC 4648 section 10
+    const RFC4648: [(&str, &str); 7] = [
+        ("", ""),
+        ("f", "Zg=="),
+        ("fo", "Zm8="),
+        ("foo", "Zm9v"),
+        ("foob", "Zm9vYg=="),
+        ("fooba", "Zm9vYmE="),
+        ("foobar", "Zm9vYmFy"),
+    ];
+
+    #[test]
+    fn rfc4648_vectors() {
+        for (text, base64) in RFC4648 {
+            assert_eq!(encode(text.as_bytes()), base64);
+            assert_eq!(decode(base64).unwrap(), text.as_bytes());
+        }
+    }
+
+    #[test]
+    fn every_byte_roundtrip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for len in 0..bytes.len() {
+            assert_eq!(decode(&encode(&bytes[..len])).unwrap(), &bytes[..len]);
+        }
+        // the last two characters of the alphabet
+        assert_eq!(encode(&[0xFB, 0xFF]), "+/8=");
+    }
+
+    #[test]
+    fn whitespace_is_ignored() {
+        assert_eq!(decode(" Zm9v\nYmFy\r\n").unwrap(), b"foobar");
+        assert_eq!(decode("Zm 8=").unwrap(), b"fo");
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(
+            decode("Zm9").unwrap_err(),
+            "base64 length must be a multiple of 4"
+        );
+        assert_eq!(decode("Z===").unwrap_err(), "invalid base64 padding");
+        assert_eq!(decode("Zg==Zm9v").unwrap_err(), "invalid base64 padding");
+        assert_eq!(decode("Zm9-").unwrap_err(), "invalid base64 character: '-'");
+        assert_eq!(decode("Zg=a").unwrap_err(), "invalid base64 character: '='");
+    }
+}
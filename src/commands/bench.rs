@@ -0,0 +1,208 @@
+use std::{io::Cursor, path::PathBuf, time::Instant};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Options, entry::UpdateKind},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    size, utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// folder of modified files, only the files in it that are also in the archive are
+    /// compressed again. if empty every compressed file of the archive is compressed again
+    #[arg(value_hint = ValueHint::DirPath, value_parser = utils::is_dir)]
+    pub input_folder: Option<PathBuf>,
+    /// compression levels to try, 0-9 (zlib) for obscure 1 and 1-13 (lzo) for obscure 2 and
+    /// final exam. stored files and the default level are always tried
+    #[arg(long, value_delimiter = ',', value_parser = clap::value_parser!(u8).range(0..=13))]
+    pub levels: Vec<u8>,
+}
+
+/// a compression setting that the archive is rebuilt with
+struct Setting {
+    skip_compression: bool,
+    level: Option<u8>,
+}
+
+impl Setting {
+    fn name(&self, game: Game) -> String {
+        match (self.skip_compression, self.level) {
+            (true, _) => "--skip-compression".to_owned(),
+            (false, None) => format!("level {} (default)", default_level(game)),
+            (false, Some(level)) => format!("--compression-level {level}"),
+        }
+    }
+}
+
+/// the level that the game archives use
+fn default_level(game: Game) -> u8 {
+    match game {
+        Game::Obscure1 => 9,
+        Game::Obscure2 => 3,
+        Game::FinalExam => 12,
+    }
+}
+
+/// levels that are tried when `--levels` isn't passed
+fn levels(game: Game) -> &'static [u8] {
+    match game {
+        Game::Obscure1 => &[1, 6],
+        Game::Obscure2 | Game::FinalExam => &[1, 8, 13],
+    }
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let game = provider.game();
+        let obscure2_names = name_maps_for_game(game)?;
+
+        let mut archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        // the updates are read once, so reading them isn't part of the measured time
+        let mut updated = 0;
+        for mut entry in archive.files_mut() {
+            let bytes = match &self.input_folder {
+                Some(folder) => {
+                    let path = folder.join(&entry.path);
+                    if !path.is_file() {
+                        continue;
+                    }
+                    std::fs::read(&path)
+                        .with_context(|| format!("failed to read {}", path.display()))?
+                }
+                // stored files stay stored, so only the compressed ones are worth updating
+                None if entry.is_compressed() => entry
+                    .get_bytes()
+                    .with_context(|| format!("failed to decompress {}", entry.path.display()))?
+                    .into_owned(),
+                None => continue,
+            };
+
+            entry.update(UpdateKind::Bytes(bytes));
+            updated += 1;
+        }
+
+        if updated == 0 {
+            anyhow::bail!("no file to compress again, so every setting give the same archive");
+        }
+
+        println!(
+            "{} rebuilding the archive in memory with {updated} files compressed again",
+            "[+]".green()
+        );
+
+        let mut levels = match self.levels.is_empty() {
+            true => levels(game).to_vec(),
+            false => self.levels.clone(),
+        };
+        levels.sort_unstable();
+        levels.dedup();
+        levels.retain(|&level| level != default_level(game));
+
+        let settings = [
+            Setting {
+                skip_compression: true,
+                level: None,
+            },
+            Setting {
+                skip_compression: false,
+                level: None,
+            },
+        ]
+        .into_iter()
+        .chain(levels.into_iter().map(|level| Setting {
+            skip_compression: false,
+            level: Some(level),
+        }));
+
+        let source_size = provider.file_len();
+        let mut results = Vec::new();
+        for setting in settings {
+            archive.options.rebuild_skip_compression = setting.skip_compression;
+            archive.options.compression_level = setting.level;
+
+            let name = setting.name(game);
+            let pb = utils::progress_bar(archive.metadata().file_count as _);
+            pb.set_message(name.clone());
+
+            let mut writer = Cursor::new(Vec::with_capacity(source_size as _));
+            let start = Instant::now();
+            utils::watch_progress(&pb, |progress| archive.rebuild(&mut writer, progress))
+                .with_context(|| format!("failed to rebuild the archive with {name}"))?;
+            let elapsed = start.elapsed();
+            pb.finish_and_clear();
+
+            results.push((setting, name, writer.into_inner().len() as u64, elapsed));
+        }
+
+        let width = results
+            .iter()
+            .map(|(_, name, ..)| name.len())
+            .max()
+            .unwrap_or_default();
+
+        println!(
+            "{} {:<width$} {:>11} {:>8} {:>9}",
+            "[?]".green(),
+            "setting",
+            "size",
+            "source",
+            "time",
+        );
+        for (_, name, size, elapsed) in &results {
+            println!(
+                " {} {name:<width$} {:>11} {:>7.1}% {:>8.2}s",
+                "|>".cyan(),
+                size::format(*size),
+                match source_size {
+                    0 => 0.0,
+                    source => *size as f64 / source as f64 * 100.0,
+                },
+                elapsed.as_secs_f64(),
+            );
+        }
+
+        output::report(
+            "bench",
+            json!({
+                "source_size": source_size,
+                "updated_files": updated,
+                "results": results
+                    .iter()
+                    .map(|(setting, name, size, elapsed)| json!({
+                        "setting": name,
+                        "skip_compression": setting.skip_compression,
+                        "compression_level": setting.level,
+                        "size": size,
+                        "elapsed_ms": elapsed.as_millis() as u64,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,662 @@
+//! interactive terminal browser over the archive, a small file manager to look at the files,
+//! extract some of them and replace others
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{
+        Archive, Options,
+        entry::{Entry, FileEntry, UpdateKind},
+        thumbnail::{TextureFormat, Thumbnail},
+    },
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+};
+
+use super::{name_maps_for_game, output::println, size, utils};
+
+/// the most bytes of a file that are shown in the hexdump
+const PREVIEW_LEN: u64 = 1024;
+
+const HELP: &str = "↑↓ move  enter open  ⌫ back  space mark  a mark all  x extract  r replace  u undo  w write and quit  q quit";
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// folder that the files are extracted to, if empty a folder with the same name as input will be used
+    #[arg(long, short = 'e', value_hint = ValueHint::DirPath)]
+    pub extract_to: Option<PathBuf>,
+    /// archive that is written with the replaced files, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+        if output == self.input {
+            anyhow::bail!("the replaced files can't be written to the archive that is browsed");
+        }
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+        let mut archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        let mut browser = Browser::new(
+            &archive,
+            self.extract_to
+                .clone()
+                .unwrap_or_else(|| self.input.with_extension("")),
+        );
+        let mut terminal = ratatui::init();
+        let result = browser.run(&mut terminal);
+        ratatui::restore();
+
+        let replaced = match result? {
+            Exit::Quit => return Ok(()),
+            Exit::Write => browser.replaced,
+        };
+
+        for mut entry in archive.files_mut() {
+            if let Some(path) = replaced.get(&entry.path) {
+                entry.update(UpdateKind::File(path.clone()));
+            }
+        }
+
+        println!(
+            "{} writing {} replaced files to {}",
+            "[+]".green(),
+            replaced.len(),
+            output.display()
+        );
+
+        std::fs::copy(&self.input, &output)
+            .context("failed to copy the archive to the output path")?;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&output)
+            .context("failed to open the output hvp archive")?;
+        let report = archive
+            .patch(&mut file)
+            .context("failed to write the replaced files")?;
+
+        println!(
+            "{} replaced {} files ({} in place, {} appended)",
+            "[+]".green(),
+            report.patched(),
+            report.in_place + report.reclaimed,
+            report.appended
+        );
+
+        Ok(())
+    }
+}
+
+/// how the browser was closed
+enum Exit {
+    Quit,
+    /// write the replaced files to the output archive
+    Write,
+}
+
+struct Browser<'a, 'p> {
+    archive: &'a Archive<'p>,
+    extract_to: PathBuf,
+    /// index of every directory from the root to the current one
+    dirs: Vec<usize>,
+    list: ListState,
+    /// paths of the files that are marked for extraction
+    marked: HashSet<PathBuf>,
+    /// files that are replaced, by their path in the archive
+    replaced: BTreeMap<PathBuf, PathBuf>,
+    /// the path that is being typed for the replace prompt
+    input: Option<String>,
+    status: String,
+    /// the first bytes of the last previewed file
+    preview: Option<(PathBuf, Result<Vec<u8>, String>)>,
+    /// the last previewed texture and the size that it was scaled to
+    thumbnail: Option<(PathBuf, u32, Result<Thumbnail, String>)>,
+    /// quit was pressed while there are replaced files that aren't written
+    confirm_quit: bool,
+}
+
+impl<'a, 'p> Browser<'a, 'p> {
+    fn new(archive: &'a Archive<'p>, extract_to: PathBuf) -> Self {
+        Self {
+            archive,
+            extract_to,
+            dirs: Vec::new(),
+            list: ListState::default().with_selected(Some(0)),
+            marked: HashSet::new(),
+            replaced: BTreeMap::new(),
+            input: None,
+            status: HELP.to_owned(),
+            preview: None,
+            thumbnail: None,
+            confirm_quit: false,
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<Exit> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if self.input.is_some() {
+                self.prompt_key(key.code);
+                continue;
+            }
+
+            let quit = std::mem::take(&mut self.confirm_quit);
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc if quit || self.replaced.is_empty() => {
+                    return Ok(Exit::Quit);
+                }
+                KeyCode::Char('q') | KeyCode::Esc => {
+                    self.confirm_quit = true;
+                    self.status = format!(
+                        "{} replaced files aren't written, press w to write them or q again to quit",
+                        self.replaced.len()
+                    );
+                }
+                KeyCode::Char('w') if self.replaced.is_empty() => {
+                    self.status = "no file is replaced, nothing to write".to_owned();
+                }
+                KeyCode::Char('w') => return Ok(Exit::Write),
+                KeyCode::Up | KeyCode::Char('k') => self.list.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => self.list.select_next(),
+                KeyCode::PageUp => self.list.scroll_up_by(20),
+                KeyCode::PageDown => self.list.scroll_down_by(20),
+                KeyCode::Home => self.list.select_first(),
+                KeyCode::End => self.list.select_last(),
+                KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.open(),
+                KeyCode::Backspace | KeyCode::Left | KeyCode::Char('h') => self.back(),
+                KeyCode::Char(' ') => {
+                    if let Some(idx) = self.list.selected() {
+                        self.toggle_mark(idx..idx + 1);
+                        self.list.select_next();
+                    }
+                }
+                KeyCode::Char('a') => self.toggle_mark(0..self.entries().len()),
+                KeyCode::Char('x') => self.extract(),
+                KeyCode::Char('r') => match self.selected_file() {
+                    Some(_) => self.input = Some(String::new()),
+                    None => self.status = "only files can be replaced".to_owned(),
+                },
+                KeyCode::Char('u') => {
+                    if let Some(path) = self.selected_file()
+                        && self.replaced.remove(&path).is_some()
+                    {
+                        self.status = format!("{} isn't replaced anymore", path.display());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// handle a key of the replace prompt
+    fn prompt_key(&mut self, code: KeyCode) {
+        let Some(input) = &mut self.input else {
+            return;
+        };
+
+        match code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.input = None,
+            KeyCode::Enter => {
+                let replacement = PathBuf::from(std::mem::take(input).trim());
+                self.input = None;
+                let Some(path) = self.selected_file() else {
+                    return;
+                };
+
+                self.status = match replacement.is_file() {
+                    true => {
+                        let status = format!(
+                            "{} is replaced with {}, press w to write the archive",
+                            path.display(),
+                            replacement.display()
+                        );
+                        self.replaced.insert(path, replacement);
+                        status
+                    }
+                    false => format!("{} isn't a file", replacement.display()),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    /// entries of the current directory
+    fn entries(&self) -> &'a [Entry<'p>] {
+        let mut entries = self.archive.entries();
+        for &idx in &self.dirs {
+            match &entries[idx] {
+                Entry::Dir(dir) => entries = &dir.entries,
+                Entry::File(_) => unreachable!("only directories are opened"),
+            }
+        }
+        entries
+    }
+
+    /// path of the current directory inside the archive
+    fn current_dir(&self) -> PathBuf {
+        let mut entries = self.archive.entries();
+        let mut path = PathBuf::new();
+        for &idx in &self.dirs {
+            path.push(entries[idx].name());
+            if let Entry::Dir(dir) = &entries[idx] {
+                entries = &dir.entries;
+            }
+        }
+        path
+    }
+
+    fn selected(&self) -> Option<&'a Entry<'p>> {
+        self.entries().get(self.list.selected()?)
+    }
+
+    /// path of the selected entry, if it's a file
+    fn selected_file(&self) -> Option<PathBuf> {
+        match self.selected()? {
+            Entry::File(file) => Some(self.current_dir().join(file.name())),
+            Entry::Dir(_) => None,
+        }
+    }
+
+    fn open(&mut self) {
+        if let (Some(idx), Some(Entry::Dir(_))) = (self.list.selected(), self.selected()) {
+            self.dirs.push(idx);
+            self.list.select(Some(0));
+        }
+    }
+
+    fn back(&mut self) {
+        if let Some(idx) = self.dirs.pop() {
+            self.list.select(Some(idx));
+        }
+    }
+
+    /// mark the files of the entries of the current directory in `range`, or unmark them
+    /// if all of them are already marked
+    fn toggle_mark(&mut self, range: std::ops::Range<usize>) {
+        let dir = self.current_dir();
+        let files: Vec<PathBuf> = self.entries()[range]
+            .iter()
+            .flat_map(Entry::flatten_to_files)
+            .map(|file| dir.join(file.path))
+            .collect();
+
+        if files.iter().all(|path| self.marked.contains(path)) {
+            for path in &files {
+                self.marked.remove(path);
+            }
+        } else {
+            self.marked.extend(files);
+        }
+        self.status = format!("{} files are marked", self.marked.len());
+    }
+
+    /// extract the marked files, or the selected entry if nothing is marked
+    fn extract(&mut self) {
+        let selected: HashSet<PathBuf> = match self.marked.is_empty() {
+            false => self.marked.clone(),
+            true => match self.selected() {
+                Some(entry) => {
+                    let dir = self.current_dir();
+                    entry
+                        .flatten_to_files()
+                        .into_iter()
+                        .map(|file| dir.join(file.path))
+                        .collect()
+                }
+                None => return,
+            },
+        };
+
+        let mut extracted = 0;
+        for file in self.archive.files() {
+            if !selected.contains(&file.path) {
+                continue;
+            }
+
+            let target = self.extract_to.join(&file.path);
+            let result = std::fs::create_dir_all(target.with_file_name(""))
+                .and_then(|_| File::create(&target))
+                .and_then(|target| {
+                    let mut target = BufWriter::new(target);
+                    io::copy(&mut file.reader(), &mut target)?;
+                    target.flush()
+                });
+
+            if let Err(e) = result {
+                self.status = format!("failed to extract {}: {e}", file.path.display());
+                return;
+            }
+            extracted += 1;
+        }
+
+        self.marked.clear();
+        self.status = format!(
+            "extracted {extracted} files to {}",
+            self.extract_to.display()
+        );
+    }
+
+    /// the first bytes of a file, read again only when another file is selected
+    fn preview(&mut self, path: &Path, file: &FileEntry) -> &[u8] {
+        if self.preview.as_ref().is_none_or(|(p, _)| p != path) {
+            let mut bytes = Vec::new();
+            let result = file
+                .reader()
+                .take(PREVIEW_LEN)
+                .read_to_end(&mut bytes)
+                .map(|_| bytes)
+                .map_err(|e| e.to_string());
+            self.preview = Some((path.to_owned(), result));
+        }
+
+        match &self.preview {
+            Some((_, Ok(bytes))) => bytes,
+            _ => &[],
+        }
+    }
+
+    /// preview of the file if it's a texture, made again only when another file is selected or
+    /// the details are resized
+    fn thumbnail(
+        &mut self,
+        path: &Path,
+        file: &FileEntry,
+        max_dim: u32,
+    ) -> Option<&Result<Thumbnail, String>> {
+        TextureFormat::detect(path, self.preview(path, file))?;
+
+        if self
+            .thumbnail
+            .as_ref()
+            .is_none_or(|(p, dim, _)| p != path || *dim != max_dim)
+        {
+            let result = self
+                .archive
+                .thumbnail(path, max_dim)
+                .map_err(|e| e.to_string());
+            self.thumbnail = Some((path.to_owned(), max_dim, result));
+        }
+
+        self.thumbnail.as_ref().map(|(_, _, result)| result)
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(3), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, details_area] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .areas(main);
+
+        let dir = self.current_dir();
+        let items: Vec<ListItem> = self
+            .entries()
+            .iter()
+            .map(|entry| {
+                let path = dir.join(entry.name());
+                let (flag, style) = match entry {
+                    Entry::Dir(_) => (' ', Style::new().fg(Color::Blue)),
+                    Entry::File(_) if self.replaced.contains_key(&path) => {
+                        ('R', Style::new().fg(Color::Yellow))
+                    }
+                    Entry::File(_) if self.marked.contains(&path) => {
+                        ('*', Style::new().fg(Color::Green))
+                    }
+                    Entry::File(_) => (' ', Style::new()),
+                };
+                let name = match entry {
+                    Entry::Dir(_) => format!("{}/", entry.name()),
+                    Entry::File(_) => entry.name().to_owned(),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{flag} ")),
+                    Span::styled(name, style),
+                ]))
+            })
+            .collect();
+
+        let list = List::new(items)
+            .block(Block::bordered().title(format!("/{}", dir.display())))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        let details = match self.selected() {
+            Some(Entry::File(file)) => {
+                let path = dir.join(file.name());
+                let mut lines = vec![
+                    Line::from(format!("path: {}", path.display())),
+                    Line::from(format!(
+                        "size: {} (stored {})",
+                        size::format(file.uncompressed_size() as _),
+                        size::format(file.raw_bytes.len() as _)
+                    )),
+                    Line::from(format!(
+                        "compression: {}",
+                        file.compression_info()
+                            .map_or("none".to_owned(), |info| format!(
+                                "{:?}",
+                                info.compression_type
+                            ))
+                    )),
+                    Line::from(format!(
+                        "checksum: {:#010x} ({})",
+                        file.stored_checksum(),
+                        match file.checksum_match() {
+                            true => "valid",
+                            false => "invalid",
+                        }
+                    )),
+                ];
+                if let Some(replacement) = self.replaced.get(&path) {
+                    lines.push(Line::from(format!(
+                        "replaced with: {}",
+                        replacement.display()
+                    )));
+                }
+                lines.push(Line::default());
+
+                // textures are shown with two pixels per character, the others as a hexdump
+                let rows = details_area.height.saturating_sub(lines.len() as u16 + 2);
+                let max_dim = details_area.width.saturating_sub(2).min(rows * 2) as u32;
+                match self.thumbnail(&path, file, max_dim).filter(|_| max_dim > 0) {
+                    Some(Ok(thumbnail)) => lines.extend(thumbnail_lines(thumbnail)),
+                    thumbnail => {
+                        let error = thumbnail.and_then(|t| t.as_ref().err()).cloned();
+                        let bytes = self.preview(&path, file);
+                        lines.extend(hexdump(bytes).into_iter().map(Line::from));
+                        if let Some((_, Err(e))) = &self.preview {
+                            lines.push(Line::from(format!("can't be decompressed: {e}")));
+                        } else if let Some(e) = error {
+                            lines.push(Line::from(format!("no preview: {e}")));
+                        }
+                    }
+                }
+                lines
+            }
+            Some(Entry::Dir(dir_entry)) => {
+                let (files, size) = count_files(&dir_entry.entries);
+                vec![
+                    Line::from(format!("path: {}", dir.join(&dir_entry.name).display())),
+                    Line::from(format!("entries: {}", dir_entry.entries.len())),
+                    Line::from(format!("files: {files} ({})", size::format(size))),
+                ]
+            }
+            None => Vec::new(),
+        };
+        frame.render_widget(
+            Paragraph::new(details).block(Block::bordered().title("details")),
+            details_area,
+        );
+
+        let status_line = match &self.input {
+            Some(input) => format!("replace with: {input}█"),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+/// number of files under the entries and their total size
+fn count_files(entries: &[Entry]) -> (usize, u64) {
+    entries
+        .iter()
+        .fold((0, 0), |(count, size), entry| match entry {
+            Entry::File(file) => (count + 1, size + file.uncompressed_size() as u64),
+            Entry::Dir(dir) => {
+                let (files, dir_size) = count_files(&dir.entries);
+                (count + files, size + dir_size)
+            }
+        })
+}
+
+/// half blocks colored with two rows of pixels each, the top pixel is the color of the block
+/// and the bottom one its background. transparent pixels are blended with black
+fn thumbnail_lines(thumbnail: &Thumbnail) -> Vec<Line<'static>> {
+    let color = |[r, g, b, a]: [u8; 4]| {
+        let blend = |c: u8| (c as u16 * a as u16 / 255) as u8;
+        Color::Rgb(blend(r), blend(g), blend(b))
+    };
+
+    (0..thumbnail.height)
+        .step_by(2)
+        .map(|y| {
+            let spans: Vec<_> = (0..thumbnail.width)
+                .map(|x| {
+                    let bottom = match y + 1 < thumbnail.height {
+                        true => color(thumbnail.pixel(x, y + 1)),
+                        false => Color::Reset,
+                    };
+                    Span::styled(
+                        "▀",
+                        Style::new().fg(color(thumbnail.pixel(x, y))).bg(bottom),
+                    )
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// 16 bytes per line, with the offset and the printable ascii characters
+fn hexdump(bytes: &[u8]) -> Vec<String> {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(idx, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| match b.is_ascii_graphic() || b == b' ' {
+                    true => b as char,
+                    false => '.',
+                })
+                .collect();
+            format!("{:08x}  {:<47}  |{ascii}|", idx * 16, hex.join(" "))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hexdump_lines() {
+        assert!(hexdump(&[]).is_empty());
+        assert_eq!(
+            hexdump(b"hvp archive\x00\x01\xff~ tail"),
+            [
+                "00000000  68 76 70 20 61 72 63 68 69 76 65 00 01 ff 7e 20  |hvp archive...~ |",
+                "00000010  74 61 69 6c                                      |tail|",
+            ]
+        );
+    }
+
+    #[test]
+    fn thumbnail_half_blocks() {
+        let thumbnail = Thumbnail {
+            width: 2,
+            height: 3,
+            pixels: [
+                [0xFF, 0, 0, 0xFF],
+                [0, 0xFF, 0, 0xFF],
+                [0, 0, 0xFF, 0xFF],
+                [0xFF, 0xFF, 0xFF, 0x80],
+                [0x10, 0x20, 0x30, 0xFF],
+                [0; 4],
+            ]
+            .concat(),
+        };
+
+        let lines = thumbnail_lines(&thumbnail);
+        let styles: Vec<Vec<_>> = lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .map(|s| (s.style.fg, s.style.bg))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            styles,
+            [
+                vec![
+                    (Some(Color::Rgb(0xFF, 0, 0)), Some(Color::Rgb(0, 0, 0xFF))),
+                    (
+                        Some(Color::Rgb(0, 0xFF, 0)),
+                        Some(Color::Rgb(0x80, 0x80, 0x80))
+                    ),
+                ],
+                vec![
+                    (Some(Color::Rgb(0x10, 0x20, 0x30)), Some(Color::Reset)),
+                    (Some(Color::Rgb(0, 0, 0)), Some(Color::Reset)),
+                ],
+            ]
+        );
+    }
+}
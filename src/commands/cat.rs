@@ -0,0 +1,165 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Obscure2NameMap, Options},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{base64, load_name_maps, output, pattern::Encoding, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path of the file inside the archive, for example `some/dir/file.bin`
+    pub path: PathBuf,
+    /// write the file to this path instead of stdout, `-` is stdout too
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+    /// print the file as text, converted from its encoding to utf-8
+    #[arg(long, default_value_t = false, required = false)]
+    pub as_text: bool,
+    /// encoding of the text with --as-text, `auto` or one of utf8, latin1, windows-1250,
+    /// windows-1251, windows-1252, utf16le and utf16be. auto guess it from the content
+    #[arg(long, default_value = "auto", value_parser = parse_encoding, requires = "as_text")]
+    pub encoding: TextEncoding,
+}
+
+/// encoding selected by the user
+#[derive(Clone, Copy, Debug)]
+pub enum TextEncoding {
+    Auto,
+    Fixed(Encoding),
+}
+
+fn parse_encoding(s: &str) -> Result<TextEncoding, String> {
+    match s {
+        "auto" => Ok(TextEncoding::Auto),
+        _ => Encoding::from_str(s, true).map(TextEncoding::Fixed),
+    }
+}
+
+fn encoding_name(encoding: Encoding) -> String {
+    encoding
+        .to_possible_value()
+        .map_or_else(|| format!("{encoding:?}"), |v| v.get_name().to_owned())
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        // we don't print anything here, because stdout may be used for the file content
+        let obscure2_names = match provider.game() {
+            Game::Obscure2 => load_name_maps(false)
+                .context("failed to load name maps")?
+                .unwrap_or_default(),
+            _ => Obscure2NameMap::default(),
+        };
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        let output = self.output.filter(|o| o != Path::new("-"));
+
+        let entry = archive
+            .find_file(&self.path)
+            .with_context(|| format!("can't find {} in the archive", self.path.display()))?;
+
+        let bytes = entry
+            .get_bytes()
+            .with_context(|| format!("failed to decompress {}", self.path.display()))?;
+
+        if self.as_text {
+            let encoding = match self.encoding {
+                TextEncoding::Auto => Encoding::detect(&bytes),
+                TextEncoding::Fixed(encoding) => encoding,
+            };
+            let text = encoding.decode(&bytes[encoding.bom_len(&bytes)..]);
+
+            if let TextEncoding::Auto = self.encoding {
+                // stderr, so the text can still be piped
+                anstream::eprintln!(
+                    "{} detected encoding: {}",
+                    "[?]".green(),
+                    encoding_name(encoding)
+                );
+            }
+
+            match output {
+                Some(output) => {
+                    std::fs::write(&output, &text).context("failed to write output file")?;
+                    output::report(
+                        "cat",
+                        json!({
+                            "path": entry.path,
+                            "size": bytes.len(),
+                            "encoding": encoding_name(encoding),
+                            "output": output,
+                        }),
+                    );
+                }
+                None if output::is_json() => output::report(
+                    "cat",
+                    json!({
+                        "path": entry.path,
+                        "size": bytes.len(),
+                        "encoding": encoding_name(encoding),
+                        "text": text,
+                    }),
+                ),
+                None => {
+                    let mut stdout = std::io::stdout().lock();
+                    stdout
+                        .write_all(text.as_bytes())
+                        .and_then(|_| stdout.flush())
+                        .context("failed to write to stdout")?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        match output {
+            Some(output) => {
+                std::fs::write(&output, &bytes).context("failed to write output file")?;
+                output::report(
+                    "cat",
+                    json!({ "path": entry.path, "size": bytes.len(), "output": output }),
+                );
+            }
+            // stdout is used by the json report, so the content is added to it as base64
+            None if output::is_json() => output::report(
+                "cat",
+                json!({
+                    "path": entry.path,
+                    "size": bytes.len(),
+                    "data": base64::encode(&bytes),
+                }),
+            ),
+            None => {
+                let mut stdout = std::io::stdout().lock();
+                stdout
+                    .write_all(&bytes)
+                    .and_then(|_| stdout.flush())
+                    .context("failed to write to stdout")?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! single byte windows code pages that the pc versions of the games use for their texts
+//!
+//! bytes that are not defined by a code page are mapped to the c1 control character with
+//! the same value, so decoding and encoding again always give back the same bytes.
+
+/// characters of the bytes `0x80..=0xff` in windows-1250 (central european)
+pub const WINDOWS_1250: [char; 128] = [
+    '\u{20ac}', '\u{0081}', '\u{201a}', '\u{0083}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{0088}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{015a}', '\u{0164}', '\u{017d}', '\u{0179}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{0098}', '\u{2122}', '\u{0161}', '\u{203a}', '\u{015b}', '\u{0165}', '\u{017e}', '\u{017a}',
+    '\u{00a0}', '\u{02c7}', '\u{02d8}', '\u{0141}', '\u{00a4}', '\u{0104}', '\u{00a6}', '\u{00a7}',
+    '\u{00a8}', '\u{00a9}', '\u{015e}', '\u{00ab}', '\u{00ac}', '\u{00ad}', '\u{00ae}', '\u{017b}',
+    '\u{00b0}', '\u{00b1}', '\u{02db}', '\u{0142}', '\u{00b4}', '\u{00b5}', '\u{00b6}', '\u{00b7}',
+    '\u{00b8}', '\u{0105}', '\u{015f}', '\u{00bb}', '\u{013d}', '\u{02dd}', '\u{013e}', '\u{017c}',
+    '\u{0154}', '\u{00c1}', '\u{00c2}', '\u{0102}', '\u{00c4}', '\u{0139}', '\u{0106}', '\u{00c7}',
+    '\u{010c}', '\u{00c9}', '\u{0118}', '\u{00cb}', '\u{011a}', '\u{00cd}', '\u{00ce}', '\u{010e}',
+    '\u{0110}', '\u{0143}', '\u{0147}', '\u{00d3}', '\u{00d4}', '\u{0150}', '\u{00d6}', '\u{00d7}',
+    '\u{0158}', '\u{016e}', '\u{00da}', '\u{0170}', '\u{00dc}', '\u{00dd}', '\u{0162}', '\u{00df}',
+    '\u{0155}', '\u{00e1}', '\u{00e2}', '\u{0103}', '\u{00e4}', '\u{013a}', '\u{0107}', '\u{00e7}',
+    '\u{010d}', '\u{00e9}', '\u{0119}', '\u{00eb}', '\u{011b}', '\u{00ed}', '\u{00ee}', '\u{010f}',
+    '\u{0111}', '\u{0144}', '\u{0148}', '\u{00f3}', '\u{00f4}', '\u{0151}', '\u{00f6}', '\u{00f7}',
+    '\u{0159}', '\u{016f}', '\u{00fa}', '\u{0171}', '\u{00fc}', '\u{00fd}', '\u{0163}', '\u{02d9}',
+];
+
+/// characters of the bytes `0x80..=0xff` in windows-1251 (cyrillic)
+pub const WINDOWS_1251: [char; 128] = [
+    '\u{0402}', '\u{0403}', '\u{201a}', '\u{0453}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{20ac}', '\u{2030}', '\u{0409}', '\u{2039}', '\u{040a}', '\u{040c}', '\u{040b}', '\u{040f}',
+    '\u{0452}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{0098}', '\u{2122}', '\u{0459}', '\u{203a}', '\u{045a}', '\u{045c}', '\u{045b}', '\u{045f}',
+    '\u{00a0}', '\u{040e}', '\u{045e}', '\u{0408}', '\u{00a4}', '\u{0490}', '\u{00a6}', '\u{00a7}',
+    '\u{0401}', '\u{00a9}', '\u{0404}', '\u{00ab}', '\u{00ac}', '\u{00ad}', '\u{00ae}', '\u{0407}',
+    '\u{00b0}', '\u{00b1}', '\u{0406}', '\u{0456}', '\u{0491}', '\u{00b5}', '\u{00b6}', '\u{00b7}',
+    '\u{0451}', '\u{2116}', '\u{0454}', '\u{00bb}', '\u{0458}', '\u{0405}', '\u{0455}', '\u{0457}',
+    '\u{0410}', '\u{0411}', '\u{0412}', '\u{0413}', '\u{0414}', '\u{0415}', '\u{0416}', '\u{0417}',
+    '\u{0418}', '\u{0419}', '\u{041a}', '\u{041b}', '\u{041c}', '\u{041d}', '\u{041e}', '\u{041f}',
+    '\u{0420}', '\u{0421}', '\u{0422}', '\u{0423}', '\u{0424}', '\u{0425}', '\u{0426}', '\u{0427}',
+    '\u{0428}', '\u{0429}', '\u{042a}', '\u{042b}', '\u{042c}', '\u{042d}', '\u{042e}', '\u{042f}',
+    '\u{0430}', '\u{0431}', '\u{0432}', '\u{0433}', '\u{0434}', '\u{0435}', '\u{0436}', '\u{0437}',
+    '\u{0438}', '\u{0439}', '\u{043a}', '\u{043b}', '\u{043c}', '\u{043d}', '\u{043e}', '\u{043f}',
+    '\u{0440}', '\u{0441}', '\u{0442}', '\u{0443}', '\u{0444}', '\u{0445}', '\u{0446}', '\u{0447}',
+    '\u{0448}', '\u{0449}', '\u{044a}', '\u{044b}', '\u{044c}', '\u{044d}', '\u{044e}', '\u{044f}',
+];
+
+/// characters of the bytes `0x80..=0xff` in windows-1252 (western european)
+pub const WINDOWS_1252: [char; 128] = [
+    '\u{20ac}', '\u{0081}', '\u{201a}', '\u{0192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02c6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008d}', '\u{017d}', '\u{008f}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02dc}', '\u{2122}', '\u{0161}', '\u{203a}', '\u{0153}', '\u{009d}', '\u{017e}', '\u{0178}',
+    '\u{00a0}', '\u{00a1}', '\u{00a2}', '\u{00a3}', '\u{00a4}', '\u{00a5}', '\u{00a6}', '\u{00a7}',
+    '\u{00a8}', '\u{00a9}', '\u{00aa}', '\u{00ab}', '\u{00ac}', '\u{00ad}', '\u{00ae}', '\u{00af}',
+    '\u{00b0}', '\u{00b1}', '\u{00b2}', '\u{00b3}', '\u{00b4}', '\u{00b5}', '\u{00b6}', '\u{00b7}',
+    '\u{00b8}', '\u{00b9}', '\u{00ba}', '\u{00bb}', '\u{00bc}', '\u{00bd}', '\u{00be}', '\u{00bf}',
+    '\u{00c0}', '\u{00c1}', '\u{00c2}', '\u{00c3}', '\u{00c4}', '\u{00c5}', '\u{00c6}', '\u{00c7}',
+    '\u{00c8}', '\u{00c9}', '\u{00ca}', '\u{00cb}', '\u{00cc}', '\u{00cd}', '\u{00ce}', '\u{00cf}',
+    '\u{00d0}', '\u{00d1}', '\u{00d2}', '\u{00d3}', '\u{00d4}', '\u{00d5}', '\u{00d6}', '\u{00d7}',
+    '\u{00d8}', '\u{00d9}', '\u{00da}', '\u{00db}', '\u{00dc}', '\u{00dd}', '\u{00de}', '\u{00df}',
+    '\u{00e0}', '\u{00e1}', '\u{00e2}', '\u{00e3}', '\u{00e4}', '\u{00e5}', '\u{00e6}', '\u{00e7}',
+    '\u{00e8}', '\u{00e9}', '\u{00ea}', '\u{00eb}', '\u{00ec}', '\u{00ed}', '\u{00ee}', '\u{00ef}',
+    '\u{00f0}', '\u{00f1}', '\u{00f2}', '\u{00f3}', '\u{00f4}', '\u{00f5}', '\u{00f6}', '\u{00f7}',
+    '\u{00f8}', '\u{00f9}', '\u{00fa}', '\u{00fb}', '\u{00fc}', '\u{00fd}', '\u{00fe}', '\u{00ff}',
+];
+
+/// decode the bytes using the upper half of a code page
+pub fn decode(bytes: &[u8], table: &[char; 128]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            0..0x80 => b as char,
+            _ => table[(b - 0x80) as usize],
+        })
+        .collect()
+}
+
+/// encode a single character, `None` if the code page can't hold it
+pub fn encode(c: char, table: &[char; 128]) -> Option<u8> {
+    match u8::try_from(c as u32) {
+        Ok(b) if b < 0x80 => Some(b),
+        _ => table
+            .iter()
+            .position(|&t| t == c)
+            .map(|idx| 0x80 + idx as u8),
+    }
+}
+
+/// how much the decoded text look like a real text, used to guess the code page.
+/// letters add to the score, while unusual symbols and words that mix cyrillic with latin
+/// letters (or latin words that are only made of accented letters) reduce it
+pub fn score(text: &str) -> i64 {
+    let mut score = 0;
+
+    for word in text.split(|c: char| !c.is_alphabetic()) {
+        let has_ascii = word.chars().any(|c| c.is_ascii());
+        score += word
+            .chars()
+            .filter(|c| !c.is_ascii())
+            .map(|c| match (matches!(c, '\u{400}'..='\u{4ff}'), has_ascii) {
+                (true, false) | (false, true) => 1,
+                (true, true) | (false, false) => -1,
+            })
+            .sum::<i64>();
+    }
+
+    score
+        - 2 * text
+            .chars()
+            .filter(|&c| !c.is_ascii() && !c.is_alphabetic())
+            .filter(|c| {
+                !matches!(
+                    c,
+                    '\u{a0}'
+                        | '«'
+                        | '»'
+                        | '°'
+                        | '€'
+                        | '…'
+                        | '‘'
+                        | '’'
+                        | '“'
+                        | '”'
+                        | '„'
+                        | '–'
+                        | '—'
+                        | '•'
+                        | '©'
+                        | '®'
+                        | '™'
+                        | '№'
+                )
+            })
+            .count() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TABLES: [&[char; 128]; 3] = [&WINDOWS_1250, &WINDOWS_1251, &WINDOWS_1252];
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// texts and their bytes in the code page, the bytes are from the python cp125x codecs
+    fn samples() -> [(&'static str, Vec<u8>, &'static [char; 128]); 3] {
+        [
+            (
+                "Zażółć gęślą jaźń",
+                hex("5a61bff3b3e62067ea9c6cb9206a619ff1"),
+                &WINDOWS_1250,
+            ),
+            (
+                "Съешь же ещё",
+                hex("d1fae5f8fc20e6e520e5f9b8"),
+                &WINDOWS_1251,
+            ),
+            ("Café € “ok”", hex("436166e9208020936f6b94"), &WINDOWS_1252),
+        ]
+    }
+
+    #[test]
+    fn known_texts() {
+        for (text, bytes, table) in samples() {
+            assert_eq!(decode(&bytes, table), text);
+            let encoded: Vec<u8> = text.chars().map(|c| encode(c, table).unwrap()).collect();
+            assert_eq!(encoded, bytes, "{text}");
+        }
+    }
+
+    #[test]
+    fn every_byte_roundtrip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for table in TABLES {
+            let text = decode(&bytes, table);
+            assert_eq!(text.chars().count(), 256);
+            let encoded: Vec<u8> = text.chars().map(|c| encode(c, table).unwrap()).collect();
+            assert_eq!(encoded, bytes);
+        }
+
+        // bytes that windows-1252 doesn't define are kept as c1 control characters
+        assert_eq!(
+            decode(&[0x81, 0x8d, 0x8f, 0x90, 0x9d], &WINDOWS_1252),
+            "\u{81}\u{8d}\u{8f}\u{90}\u{9d}"
+        );
+    }
+
+    #[test]
+    fn unmappable_characters() {
+        assert_eq!(encode('ж', &WINDOWS_1252), None);
+        assert_eq!(encode('ł', &WINDOWS_1251), None);
+        assert_eq!(encode('ñ', &WINDOWS_1250), None);
+        // latin-1 characters that the code page moved elsewhere
+        assert_eq!(encode('\u{80}', &WINDOWS_1252), None);
+        assert_eq!(encode('a', &WINDOWS_1251), Some(b'a'));
+    }
+
+    #[test]
+    fn score_prefer_the_right_code_page() {
+        for (text, bytes, table) in samples() {
+            let right = score(&decode(&bytes, table));
+            for other in TABLES {
+                if other != table {
+                    let wrong = decode(&bytes, other);
+                    assert!(score(&wrong) <= right, "{text} scored lower than {wrong}");
+                }
+            }
+        }
+        assert_eq!(score("plain ascii text"), 0);
+    }
+}
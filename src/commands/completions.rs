@@ -0,0 +1,34 @@
+use std::io::Write;
+
+use anyhow::Context;
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// the shell to generate the completion script for
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self) -> anyhow::Result<()> {
+        // generate panic when the writer fail, so the script is written to stdout after it.
+        // the script is the output, so nothing else is printed
+        let mut script = Vec::new();
+        clap_complete::generate(
+            self.shell,
+            &mut super::Commands::command(),
+            "obscure-hvp",
+            &mut script,
+        );
+
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(&script)
+            .and_then(|_| stdout.flush())
+            .context("failed to write to stdout")
+    }
+}
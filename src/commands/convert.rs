@@ -0,0 +1,164 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    convert,
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{Game, name_maps_for_game, names, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path of the converted hvp archive
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub output: PathBuf,
+    /// game that the archive is converted for
+    #[arg(long, value_enum)]
+    pub to: Game,
+    /// write the archive in big endian (wii version of obscure 2), ignored for obscure 1
+    #[arg(long, default_value_t = false, required = false)]
+    pub big_endian: bool,
+    /// store the files without compression
+    #[arg(long, short = 'c', default_value_t = false, required = false)]
+    pub skip_compression: bool,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let Some(game) = Option::<hvp_archive::Game>::from(self.to) else {
+            anyhow::bail!("--to must be a game, it can't be auto detected");
+        };
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        println!(
+            "{} converting {:?} archive to {game:?}",
+            "[+]".green(),
+            archive.metadata().game
+        );
+
+        let builder = convert::hvp_to_game(&archive, game)
+            .context("failed to read the files of the archive")?
+            .big_endian(self.big_endian)
+            .skip_compression(self.skip_compression);
+
+        // names that can't be read back from the converted archive
+        let unknown_names = match game {
+            hvp_archive::Game::Obscure2 => {
+                let loaded;
+                let target_names = match archive.metadata().game {
+                    hvp_archive::Game::Obscure2 => &archive.options.obscure2_names,
+                    _ => {
+                        loaded = name_maps_for_game(game)?;
+                        &loaded
+                    }
+                };
+                let mut names: Vec<_> = archive
+                    .files()
+                    .flat_map(|f| {
+                        f.path
+                            .iter()
+                            .map(|n| n.to_string_lossy().into_owned())
+                            .collect::<Vec<_>>()
+                    })
+                    .filter(|n| !names::is_unknown(n) && !target_names.contains_name(n))
+                    .collect();
+                names.sort();
+                names.dedup();
+                names
+            }
+            hvp_archive::Game::Obscure1 | hvp_archive::Game::FinalExam => Vec::new(),
+        };
+
+        if game != hvp_archive::Game::Obscure2 {
+            // the other games store the names, so the placeholders become the real names
+            let placeholders = archive
+                .files()
+                .filter(|f| {
+                    f.path
+                        .iter()
+                        .any(|n| names::is_unknown(&n.to_string_lossy()))
+                })
+                .count();
+
+            if placeholders > 0 {
+                println!(
+                    "{} {placeholders} files have unknown names in their path, they are stored with the placeholder names",
+                    "[!]".yellow()
+                );
+            }
+        }
+
+        if !unknown_names.is_empty() {
+            println!(
+                "{} these names are not in the name maps, add them to a txt file inside hashes directory to be able to see them after extraction: {}",
+                "[!]".yellow(),
+                unknown_names.join(", ")
+            );
+        }
+
+        println!(
+            "{} output hvp archive: {}",
+            "[+]".green(),
+            self.output.display()
+        );
+
+        let mut writer = BufWriter::new(
+            File::create(&self.output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(builder.metadata().file_count as _);
+        let metadata = utils::watch_progress(&pb, |progress| builder.build(&mut writer, progress))
+            .context("failed to build the converted archive")?;
+
+        pb.finish_with_message(
+            "convert finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} converted {} files to {game:?}",
+            "[+]".green(),
+            metadata.file_count
+        );
+
+        utils::report_rebuild(
+            &self.output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            metadata,
+            json!({
+                "from": format!("{:?}", archive.metadata().game),
+                "to": format!("{game:?}"),
+            }),
+        );
+
+        Ok(())
+    }
+}
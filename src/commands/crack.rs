@@ -0,0 +1,307 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::archive::Obscure2NameMap;
+use indicatif::ParallelProgressIterator;
+use owo_colors::OwoColorize;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde_json::json;
+
+use super::{
+    output::{self, println},
+    utils,
+};
+
+/// refuse to start if there are more candidates than this, the user probably made a mistake
+const MAX_CANDIDATES: u128 = 1 << 40;
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// crc32s to crack, as hex (`0x179ce296`), decimal or an unknown name (`unk_file_396157590.dat`)
+    pub crc32s: Vec<String>,
+    /// also read crc32s from this file, one per line
+    #[arg(long, short = 'f', value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub file: Option<PathBuf>,
+    /// wordlist file, one word per line. can be used more than once
+    #[arg(long, short = 'w', value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub wordlist: Vec<PathBuf>,
+    /// prefix to put before each word, for example `bd`. can be used more than once
+    #[arg(long, short = 'p')]
+    pub prefix: Vec<String>,
+    /// suffix to put after each word, for example `.tga`. can be used more than once
+    #[arg(long, short = 's')]
+    pub suffix: Vec<String>,
+    /// mask that is added after each word: `?l` lowercase letter, `?u` uppercase letter, `?d` digit,
+    /// `?s` `_` or `-`, `?a` any of `?l`, `?d` and `_`, `??` a `?`, other characters are used as is
+    #[arg(long, short = 'm')]
+    pub mask: Option<String>,
+    /// append the found names to this file, it can be put in the hashes directory
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self) -> anyhow::Result<()> {
+        let mut targets = Vec::new();
+        for crc32 in &self.crc32s {
+            targets.push(parse_crc32(crc32)?);
+        }
+        if let Some(file) = &self.file {
+            for line in read_lines(file).context("failed to read crc32s file")? {
+                targets.push(parse_crc32(&line)?);
+            }
+        }
+        targets.sort_unstable();
+        targets.dedup();
+
+        if targets.is_empty() {
+            anyhow::bail!("no crc32 to crack");
+        }
+
+        let mut words = Vec::new();
+        for path in &self.wordlist {
+            words.extend(read_lines(path).context("failed to read wordlist")?);
+        }
+        if words.is_empty() {
+            words.push(String::new());
+        }
+
+        let prefixes = or_empty(self.prefix);
+        let suffixes = or_empty(self.suffix);
+        let mut mask = match &self.mask {
+            Some(mask) => parse_mask(mask)?,
+            None => Vec::new(),
+        };
+
+        // the first position of the mask is part of the bases, so a mask without
+        // a wordlist is still processed in parallel
+        let first: Vec<String> = match mask.is_empty() {
+            true => vec![String::new()],
+            false => mask
+                .remove(0)
+                .into_iter()
+                .map(|b| char::from(b).to_string())
+                .collect(),
+        };
+
+        let bases: Vec<(Vec<u8>, String)> = prefixes
+            .iter()
+            .flat_map(|p| words.iter().map(move |w| format!("{p}{w}")))
+            .flat_map(|base| first.iter().map(move |f| format!("{base}{f}")))
+            .filter_map(|base| Some((encode(&base)?, base)))
+            .collect();
+        let suffixes: Vec<(Vec<u8>, &str)> = suffixes
+            .iter()
+            .filter_map(|s| Some((encode(s)?, s.as_str())))
+            .collect();
+
+        let candidates = mask
+            .iter()
+            .fold(bases.len() as u128 * suffixes.len() as u128, |n, set| {
+                n * set.len() as u128
+            });
+
+        if candidates > MAX_CANDIDATES {
+            anyhow::bail!("{candidates} candidates is too many, use a smaller mask or wordlist");
+        }
+
+        println!(
+            "{} trying {candidates} candidates against {} crc32s",
+            "[+]".green(),
+            targets.len()
+        );
+
+        let found = Mutex::new(Vec::new());
+        let pb = utils::progress_bar(bases.len() as _);
+
+        bases
+            .into_par_iter()
+            .progress_with(pb.clone())
+            .for_each(|(bytes, base)| {
+                let mut hasher = crc32fast::Hasher::new();
+                hasher.update(&bytes);
+
+                let mut indices = vec![0; mask.len()];
+                loop {
+                    let mut masked = hasher.clone();
+                    for (set, &i) in mask.iter().zip(&indices) {
+                        masked.update(&[set[i]]);
+                    }
+
+                    for (suffix_bytes, suffix) in &suffixes {
+                        let mut hasher = masked.clone();
+                        hasher.update(suffix_bytes);
+                        let crc32 = hasher.finalize();
+
+                        if targets.binary_search(&crc32).is_ok() {
+                            let masked: String = mask
+                                .iter()
+                                .zip(&indices)
+                                .map(|(set, &i)| set[i] as char)
+                                .collect();
+                            let name = format!("{base}{masked}{suffix}");
+                            debug_assert_eq!(Obscure2NameMap::name_crc32(&name), Some(crc32));
+                            found
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .push((crc32, name));
+                        }
+                    }
+
+                    if !next_indices(&mut indices, &mask) {
+                        break;
+                    }
+                }
+            });
+
+        pb.finish_and_clear();
+
+        let mut found = found.into_inner().unwrap_or_else(|e| e.into_inner());
+        found.sort();
+        found.dedup();
+
+        for (crc32, name) in &found {
+            println!(" {} {crc32:#010x} {crc32:>10} {name}", "|>".cyan());
+        }
+
+        let remaining: Vec<u32> = targets
+            .iter()
+            .copied()
+            .filter(|t| !found.iter().any(|(c, _)| c == t))
+            .collect();
+
+        println!(
+            "{} found {} names, {} crc32s are still unknown",
+            "[+]".green(),
+            found.len(),
+            remaining.len()
+        );
+
+        if let Some(output) = &self.output
+            && !found.is_empty()
+        {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(output)
+                .context("failed to open output file")?;
+            for (_, name) in &found {
+                writeln!(file, "{name}").context("failed to write output file")?;
+            }
+            println!(
+                "{} added found names to {}",
+                "[+]".green(),
+                output.display()
+            );
+        }
+
+        output::report(
+            "crack",
+            json!({
+                "candidates": candidates as u64,
+                "found": found.iter().map(|(crc32, name)| json!({ "crc32": crc32, "name": name })).collect::<Vec<_>>(),
+                "remaining": remaining,
+            }),
+        );
+
+        Ok(())
+    }
+}
+
+fn read_lines(path: &Path) -> std::io::Result<Vec<String>> {
+    BufReader::new(File::open(path)?)
+        .lines()
+        .filter(|l| l.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|l| l.map(|l| l.trim().to_owned()))
+        .collect()
+}
+
+fn or_empty(list: Vec<String>) -> Vec<String> {
+    match list.is_empty() {
+        true => vec![String::new()],
+        false => list,
+    }
+}
+
+/// parse a crc32 as hex, decimal or from an unknown entry name
+fn parse_crc32(s: &str) -> anyhow::Result<u32> {
+    let s = s.trim();
+    let number = s
+        .strip_prefix("unk_file_")
+        .and_then(|n| n.strip_suffix(".dat"))
+        .or_else(|| s.strip_prefix("unk_folder_"))
+        .unwrap_or(s);
+
+    let crc32 = match number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => number.parse().ok(),
+    };
+
+    crc32.with_context(|| format!("invalid crc32 '{s}'"))
+}
+
+/// bytes of a name that are hashed, the same as [`Obscure2NameMap::name_crc32`].
+/// `None` if the name can't be stored in the archive
+fn encode(name: &str) -> Option<Vec<u8>> {
+    name.chars()
+        .map(|c| match c {
+            'é' => Some(0xE9),
+            c if c.is_ascii() => Some(c as u8),
+            _ => None,
+        })
+        .collect()
+}
+
+/// each position of the mask become the set of characters that can be used there
+fn parse_mask(mask: &str) -> anyhow::Result<Vec<Vec<u8>>> {
+    const LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+    const UPPER: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const DIGIT: &[u8] = b"0123456789";
+
+    let mut sets = Vec::new();
+    let mut chars = mask.chars();
+
+    while let Some(c) = chars.next() {
+        let set = match c {
+            '?' => match chars.next() {
+                Some('l') => LOWER.to_vec(),
+                Some('u') => UPPER.to_vec(),
+                Some('d') => DIGIT.to_vec(),
+                Some('s') => b"_-".to_vec(),
+                Some('a') => [LOWER, DIGIT, b"_"].concat(),
+                Some('?') => b"?".to_vec(),
+                other => anyhow::bail!(
+                    "unknown mask charset '?{}'",
+                    other.map(String::from).unwrap_or_default()
+                ),
+            },
+            c => encode(&c.to_string()).context("mask can only have ascii characters and 'é'")?,
+        };
+        sets.push(set);
+    }
+
+    Ok(sets)
+}
+
+/// move to the next combination of the mask, return `false` after the last one
+fn next_indices(indices: &mut [usize], mask: &[Vec<u8>]) -> bool {
+    for (i, set) in indices.iter_mut().zip(mask).rev() {
+        *i += 1;
+        if *i < set.len() {
+            return true;
+        }
+        *i = 0;
+    }
+    false
+}
@@ -1,42 +1,59 @@
 use std::{
-    ffi::OsStr,
     fs::File,
-    io::{BufWriter, Write},
+    io::{BufWriter, Cursor, Read, Seek, Write},
     path::{Path, PathBuf},
 };
 
-use anstream::{print, println};
 use anyhow::Context;
 use clap::{Parser, ValueHint};
 use hvp_archive::{
-    Game,
-    archive::{
-        Archive, Obscure2NameMap, Options, entry::UpdateKind, rebuild_progress::RebuildProgress,
-    },
+    archive::{Archive, Options, apply::MatchOptions, builder::ArchiveBuilder, entry::UpdateKind},
     provider::ArchiveProvider,
+    zip::{ZipEntry, ZipReader},
 };
-use indicatif::{ParallelProgressIterator, ProgressBar};
+use indicatif::ParallelProgressIterator;
 use owo_colors::OwoColorize;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde_json::json;
 
 use crate::commands::ChecksumValidation;
 
-use super::{HASHES_FILE, load_name_maps, utils};
+use super::{
+    DEDUP_FILE, HASHES_FILE, MANIFEST_FILE,
+    dedup::Deduped,
+    hashes, is_sidecar,
+    manifest::Manifest,
+    name_maps_for_game,
+    output::{self, print, println},
+    size, utils,
+    watch::FolderWatcher,
+};
 
 #[derive(Parser)]
 #[command(arg_required_else_help = true)]
 pub struct Commands {
-    /// path to input hvp archive
-    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    /// path to input hvp archive, with --from-scratch the path of the new hvp archive
+    #[arg(value_hint = ValueHint::FilePath)]
     pub input_hvp: PathBuf,
-    /// path to folder of exported data
-    #[arg(value_hint = ValueHint::DirPath, value_parser = utils::is_dir)]
-    pub input_folder: PathBuf,
-    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    /// path to folder of exported data, or a zip of it (same as --input-zip). `-` read the new
+    /// content of the --entry file from stdin
+    #[arg(value_hint = ValueHint::AnyPath, value_parser = parse_input_folder, required_unless_present = "input_zip")]
+    pub input_folder: Option<PathBuf>,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new).
+    /// `-` write the new archive to stdout
     pub output: Option<PathBuf>,
     /// skip compression of the files
     #[arg(long, short = 'c', default_value_t = false, required = false)]
     pub skip_compression: bool,
+    /// compression level of the updated files, 0-9 (zlib) for obscure 1 and 1-13 (lzo) for
+    /// obscure 2 and final exam. higher levels make smaller archives but take longer, the
+    /// default is the level that the game archives use
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..=13), conflicts_with = "skip_compression")]
+    pub compression_level: Option<u8>,
+    /// align the data of every file to this many bytes, like 2048 for the console versions that
+    /// expect sector aligned data. the default is the alignment that the game archives use
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), conflicts_with = "guarantee_roundtrip")]
+    pub align: Option<u32>,
     /// validate checksums of the files
     #[arg(long, short = 's', default_value_t = ChecksumValidation::Yes, value_enum, required = false)]
     pub checksum_validation: ChecksumValidation,
@@ -46,96 +63,775 @@ pub struct Commands {
     /// create archive even when no files changed
     #[arg(long, default_value_t = false, required = false)]
     pub generate_anyway: bool,
+    /// when no file changed, fail if the new archive isn't byte identical to the original one
+    #[arg(
+        long,
+        default_value_t = false,
+        required = false,
+        conflicts_with = "from_scratch"
+    )]
+    pub guarantee_roundtrip: bool,
+    /// reopen the new archive after writing it and check the crc32s of its header and the
+    /// checksums of every file
+    #[arg(
+        long,
+        default_value_t = false,
+        required = false,
+        conflicts_with = "from_scratch"
+    )]
+    pub verify: bool,
+    /// with --verify, also decompress every updated file of the new archive
+    #[arg(long, default_value_t = false, required = false, requires = "verify")]
+    pub verify_updated: bool,
+    /// read the modified files from a zip file instead of input folder
+    #[arg(long, value_hint = ValueHint::FilePath, value_parser = utils::is_file, conflicts_with = "input_folder")]
+    pub input_zip: Option<PathBuf>,
+    /// build a brand new archive from the input folder without an original archive, require --game
+    #[arg(long, default_value_t = false, required = false, requires = "input_folder", conflicts_with_all = ["input_zip", "output"])]
+    pub from_scratch: bool,
+    /// write the new archive in big endian (wii version of obscure 2), only used with --from-scratch
+    #[arg(
+        long,
+        default_value_t = false,
+        required = false,
+        requires = "from_scratch"
+    )]
+    pub big_endian: bool,
+    /// file with the directories whose files are stored or compressed, one `stored <dir>` or
+    /// `compressed <dir>` per line. only used with --from-scratch, files that manifest.json
+    /// record as stored are always stored
+    #[arg(long, value_hint = ValueHint::FilePath, value_parser = utils::is_file, requires = "from_scratch")]
+    pub compression_rules: Option<PathBuf>,
+    /// only estimate the size of the new archive without writing it
+    #[arg(
+        long,
+        default_value_t = false,
+        required = false,
+        conflicts_with = "from_scratch"
+    )]
+    pub dry_run: bool,
+    /// fail before writing anything if the estimated size of the new archive is larger than this,
+    /// for example `700M`
+    #[arg(long, value_parser = size::parse, conflicts_with = "from_scratch")]
+    pub max_size: Option<u64>,
+    /// keep running and rebuild the archive every time a file in the input folder (or the first
+    /// extracted copy of a deduplicated file) changes
+    #[arg(long, default_value_t = false, required = false, requires = "input_folder", conflicts_with_all = ["from_scratch", "dry_run"])]
+    pub watch: bool,
+    /// match the files of the input folder with the files of the archive whatever their case
+    #[arg(long, default_value_t = false, required = false, conflicts_with_all = ["input_zip", "from_scratch"])]
+    pub ignore_case: bool,
+    /// path of the file inside the archive that is replaced by the data from stdin, when
+    /// the input folder is `-`
+    #[arg(long, conflicts_with_all = ["input_zip", "from_scratch", "watch"])]
+    pub entry: Option<PathBuf>,
+}
+
+/// `-` for stdin and stdout, like most command line tools
+const STDIO: &str = "-";
+
+fn parse_input_folder(path: &str) -> Result<PathBuf, String> {
+    match path {
+        STDIO => Ok(PathBuf::from(STDIO)),
+        _ => utils::is_dir_or_zip(path),
+    }
+}
+
+/// rebuild the archive with a progress bar
+fn rebuild(archive: &Archive, writer: &mut (impl Write + Seek)) -> anyhow::Result<()> {
+    let pb = utils::progress_bar(archive.metadata().file_count as _);
+    utils::watch_progress(&pb, |progress| archive.rebuild(&mut *writer, progress))
+        .context("failed to rebuild the archive")?;
+
+    pb.finish_with_message(
+        "rebuild finished"
+            .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+            .to_string(),
+    );
+
+    writer.flush().context("failed to flush writer")
 }
 
 impl Commands {
     /// handle the user command
-    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
-        let obscure2_names = match provider.game() {
-            Game::Obscure2 => match load_name_maps().context("failed to load name maps")? {
-                Some(names) => names,
-                None => {
-                    println!(
-                        "{} failed to load obscure2 (or alone in the dark 2008) name maps because no hash file was found",
-                        "[!]".yellow()
-                    );
+    pub fn start(mut self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        // a zip can be passed in place of the input folder, it's read like --input-zip
+        if let Some(input_zip) = self.input_folder.take_if(|p| p.is_file()) {
+            if self.from_scratch || self.watch {
+                anyhow::bail!(
+                    "--from-scratch and --watch need an input folder, they can't be used with a zip"
+                );
+            }
+            self.input_zip = Some(input_zip);
+        }
 
-                    Obscure2NameMap::default()
-                }
-            },
-            _ => Obscure2NameMap::default(), // we don't need to load name map for any other game
+        if self.output.as_deref() == Some(Path::new(STDIO)) {
+            if output::is_json() {
+                anyhow::bail!(
+                    "`-` already write the archive to stdout, it can't be used with `--json`"
+                );
+            }
+            if self.watch {
+                anyhow::bail!("--watch can't write the archive to stdout");
+            }
+            if self.verify {
+                anyhow::bail!(
+                    "--verify need an output file, it can't verify the archive on stdout"
+                );
+            }
+            output::set_streaming(true);
+        }
+
+        // read before anything else, so a prompt doesn't take the data from stdin
+        let stdin = match (
+            self.input_folder.as_deref() == Some(Path::new(STDIO)),
+            &self.entry,
+        ) {
+            (true, Some(_)) => {
+                let mut bytes = Vec::new();
+                std::io::stdin()
+                    .lock()
+                    .read_to_end(&mut bytes)
+                    .context("failed to read stdin")?;
+                Some(bytes)
+            }
+            (true, None) => anyhow::bail!(
+                "`-` as input folder need --entry, the file that is replaced by stdin"
+            ),
+            (false, Some(_)) => anyhow::bail!("--entry is only used when the input folder is `-`"),
+            (false, None) => None,
         };
 
+        if !self.watch {
+            return self.build(&provider, stdin);
+        }
+
+        let org_working_dir =
+            std::env::current_dir().context("failed to get current working directory")?;
+        let input_folder = std::path::absolute(
+            self.input_folder
+                .as_ref()
+                .expect("clap should require input folder"),
+        )
+        .context("failed to get absolute path of input folder")?;
+
+        // the output may be inside the input folder, it shouldn't trigger another rebuild
+        let mut ignore = vec![
+            PathBuf::from(HASHES_FILE),
+            PathBuf::from(MANIFEST_FILE),
+            PathBuf::from(DEDUP_FILE),
+        ];
+        if let Ok(output) = std::path::absolute(self.output_path())
+            && let Ok(output) = output.strip_prefix(&input_folder)
+        {
+            ignore.push(output.to_path_buf());
+        }
+
+        let mut watcher = FolderWatcher::new(input_folder.clone(), ignore)
+            .context("failed to watch the input folder")?;
+        // the deduplicated files are read from where they were first extracted
+        if let Some(deduped) = Deduped::load(&input_folder.join(DEDUP_FILE))? {
+            watcher
+                .watch_files(deduped.files.into_values().map(|entry| entry.source))
+                .context("failed to watch the sources of the deduplicated files")?;
+        }
+
+        loop {
+            if let Err(e) = self.build(&provider, None) {
+                println!("{} {e:#}", "[!]".red());
+            }
+
+            std::env::set_current_dir(&org_working_dir)
+                .context("failed to change working directory to original base path")?;
+
+            println!(
+                "{} watching {} for changes, press ctrl+c to stop",
+                "[?]".green(),
+                input_folder.display()
+            );
+
+            let changed = watcher.wait_for_change();
+            println!("{} {} files changed:", "[+]".green(), changed.len());
+            for path in &changed {
+                println!(" {} {}", "|>".cyan(), path.display());
+            }
+        }
+    }
+
+    /// output path of the new archive
+    fn output_path(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input_hvp))
+    }
+
+    /// update the original archive with the modified files and rebuild it, `stdin` is the new
+    /// content of --entry
+    fn build(&self, provider: &ArchiveProvider, stdin: Option<Vec<u8>>) -> anyhow::Result<()> {
+        let mut obscure2_names = name_maps_for_game(provider.game())?;
+
+        let manifest = self.load_manifest()?;
+        check_manifest_raw(manifest.as_ref())?;
+        let manifest = manifest.filter(|manifest| check_manifest_game(manifest, provider.game()));
+        if let Some(manifest) = &manifest
+            && manifest.game == hvp_archive::Game::Obscure2
+        {
+            let names = manifest.obscure2_names();
+            println!(
+                "{} adding {} names from manifest.json to the name maps",
+                "[+]".green(),
+                names.len()
+            );
+            obscure2_names.extend(names);
+        }
+
         let mut archive = Archive::new_with_options(
-            &provider,
+            provider,
             Options {
                 obscure2_names,
                 rebuild_skip_compression: self.skip_compression,
+                compression_level: self.compression_level,
+                alignment: self.align,
+                guarantee_roundtrip: self.guarantee_roundtrip,
+                ..Default::default()
             },
         );
 
         utils::print_metadata(archive.metadata());
 
-        if matches!(
-            self.checksum_validation,
-            ChecksumValidation::Yes | ChecksumValidation::Prompt
-        ) {
-            println!("{} validating entries checksum", "[+]".green());
-            if !archive.entries_checksum_match() {
-                let mut should_exit = true;
-
-                if self.checksum_validation == ChecksumValidation::Prompt {
-                    print!(
-                        "{} checksum mismatch, continue anyway? [y/n]: ",
-                        "[!]".yellow()
-                    );
-                    anstream::stdout().flush()?;
-                    let input = utils::prompt()?.to_lowercase();
-                    match input.as_str() {
-                        "y" => should_exit = false,
-                        "n" => should_exit = true,
-                        _ => {
-                            println!("{} invalid input: '{}'", "[!]".red(), input);
-                            should_exit = true;
-                        }
+        utils::validate_checksums(&archive, self.checksum_validation)?;
+
+        if let Some(manifest) = &manifest {
+            check_manifest_files(manifest, &archive);
+        }
+
+        let output = self.output_path();
+        let to_stdout = output == Path::new(STDIO);
+
+        match to_stdout {
+            true => println!("{} writing the new hvp archive to stdout", "[+]".green()),
+            false => println!("{} output hvp archive: {}", "[+]".green(), output.display()),
+        }
+
+        let org_working_dir =
+            std::env::current_dir().context("failed to get current working directory")?;
+
+        // deduplicated files whose first extracted copy was modified
+        let linked: ahash::HashMap<PathBuf, PathBuf> = match &self.input_folder {
+            Some(input_folder) if self.input_zip.is_none() && stdin.is_none() => {
+                Deduped::load(&input_folder.join(DEDUP_FILE))?
+                    .map(|deduped| deduped.changed(input_folder))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect()
+            }
+            _ => ahash::HashMap::default(),
+        };
+        if !linked.is_empty() {
+            println!(
+                "{} {} deduplicated files were modified where they were first extracted",
+                "[+]".green(),
+                linked.len()
+            );
+        }
+
+        let mut source = match (&self.input_folder, &self.input_zip, stdin) {
+            (_, _, Some(bytes)) => {
+                let entry = self
+                    .entry
+                    .as_ref()
+                    .expect("stdin is only read with --entry");
+                let path = archive
+                    .find_file(entry)
+                    .with_context(|| format!("can't find {} in the archive", entry.display()))?
+                    .path;
+                println!(
+                    "{} read {} bytes from stdin for {}",
+                    "[+]".green(),
+                    bytes.len(),
+                    path.display()
+                );
+                Source::Stdin(path, Some(bytes))
+            }
+            (_, Some(input_zip), None) => self.zip_source(input_zip)?,
+            (Some(input_folder), None, None) => Source::Folder(self.folder_files(input_folder)?),
+            (None, None, None) => unreachable!("clap should require input folder or input zip"),
+        };
+
+        if source.is_empty() && linked.is_empty() && !self.generate_anyway {
+            anyhow::bail!("no modified file found, so there is nothing to import. aborting")
+        }
+
+        println!("{} updating archive entries", "[+]".green());
+
+        let mut updated_files = Vec::new();
+        // files of the input folder that aren't in the archive
+        let mut unmatched = Vec::new();
+        if let Source::Folder(files) = &source {
+            // the working directory is the input folder
+            let report = archive.apply_paths(
+                "",
+                files.iter().cloned(),
+                MatchOptions {
+                    ignore_case: self.ignore_case,
+                },
+            );
+
+            for path in &report.conflicts {
+                println!(
+                    "{} {}: another file of the input folder match the same file, skipping it",
+                    "[!]".yellow(),
+                    path.display()
+                );
+            }
+
+            updated_files.extend(report.matched.into_iter().map(|(path, _)| path));
+            unmatched = report.unmatched;
+        }
+
+        let applied: ahash::HashSet<PathBuf> = updated_files.iter().cloned().collect();
+        for mut entry in archive.files_mut() {
+            let update = match &mut source {
+                Source::Folder(_) => match linked.get(&entry.path) {
+                    Some(source) if !applied.contains(&entry.path) => {
+                        UpdateKind::File(source.clone())
                     }
+                    _ => continue,
+                },
+                Source::Zip(zip, files) => {
+                    let Some(zip_entry) = files.get(&entry.path) else {
+                        continue;
+                    };
+
+                    let bytes = zip.read(zip_entry).with_context(|| {
+                        format!("failed to read {} from input zip", zip_entry.name)
+                    })?;
+
+                    UpdateKind::Bytes(bytes)
                 }
+                Source::Stdin(path, bytes) => match bytes.take_if(|_| entry.path == *path) {
+                    Some(bytes) => UpdateKind::Bytes(bytes),
+                    None => continue,
+                },
+            };
+
+            entry.update(update);
+            updated_files.push(entry.path.clone());
+        }
+
+        if updated_files.is_empty() && !self.generate_anyway {
+            anyhow::bail!("nothing in the archive updated. aborting")
+        } else if self.generate_anyway {
+            println!(
+                "{} updated nothing in the archive, rebuilding anyway",
+                "[+]".green()
+            );
+        }
+
+        // paths of the updated files are relative to the current working directory
+        if self.dry_run || self.max_size.is_some() {
+            let archive_paths: ahash::HashSet<PathBuf> = archive.files().map(|f| f.path).collect();
+            let ignored: Vec<&Path> = match &source {
+                Source::Folder(_) => unmatched.iter().map(PathBuf::as_path).collect(),
+                _ => source
+                    .paths()
+                    .filter(|p| !archive_paths.contains(*p))
+                    .collect(),
+            };
+
+            self.check_preview(&archive, &ignored)?;
+            if self.dry_run {
+                return Ok(());
+            }
+        }
+
+        println!(
+            "{} starting the process of creating a new hvp archive",
+            "[+]".green()
+        );
+
+        // this is hacky but it'll work
+        std::env::set_current_dir(&org_working_dir)
+            .context("failed to change working directory to original base path")?;
+
+        let mut writer = match to_stdout {
+            true => None,
+            false => Some(BufWriter::new(
+                File::create(&output).context("failed to create output hvp archive file")?,
+            )),
+        };
+
+        if let Source::Folder(_) = source {
+            let input_folder = self
+                .input_folder
+                .as_ref()
+                .expect("folder source without folder");
+            std::env::set_current_dir(input_folder)
+                .context("failed to change working directory to output path")?;
+        }
+
+        let size = match &mut writer {
+            Some(writer) => {
+                rebuild(&archive, writer)?;
+                writer.get_ref().metadata().map_or(0, |m| m.len())
+            }
+            // stdout can't seek, so the archive is built in memory first
+            None => {
+                let mut writer = Cursor::new(Vec::new());
+                rebuild(&archive, &mut writer)?;
+                let bytes = writer.into_inner();
+
+                let mut stdout = std::io::stdout().lock();
+                stdout
+                    .write_all(&bytes)
+                    .and_then(|_| stdout.flush())
+                    .context("failed to write the archive to stdout")?;
+                bytes.len() as u64
+            }
+        };
+
+        println!("{} rebuild finished", "[+]".green());
+
+        // the working directory may be the input folder now
+        let verified = match self.verify {
+            true => Some(self.verify_output(
+                &org_working_dir.join(&output),
+                &mut archive,
+                &updated_files,
+            )?),
+            false => None,
+        };
+
+        utils::report_rebuild(
+            &output,
+            size,
+            archive.metadata(),
+            json!({ "updated_files": updated_files.len(), "verified": verified }),
+        );
+
+        Ok(())
+    }
+
+    /// reopen the written archive and check its checksums, and decompress the updated files
+    /// with --verify-updated. the output is kept when the verification fails
+    fn verify_output(
+        &self,
+        output: &Path,
+        archive: &mut Archive,
+        updated_files: &[PathBuf],
+    ) -> anyhow::Result<serde_json::Value> {
+        println!("{} verifying the new hvp archive", "[+]".green());
+
+        let file = File::open(output).context("failed to open the new hvp archive")?;
+        let provider = ArchiveProvider::new(file, Some(archive.metadata().game))
+            .context("the new hvp archive can't be loaded")?;
+        // the same names, so the updated files can be found by their path
+        let new_archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names: std::mem::take(&mut archive.options.obscure2_names),
+                ..Default::default()
+            },
+        );
+
+        let report = new_archive.verify();
+        for crc in report.header_crcs.iter().filter(|c| !c.is_match()) {
+            println!(
+                "{} {} crc32: stored {:#010x}, computed {:#010x}",
+                "[!]".red(),
+                crc.name,
+                crc.stored,
+                crc.computed,
+            );
+        }
+        for mismatch in &report.mismatches {
+            println!(
+                "{} {}: stored checksum {:#010x}, computed from stored bytes {:#010x}",
+                "[!]".red(),
+                mismatch.path.display(),
+                mismatch.expected,
+                mismatch.computed,
+            );
+        }
+        if !report.is_ok() {
+            anyhow::bail!(
+                "the new hvp archive has invalid checksums, {} of {} files",
+                report.mismatches.len(),
+                report.files_checked
+            );
+        }
+
+        let mut decompressed = 0;
+        if self.verify_updated {
+            for path in updated_files {
+                let file = new_archive.find_file(path).with_context(|| {
+                    format!("{} is missing from the new hvp archive", path.display())
+                })?;
+                file.get_bytes().with_context(|| {
+                    format!(
+                        "{} can't be decompressed in the new hvp archive",
+                        path.display()
+                    )
+                })?;
+                decompressed += 1;
+            }
+        }
+
+        match self.verify_updated {
+            true => println!(
+                "{} verified {} files, {decompressed} updated files were decompressed",
+                "[+]".green(),
+                report.files_checked
+            ),
+            false => println!("{} verified {} files", "[+]".green(), report.files_checked),
+        }
+
+        Ok(json!({
+            "files_checked": report.files_checked,
+            "decompressed_files": self.verify_updated.then_some(decompressed),
+        }))
+    }
+
+    /// build a new archive from all the files of input folder
+    /// size of the largest file of the input folder or zip, they are all compressed in memory
+    pub fn largest_input_size(&self) -> u64 {
+        let folder = self.input_folder.as_deref();
+        if let Some(zip) = self.input_zip.as_deref().or(folder.filter(|p| p.is_file())) {
+            return ZipReader::open(zip).map_or(0, |zip| {
+                zip.entries()
+                    .iter()
+                    .map(|e| e.uncompressed_size as u64)
+                    .max()
+                    .unwrap_or(0)
+            });
+        }
+        match folder.filter(|p| p.is_dir()) {
+            Some(folder) => walkdir::WalkDir::new(folder)
+                .into_iter()
+                .filter_map(|e| e.ok()?.metadata().ok())
+                .filter(|m| m.is_file())
+                .map(|m| m.len())
+                .max()
+                .unwrap_or(0),
+            None => 0,
+        }
+    }
 
-                if should_exit {
+    pub fn start_from_scratch(self, game: Option<hvp_archive::Game>) -> anyhow::Result<()> {
+        let input_folder = self
+            .input_folder
+            .as_ref()
+            .expect("clap should require input folder");
+        if input_folder == Path::new(STDIO) {
+            anyhow::bail!("--from-scratch need an input folder, it can't read from stdin");
+        }
+
+        // the manifest keep the files that were stored without compression the same way
+        let manifest = Manifest::load(&input_folder.join(MANIFEST_FILE))?;
+        check_manifest_raw(manifest.as_ref())?;
+        let game = match (game, &manifest) {
+            (Some(game), Some(manifest)) => {
+                check_manifest_game(manifest, game);
+                game
+            }
+            (Some(game), None) => game,
+            (None, Some(manifest)) => {
+                println!(
+                    "{} using game {:?} from manifest.json",
+                    "[+]".green(),
+                    manifest.game
+                );
+                manifest.game
+            }
+            (None, None) => {
+                anyhow::bail!("--game is required when creating an archive from scratch")
+            }
+        };
+        let manifest = manifest.filter(|m| m.game == game);
+
+        let mut builder = ArchiveBuilder::new(game)
+            .big_endian(self.big_endian)
+            .skip_compression(self.skip_compression)
+            .compression_level(self.compression_level)
+            .alignment(self.align);
+        if let Some(path) = &self.compression_rules {
+            builder = builder.compression_rules(utils::load_compression_rules(path)?);
+        }
+
+        let mut files: Vec<(PathBuf, PathBuf)> = utils::list_files(input_folder, true)
+            .into_iter()
+            .filter(|p| !is_sidecar(p))
+            .map(|p| (input_folder.join(&p), p))
+            .collect();
+
+        // deduplicated files are read from where they were first extracted
+        if let Some(deduped) = Deduped::load(&input_folder.join(DEDUP_FILE))? {
+            for (path, entry) in deduped.files {
+                if input_folder.join(&path).is_file() {
+                    continue;
+                }
+                if !entry.source.is_file() {
                     anyhow::bail!(
-                        "archive entries checksum doesn't match, maybe the archive is broken?"
+                        "{} is deduplicated, but {} where it was first extracted doesn't exist",
+                        path.display(),
+                        entry.source.display()
                     );
                 }
+                files.push((entry.source, path));
+            }
+        }
+
+        for (file, path) in files {
+            let data = UpdateKind::File(file);
+            let uncompressed = manifest
+                .as_ref()
+                .and_then(|m| m.files.get(&path))
+                .is_some_and(|e| !e.compressed);
+
+            match uncompressed {
+                true => builder.add_file_uncompressed(&path, data),
+                false => builder.add_file(&path, data),
             }
+            .with_context(|| format!("failed to add {} to the archive", path.display()))?;
+        }
+
+        let metadata = builder.metadata();
+        if metadata.file_count == 0 {
+            anyhow::bail!("no file found in input folder")
         }
 
-        let output = self.output.unwrap_or_else(|| {
-            self.input_hvp.with_extension(
-                self.input_hvp
-                    .extension()
-                    .and_then(OsStr::to_str)
-                    .map(|e| format!("new.{e}"))
-                    .unwrap_or("new".to_owned()),
-            )
-        });
+        utils::print_metadata(metadata);
+
+        println!(
+            "{} output hvp archive: {}",
+            "[+]".green(),
+            self.input_hvp.display()
+        );
+
+        let mut writer = BufWriter::new(
+            File::create(&self.input_hvp).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(metadata.file_count as _);
+        let metadata = utils::watch_progress(&pb, |progress| builder.build(&mut writer, progress))
+            .context("failed to build the archive")?;
+
+        pb.finish_with_message(
+            "build finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!("{} build finished", "[+]".green());
 
-        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+        utils::report_rebuild(
+            &self.input_hvp,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            metadata,
+            json!({}),
+        );
 
-        let files = utils::list_files(&self.input_folder, true);
+        Ok(())
+    }
+
+    /// print the estimated result of the rebuild and check it against the size budget
+    fn check_preview(&self, archive: &Archive, ignored: &[&Path]) -> anyhow::Result<()> {
+        let preview = archive
+            .preview_rebuild()
+            .context("failed to preview the rebuild")?;
+
+        let skipped = archive.metadata().file_count - preview.entries.len();
+        println!(
+            "{} {} files will be updated, {} unchanged files will be copied as is",
+            "[+]".green(),
+            preview.entries.len(),
+            skipped
+        );
+
+        for entry in &preview.entries {
+            println!(
+                " {} {} {} -> {}{} ({:+})",
+                "|>".cyan(),
+                entry.path.display(),
+                entry.original_size,
+                if entry.estimated { "~" } else { "" },
+                entry.projected_size,
+                entry.delta()
+            );
+        }
+
+        println!(
+            "{} estimated size: {} ({:+} bytes), {} files will be compressed",
+            "[+]".green(),
+            size::format(preview.projected_size),
+            preview.size_delta(),
+            preview.recompressions
+        );
+
+        if !ignored.is_empty() {
+            println!(
+                "{} {} modified files aren't in the archive and will be ignored:",
+                "[!]".yellow(),
+                ignored.len()
+            );
+            for path in ignored {
+                println!(" {} {}", "|>".cyan(), path.display());
+            }
+        }
+
+        output::report(
+            "preview",
+            json!({
+                "updated": preview.entries.len(),
+                "skipped": skipped,
+                "ignored": ignored,
+                "original_size": preview.original_size,
+                "projected_size": preview.projected_size,
+                "size_delta": preview.size_delta(),
+                "recompressions": preview.recompressions,
+                "entries": preview.entries.iter().map(|e| json!({
+                    "path": e.path,
+                    "original_size": e.original_size,
+                    "projected_size": e.projected_size,
+                    "delta": e.delta(),
+                    "estimated": e.estimated,
+                })).collect::<Vec<_>>(),
+            }),
+        );
+
+        if let Some(max_size) = self.max_size
+            && preview.projected_size > max_size
+        {
+            anyhow::bail!(
+                "estimated size {} is larger than --max-size {}",
+                size::format(preview.projected_size),
+                size::format(max_size)
+            );
+        }
+
+        Ok(())
+    }
+
+    /// list the files of input folder and filter them based on hashes.json if possible.
+    /// this will also change the working directory to input folder
+    fn folder_files(&self, input_folder: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let files: Vec<PathBuf> = utils::list_files(input_folder, true)
+            .into_iter()
+            .filter(|p| !is_sidecar(p))
+            .collect();
 
         if files.is_empty() && self.generate_anyway {
             anyhow::bail!("no file found in input folder")
         }
 
-        let org_working_dir =
-            std::env::current_dir().context("failed to get current working directory")?;
-
         // we do this so we don't have to join output dir with entry path each time
         println!(
             "{} changing working directory to input folder",
             "[+]".green()
         );
-        std::env::set_current_dir(&self.input_folder)
+        std::env::set_current_dir(input_folder)
             .context("failed to change working directory to output path")?;
 
         print!(
@@ -146,8 +842,8 @@ impl Commands {
 
         let files = if Path::new(HASHES_FILE).is_file() && !self.update_all_files {
             println!(". {}", "filtering based on modified files".blink().cyan());
-            let txt = std::fs::read_to_string(HASHES_FILE).context("failed to read hashes.json")?;
-            let hashes: ahash::HashMap<u32, u32> = serde_json::from_str(&txt).context(
+            let txt = std::fs::read(HASHES_FILE).context("failed to read hashes.json")?;
+            let hashes = hashes::load(&txt).context(
                 "failed to load file hashes from hashes.json, if you modified it just remove it",
             )?;
 
@@ -161,7 +857,7 @@ impl Commands {
                     let bytes = std::fs::read(&path)?;
                     let path_str = path.display().to_string();
 
-                    let name_crc32 = crc32fast::hash(path_str.as_bytes());
+                    let name_crc32 = hashes::path_crc32(&path);
                     let content_crc32 = crc32fast::hash(&bytes);
 
                     pb.set_message(path_str);
@@ -208,82 +904,170 @@ impl Commands {
             files
         };
 
-        if files.is_empty() && !self.generate_anyway {
-            anyhow::bail!("no modified file found, so there is nothing to import. aborting")
+        Ok(files)
+    }
+
+    /// open the input zip and filter its files based on hashes.json inside it if possible
+    fn zip_source(&self, input_zip: &Path) -> anyhow::Result<Source> {
+        let mut zip = ZipReader::open(input_zip).context("failed to open input zip")?;
+
+        let hashes_file = Path::new(HASHES_FILE);
+        let files: Vec<ZipEntry> = zip
+            .entries()
+            .iter()
+            .filter(|e| !is_sidecar(&e.path()))
+            .cloned()
+            .collect();
+
+        if files.is_empty() && self.generate_anyway {
+            anyhow::bail!("no file found in input zip")
         }
 
-        println!("{} updating archive entries", "[+]".green());
+        print!("{} found {} files in input zip", "[+]".green(), files.len());
 
-        let mut updated = false;
-        for mut entry in archive.files_mut() {
-            if !files.contains(&entry.path) {
-                continue;
+        let hashes_entry = zip.find(hashes_file).cloned();
+
+        let files = match hashes_entry {
+            Some(hashes_entry) if !self.update_all_files => {
+                println!(". {}", "filtering based on modified files".blink().cyan());
+                let txt = zip
+                    .read(&hashes_entry)
+                    .context("failed to read hashes.json from input zip")?;
+                let hashes = hashes::load(&txt).context(
+                    "failed to load file hashes from hashes.json, if you modified it just remove it",
+                )?;
+
+                let all_files_len = files.len();
+
+                // zip already store crc32 of the content, so there is no need to read anything
+                let filterd_files: Vec<ZipEntry> = files
+                    .into_iter()
+                    .filter(|e| {
+                        let name_crc32 = hashes::path_crc32(&e.path());
+                        hashes.get(&name_crc32) != Some(&e.crc32)
+                    })
+                    .collect();
+
+                println!(
+                    "{} found {} modified files in input zip, {} files were untoched so we skip them",
+                    "[+]".green(),
+                    filterd_files.len(),
+                    all_files_len - filterd_files.len(),
+                );
+
+                filterd_files
+            }
+            _ => {
+                println!();
+                files
             }
+        };
+
+        let files = files.into_iter().map(|e| (e.path(), e)).collect();
+
+        Ok(Source::Zip(zip, files))
+    }
 
-            entry.update(UpdateKind::File(entry.path.clone()));
-            updated = true;
+    /// load manifest.json from the input folder or input zip, if there is one
+    fn load_manifest(&self) -> anyhow::Result<Option<Manifest>> {
+        if let Some(input_zip) = &self.input_zip {
+            let mut zip = ZipReader::open(input_zip).context("failed to open input zip")?;
+            let Some(entry) = zip.find(Path::new(MANIFEST_FILE)).cloned() else {
+                return Ok(None);
+            };
+
+            let bytes = zip
+                .read(&entry)
+                .context("failed to read manifest.json from input zip")?;
+            return Manifest::from_json(&bytes)
+                .context("failed to load manifest.json, if you modified it just remove it")
+                .map(Some);
         }
 
-        if !updated && !self.generate_anyway {
-            anyhow::bail!("nothing in the archive updated. aborting")
-        } else if self.generate_anyway {
-            println!(
-                "{} updated nothing in the archive, rebuilding anyway",
-                "[+]".green()
-            );
+        match &self.input_folder {
+            Some(input_folder) => Manifest::load(&input_folder.join(MANIFEST_FILE)),
+            None => Ok(None),
         }
+    }
+}
 
-        println!(
-            "{} starting the process of creating a new hvp archive",
-            "[+]".green()
+/// files that are extracted with `--raw` are still compressed, so they can't be added as they are
+fn check_manifest_raw(manifest: Option<&Manifest>) -> anyhow::Result<()> {
+    if manifest.is_some_and(|m| m.raw) {
+        anyhow::bail!(
+            "the files were extracted with --raw and are still compressed, extract them again without it"
         );
+    }
 
-        // this is hacky but it'll work
-        std::env::set_current_dir(org_working_dir)
-            .context("failed to change working directory to original base path")?;
+    Ok(())
+}
 
-        let mut writer = BufWriter::new(
-            File::create(output).context("failed to create output hvp archive file")?,
+/// warn about a manifest of another game, return whatever it can be used
+fn check_manifest_game(manifest: &Manifest, game: hvp_archive::Game) -> bool {
+    if manifest.game != game {
+        println!(
+            "{} manifest.json is for {:?} but the archive is for {:?}, ignoring it",
+            "[!]".yellow(),
+            manifest.game,
+            game
         );
+    }
 
-        std::env::set_current_dir(&self.input_folder)
-            .context("failed to change working directory to output path")?;
-
-        let pb = utils::progress_bar(archive.metadata().file_count as _);
-        let progress = RebuildProgressCli(pb.clone());
-
-        archive
-            .rebuild(&mut writer, progress)
-            .context("failed to rebuild the archive")?;
-
-        pb.finish_with_message(
-            "rebuild finished"
-                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
-                .to_string(),
-        );
+    manifest.game == game
+}
 
-        writer.flush().context("failed to flush writer")?;
+/// warn about the files whose compression or checksum doesn't match the manifest,
+/// it usually mean that the input folder is extracted from another version of the archive
+fn check_manifest_files(manifest: &Manifest, archive: &Archive) {
+    let mismatched: Vec<PathBuf> = archive
+        .files()
+        .filter(|file| {
+            manifest.files.get(&file.path).is_some_and(|entry| {
+                entry.compressed != file.is_compressed() || entry.checksum != file.stored_checksum()
+            })
+        })
+        .map(|file| file.path)
+        .collect();
 
-        println!("{} rebuild finished", "[+]".green());
+    if mismatched.is_empty() {
+        return;
+    }
 
-        Ok(())
+    println!(
+        "{} {} files don't match manifest.json, the input may be extracted from another version of the archive:",
+        "[!]".yellow(),
+        mismatched.len()
+    );
+    for path in &mismatched {
+        println!(" {} {}", "|>".cyan(), path.display());
     }
 }
 
-struct RebuildProgressCli(ProgressBar);
+/// where the updated files come from
+enum Source {
+    /// list of files inside input folder, relative to it
+    Folder(Vec<PathBuf>),
+    /// opened zip and its files by their path
+    Zip(ZipReader, ahash::HashMap<PathBuf, ZipEntry>),
+    /// path of the file and its new content, taken when the file is updated
+    Stdin(PathBuf, Option<Vec<u8>>),
+}
 
-impl RebuildProgress for RebuildProgressCli {
-    fn inc(&self, message: Option<String>) {
-        self.0.inc(1);
-        if let Some(msg) = message {
-            self.0.set_message(msg);
+impl Source {
+    /// paths of the modified files
+    fn paths(&self) -> Box<dyn Iterator<Item = &Path> + '_> {
+        match self {
+            Source::Folder(files) => Box::new(files.iter().map(PathBuf::as_path)),
+            Source::Zip(_, files) => Box::new(files.keys().map(PathBuf::as_path)),
+            Source::Stdin(path, _) => Box::new(std::iter::once(path.as_path())),
         }
     }
 
-    fn inc_n(&self, n: usize, message: Option<String>) {
-        self.0.inc(n as _);
-        if let Some(msg) = message {
-            self.0.set_message(msg);
+    fn is_empty(&self) -> bool {
+        match self {
+            Source::Folder(files) => files.is_empty(),
+            Source::Zip(_, files) => files.is_empty(),
+            Source::Stdin(..) => false,
         }
     }
 }
@@ -0,0 +1,777 @@
+//! a long running daemon that keep archives open between requests
+//!
+//! the daemon listen on a local tcp socket and speak json-rpc 2.0, each request and
+//! each response is a single line of json. binary data is sent as base64.
+//!
+//! available methods:
+//! - `open {path, game?}`: open an archive (or reuse it if already open), return its handle and metadata
+//! - `close {handle}`: close the archive and drop all of its pending replacements
+//! - `list {handle}`: list all the files of the archive
+//! - `read {handle, path}`: content of a file, pending replacements are returned instead of the original data
+//! - `replace {handle, path, data}`: queue new content for a file, `data: null` drop the queued content
+//! - `save {handle, output, skip_compression?}`: rebuild the archive with queued replacements to output
+//! - `shutdown`: stop the daemon and close the connections of the other clients
+//!
+//! editor plugins can use sessions to push assets straight into the game files:
+//! - `session.start {path, game?}`: open the archive and start a session over it, return the session id
+//! - `session.push {session, path, data}`: queue new content for a file in the session
+//! - `session.patch {session, skip_compression?}`: patch the queued files in place into the archive file
+//! - `session.end {session}`: end the session and drop its queued files
+//!
+//! every open archive has its own lock, so a slow `save` or `session.patch` only hold the
+//! requests to the same archive.
+
+use std::{
+    collections::BTreeMap,
+    ffi::OsString,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, MutexGuard,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use hvp_archive::{
+    archive::{
+        Archive, DeadSpacePolicy, Obscure2NameMap, Options, entry::UpdateKind,
+        rebuild_progress::RebuildProgress,
+    },
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+
+use super::{Game, base64, name_maps_for_game, output::println};
+
+#[derive(Parser)]
+pub struct Commands {
+    /// port to listen on
+    #[arg(long, short = 'p', default_value_t = 4870)]
+    pub port: u16,
+    /// address to bind to
+    #[arg(long, default_value = "127.0.0.1")]
+    pub bind: String,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self) -> anyhow::Result<()> {
+        let listener = TcpListener::bind((self.bind.as_str(), self.port))
+            .with_context(|| format!("failed to listen on {}:{}", self.bind, self.port))?;
+        let address = listener.local_addr()?;
+
+        println!("{} daemon listening on {address}", "[+]".green());
+
+        let daemon = Daemon::default();
+        let shutdown = AtomicBool::new(false);
+        // clones of the open connections, they are closed on shutdown so the threads that wait
+        // for the next request of an idle client can end
+        let connections = Mutex::new(BTreeMap::new());
+        // a listener bound to all interfaces can't always be connected to with its own address
+        let wake_address = match address.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => (Ipv4Addr::LOCALHOST, address.port()).into(),
+            IpAddr::V6(ip) if ip.is_unspecified() => (Ipv6Addr::LOCALHOST, address.port()).into(),
+            _ => address,
+        };
+
+        std::thread::scope(|s| {
+            for (id, stream) in listener.incoming().enumerate() {
+                if shutdown.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let stream = match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                    Ok((clone, stream)) => {
+                        lock(&connections).insert(id, clone);
+                        stream
+                    }
+                    Err(e) => {
+                        println!("{} failed to accept connection: {e}", "[!]".yellow());
+                        continue;
+                    }
+                };
+
+                let (daemon, shutdown, connections) = (&daemon, &shutdown, &connections);
+                s.spawn(move || {
+                    if let Err(e) = handle_connection(daemon, shutdown, stream) {
+                        // closing the connections on shutdown can fail a read
+                        if !shutdown.load(Ordering::Relaxed) {
+                            println!("{} connection closed with error: {e}", "[!]".yellow());
+                        }
+                    }
+                    lock(connections).remove(&id);
+
+                    if shutdown.load(Ordering::Relaxed) {
+                        // wake up the listener so it can see the shutdown flag
+                        let _ = TcpStream::connect(wake_address);
+                    }
+                });
+            }
+
+            for stream in lock(&connections).values() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        });
+
+        println!("{} daemon stopped", "[+]".green());
+
+        Ok(())
+    }
+}
+
+fn handle_connection(
+    daemon: &Daemon,
+    shutdown: &AtomicBool,
+    stream: TcpStream,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(&stream);
+
+    for line in BufReader::new(&stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let Some(response) = handle_message(daemon, shutdown, &line) else {
+            continue;
+        };
+
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+
+        if shutdown.load(Ordering::Relaxed) {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// handle a single json-rpc message, return `None` for notifications
+fn handle_message(daemon: &Daemon, shutdown: &AtomicBool, line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return Some(error_response(Value::Null, RpcError::parse(e))),
+    };
+
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return Some(error_response(
+            id.unwrap_or_default(),
+            RpcError::invalid_request("missing method"),
+        ));
+    };
+    let params = request.get("params").cloned().unwrap_or_default();
+
+    println!("{} {method}", "[?]".green());
+
+    let result = match method {
+        "shutdown" => {
+            shutdown.store(true, Ordering::Relaxed);
+            Ok(Value::Null)
+        }
+        _ => daemon.call(method, &params),
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => error_response(id, e),
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+/// json-rpc error
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn parse(e: impl std::fmt::Display) -> Self {
+        Self {
+            code: -32700,
+            message: format!("parse error: {e}"),
+        }
+    }
+
+    fn invalid_request(message: &str) -> Self {
+        Self {
+            code: -32600,
+            message: message.to_owned(),
+        }
+    }
+
+    fn method_not_found(method: &str) -> Self {
+        Self {
+            code: -32601,
+            message: format!("unknown method: {method}"),
+        }
+    }
+
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self {
+            code: -32602,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(e: anyhow::Error) -> Self {
+        Self {
+            code: -32000,
+            message: format!("{e:#}"),
+        }
+    }
+}
+
+type RpcResult = Result<Value, RpcError>;
+
+self_cell::self_cell!(
+    /// the provider of an open archive and the archive over it, so the archive is only built once
+    struct LoadedArchive {
+        owner: ArchiveProvider,
+        #[not_covariant]
+        dependent: Archive,
+    }
+);
+
+impl LoadedArchive {
+    fn load(provider: ArchiveProvider, names: Obscure2NameMap) -> Self {
+        Self::new(provider, |provider| {
+            Archive::new_with_options(
+                provider,
+                Options {
+                    obscure2_names: names,
+                    ..Default::default()
+                },
+            )
+        })
+    }
+
+    /// a new archive over the same provider, for changes that shouldn't touch the shared one
+    fn fresh(&self, f: impl for<'p> FnOnce(Archive<'p>) -> RpcResult) -> RpcResult {
+        self.with_dependent(|provider, archive| {
+            f(Archive::new_with_options(
+                provider,
+                Options {
+                    obscure2_names: archive.options.obscure2_names.clone(),
+                    ..Default::default()
+                },
+            ))
+        })
+    }
+}
+
+/// an archive that is kept open by the daemon
+struct OpenArchive {
+    path: PathBuf,
+    /// `None` when the archive couldn't be loaded again after patching
+    loaded: Option<LoadedArchive>,
+    /// queued content for files, applied on save
+    replacements: BTreeMap<PathBuf, Vec<u8>>,
+    /// queued content for files of each session over the archive, applied on patch
+    sessions: BTreeMap<u64, BTreeMap<PathBuf, Vec<u8>>>,
+}
+
+impl OpenArchive {
+    fn loaded(&self) -> Result<&LoadedArchive, RpcError> {
+        self.loaded.as_ref().ok_or_else(|| {
+            RpcError::from(anyhow::anyhow!(
+                "{} couldn't be loaded again after patching, close and open it again",
+                self.path.display()
+            ))
+        })
+    }
+
+    /// pass the archive to `f`
+    fn with_archive<R>(&self, f: impl for<'p> FnOnce(&Archive<'p>) -> R) -> Result<R, RpcError> {
+        Ok(self.loaded()?.with_dependent(|_, archive| f(archive)))
+    }
+
+    /// make sure the file exist in the archive
+    fn check_file(&self, path: &Path) -> Result<(), RpcError> {
+        match self.with_archive(|archive| archive.find_file(path).is_some())? {
+            true => Ok(()),
+            false => Err(unknown_file(path)),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    archives: BTreeMap<u64, Arc<Mutex<OpenArchive>>>,
+    /// handle of each open archive by its canonical path
+    paths: BTreeMap<PathBuf, u64>,
+    next_handle: u64,
+    /// handle of the archive of each session
+    sessions: BTreeMap<u64, u64>,
+    next_session: u64,
+    /// obscure 2 name maps, loaded on first use
+    names: Option<Obscure2NameMap>,
+}
+
+#[derive(Default)]
+struct Daemon {
+    /// only held while looking up or changing the open archives and sessions
+    state: Mutex<State>,
+}
+
+impl Daemon {
+    fn call(&self, method: &str, params: &Value) -> RpcResult {
+        match method {
+            "open" => self.open(params).map(|(result, _)| result),
+            "close" => self.close(params),
+            "list" => self.list(params),
+            "read" => self.read(params),
+            "replace" => self.replace(params),
+            "save" => self.save(params),
+            "session.start" => self.session_start(params),
+            "session.push" => self.session_push(params),
+            "session.patch" => self.session_patch(params),
+            "session.end" => self.session_end(params),
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// the open archive of the handle
+    fn archive(&self, handle: u64) -> Result<Arc<Mutex<OpenArchive>>, RpcError> {
+        self.state()
+            .archives
+            .get(&handle)
+            .cloned()
+            .ok_or_else(|| unknown_handle(handle))
+    }
+
+    /// the handle of the session archive and the archive
+    fn session(&self, session: u64) -> Result<(u64, Arc<Mutex<OpenArchive>>), RpcError> {
+        let state = self.state();
+        let handle = *state
+            .sessions
+            .get(&session)
+            .ok_or_else(|| unknown_session(session))?;
+        let open = state
+            .archives
+            .get(&handle)
+            .cloned()
+            .expect("handle of a session is always open");
+        Ok((handle, open))
+    }
+
+    /// open the archive or reuse it, return the result of the call and the handle
+    fn open(&self, params: &Value) -> Result<(Value, u64), RpcError> {
+        let path = PathBuf::from(str_param(params, "path")?);
+        let game = match params.get("game").and_then(Value::as_str) {
+            Some(game) => Game::from_str(game, true).map_err(RpcError::invalid_params)?,
+            None => Game::Auto,
+        };
+
+        let canonical = path
+            .canonicalize()
+            .with_context(|| format!("failed to open {}", path.display()))?;
+
+        let find = |state: &State| {
+            let handle = *state.paths.get(&canonical)?;
+            Some((handle, state.archives[&handle].clone()))
+        };
+
+        let existing = find(&self.state());
+        let (handle, open) = match existing {
+            Some(existing) => existing,
+            None => {
+                // loading can take a while, so the other archives aren't held meanwhile
+                let provider = open_provider(&canonical, game.into())?;
+
+                let names = match provider.game() {
+                    hvp_archive::Game::Obscure2 => {
+                        let mut state = self.state();
+                        if state.names.is_none() {
+                            state.names = Some(name_maps_for_game(provider.game())?);
+                        }
+                        state.names.clone().unwrap_or_default()
+                    }
+                    _ => Obscure2NameMap::default(),
+                };
+                let loaded = LoadedArchive::load(provider, names);
+
+                let mut state = self.state();
+                match find(&state) {
+                    // opened by another request while this one was loading
+                    Some(existing) => existing,
+                    None => {
+                        let handle = state.next_handle;
+                        state.next_handle += 1;
+                        state.paths.insert(canonical.clone(), handle);
+                        let open = Arc::new(Mutex::new(OpenArchive {
+                            path: canonical,
+                            loaded: Some(loaded),
+                            replacements: BTreeMap::new(),
+                            sessions: BTreeMap::new(),
+                        }));
+                        state.archives.insert(handle, open.clone());
+                        (handle, open)
+                    }
+                }
+            }
+        };
+
+        let metadata = lock(&open).with_archive(|archive| archive.metadata())?;
+
+        Ok((
+            json!({
+                "handle": handle,
+                "game": format!("{:?}", metadata.game),
+                "dir_count": metadata.dir_count,
+                "file_count": metadata.file_count,
+            }),
+            handle,
+        ))
+    }
+
+    fn close(&self, params: &Value) -> RpcResult {
+        let handle = handle_param(params)?;
+        let mut state = self.state();
+        match state.archives.remove(&handle) {
+            Some(_) => {
+                state.paths.retain(|_, h| *h != handle);
+                state.sessions.retain(|_, h| *h != handle);
+                Ok(Value::Null)
+            }
+            None => Err(unknown_handle(handle)),
+        }
+    }
+
+    fn list(&self, params: &Value) -> RpcResult {
+        let open = self.archive(handle_param(params)?)?;
+        let open = lock(&open);
+
+        open.with_archive(|archive| {
+            let files: Vec<_> = archive
+                .files()
+                .map(|f| {
+                    json!({
+                        "path": f.path.to_string_lossy().replace('\\', "/"),
+                        "size": f.uncompressed_size(),
+                        "stored_size": f.raw_bytes.len(),
+                        "compressed": f.is_compressed(),
+                        "replaced": open.replacements.contains_key(&f.path),
+                    })
+                })
+                .collect();
+            json!(files)
+        })
+    }
+
+    fn read(&self, params: &Value) -> RpcResult {
+        let path = archive_path(str_param(params, "path")?);
+        let open = self.archive(handle_param(params)?)?;
+        let open = lock(&open);
+
+        if let Some(bytes) = open.replacements.get(&path) {
+            return Ok(json!({ "data": base64::encode(bytes) }));
+        }
+
+        open.with_archive(|archive| {
+            let entry = archive
+                .find_file(&path)
+                .ok_or_else(|| unknown_file(&path))?;
+            let bytes = entry
+                .get_bytes()
+                .with_context(|| format!("failed to decompress {}", path.display()))?;
+
+            Ok(json!({ "data": base64::encode(&bytes) }))
+        })?
+    }
+
+    fn replace(&self, params: &Value) -> RpcResult {
+        let handle = handle_param(params)?;
+        let path = archive_path(str_param(params, "path")?);
+
+        let data = match params.get("data") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(data)) => Some(
+                base64::decode(data)
+                    .map_err(|e| RpcError::invalid_params(format!("invalid data: {e}")))?,
+            ),
+            Some(_) => return Err(RpcError::invalid_params("data must be a base64 string")),
+        };
+
+        let open = self.archive(handle)?;
+        let mut open = lock(&open);
+        open.check_file(&path)?;
+
+        match data {
+            Some(data) => open.replacements.insert(path, data),
+            None => open.replacements.remove(&path),
+        };
+
+        Ok(json!({ "pending": open.replacements.len() }))
+    }
+
+    fn save(&self, params: &Value) -> RpcResult {
+        let handle = handle_param(params)?;
+        let output = PathBuf::from(str_param(params, "output")?);
+        let skip_compression = params
+            .get("skip_compression")
+            .and_then(Value::as_bool)
+            .unwrap_or_default();
+
+        let open = self.archive(handle)?;
+        let open = lock(&open);
+
+        // the input archive is still mapped and used as the source of the unchanged files
+        if output.canonicalize().is_ok_and(|o| o == open.path) {
+            return Err(RpcError::invalid_params(
+                "output can't be the same as the open archive",
+            ));
+        }
+
+        open.loaded()?.fresh(|mut archive| {
+            archive.options.rebuild_skip_compression = skip_compression;
+
+            for mut entry in archive.files_mut() {
+                if let Some(bytes) = open.replacements.get(&entry.path) {
+                    entry.update(UpdateKind::Bytes(bytes.clone()));
+                }
+            }
+
+            let mut writer = BufWriter::new(
+                File::create(&output).context("failed to create output hvp archive file")?,
+            );
+            archive
+                .rebuild(&mut writer, NoProgress)
+                .context("failed to rebuild the archive")?;
+            writer.flush().context("failed to flush writer")?;
+
+            println!(
+                "{} saved {} with {} replaced files",
+                "[+]".green(),
+                output.display(),
+                open.replacements.len()
+            );
+
+            Ok(json!({ "replaced": open.replacements.len() }))
+        })
+    }
+
+    fn session_start(&self, params: &Value) -> RpcResult {
+        let (mut result, handle) = self.open(params)?;
+
+        let mut state = self.state();
+        // the archive can be closed by another request after opening it
+        let open = state
+            .archives
+            .get(&handle)
+            .cloned()
+            .ok_or_else(|| unknown_handle(handle))?;
+        let session = state.next_session;
+        state.next_session += 1;
+        state.sessions.insert(session, handle);
+        drop(state);
+
+        lock(&open).sessions.insert(session, BTreeMap::new());
+
+        result["session"] = json!(session);
+        Ok(result)
+    }
+
+    fn session_push(&self, params: &Value) -> RpcResult {
+        let session = session_param(params)?;
+        let path = archive_path(str_param(params, "path")?);
+        let data = base64::decode(str_param(params, "data")?)
+            .map_err(|e| RpcError::invalid_params(format!("invalid data: {e}")))?;
+
+        let (_, open) = self.session(session)?;
+        let mut open = lock(&open);
+        open.check_file(&path)?;
+
+        let pending = open.sessions.entry(session).or_default();
+        pending.insert(path, data);
+
+        Ok(json!({ "pending": pending.len() }))
+    }
+
+    /// patch a copy of the archive file and move it over the original one, so a failure never
+    /// leave a half written archive and the mapped file isn't changed while it's used
+    fn session_patch(&self, params: &Value) -> RpcResult {
+        let session = session_param(params)?;
+        let skip_compression = params
+            .get("skip_compression")
+            .and_then(Value::as_bool)
+            .unwrap_or_default();
+        let dead_space = match params.get("dead_space").and_then(Value::as_str) {
+            None | Some("preserve") => DeadSpacePolicy::Preserve,
+            Some("reclaim") => DeadSpacePolicy::Reclaim,
+            Some(policy) => {
+                return Err(RpcError::invalid_params(format!(
+                    "unknown dead space policy `{policy}`, expected `preserve` or `reclaim`"
+                )));
+            }
+        };
+
+        let (_, open) = self.session(session)?;
+        let mut open = lock(&open);
+        let open = &mut *open;
+
+        let pending = open.sessions.remove(&session).unwrap_or_default();
+        let temp = temp_path(&open.path);
+
+        let result = open.loaded()?.fresh(|mut archive| {
+            archive.options.rebuild_skip_compression = skip_compression;
+            archive.options.dead_space = dead_space;
+
+            for mut entry in archive.files_mut() {
+                if let Some(bytes) = pending.get(&entry.path) {
+                    entry.update(UpdateKind::Bytes(bytes.clone()));
+                }
+            }
+
+            std::fs::copy(&open.path, &temp).context("failed to copy hvp archive for patching")?;
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&temp)
+                .context("failed to open hvp archive copy for patching")?;
+
+            let report = archive
+                .patch(&mut file)
+                .context("failed to patch the archive")?;
+            file.sync_all().context("failed to flush patched archive")?;
+
+            Ok(json!({
+                "in_place": report.in_place,
+                "reclaimed": report.reclaimed,
+                "appended": report.appended,
+            }))
+        });
+
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                let _ = std::fs::remove_file(&temp);
+                // keep the files so the client can try again
+                open.sessions.insert(session, pending);
+                return Err(e);
+            }
+        };
+
+        // the mapping must be gone before the file is replaced, and the parsed entries are out
+        // of date after it, so the archive is loaded again
+        let loaded = open.loaded.take().expect("checked by loaded() above");
+        let game = loaded.borrow_owner().game();
+        let names = loaded.with_dependent(|_, archive| archive.options.obscure2_names.clone());
+        drop(loaded);
+
+        let renamed = std::fs::rename(&temp, &open.path);
+        let provider = open_provider(&open.path, Some(game))
+            .context("failed to reload patched hvp archive")?;
+        open.loaded = Some(LoadedArchive::load(provider, names));
+        if let Err(e) = renamed {
+            let _ = std::fs::remove_file(&temp);
+            return Err(anyhow::Error::new(e)
+                .context("failed to replace the archive with the patched copy")
+                .into());
+        }
+
+        println!(
+            "{} patched {} files into {} ({} in place, {} reclaimed, {} appended)",
+            "[+]".green(),
+            pending.len(),
+            open.path.display(),
+            report["in_place"],
+            report["reclaimed"],
+            report["appended"]
+        );
+
+        Ok(report)
+    }
+
+    fn session_end(&self, params: &Value) -> RpcResult {
+        let session = session_param(params)?;
+        let (_, open) = self.session(session)?;
+
+        self.state().sessions.remove(&session);
+        lock(&open).sessions.remove(&session);
+        Ok(Value::Null)
+    }
+}
+
+fn open_provider(path: &Path, game: Option<hvp_archive::Game>) -> anyhow::Result<ArchiveProvider> {
+    let file = File::open(path).context("failed to open hvp archive")?;
+    ArchiveProvider::new(file, game).context("failed to load hvp archive")
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// path of the patched copy, next to the archive so it can be renamed over it
+fn temp_path(path: &Path) -> PathBuf {
+    let mut temp = OsString::from(path.as_os_str());
+    temp.push(".patching");
+    temp.into()
+}
+
+struct NoProgress;
+
+impl RebuildProgress for NoProgress {
+    fn inc(&self, _: Option<String>) {}
+    fn inc_n(&self, _: usize, _: Option<String>) {}
+}
+
+fn str_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::invalid_params(format!("missing string param `{name}`")))
+}
+
+fn handle_param(params: &Value) -> Result<u64, RpcError> {
+    params
+        .get("handle")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| RpcError::invalid_params("missing integer param `handle`"))
+}
+
+/// path inside the archive, `/` and `\` are both accepted as separator
+fn archive_path(path: &str) -> PathBuf {
+    path.split(['/', '\\']).filter(|p| !p.is_empty()).collect()
+}
+
+fn session_param(params: &Value) -> Result<u64, RpcError> {
+    params
+        .get("session")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| RpcError::invalid_params("missing integer param `session`"))
+}
+
+fn unknown_session(session: u64) -> RpcError {
+    RpcError::invalid_params(format!("no session with id {session}"))
+}
+
+fn unknown_handle(handle: u64) -> RpcError {
+    RpcError::invalid_params(format!("no open archive with handle {handle}"))
+}
+
+fn unknown_file(path: &Path) -> RpcError {
+    RpcError::invalid_params(format!("can't find {} in the archive", path.display()))
+}
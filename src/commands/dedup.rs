@@ -0,0 +1,161 @@
+//! deduplication of the files that are extracted from many archives, like the discs of console versions
+//!
+//! with `extract --dedup-index <file>` every extracted file is recorded in a shared index. a file
+//! with the same content as an already extracted file (from any archive that use the same index)
+//! isn't written again, it's recorded in `dedup.json` of the output folder instead. `create`
+//! read the recorded files from where they were first extracted, so editing that copy update
+//! every archive that contain the file.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::Context;
+use serde_json::{Value, json};
+
+use super::manifest::path_str;
+
+/// index of the files that are extracted in a session, by their size and crc32
+pub struct DedupIndex {
+    path: PathBuf,
+    files: Mutex<BTreeMap<String, PathBuf>>,
+}
+
+impl DedupIndex {
+    /// load the index from the file, an index that doesn't exist yet is empty
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let files = match path.is_file() {
+            true => {
+                let bytes = std::fs::read(path).context("failed to read dedup index")?;
+                serde_json::from_slice(&bytes).context("failed to load dedup index")?
+            }
+            false => BTreeMap::new(),
+        };
+
+        Ok(Self {
+            // the working directory may change before the index is saved
+            path: std::path::absolute(path)
+                .context("failed to get absolute path of dedup index")?,
+            files: Mutex::new(files),
+        })
+    }
+
+    /// the already extracted file with the same content as `bytes`. if there is none,
+    /// `target` is recorded as the file with this content and `None` is returned
+    pub fn find_or_insert(
+        &self,
+        bytes: &[u8],
+        crc32: u32,
+        target: &Path,
+    ) -> std::io::Result<Option<PathBuf>> {
+        let key = format!("{}:{crc32:08x}", bytes.len());
+        let target = std::path::absolute(target)?;
+
+        let existing = self.files.lock().unwrap().get(&key).cloned();
+
+        // the recorded file may be modified or removed since it was extracted
+        if let Some(existing) = existing
+            && existing != target
+            && std::fs::read(&existing).is_ok_and(|b| b == bytes)
+        {
+            return Ok(Some(existing));
+        }
+
+        self.files.lock().unwrap().insert(key, target);
+        Ok(None)
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let json = serde_json::to_vec_pretty(&*self.files.lock().unwrap())
+            .context("failed to serialize dedup index")?;
+        std::fs::write(&self.path, json).context("failed to write dedup index")
+    }
+}
+
+/// a file that wasn't written because its content was already extracted
+#[derive(Debug, Clone)]
+pub struct DedupEntry {
+    /// absolute path of the already extracted file
+    pub source: PathBuf,
+    /// crc32 of the content when it was extracted
+    pub crc32: u32,
+}
+
+/// the `dedup.json` of an extracted folder, deduplicated files by their path inside the archive
+#[derive(Default)]
+pub struct Deduped {
+    pub files: BTreeMap<PathBuf, DedupEntry>,
+}
+
+impl Deduped {
+    pub fn to_json(&self) -> Value {
+        json!(
+            self.files
+                .iter()
+                .map(|(path, entry)| json!({
+                    "path": path_str(path),
+                    "source": entry.source,
+                    "crc32": entry.crc32,
+                }))
+                .collect::<Vec<_>>()
+        )
+    }
+
+    pub fn from_json(bytes: &[u8]) -> anyhow::Result<Self> {
+        let value: Value = serde_json::from_slice(bytes).context("invalid json")?;
+
+        let files = value
+            .as_array()
+            .context("expected a list of files")?
+            .iter()
+            .map(|file| {
+                let path = file
+                    .get("path")
+                    .and_then(Value::as_str)
+                    .context("file without path")?;
+                let entry = DedupEntry {
+                    source: file
+                        .get("source")
+                        .and_then(Value::as_str)
+                        .map(PathBuf::from)
+                        .with_context(|| format!("{path} without source"))?,
+                    crc32: file
+                        .get("crc32")
+                        .and_then(Value::as_u64)
+                        .and_then(|c| u32::try_from(c).ok())
+                        .with_context(|| format!("{path} without a valid crc32"))?,
+                };
+                Ok((path.split('/').collect(), entry))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { files })
+    }
+
+    /// load `dedup.json` from the file, `None` if it doesn't exist
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let bytes = std::fs::read(path).context("failed to read dedup.json")?;
+        Self::from_json(&bytes)
+            .context("failed to load dedup.json, if you modified it just remove it")
+            .map(Some)
+    }
+
+    /// deduplicated files that aren't in the folder and whose source changed since the extraction,
+    /// with the path of their source
+    pub fn changed(&self, folder: &Path) -> Vec<(PathBuf, PathBuf)> {
+        self.files
+            .iter()
+            .filter(|(path, _)| !folder.join(path).is_file())
+            .filter(|(_, entry)| {
+                std::fs::read(&entry.source).is_ok_and(|b| crc32fast::hash(&b) != entry.crc32)
+            })
+            .map(|(path, entry)| (path.clone(), entry.source.clone()))
+            .collect()
+    }
+}
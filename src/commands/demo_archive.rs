@@ -0,0 +1,108 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::archive::{builder::ArchiveBuilder, entry::UpdateKind};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{output::println, utils};
+
+/// text of `demo/config.txt`
+const CONFIG: &str = "\
+; demo archive made by obscure-hvp, the content of the files is made up
+language = english
+subtitles = 1
+music_volume = 80
+sound_volume = 100
+";
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path of the new demo hvp archive
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub output: PathBuf,
+    /// write the archive in big endian (wii version of obscure 2), ignored for obscure 1
+    #[arg(long, default_value_t = false, required = false)]
+    pub big_endian: bool,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, game: Option<hvp_archive::Game>) -> anyhow::Result<()> {
+        let Some(game) = game else {
+            anyhow::bail!("--game is required to make a demo archive");
+        };
+
+        let mut builder = ArchiveBuilder::new(game).big_endian(self.big_endian);
+
+        // the names are in the obscure 2 hashes list, so they can be read back by every command
+        let files = [
+            ("demo/config.txt", CONFIG.as_bytes().to_vec(), true),
+            ("demo/textures/rollover.tga", tga(16, 16), true),
+            ("demo/textures/font_arial14b.tga", tga(64, 32), false),
+        ];
+
+        for (path, data, compress) in files {
+            let data = UpdateKind::Bytes(data);
+            match compress {
+                true => builder.add_file(path, data),
+                false => builder.add_file_uncompressed(path, data),
+            }
+            .with_context(|| format!("failed to add {path} to the demo archive"))?;
+        }
+
+        utils::print_metadata(builder.metadata());
+
+        println!(
+            "{} output hvp archive: {}",
+            "[+]".green(),
+            self.output.display()
+        );
+
+        let mut writer = BufWriter::new(
+            File::create(&self.output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(builder.metadata().file_count as _);
+        let metadata = utils::watch_progress(&pb, |progress| builder.build(&mut writer, progress))
+            .context("failed to build the demo archive")?;
+        pb.finish_and_clear();
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!("{} demo archive created", "[+]".green());
+
+        utils::report_rebuild(
+            &self.output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            metadata,
+            json!({ "demo": true }),
+        );
+
+        Ok(())
+    }
+}
+
+/// an uncompressed 32 bit tga image with a simple gradient
+fn tga(width: u16, height: u16) -> Vec<u8> {
+    let mut image = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    image.extend(width.to_le_bytes());
+    image.extend(height.to_le_bytes());
+    image.extend([32, 8]);
+
+    for y in 0..height {
+        for x in 0..width {
+            let blue = (x * 255 / width) as u8;
+            let green = (y * 255 / height) as u8;
+            image.extend([blue, green, 0x80, 0xff]);
+        }
+    }
+
+    image
+}
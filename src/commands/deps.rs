@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{
+        Archive, Options,
+        deps::{DependencyScan, SCRIPT_EXTENSIONS},
+    },
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    manifest::path_str,
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path of the file inside the archive, for example `characters/boy0/boy0.cbe`
+    pub entry: PathBuf,
+    /// also follow the references of the referenced files, and the files that reference them
+    #[arg(long, short = 'r', default_value_t = false, required = false)]
+    pub recursive: bool,
+    /// look for references inside every file, not just the script and level files
+    #[arg(long, short = 'a', default_value_t = false, required = false)]
+    pub all: bool,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let Some(entry) = archive.find_file(&self.entry).map(|f| f.path) else {
+            anyhow::bail!("{} doesn't exist in the archive", self.entry.display());
+        };
+
+        let scan = match self.all {
+            true => DependencyScan::All,
+            false => DependencyScan::Scripts,
+        };
+
+        match scan {
+            DependencyScan::All => {
+                println!("{} looking for references in every file", "[+]".green())
+            }
+            DependencyScan::Scripts => println!(
+                "{} looking for references in the {} files",
+                "[+]".green(),
+                SCRIPT_EXTENSIONS.join(", ")
+            ),
+        }
+
+        let graph = archive.dependencies(scan);
+
+        if !graph.failed.is_empty() {
+            println!(
+                "{} {} files couldn't be decompressed, they weren't scanned",
+                "[!]".yellow(),
+                graph.failed.len()
+            );
+        }
+
+        let (references, referenced_by): (Vec<&Path>, Vec<&Path>) = match self.recursive {
+            true => (
+                graph.transitive(&entry, false).into_iter().collect(),
+                graph.transitive(&entry, true).into_iter().collect(),
+            ),
+            false => (
+                graph.references(&entry).collect(),
+                graph.referenced_by(&entry).collect(),
+            ),
+        };
+
+        println!(
+            "{} {} reference {} entries:",
+            "[+]".green(),
+            entry.display(),
+            references.len()
+        );
+        for path in &references {
+            println!(" {} {}", "|>".cyan(), path.display());
+        }
+
+        println!(
+            "{} {} is referenced by {} files, they may need to be updated too:",
+            "[+]".green(),
+            entry.display(),
+            referenced_by.len()
+        );
+        for path in &referenced_by {
+            println!(" {} {}", "|>".cyan(), path.display());
+        }
+
+        println!(
+            "{} scanned {} files, references are found by name so some may be missing or wrong",
+            "[?]".green(),
+            graph.files_scanned
+        );
+
+        output::report(
+            "deps",
+            json!({
+                "entry": path_str(&entry),
+                "recursive": self.recursive,
+                "files_scanned": graph.files_scanned,
+                "failed": graph.failed.iter().map(|p| path_str(p)).collect::<Vec<_>>(),
+                "references": references.iter().map(|p| path_str(p)).collect::<Vec<_>>(),
+                "referenced_by": referenced_by.iter().map(|p| path_str(p)).collect::<Vec<_>>(),
+            }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+use std::{fs::File, path::PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Options, diff::DiffEntry},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to the original hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path to the modified hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub modified: PathBuf,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider, game: Option<Game>) -> anyhow::Result<()> {
+        let file = File::open(&self.modified).context("failed to open modified hvp archive")?;
+        let modified_provider =
+            ArchiveProvider::new(file, game).context("failed to load modified hvp archive")?;
+
+        if provider.game() != modified_provider.game() {
+            anyhow::bail!(
+                "can't compare archives from different games ({:?} and {:?})",
+                provider.game(),
+                modified_provider.game()
+            );
+        }
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        let obscure2_names = name_maps_for_game(modified_provider.game())?;
+        let modified_archive = Archive::new_with_options(
+            &modified_provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        println!(
+            "{} comparing {} with {}",
+            "[+]".green(),
+            self.input.display(),
+            self.modified.display()
+        );
+
+        let diff = archive.diff(&modified_archive);
+
+        let (mut added, mut removed, mut changed) = (0, 0, 0);
+
+        for entry in &diff.entries {
+            match entry {
+                DiffEntry::Added(file) => {
+                    added += 1;
+                    println!(
+                        " {} {} ({} bytes)",
+                        "[A]".green(),
+                        file.path.display(),
+                        file.stored_size
+                    );
+                }
+                DiffEntry::Removed(file) => {
+                    removed += 1;
+                    println!(
+                        " {} {} ({} bytes)",
+                        "[R]".red(),
+                        file.path.display(),
+                        file.stored_size
+                    );
+                }
+                DiffEntry::Changed { old, new } => {
+                    changed += 1;
+                    println!(
+                        " {} {}: {} -> {} bytes ({:+}), checksum {:#010x} -> {:#010x}",
+                        "[M]".yellow(),
+                        new.path.display(),
+                        old.stored_size,
+                        new.stored_size,
+                        entry.size_delta(),
+                        old.checksum,
+                        new.checksum,
+                    );
+                }
+            }
+        }
+
+        println!(
+            "{} {added} added, {removed} removed, {changed} changed and {} unchanged files, size delta: {:+} bytes",
+            "[+]".green(),
+            diff.unchanged,
+            diff.size_delta(),
+        );
+
+        let files = |kind: fn(&DiffEntry) -> bool| {
+            diff.entries
+                .iter()
+                .filter(|e| kind(e))
+                .map(|entry| match entry {
+                    DiffEntry::Added(file) | DiffEntry::Removed(file) => json!({
+                        "path": file.path,
+                        "size": file.stored_size,
+                    }),
+                    DiffEntry::Changed { old, new } => json!({
+                        "path": new.path,
+                        "old_size": old.stored_size,
+                        "new_size": new.stored_size,
+                        "old_checksum": old.checksum,
+                        "new_checksum": new.checksum,
+                    }),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        output::report(
+            "diff",
+            json!({
+                "added": files(|e| matches!(e, DiffEntry::Added(_))),
+                "removed": files(|e| matches!(e, DiffEntry::Removed(_))),
+                "changed": files(|e| matches!(e, DiffEntry::Changed { .. })),
+                "unchanged": diff.unchanged,
+                "size_delta": diff.size_delta(),
+            }),
+        );
+
+        Ok(())
+    }
+}
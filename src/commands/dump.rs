@@ -1,15 +1,23 @@
-use std::{fs::File, io::BufWriter, path::PathBuf};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
 
-use anstream::{print, println};
 use anyhow::Context;
-use clap::{Parser, ValueHint};
+use clap::{Parser, ValueEnum, ValueHint};
 use hvp_archive::{
-    archive::Archive,
+    archive::{Archive, Options, entry::Entry},
     provider::{ArchiveProvider, RawArchive},
 };
 use owo_colors::OwoColorize;
+use serde_json::{Map, Value, json};
 
-use super::utils;
+use super::{
+    name_maps_for_game,
+    output::{self, print, println},
+    size, utils, yaml,
+};
 
 #[derive(Parser)]
 #[command(arg_required_else_help = true)]
@@ -17,38 +25,371 @@ pub struct Commands {
     /// path to input hvp archive
     #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
     pub input: PathBuf,
-    /// output json file, if empty a json file with the same name of input hvp will be created
+    /// output file, if empty a file with the same name of input hvp and the extension of the format will be created
     pub output: Option<PathBuf>,
+    /// format of the output file
+    #[arg(long, short = 'f', default_value_t = DumpFormat::Json, value_enum)]
+    pub format: DumpFormat,
+    /// dump the entries tree with full paths and resolved names instead of the raw TOC
+    #[arg(long, default_value_t = false, required = false)]
+    pub mapped: bool,
+    /// write the directory tree as a graphviz dot file instead of dumping the TOC
+    #[arg(long, value_hint = ValueHint::FilePath, conflicts_with_all = ["output", "format", "mapped"])]
+    pub graphviz: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default)]
+pub enum DumpFormat {
+    /// pretty printed json
+    #[default]
+    Json,
+    /// yaml
+    Yaml,
+    /// one row per entry, nested fields are flattened into columns
+    Csv,
+    /// one line per entry, good for diffing and grepping
+    Text,
+}
+
+impl DumpFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Csv => "csv",
+            Self::Text => "txt",
+        }
+    }
 }
 
 impl Commands {
     /// handle the user command
     pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
-        let archive = Archive::new(&provider);
+        let obscure2_names = match self.mapped || self.graphviz.is_some() {
+            true => name_maps_for_game(provider.game())?,
+            false => Default::default(),
+        };
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
 
         utils::print_metadata(archive.metadata());
 
+        if let Some(output) = &self.graphviz {
+            return self.write_graphviz(&archive, output);
+        }
+
         let output = self
             .output
-            .unwrap_or_else(|| self.input.with_extension("json"));
+            .unwrap_or_else(|| self.input.with_extension(self.format.extension()));
 
         println!("{} output file: {}", "[+]".green(), output.display());
-        print!("{} serializng entries to json", "[+]".green());
+        print!(
+            "{} serializng entries to {}",
+            "[+]".green(),
+            self.format.extension()
+        );
 
-        let writer =
-            BufWriter::new(File::create(output).context("failed to create output json file")?);
+        let entries = match self.mapped {
+            true => mapped_entries(archive.entries(), Path::new("")),
+            false => match provider.raw_archive() {
+                RawArchive::Obscure1(archive) => serde_json::to_value(&archive.entries),
+                RawArchive::Obscure2(archive) => serde_json::to_value(&archive.entries),
+                RawArchive::FinalExam(archive) => serde_json::to_value(&archive.entries),
+            }
+            .context("failed to serialize entries")?,
+        };
+
+        let mut writer =
+            BufWriter::new(File::create(&output).context("failed to create output file")?);
 
-        match provider.raw_archive() {
-            RawArchive::Obscure1(archive) => serde_json::to_writer_pretty(writer, &archive.entries),
-            RawArchive::Obscure2(archive) => serde_json::to_writer_pretty(writer, &archive.entries),
-            RawArchive::FinalExam(archive) => {
-                serde_json::to_writer_pretty(writer, &archive.entries)
+        match self.format {
+            DumpFormat::Json => serde_json::to_writer_pretty(&mut writer, &entries)
+                .context("failed to write entries")?,
+            DumpFormat::Yaml => {
+                yaml::to_writer(&mut writer, &entries).context("failed to write entries")?
+            }
+            DumpFormat::Csv => {
+                write_csv(&mut writer, &to_rows(&entries)).context("failed to write entries")?
+            }
+            DumpFormat::Text => {
+                write_text(&mut writer, &to_rows(&entries)).context("failed to write entries")?
             }
         }
-        .context("failed to serialize entries")?;
+
+        writer.flush().context("failed to flush writer")?;
 
         println!(": Done");
 
+        output::report(
+            "dump",
+            json!({
+                "output": output,
+                "format": self.format.extension(),
+                "mapped": self.mapped,
+            }),
+        );
+
         Ok(())
     }
 }
+
+impl Commands {
+    fn write_graphviz(&self, archive: &Archive, output: &Path) -> anyhow::Result<()> {
+        println!("{} output file: {}", "[+]".green(), output.display());
+        print!("{} writing directory tree as graphviz dot", "[+]".green());
+
+        let root = self
+            .input
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut writer =
+            BufWriter::new(File::create(output).context("failed to create output file")?);
+        let mut graph = Graphviz {
+            writer: &mut writer,
+            nodes: 0,
+        };
+        graph
+            .write(&root, archive.entries())
+            .context("failed to write graphviz dot")?;
+        writer.flush().context("failed to flush writer")?;
+
+        println!(": Done");
+
+        output::report(
+            "dump",
+            json!({
+                "output": output,
+                "format": "dot",
+                "mapped": true,
+            }),
+        );
+
+        Ok(())
+    }
+}
+
+/// writer of the entries tree as a graphviz digraph, sizes of the files and the total
+/// uncompressed size of the directories are part of the node labels
+struct Graphviz<'a, W: Write> {
+    writer: &'a mut W,
+    nodes: usize,
+}
+
+impl<W: Write> Graphviz<'_, W> {
+    fn write(&mut self, root: &str, entries: &[Entry]) -> std::io::Result<()> {
+        writeln!(self.writer, "digraph hvp {{")?;
+        writeln!(self.writer, "  rankdir=LR;")?;
+        writeln!(self.writer, "  node [fontname=\"monospace\"];")?;
+        self.dir(root, entries)?;
+        writeln!(self.writer, "}}")
+    }
+
+    /// write the directory and its children, return its node id and total size
+    fn dir(&mut self, name: &str, entries: &[Entry]) -> std::io::Result<(usize, u64)> {
+        let id = self.next_id();
+        let mut size = 0;
+        let mut children = Vec::with_capacity(entries.len());
+
+        for entry in entries {
+            let (child, child_size) = match entry {
+                Entry::File(file) => {
+                    let child = self.next_id();
+                    let child_size = file.uncompressed_size() as u64;
+                    writeln!(
+                        self.writer,
+                        "  n{child} [shape=note, label=\"{}\\n{}\"];",
+                        escape(file.name()),
+                        size::format(child_size)
+                    )?;
+                    (child, child_size)
+                }
+                Entry::Dir(dir) => self.dir(&dir.name, &dir.entries)?,
+            };
+            size += child_size;
+            children.push(child);
+        }
+
+        writeln!(
+            self.writer,
+            "  n{id} [shape=folder, label=\"{}\\n{}\"];",
+            escape(name),
+            size::format(size)
+        )?;
+        for child in children {
+            writeln!(self.writer, "  n{id} -> n{child};")?;
+        }
+
+        Ok((id, size))
+    }
+
+    fn next_id(&mut self) -> usize {
+        self.nodes += 1;
+        self.nodes - 1
+    }
+}
+
+/// escape a string for a dot double quoted string
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// the entries tree of the archive, with full paths and resolved names
+fn mapped_entries(entries: &[Entry], parent: &Path) -> Value {
+    let entries = entries
+        .iter()
+        .map(|entry| match entry {
+            Entry::File(file) => json!({
+                "kind": "File",
+                "name": file.name(),
+                "path": parent.join(file.name()),
+                "stored_size": file.raw_bytes.len(),
+                "uncompressed_size": file.uncompressed_size(),
+                "compression": file.compression_info().map(|c| format!("{:?}", c.compression_type)),
+                "stored_checksum": file.stored_checksum(),
+                "checksum_match": file.checksum_match(),
+            }),
+            Entry::Dir(dir) => {
+                let path = parent.join(&dir.name);
+                json!({
+                    "kind": "Dir",
+                    "name": dir.name,
+                    "entries": mapped_entries(&dir.entries, &path),
+                    "path": path,
+                })
+            }
+        })
+        .collect();
+
+    Value::Array(entries)
+}
+
+/// flatten the entries into rows, in the same order that they are in the archive.
+///
+/// enums like `{"kind": {"File": {...}}}` become a `kind` column with the fields of the variant,
+/// nested objects become `parent.child` columns and nested entries (obscure 1 directories)
+/// become their own rows with a bigger `depth`. each row also get its position as `row`
+fn to_rows(entries: &Value) -> Vec<Map<String, Value>> {
+    fn flatten(
+        prefix: &str,
+        value: &Map<String, Value>,
+        row: &mut Map<String, Value>,
+    ) -> Vec<Value> {
+        let mut children = Vec::new();
+
+        for (key, value) in value {
+            let name = match prefix.is_empty() {
+                true => key.clone(),
+                false => format!("{prefix}.{key}"),
+            };
+
+            match value {
+                // enum variant
+                Value::Object(variant) if variant.len() == 1 && key == "kind" => {
+                    let (variant, fields) = variant.iter().next().expect("map isn't empty");
+                    row.insert(name, variant.as_str().into());
+                    if let Value::Object(fields) = fields {
+                        children.extend(flatten(prefix, fields, row));
+                    }
+                }
+                Value::Object(object) => children.extend(flatten(&name, object, row)),
+                Value::Array(items) if items.iter().all(Value::is_object) => {
+                    children.extend(items.iter().cloned())
+                }
+                value => {
+                    row.insert(name, value.clone());
+                }
+            }
+        }
+
+        children
+    }
+
+    fn walk(entries: &[Value], depth: usize, rows: &mut Vec<Map<String, Value>>) {
+        for entry in entries {
+            let mut row = Map::new();
+            row.insert("row".to_owned(), rows.len().into());
+            row.insert("depth".to_owned(), depth.into());
+
+            let children = match entry {
+                Value::Object(entry) => flatten("", entry, &mut row),
+                value => {
+                    row.insert("value".to_owned(), value.clone());
+                    Vec::new()
+                }
+            };
+
+            rows.push(row);
+            walk(&children, depth + 1, rows);
+        }
+    }
+
+    let mut rows = Vec::new();
+    if let Value::Array(entries) = entries {
+        walk(entries, 0, &mut rows);
+    }
+    rows
+}
+
+fn write_csv<W: Write>(writer: &mut W, rows: &[Map<String, Value>]) -> std::io::Result<()> {
+    // position columns first, then the rest in the order that they first appear
+    let mut columns: Vec<&str> = vec!["row", "depth"];
+    for key in rows.iter().flat_map(|r| r.keys()) {
+        if !columns.contains(&key.as_str()) {
+            columns.push(key);
+        }
+    }
+
+    let escape = |field: String| match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field,
+    };
+
+    writeln!(writer, "{}", columns.join(","))?;
+    for row in rows {
+        let fields: Vec<_> = columns
+            .iter()
+            .map(|c| escape(row.get(*c).map(to_field).unwrap_or_default()))
+            .collect();
+        writeln!(writer, "{}", fields.join(","))?;
+    }
+
+    Ok(())
+}
+
+fn write_text<W: Write>(writer: &mut W, rows: &[Map<String, Value>]) -> std::io::Result<()> {
+    for row in rows {
+        let depth = row.get("depth").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let index = row.get("row").map(to_field).unwrap_or_default();
+
+        write!(writer, "{index:>6} {:width$}", "", width = depth * 2)?;
+
+        // kind of the entry come first, the rest are sorted by name
+        let kind = row.get_key_value("kind");
+        let fields = kind
+            .into_iter()
+            .chain(
+                row.iter()
+                    .filter(|(k, _)| !matches!(k.as_str(), "row" | "depth" | "kind")),
+            )
+            .map(|(k, v)| format!("{k}={}", to_field(v)));
+        writeln!(writer, "{}", fields.collect::<Vec<_>>().join(" "))?;
+    }
+
+    Ok(())
+}
+
+fn to_field(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Obscure2NameMap, Options, entry::Entry, info::FileInfo},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    manifest::path_str,
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+const COLUMNS: &str =
+    "path,type,offset,compressed_size,uncompressed_size,compression,checksum,name_crc32";
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// output csv file, if empty a file with the same name of input hvp will be created
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let output = self
+            .output
+            .unwrap_or_else(|| self.input.with_extension("csv"));
+
+        println!("{} output file: {}", "[+]".green(), output.display());
+
+        let writer = BufWriter::new(File::create(&output).context("failed to create output file")?);
+
+        let mut toc = TocWriter {
+            writer,
+            // files are visited in the same order
+            files: archive.files_info(),
+            game: archive.metadata().game,
+            rows: 0,
+        };
+        toc.write(archive.entries())
+            .context("failed to write toc")?;
+        toc.writer.flush().context("failed to flush writer")?;
+
+        let rows = toc.rows;
+
+        println!("{} exported {rows} entries", "[+]".green());
+
+        output::report(
+            "export_toc",
+            json!({
+                "output": output,
+                "entries": rows,
+            }),
+        );
+
+        Ok(())
+    }
+}
+
+/// writer of a csv row per entry, in the same order that they are in the archive
+struct TocWriter<W: Write, I: Iterator<Item = FileInfo>> {
+    writer: W,
+    files: I,
+    game: Game,
+    rows: usize,
+}
+
+impl<W: Write, I: Iterator<Item = FileInfo>> TocWriter<W, I> {
+    fn write(&mut self, entries: &[Entry]) -> std::io::Result<()> {
+        writeln!(self.writer, "{COLUMNS}")?;
+        self.entries(entries, Path::new(""))
+    }
+
+    fn entries(&mut self, entries: &[Entry], parent: &Path) -> std::io::Result<()> {
+        for entry in entries {
+            self.rows += 1;
+
+            match entry {
+                Entry::File(_) => {
+                    let info = self.files.next().expect("number of files doesn't match");
+                    writeln!(
+                        self.writer,
+                        "{},file,{},{},{},{},{:#010x},{}",
+                        escape(&path_str(&info.path)),
+                        info.data_offset,
+                        info.stored_size,
+                        info.uncompressed_size,
+                        info.compression
+                            .map(|c| format!("{c:?}"))
+                            .unwrap_or_default(),
+                        info.stored_checksum,
+                        crc32_field(info.name_crc32),
+                    )?;
+                }
+                Entry::Dir(dir) => {
+                    let path = parent.join(&dir.name);
+                    let name_crc32 = match self.game {
+                        Game::Obscure2 => Obscure2NameMap::stored_crc32(&dir.name),
+                        Game::Obscure1 | Game::FinalExam => None,
+                    };
+                    writeln!(
+                        self.writer,
+                        "{},dir,,,,,,{}",
+                        escape(&path_str(&path)),
+                        crc32_field(name_crc32),
+                    )?;
+                    self.entries(&dir.entries, &path)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn crc32_field(crc32: Option<u32>) -> String {
+    crc32.map(|c| format!("{c:#010x}")).unwrap_or_default()
+}
+
+/// quote the field if it has a character that is special in csv
+fn escape(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_owned(),
+    }
+}
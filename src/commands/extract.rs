@@ -1,22 +1,41 @@
 use std::{
+    borrow::Cow,
     fs::File,
     io::{BufWriter, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
 };
 
-use anstream::{print, println};
 use anyhow::Context;
-use clap::{Parser, ValueHint};
+use clap::{Parser, ValueEnum, ValueHint};
 use hvp_archive::{
-    Game,
-    archive::{Archive, Obscure2NameMap, Options, entry::DecompressError},
+    archive::{
+        Archive, Options,
+        entry::{DecompressError, FullFileEntry},
+        rebuild_progress::{self, RebuildProgress},
+    },
     provider::ArchiveProvider,
+    zip::ZipWriter,
 };
-use indicatif::ParallelProgressIterator;
+use indicatif::ProgressBar;
 use owo_colors::OwoColorize;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde_json::json;
 
-use super::{ChecksumValidation, HASHES_FILE, load_name_maps, utils};
+use super::{
+    ChecksumValidation, DEDUP_FILE, HASHES_FILE, MANIFEST_FILE, Orphans,
+    dedup::{DedupEntry, DedupIndex, Deduped},
+    glob::Glob,
+    hashes::{self, FileHash, HashesFormat},
+    manifest::{self, Manifest},
+    name_maps_for_game,
+    output::{self, print, println},
+    path_template::{ArchiveFields, PathTemplate},
+    postprocess, prompt, utils,
+};
 
 #[derive(Parser)]
 #[command(arg_required_else_help = true)]
@@ -30,120 +49,416 @@ pub struct Commands {
     /// validate checksums of the files
     #[arg(long, short = 's', default_value_t = ChecksumValidation::Yes, value_enum, required = false)]
     pub checksum_validation: ChecksumValidation,
+    /// only extract files that match this glob, for example `sound/**/*.wav` (can be used multiple times)
+    #[arg(long, value_parser = Glob::new)]
+    pub include: Vec<Glob>,
+    /// don't extract files that match this glob (can be used multiple times)
+    #[arg(long, value_parser = Glob::new)]
+    pub exclude: Vec<Glob>,
+    /// only extract files inside this directory of the archive, for example `sound/music`
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+    /// remove this many leading directories from the path of extracted files,
+    /// files that don't have enough directories are skipped.
+    ///
+    /// note: the output can't be used by `create` until the stripped directories are added back
+    #[arg(long, default_value_t = 0)]
+    pub strip_components: usize,
+    /// what to do with entries that aren't reachable from the root directory (obscure 2 and final exam only)
+    #[arg(long, default_value_t = Orphans::Drop, value_enum, required = false)]
+    pub orphans: Orphans,
+    /// where each file is written inside the output folder, for example
+    /// `{game}/{archive_stem}/{entry_path}`. placeholders: {game}, {archive_name}, {archive_stem},
+    /// {entry_path}, {entry_dir}, {entry_name}, {entry_stem} and {entry_ext}.
+    ///
+    /// without --output-folder the files are written next to the input archive.
+    /// hashes.json is written where a `hashes.json` file in the root of the archive would be
+    #[arg(long, value_parser = PathTemplate::new)]
+    pub path_template: Option<PathTemplate>,
+    /// format of hashes.json, `records` also keep the path and size of every file
+    #[arg(long, default_value_t = HashesFormat::Compact, value_enum, required = false)]
+    pub hashes_format: HashesFormat,
+    /// shared index of extracted files, files that are already extracted from another archive
+    /// that use the same index aren't written again and are recorded in dedup.json instead
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub dedup_index: Option<PathBuf>,
+    /// write the files exactly as they are stored, without decompressing them. the compression
+    /// and uncompressed size of every file are recorded in manifest.json.
+    ///
+    /// note: the output can't be used by `create`
+    #[arg(long, default_value_t = false, required = false)]
+    pub raw: bool,
+    /// what to do with files that can't be decompressed
+    #[arg(long, default_value_t = OnError::Ask, value_enum, required = false)]
+    pub on_error: OnError,
+    /// write the decompressed files into this zip file instead of a folder, hashes.json
+    /// and manifest.json are written inside the zip so it can be passed to `create`
+    #[arg(long, value_hint = ValueHint::FilePath, conflicts_with_all = ["output_folder", "path_template", "dedup_index", "raw"])]
+    pub zip: Option<PathBuf>,
+    /// what to do with files that already exist in the output folder
+    #[arg(long, default_value_t = Overwrite::Always, value_enum, required = false, conflicts_with = "zip")]
+    pub overwrite: Overwrite,
+    /// don't write the files that are already extracted with the same content, so an interrupted
+    /// extraction can be resumed (same as `--overwrite changed`)
+    #[arg(long, default_value_t = false, required = false, conflicts_with_all = ["zip", "overwrite"])]
+    pub skip_existing: bool,
+    /// run a built in postprocessor on the extracted files of the game, like `text-utf8`
+    /// (can be used multiple times, `all` for every one). the extracted files aren't changed,
+    /// the output is written to the `_postprocessed` folder. they are recorded in manifest.json,
+    /// and run again when the same folder is extracted without this
+    #[arg(long, value_parser = postprocess::parse, conflicts_with_all = ["zip", "raw"])]
+    pub postprocess: Vec<String>,
+}
+
+/// what to do with the files that already exist in the output folder
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Overwrite {
+    /// always write the file
+    #[default]
+    Always,
+    /// only write the file if its size or content is different
+    Changed,
+    /// never write over an existing file, `create` see it as modified if it's different
+    Never,
+}
+
+impl Overwrite {
+    /// whatever the existing file at `target` should be kept instead of writing `bytes` to it
+    fn keep(self, target: &Path, bytes: &[u8]) -> bool {
+        match self {
+            Overwrite::Always => false,
+            Overwrite::Changed => {
+                // the size is checked first, so files that are different rarely need to be read
+                std::fs::metadata(target)
+                    .is_ok_and(|m| m.is_file() && m.len() == bytes.len() as u64)
+                    && std::fs::read(target).is_ok_and(|existing| existing == bytes)
+            }
+            Overwrite::Never => target.exists(),
+        }
+    }
+}
+
+/// what to do with the files that can't be decompressed
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OnError {
+    /// ask what to do with every file, the extraction is aborted if nobody can answer
+    #[default]
+    Ask,
+    /// abort the extraction
+    Abort,
+    /// don't write the file, `create` keep the file of the original archive
+    Skip,
+    /// write the stored (still compressed) bytes of the file
+    Raw,
+}
+
+/// what to do with a single file that can't be decompressed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FailureAction {
+    /// try to decompress the file again
+    Retry,
+    /// don't write the file
+    Skip,
+    /// write the stored (still compressed) bytes of the file, its crc32 is recorded in
+    /// hashes.json so `create` see it as unchanged
+    Raw,
+    /// abort the extraction
+    Abort,
+}
+
+/// decide what happens to a file that can't be decompressed. the terminal ask the user,
+/// other frontends can present the same choices in their own way
+pub trait FailureHandler: Sync {
+    /// `attempt` start from 1 and grow every time the file is retried
+    fn on_decompress_error(
+        &self,
+        path: &Path,
+        error: &DecompressError,
+        attempt: usize,
+    ) -> FailureAction;
+}
+
+/// always take the same action
+impl FailureHandler for FailureAction {
+    fn on_decompress_error(&self, _: &Path, _: &DecompressError, _: usize) -> FailureAction {
+        *self
+    }
+}
+
+/// ask the user in the terminal, only one question is asked at a time
+struct PromptHandler<'a> {
+    pb: &'a ProgressBar,
+    lock: Mutex<()>,
+}
+
+impl FailureHandler for PromptHandler<'_> {
+    fn on_decompress_error(
+        &self,
+        path: &Path,
+        error: &DecompressError,
+        attempt: usize,
+    ) -> FailureAction {
+        const ACTIONS: [FailureAction; 4] = [
+            FailureAction::Retry,
+            FailureAction::Skip,
+            FailureAction::Raw,
+            FailureAction::Abort,
+        ];
+
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        self.pb.suspend(|| {
+            let question = match attempt {
+                1 => format!("{} can't be decompressed ({error})", path.display()),
+                _ => format!(
+                    "{} still can't be decompressed after {attempt} attempts ({error})",
+                    path.display()
+                ),
+            };
+            // an error while reading the answer is the same as no answer
+            let choice = prompt::choose(&question, &["retry", "skip", "raw", "abort"], 3);
+            ACTIONS[choice.unwrap_or(3)]
+        })
+    }
+}
+
+/// the bytes that are written for a file
+enum Extracted<'a> {
+    Decompressed(Cow<'a, [u8]>),
+    Raw(&'a [u8]),
+    Skipped,
+}
+
+/// decompress the file, asking the handler what to do when it fails
+fn read_entry<'a>(
+    entry: &'a FullFileEntry,
+    handler: &dyn FailureHandler,
+) -> Result<Extracted<'a>, DecompressError> {
+    let mut attempt = 1;
+    loop {
+        let error = match entry.get_bytes() {
+            Ok(bytes) => return Ok(Extracted::Decompressed(bytes)),
+            Err(e) => e,
+        };
+
+        match handler.on_decompress_error(&entry.path, &error, attempt) {
+            FailureAction::Retry => attempt += 1,
+            FailureAction::Skip => return Ok(Extracted::Skipped),
+            FailureAction::Raw => return Ok(Extracted::Raw(entry.raw_bytes)),
+            FailureAction::Abort => return Err(error),
+        }
+    }
 }
 
 impl Commands {
     /// handle the user command
     pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
-        let obscure2_names = match provider.game() {
-            Game::Obscure2 => match load_name_maps().context("failed to load name maps")? {
-                Some(names) => names,
-                None => {
-                    println!(
-                        "{} failed to load obscure2 (or alone in the dark 2008) name maps because no hash file was found",
-                        "[!]".yellow()
-                    );
-
-                    Obscure2NameMap::default()
-                }
-            },
-            _ => Obscure2NameMap::default(), // we don't need to load name map for any other game
-        };
+        let obscure2_names = name_maps_for_game(provider.game())?;
 
         let archive = Archive::new_with_options(
             &provider,
             Options {
                 obscure2_names,
                 rebuild_skip_compression: false,
+                orphans: self.orphans.into(),
+                guarantee_roundtrip: false,
+                compression_level: None,
+                alignment: None,
+                dead_space: Default::default(),
             },
         );
 
         utils::print_metadata(archive.metadata());
+        utils::print_orphans(&archive);
 
-        if matches!(
-            self.checksum_validation,
-            ChecksumValidation::Yes | ChecksumValidation::Prompt
-        ) {
-            println!("{} validating entries checksum", "[+]".green());
-            if !archive.entries_checksum_match() {
-                let mut should_exit = true;
-
-                if self.checksum_validation == ChecksumValidation::Prompt {
-                    print!(
-                        "{} checksum mismatch, continue anyway? [y/n]: ",
-                        "[!]".yellow()
-                    );
-                    anstream::stdout().flush()?;
-                    let input = utils::prompt()?.to_lowercase();
-                    match input.as_str() {
-                        "y" => should_exit = false,
-                        "n" => should_exit = true,
-                        _ => {
-                            println!("{} invalid input: '{}'", "[!]".red(), input);
-                            should_exit = true;
-                        }
-                    }
-                }
+        utils::validate_checksums(&archive, self.checksum_validation)?;
 
-                if should_exit {
-                    anyhow::bail!(
-                        "archive entries checksum doesn't match, maybe the archive is broken?"
-                    );
-                }
-            }
+        // accept both `/` and `\` as separator
+        let dir: Option<PathBuf> = self.dir.as_ref().map(|dir| {
+            dir.to_string_lossy()
+                .split(['/', '\\'])
+                .filter(|c| !c.is_empty())
+                .collect()
+        });
+
+        if let Some(dir) = &dir
+            && !archive.files().any(|f| f.path.starts_with(dir))
+        {
+            anyhow::bail!(
+                "can't find any file in {} directory of the archive",
+                dir.display()
+            );
         }
 
-        let output = self
-            .output_folder
-            .unwrap_or_else(|| self.input.with_extension(""));
+        if let Some(zip) = &self.zip {
+            let (files, archive_paths) = self.select(&archive, dir.as_deref());
+            return self.extract_to_zip(&archive, files, &archive_paths, zip);
+        }
+
+        let output = match (&self.output_folder, &self.path_template) {
+            (Some(output), _) => output.clone(),
+            (None, Some(_)) => self
+                .input
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            (None, None) => self.input.with_extension(""),
+        };
 
         println!("{} output folder: {}", "[+]".green(), output.display());
 
-        if !output.is_dir() {
+        if !output.as_os_str().is_empty() && !output.is_dir() {
             println!("{} creating output folder", "[+]".green());
             std::fs::create_dir_all(&output).context("failed to create output folder")?;
         }
 
+        // loaded before changing the working directory, the path may be relative
+        let dedup_index = self
+            .dedup_index
+            .as_deref()
+            .map(DedupIndex::load)
+            .transpose()?;
+
         // we do this so we don't have to join output dir with entry path each time
         println!(
             "{} changing working directory to output path",
             "[+]".green()
         );
-        std::env::set_current_dir(output)
-            .context("failed to change working directory to output path")?;
+        if !output.as_os_str().is_empty() {
+            std::env::set_current_dir(&output)
+                .context("failed to change working directory to output path")?;
+        }
+
+        let fields = ArchiveFields {
+            game: archive.metadata().game,
+            archive: self.input.clone(),
+        };
+        let target = |path: &Path| match &self.path_template {
+            Some(template) => template.render(&fields, path),
+            None => path.to_path_buf(),
+        };
 
         // we collect everything in a vector so rayon can access them in random order
-        let files: Vec<_> = archive.files().collect();
+        let (files, archive_paths) = self.select(&archive, dir.as_deref());
+        let files: Vec<_> = files
+            .into_iter()
+            .map(|f| {
+                let target = target(&f.path);
+                (f, target)
+            })
+            .collect();
+
+        if self.path_template.is_some() {
+            let mut targets = ahash::HashSet::default();
+            if let Some((file, target)) = files.iter().find(|(_, t)| !targets.insert(t)) {
+                anyhow::bail!(
+                    "path template writes more than one file to {}, for example {}",
+                    target.display(),
+                    file.path.display()
+                );
+            }
+        }
+
+        if files.len() != archive.metadata().file_count {
+            println!(
+                "{} {} of {} files matched the filters",
+                "[+]".green(),
+                files.len(),
+                archive.metadata().file_count
+            );
+        }
 
         println!("{} starting the extraction", "[+]".green());
 
         let pb = utils::progress_bar(files.len() as _);
 
-        let hashes: ahash::HashMap<u32, u32> = files
-            .into_par_iter()
-            .map_with(pb.clone(), |pb, entry| {
-                let path_crc32 = crc32fast::hash(entry.path.display().to_string().as_bytes());
+        let handler = self.failure_handler(&pb);
 
-                // create output dir if not exist
-                let path = entry.path.with_file_name("");
-                if !path.is_dir() {
-                    std::fs::create_dir_all(path)?;
-                }
+        let overwrite = match self.skip_existing {
+            true => Overwrite::Changed,
+            false => self.overwrite,
+        };
+        let kept = AtomicUsize::new(0);
+
+        let game = archive.metadata().game;
+        let postprocess = self.postprocess_names(&target(Path::new(MANIFEST_FILE)));
+        let postprocessors = postprocess::Selected::new(&postprocess, game);
+        let postprocessed = AtomicUsize::new(0);
+
+        let extracted_files = files.len();
+        let total_bytes = files
+            .iter()
+            .map(|(f, _)| match self.raw {
+                true => f.raw_bytes.len() as u64,
+                false => f.uncompressed_size() as u64,
+            })
+            .sum();
+        // `None` for skipped files, the flag is set for files that are written as stored
+        let extracted: Vec<Option<(FileHash, Option<DedupEntry>, bool)>> =
+            utils::watch_progress_with_bytes(&pb, Some(total_bytes), |progress| {
+                files
+                    .into_par_iter()
+                    .map(|(entry, target)| {
+                        progress.working_on(entry.path.display().to_string());
 
-                // not the best way, but right now I really don't want to deal with custom error type
-                let bytes = entry.get_bytes()?;
+                        // create output dir if not exist
+                        let path = target.with_file_name("");
+                        if !path.is_dir() {
+                            std::fs::create_dir_all(path)?;
+                        }
+
+                        // not the best way, but right now I really don't want to deal with custom error type
+                        let (bytes, stored) = match self.raw {
+                            true => (Cow::Borrowed(entry.raw_bytes), false),
+                            false => match read_entry(&entry, handler.as_ref())? {
+                                Extracted::Decompressed(bytes) => (bytes, false),
+                                Extracted::Raw(bytes) => (Cow::Borrowed(bytes), true),
+                                Extracted::Skipped => {
+                                    progress.inc(Some(format!("(skp) {}", target.display())));
+                                    return Ok(None);
+                                }
+                            },
+                        };
+
+                        let crc32 = crc32fast::hash(&bytes);
 
-                // write to disk
-                std::fs::write(&entry.path, &bytes)?;
+                        let source = match &dedup_index {
+                            Some(index) => index.find_or_insert(&bytes, crc32, &target)?,
+                            None => None,
+                        };
+
+                        // write to disk, unless the same content is already extracted or the
+                        // existing file is kept
+                        let mut message = target.display().to_string();
+                        if source.is_none() {
+                            if overwrite.keep(&target, &bytes) {
+                                kept.fetch_add(1, Ordering::Relaxed);
+                                message = format!("(old) {message}");
+                            } else {
+                                let mut file = File::create(&target)?;
+                                rebuild_progress::write_data(&mut file, progress, &bytes)?;
+                            }
+                        }
 
-                pb.set_message(entry.path.display().to_string());
+                        if !postprocessors.is_empty() {
+                            let written = postprocessors.run(&target, &bytes)?;
+                            postprocessed.fetch_add(written.len(), Ordering::Relaxed);
+                        }
 
-                let content_crc32 = crc32fast::hash(&bytes);
+                        progress.inc(Some(message));
 
-                Ok((path_crc32, content_crc32))
+                        let hash = FileHash {
+                            path: entry.path.clone(),
+                            size: bytes.len() as _,
+                            crc32,
+                        };
+                        Ok(Some((
+                            hash,
+                            source.map(|source| DedupEntry { source, crc32 }),
+                            stored,
+                        )))
+                    })
+                    .collect::<Result<_, ExtractError>>()
             })
-            .progress_with(pb.clone())
-            .collect::<Result<_, ExtractError>>()
             .context("extraction failed")?;
 
         pb.finish_with_message(
@@ -153,17 +468,329 @@ impl Commands {
         );
 
         println!("{} extraction finished", "[+]".green());
+
+        let skipped = extracted.iter().filter(|e| e.is_none()).count();
+        let stored_files: Vec<_> = extracted
+            .iter()
+            .flatten()
+            .filter(|(_, _, stored)| *stored)
+            .map(|(hash, _, _)| hash.path.clone())
+            .collect();
+
+        print_failures(skipped, &stored_files);
+
+        let kept = kept.into_inner();
+        if kept != 0 {
+            println!(
+                "{} {kept} files already existed in the output folder and were kept",
+                "[+]".green()
+            );
+        }
+
+        let postprocessed = postprocessed.into_inner();
+        if !postprocessors.is_empty() {
+            println!(
+                "{} postprocessors wrote {postprocessed} files to {}",
+                "[+]".green(),
+                postprocess::POSTPROCESSED_DIR
+            );
+        }
+
+        let mut deduped = Deduped::default();
+        let hashes: Vec<FileHash> = extracted
+            .into_iter()
+            .flatten()
+            .map(|(hash, dedup, _)| {
+                if let Some(dedup) = dedup {
+                    deduped.files.insert(hash.path.clone(), dedup);
+                }
+                hash
+            })
+            .collect();
+
+        if let Some(index) = &dedup_index {
+            println!(
+                "{} {} files were already extracted, they are recorded in dedup.json instead",
+                "[+]".green(),
+                deduped.files.len()
+            );
+            index.save()?;
+
+            let writer = BufWriter::new(
+                File::create(target(Path::new(DEDUP_FILE)))
+                    .context("failed to create dedup.json file")?,
+            );
+            serde_json::to_writer_pretty(writer, &deduped.to_json())
+                .context("failed to serialize dedup.json")?;
+        }
+
         print!("{} writing hashes.json to output folder", "[+]".green());
 
-        let writer =
-            BufWriter::new(File::create(HASHES_FILE).context("failed to create hashes.json file")?);
+        let hashes_file = target(Path::new(HASHES_FILE));
+        if let Some(dir) = hashes_file.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            std::fs::create_dir_all(dir).context("failed to create hashes.json folder")?;
+        }
+        let writer = BufWriter::new(
+            File::create(&hashes_file).context("failed to create hashes.json file")?,
+        );
+
+        hashes::write(writer, hashes, self.hashes_format)?;
+
+        println!(": Done");
+        print!("{} writing manifest.json to output folder", "[+]".green());
 
-        serde_json::to_writer_pretty(writer, &hashes).context("failed to serialize file hashes")?;
+        let mut manifest = Manifest::new(&archive, &archive_paths, self.raw);
+        manifest.postprocess = postprocess;
+        let writer = BufWriter::new(
+            File::create(target(Path::new(MANIFEST_FILE)))
+                .context("failed to create manifest.json file")?,
+        );
+
+        serde_json::to_writer_pretty(writer, &manifest.to_json())
+            .context("failed to serialize manifest")?;
 
         println!(": Done");
 
+        output::report(
+            "extract",
+            json!({
+                "output": output,
+                "extracted_files": extracted_files - skipped,
+                "deduplicated_files": deduped.files.len(),
+                "archive_files": archive.metadata().file_count,
+                "raw": self.raw,
+                "skipped_files": skipped,
+                "existing_files": kept,
+                "postprocessed_files": postprocessed,
+                "stored_files": stored_files,
+            }),
+        );
+
+        Ok(())
+    }
+}
+
+impl Commands {
+    /// files that match the filters with their leading directories stripped, and the paths
+    /// of them inside the archive
+    fn select<'p>(
+        &self,
+        archive: &Archive<'p>,
+        dir: Option<&Path>,
+    ) -> (Vec<FullFileEntry<'p>>, ahash::HashSet<PathBuf>) {
+        // paths of the extracted files inside the archive, before stripping any directory
+        let mut archive_paths = ahash::HashSet::default();
+
+        let files = archive
+            .files()
+            .filter(|f| {
+                dir.is_none_or(|dir| f.path.starts_with(dir))
+                    && (self.include.is_empty() || self.include.iter().any(|g| g.is_match(&f.path)))
+                    && !self.exclude.iter().any(|g| g.is_match(&f.path))
+            })
+            .filter_map(|mut f| {
+                let archive_path = f.path.clone();
+                f.path = strip_components(&f.path, self.strip_components)?;
+                archive_paths.insert(archive_path);
+                Some(f)
+            })
+            .collect();
+
+        (files, archive_paths)
+    }
+
+    /// the postprocessors of `--postprocess`, or the ones that the manifest in the output
+    /// folder record when it's not given
+    fn postprocess_names(&self, manifest: &Path) -> Vec<String> {
+        if !self.postprocess.is_empty() || self.raw {
+            return self.postprocess.clone();
+        }
+
+        match Manifest::load(manifest) {
+            Ok(Some(manifest)) if !manifest.postprocess.is_empty() => {
+                println!(
+                    "{} using postprocessors {} from manifest.json",
+                    "[+]".green(),
+                    manifest.postprocess.join(", ")
+                );
+                manifest.postprocess
+            }
+            Ok(_) => Vec::new(),
+            Err(e) => {
+                println!(
+                    "{} ignoring the postprocessors of manifest.json: {e:#}",
+                    "[!]".yellow()
+                );
+                Vec::new()
+            }
+        }
+    }
+
+    /// write the decompressed files into a zip, the files are decompressed in order because
+    /// the zip is written sequentially
+    fn extract_to_zip(
+        &self,
+        archive: &Archive,
+        files: Vec<FullFileEntry>,
+        archive_paths: &ahash::HashSet<PathBuf>,
+        zip: &Path,
+    ) -> anyhow::Result<()> {
+        let mut names = ahash::HashSet::default();
+        if let Some(file) = files
+            .iter()
+            .find(|f| !names.insert(manifest::path_str(&f.path)))
+        {
+            anyhow::bail!(
+                "more than one file is written to {} after stripping the directories",
+                file.path.display()
+            );
+        }
+
+        if files.len() != archive.metadata().file_count {
+            println!(
+                "{} {} of {} files matched the filters",
+                "[+]".green(),
+                files.len(),
+                archive.metadata().file_count
+            );
+        }
+
+        println!("{} output zip: {}", "[+]".green(), zip.display());
+
+        let mut writer = ZipWriter::new(BufWriter::new(
+            File::create(zip).context("failed to create output zip")?,
+        ));
+
+        let pb = utils::progress_bar(files.len() as _);
+        let handler = self.failure_handler(&pb);
+
+        let total_bytes = files.iter().map(|f| f.uncompressed_size() as u64).sum();
+        let (skipped, stored_files, hashes) =
+            utils::watch_progress_with_bytes(&pb, Some(total_bytes), |progress| {
+                let mut skipped = 0;
+                let mut stored_files = Vec::new();
+                let mut hashes = Vec::with_capacity(files.len());
+
+                for entry in &files {
+                    progress.working_on(entry.path.display().to_string());
+
+                    let bytes = match read_entry(entry, handler.as_ref())? {
+                        Extracted::Decompressed(bytes) => bytes,
+                        Extracted::Raw(bytes) => {
+                            stored_files.push(entry.path.clone());
+                            Cow::Borrowed(bytes)
+                        }
+                        Extracted::Skipped => {
+                            skipped += 1;
+                            progress.inc(Some(format!("(skp) {}", entry.path.display())));
+                            continue;
+                        }
+                    };
+
+                    writer.add(&manifest::path_str(&entry.path), &bytes)?;
+                    progress.written(bytes.len() as _);
+                    progress.inc(Some(entry.path.display().to_string()));
+
+                    hashes.push(FileHash {
+                        path: entry.path.clone(),
+                        size: bytes.len() as _,
+                        crc32: crc32fast::hash(&bytes),
+                    });
+                }
+
+                anyhow::Ok((skipped, stored_files, hashes))
+            })
+            .context("extraction failed")?;
+
+        // same sidecars as the extracted folder, so `create` can skip the files that weren't modified
+        let mut sidecar = Vec::new();
+        hashes::write(&mut sidecar, hashes, self.hashes_format)?;
+        writer
+            .add(HASHES_FILE, &sidecar)
+            .context("failed to write hashes.json to output zip")?;
+
+        let manifest = Manifest::new(archive, archive_paths, false);
+        let sidecar = serde_json::to_vec_pretty(&manifest.to_json())
+            .context("failed to serialize manifest")?;
+        writer
+            .add(MANIFEST_FILE, &sidecar)
+            .context("failed to write manifest.json to output zip")?;
+
+        writer
+            .finish()
+            .and_then(|mut w| w.flush().map_err(Into::into))
+            .context("failed to finish output zip")?;
+
+        pb.finish_with_message(
+            "extraction finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        print_failures(skipped, &stored_files);
+        println!("{} extraction finished", "[+]".green());
+
+        output::report(
+            "extract",
+            json!({
+                "output": zip,
+                "zip": true,
+                "extracted_files": files.len() - skipped,
+                "deduplicated_files": 0,
+                "archive_files": archive.metadata().file_count,
+                "raw": false,
+                "skipped_files": skipped,
+                "stored_files": stored_files,
+            }),
+        );
+
         Ok(())
     }
+
+    fn failure_handler<'a>(&self, pb: &'a ProgressBar) -> Box<dyn FailureHandler + 'a> {
+        match self.on_error {
+            OnError::Ask => Box::new(PromptHandler {
+                pb,
+                lock: Mutex::new(()),
+            }),
+            OnError::Abort => Box::new(FailureAction::Abort),
+            OnError::Skip => Box::new(FailureAction::Skip),
+            OnError::Raw => Box::new(FailureAction::Raw),
+        }
+    }
+}
+
+fn print_failures(skipped: usize, stored_files: &[PathBuf]) {
+    if skipped != 0 {
+        println!(
+            "{} {skipped} files can't be decompressed and were skipped",
+            "[!]".yellow()
+        );
+    }
+    for path in stored_files {
+        println!(
+            "{} {} can't be decompressed, its stored bytes were written",
+            "[!]".yellow(),
+            path.display()
+        );
+    }
+}
+
+/// remove `count` leading directories from the path, return `None` if the file name would be removed too
+/// (the path has `count` or fewer components)
+fn strip_components(path: &Path, count: usize) -> Option<PathBuf> {
+    let mut components = path.components();
+    if components.clone().count() <= count {
+        return None;
+    }
+
+    for _ in 0..count {
+        components.next();
+    }
+
+    Some(components.as_path().to_path_buf())
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -0,0 +1,95 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    convert,
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{name_maps_for_game, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to the original hvp archive, used as the template of the new one
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path to the zip file, usually made using to-zip
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub zip: PathBuf,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+    /// skip compression of the updated files
+    #[arg(long, short = 'c', default_value_t = false, required = false)]
+    pub skip_compression: bool,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let mut archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                rebuild_skip_compression: self.skip_compression,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let zip = File::open(&self.zip).context("failed to open input zip")?;
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(archive.metadata().file_count as _);
+        let report = utils::watch_progress(&pb, |progress| {
+            convert::zip_to_hvp(BufReader::new(zip), &mut archive, &mut writer, progress)
+        })
+        .context("failed to convert zip to hvp")?;
+
+        pb.finish_with_message(
+            "rebuild finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} updated {} files, {} files were unchanged",
+            "[+]".green(),
+            report.converted,
+            report.unchanged
+        );
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            archive.metadata(),
+            json!({ "updated_files": report.converted, "unchanged_files": report.unchanged }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,252 @@
+//! a small glob matcher for paths inside archives
+//!
+//! supported syntax:
+//! - `*` match anything except `/`
+//! - `**` match anything, `**/` match zero or more directories
+//! - `?` match a single character except `/`
+//! - `[abc]`, `[a-z]` and `[!abc]` match a single character from (or not from) the set
+//!
+//! matching is ascii case insensitive and a pattern without any `/` is only matched
+//! against the file name, so `*.wav` match wav files in any directory.
+
+use std::path::Path;
+
+use ahash::HashSet;
+
+/// a compiled glob pattern
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: Vec<char>,
+    file_name_only: bool,
+}
+
+impl Glob {
+    /// parse the glob pattern, return an error if the pattern is invalid
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let pattern: Vec<char> = pattern.replace('\\', "/").chars().collect();
+
+        let mut i = 0;
+        while i < pattern.len() {
+            if pattern[i] == '[' {
+                let Some(end) = class_end(&pattern[i..]) else {
+                    return Err("unclosed '[' in glob pattern".to_owned());
+                };
+                i += end;
+            }
+            i += 1;
+        }
+
+        Ok(Self {
+            file_name_only: !pattern.contains(&'/'),
+            pattern,
+        })
+    }
+
+    /// check whatever the path match the pattern
+    pub fn is_match(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy().replace('\\', "/");
+        let text = match self.file_name_only {
+            true => path.rsplit('/').next().unwrap_or_default(),
+            false => path.as_str(),
+        };
+        let text: Vec<char> = text.chars().collect();
+
+        matches(&self.pattern, &text)
+    }
+}
+
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    Matcher::default().matches(pattern, text)
+}
+
+/// remember the suffixes of the pattern and the text that don't match, so patterns with many
+/// `*` or `**` don't try the same split again and again
+#[derive(Default)]
+struct Matcher {
+    failed: HashSet<(usize, usize)>,
+}
+
+impl Matcher {
+    fn matches(&mut self, pattern: &[char], text: &[char]) -> bool {
+        // the pattern and the text are always suffixes, so their length identify them
+        let key = (pattern.len(), text.len());
+        if self.failed.contains(&key) {
+            return false;
+        }
+
+        let found = match pattern {
+            [] => text.is_empty(),
+            ['*', '*', '/', rest @ ..] => {
+                // zero directories, or skip any number of full directories
+                self.matches(rest, text)
+                    || text
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| **c == '/')
+                        .any(|(i, _)| self.matches(rest, &text[i + 1..]))
+            }
+            ['*', '*', rest @ ..] => (0..=text.len()).any(|i| self.matches(rest, &text[i..])),
+            ['*', rest @ ..] => {
+                let max = text.iter().position(|c| *c == '/').unwrap_or(text.len());
+                (0..=max).any(|i| self.matches(rest, &text[i..]))
+            }
+            ['?', rest @ ..] => match text {
+                [c, text @ ..] if *c != '/' => self.matches(rest, text),
+                _ => false,
+            },
+            ['[', ..] => {
+                let end = class_end(pattern).expect("glob pattern is validated on creation");
+                match text {
+                    [c, text @ ..] if *c != '/' && class_match(&pattern[1..end], *c) => {
+                        self.matches(&pattern[end + 1..], text)
+                    }
+                    _ => false,
+                }
+            }
+            [p, rest @ ..] => match text {
+                [c, text @ ..] if p.eq_ignore_ascii_case(c) => self.matches(rest, text),
+                _ => false,
+            },
+        };
+
+        if !found {
+            self.failed.insert(key);
+        }
+        found
+    }
+}
+
+/// index of the `]` that close the class at the start of pattern
+fn class_end(pattern: &[char]) -> Option<usize> {
+    // a `]` right after `[` or `[!` is part of the class
+    let start = if pattern.get(1) == Some(&'!') { 3 } else { 2 };
+    (start..pattern.len()).find(|&i| pattern[i] == ']')
+}
+
+fn class_match(class: &[char], c: char) -> bool {
+    let (negate, class) = match class {
+        ['!', class @ ..] => (true, class),
+        class => (false, class),
+    };
+
+    let c = c.to_ascii_lowercase();
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            let (start, end) = (
+                class[i].to_ascii_lowercase(),
+                class[i + 2].to_ascii_lowercase(),
+            );
+            found |= (start..=end).contains(&c);
+            i += 3;
+        } else {
+            found |= class[i].to_ascii_lowercase() == c;
+            i += 1;
+        }
+    }
+
+    found != negate
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn is_match(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).unwrap().is_match(Path::new(path))
+    }
+
+    #[test]
+    fn file_name_only() {
+        assert!(is_match("*.wav", "sound/music/intro.wav"));
+        assert!(is_match("*.wav", "intro.wav"));
+        assert!(is_match("intro.*", "sound/intro.wav"));
+        assert!(!is_match("*.wav", "sound/intro.wav.bak"));
+        assert!(!is_match("sound", "sound/intro.wav"));
+    }
+
+    #[test]
+    fn star_stays_in_its_directory() {
+        assert!(is_match("sound/*.wav", "sound/intro.wav"));
+        assert!(!is_match("sound/*.wav", "sound/music/intro.wav"));
+        assert!(is_match("sound/*/*.wav", "sound/music/intro.wav"));
+        assert!(is_match("sound/*", "sound/intro.wav"));
+        assert!(!is_match("sound/*", "sound/music/intro.wav"));
+        assert!(is_match("*/intro.wav", "sound/intro.wav"));
+    }
+
+    #[test]
+    fn double_star() {
+        assert!(is_match("sound/**/*.wav", "sound/intro.wav"));
+        assert!(is_match("sound/**/*.wav", "sound/music/boss/intro.wav"));
+        assert!(is_match("**/intro.wav", "intro.wav"));
+        assert!(is_match("**/intro.wav", "a/b/c/intro.wav"));
+        assert!(!is_match("**/intro.wav", "a/b/c/outro.wav"));
+        assert!(is_match("sound/**", "sound/music/intro.wav"));
+        assert!(!is_match("sound/**", "levels/sound/intro.wav"));
+        // `**` that isn't followed by `/` can cross directories in the middle of a name
+        assert!(is_match("sound/m**.wav", "sound/music/intro.wav"));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(is_match("levels/l0?.lvl", "levels/l01.lvl"));
+        assert!(!is_match("levels/l0?.lvl", "levels/l0.lvl"));
+        assert!(!is_match("levels/l0?.lvl", "levels/l012.lvl"));
+        assert!(!is_match("a?b/c", "a/b/c"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(is_match("l[0-3].lvl", "l2.lvl"));
+        assert!(!is_match("l[0-3].lvl", "l4.lvl"));
+        assert!(is_match("l[!0-3].lvl", "l4.lvl"));
+        assert!(!is_match("l[!0-3].lvl", "l2.lvl"));
+        assert!(is_match("[abc]x", "bx"));
+        assert!(is_match("[a-cx-z]x", "yx"));
+        // `]` right after `[` or `[!` is a member of the class
+        assert!(is_match("[]]x", "]x"));
+        assert!(is_match("[!]]x", "ax"));
+        assert!(!is_match("[!]]x", "]x"));
+        // a `-` at the end is a member too
+        assert!(is_match("[a-]x", "-x"));
+        assert!(!is_match("a[/]b/c", "a/b/c"));
+    }
+
+    #[test]
+    fn case_insensitive_and_separators() {
+        assert!(is_match("SOUND/*.WAV", "sound/intro.wav"));
+        assert!(is_match("[A-C]*.wav", "b.wav"));
+        assert!(is_match(r"sound\*.wav", "sound/intro.wav"));
+        assert!(is_match("sound/*.wav", r"sound\intro.wav"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        for pattern in ["[abc", "a/[!]", "[]"] {
+            assert_eq!(
+                Glob::new(pattern).unwrap_err(),
+                "unclosed '[' in glob pattern",
+                "{pattern}"
+            );
+        }
+        assert!(Glob::new("").unwrap().is_match(Path::new("")));
+        assert!(!Glob::new("").unwrap().is_match(Path::new("a")));
+    }
+
+    #[test]
+    fn pathological_patterns_are_fast() {
+        let long = format!("{}/{}", "a/".repeat(200), "a".repeat(200));
+        let start = Instant::now();
+        assert!(!is_match(&format!("{}b", "**a".repeat(30)), &long));
+        assert!(!is_match(
+            &format!("{}b", "*a".repeat(30)),
+            &"a".repeat(500)
+        ));
+        assert!(!is_match(&format!("{}b", "**/a".repeat(30)), &long));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
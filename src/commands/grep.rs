@@ -0,0 +1,169 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options, entry::FullFileEntry},
+    provider::ArchiveProvider,
+};
+use indicatif::ParallelProgressIterator;
+use owo_colors::OwoColorize;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde_json::json;
+
+use super::{
+    glob::Glob,
+    name_maps_for_game,
+    output::{self, println},
+    pattern::{Encoding, Pattern},
+    utils,
+};
+
+/// number of characters of each match that are printed
+const PREVIEW_LEN: usize = 60;
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// text to search for, a regex with --regex or bytes with --hex
+    pub pattern: String,
+    /// the pattern is a regex, for example `open_door_\d+`
+    #[arg(long, short = 'E', default_value_t = false, conflicts_with = "hex")]
+    pub regex: bool,
+    /// the pattern is hex bytes, for example `de ad be ef`
+    #[arg(long, short = 'x', default_value_t = false)]
+    pub hex: bool,
+    /// how the text is stored inside the files
+    #[arg(long, default_value_t = Encoding::Utf8, value_enum, conflicts_with = "hex")]
+    pub encoding: Encoding,
+    /// ignore the case of ascii letters
+    #[arg(long, short = 'i', default_value_t = false, conflicts_with = "hex")]
+    pub ignore_case: bool,
+    /// only search files that match this glob, for example `**/*.txt` (can be used multiple times)
+    #[arg(long, value_parser = Glob::new)]
+    pub include: Vec<Glob>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let pattern = match (self.regex, self.hex) {
+            (_, true) => Pattern::hex(&self.pattern),
+            (true, false) => Pattern::regex(&self.pattern, self.encoding, self.ignore_case),
+            (false, false) => Pattern::text(&self.pattern, self.encoding, self.ignore_case),
+        }
+        .map_err(anyhow::Error::msg)
+        .context("invalid pattern")?;
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        let included = |file: &FullFileEntry| {
+            self.include.is_empty() || self.include.iter().any(|g| g.is_match(&file.path))
+        };
+        let files: Vec<FullFileEntry> = archive.files().filter(included).collect();
+
+        println!(
+            "{} searching the content of {} files",
+            "[+]".green(),
+            files.len()
+        );
+
+        let pb = utils::progress_bar(files.len() as _);
+
+        let results: Vec<_> = files
+            .into_par_iter()
+            .progress_with(pb.clone())
+            .map(|file| {
+                let matches = file.get_bytes().map(|bytes| {
+                    pattern
+                        .find_all(&bytes)
+                        .into_iter()
+                        .map(|range| (range.start, self.preview(&bytes[range])))
+                        .collect::<Vec<_>>()
+                });
+                (file.path, matches)
+            })
+            .collect();
+
+        pb.finish_and_clear();
+
+        let mut found = Vec::new();
+        let mut failed = 0;
+
+        for (path, matches) in results {
+            let matches = match matches {
+                Ok(matches) if matches.is_empty() => continue,
+                Ok(matches) => matches,
+                Err(e) => {
+                    println!(
+                        "{} failed to decompress {}: {e}",
+                        "[!]".yellow(),
+                        path.display()
+                    );
+                    failed += 1;
+                    continue;
+                }
+            };
+
+            println!(" {} {}", "|>".cyan(), path.display());
+            for (offset, preview) in &matches {
+                println!("    {offset:#010x}: {preview}");
+            }
+
+            found.push(json!({
+                "path": path,
+                "matches": matches
+                    .iter()
+                    .map(|(offset, text)| json!({ "offset": offset, "text": text }))
+                    .collect::<Vec<_>>(),
+            }));
+        }
+
+        println!("{} found matches in {} files", "[+]".green(), found.len());
+
+        output::report("grep", json!({ "files": found, "failed": failed }));
+
+        if failed != 0 {
+            anyhow::bail!("{failed} files couldn't be decompressed");
+        }
+
+        Ok(())
+    }
+
+    /// the matched bytes as printable text
+    fn preview(&self, bytes: &[u8]) -> String {
+        let text = match self.hex {
+            true => bytes
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<_>>()
+                .join(" "),
+            false => self
+                .encoding
+                .decode(bytes)
+                .chars()
+                .map(|c| if c.is_control() { '.' } else { c })
+                .collect(),
+        };
+
+        match text.chars().count() > PREVIEW_LEN {
+            true => text
+                .chars()
+                .take(PREVIEW_LEN)
+                .chain("...".chars())
+                .collect(),
+            false => text,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::archive::Obscure2NameMap;
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// names to hash, for example `menu.tga`
+    pub names: Vec<String>,
+    /// also hash every line of this file
+    #[arg(long, short = 'f', value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub file: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self) -> anyhow::Result<()> {
+        let mut names = self.names;
+
+        if let Some(file) = &self.file {
+            let reader = BufReader::new(File::open(file).context("failed to open names file")?);
+            for line in reader.lines() {
+                let line = line.context("failed to read names file")?;
+                let line = line.trim();
+                if !line.is_empty() {
+                    names.push(line.to_owned());
+                }
+            }
+        }
+
+        if names.is_empty() {
+            anyhow::bail!("no name to hash");
+        }
+
+        let mut hashes = Vec::with_capacity(names.len());
+
+        for name in &names {
+            match Obscure2NameMap::name_crc32(name) {
+                Some(crc32) => {
+                    println!("{crc32:#010x} {crc32:>10} {name}");
+                    hashes.push(json!({ "name": name, "crc32": crc32 }));
+                }
+                None => {
+                    println!(
+                        "{} {name}: only ascii characters and 'é' can be used in names",
+                        "[!]".yellow()
+                    );
+                    hashes.push(json!({ "name": name, "crc32": null }));
+                }
+            }
+        }
+
+        output::report("hash", json!(hashes));
+
+        Ok(())
+    }
+}
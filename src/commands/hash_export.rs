@@ -0,0 +1,144 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    digest,
+    manifest::path_str,
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// output checksum file, if empty a file with the same name of input hvp and the extension
+    /// of the format will be created
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+    /// format of the checksum file
+    #[arg(long, short = 'f', default_value_t = HashFormat::Sfv, value_enum, required = false)]
+    pub format: HashFormat,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HashFormat {
+    /// crc32 of every file, for sfv checkers like `cksfv`
+    #[default]
+    Sfv,
+    /// md5 of every file, for `md5sum -c`
+    Md5sum,
+    /// sha256 of every file, for `sha256sum -c`
+    Sha256sum,
+}
+
+impl HashFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Sfv => "sfv",
+            Self::Md5sum => "md5",
+            Self::Sha256sum => "sha256",
+        }
+    }
+
+    fn line(self, path: &str, bytes: &[u8]) -> String {
+        match self {
+            Self::Sfv => format!("{path} {:08X}", crc32fast::hash(bytes)),
+            Self::Md5sum => format!("{}  {path}", digest::hex(&digest::md5(bytes))),
+            Self::Sha256sum => format!("{}  {path}", digest::hex(&digest::sha256(bytes))),
+        }
+    }
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let output = self
+            .output
+            .unwrap_or_else(|| self.input.with_extension(self.format.extension()));
+
+        println!("{} output file: {}", "[+]".green(), output.display());
+
+        let mut writer =
+            BufWriter::new(File::create(&output).context("failed to create output file")?);
+
+        if self.format == HashFormat::Sfv {
+            writeln!(
+                writer,
+                "; generated by obscure-hvp from {}",
+                self.input.file_name().unwrap_or_default().to_string_lossy()
+            )
+            .context("failed to write checksum file")?;
+        }
+
+        let pb = utils::progress_bar(archive.metadata().file_count as _);
+
+        // paths are relative to the extracted folder, so the tools can check an extraction
+        let mut files = 0;
+        let mut failed = Vec::new();
+        for file in archive.files() {
+            pb.inc(1);
+
+            let path = path_str(&file.path);
+            match file.get_bytes() {
+                Ok(bytes) => {
+                    writeln!(writer, "{}", self.format.line(&path, &bytes))
+                        .context("failed to write checksum file")?;
+                    files += 1;
+                }
+                Err(e) => {
+                    pb.suspend(|| println!("{} failed to decompress {path}: {e}", "[!]".yellow()));
+                    failed.push(path);
+                }
+            }
+        }
+
+        pb.finish_and_clear();
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} wrote checksums of {files} files, {} files failed",
+            "[+]".green(),
+            failed.len()
+        );
+
+        output::report(
+            "hash_export",
+            json!({
+                "output": output,
+                "format": self.format.extension(),
+                "files": files,
+                "failed": failed,
+            }),
+        );
+
+        Ok(())
+    }
+}
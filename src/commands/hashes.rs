@@ -0,0 +1,138 @@
+//! `hashes.json` that is written next to the extracted files
+//!
+//! it keep the crc32 of every extracted file, so `create` can skip the files that weren't modified.
+//! the files are always written in the same order, so the file doesn't change between extractions
+//! of the same archive.
+
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde_json::{Value, json};
+
+use super::manifest::path_str;
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum HashesFormat {
+    /// crc32 of the path mapped to the crc32 of the content
+    #[default]
+    Compact,
+    /// a record with the path, size and crc32 of every file, easier to read and diff
+    Records,
+}
+
+/// hash of a single extracted file
+pub struct FileHash {
+    /// path relative to the extracted folder
+    pub path: PathBuf,
+    pub size: u64,
+    pub crc32: u32,
+}
+
+/// crc32 of the path that is used as the key of the compact format
+pub fn path_crc32(path: &Path) -> u32 {
+    crc32fast::hash(path.display().to_string().as_bytes())
+}
+
+/// write the hashes of the files, sorted by their path or path crc32
+pub fn write(
+    writer: impl Write,
+    mut files: Vec<FileHash>,
+    format: HashesFormat,
+) -> anyhow::Result<()> {
+    match format {
+        HashesFormat::Compact => {
+            let hashes: BTreeMap<u32, u32> = files
+                .iter()
+                .map(|file| (path_crc32(&file.path), file.crc32))
+                .collect();
+            serde_json::to_writer_pretty(writer, &hashes)
+        }
+        HashesFormat::Records => {
+            files.sort_by(|a, b| a.path.cmp(&b.path));
+            let records: Vec<Value> = files
+                .iter()
+                .map(|file| {
+                    json!({
+                        // always use `/` so the file is the same on every platform
+                        "path": path_str(&file.path),
+                        "size": file.size,
+                        "crc32": file.crc32,
+                    })
+                })
+                .collect();
+            serde_json::to_writer_pretty(writer, &records)
+        }
+    }
+    .context("failed to serialize file hashes")
+}
+
+/// load the hashes in any of the formats, content crc32 by path crc32
+pub fn load(bytes: &[u8]) -> anyhow::Result<ahash::HashMap<u32, u32>> {
+    let value: Value = serde_json::from_slice(bytes).context("invalid json")?;
+
+    if let Value::Array(records) = value {
+        return records
+            .iter()
+            .map(|record| {
+                let path = record
+                    .get("path")
+                    .and_then(Value::as_str)
+                    .context("record without path")?;
+                let crc32 = record
+                    .get("crc32")
+                    .and_then(Value::as_u64)
+                    .and_then(|c| u32::try_from(c).ok())
+                    .with_context(|| format!("{path} without a valid crc32"))?;
+                Ok((path_crc32(&path.split('/').collect::<PathBuf>()), crc32))
+            })
+            .collect();
+    }
+
+    serde_json::from_value(value).context("invalid hashes")
+}
+
+/// move the hashes of the renamed files (old and new path) to their new path, the hashes are
+/// written back in the format that they were loaded from
+pub fn rename(bytes: &[u8], renamed: &[(PathBuf, PathBuf)]) -> anyhow::Result<Vec<u8>> {
+    let value: Value = serde_json::from_slice(bytes).context("invalid json")?;
+
+    match value {
+        Value::Array(mut records) => {
+            let renamed: ahash::HashMap<String, String> = renamed
+                .iter()
+                .map(|(old, new)| (path_str(old), path_str(new)))
+                .collect();
+
+            for record in &mut records {
+                let new = record
+                    .get("path")
+                    .and_then(Value::as_str)
+                    .and_then(|path| renamed.get(path));
+                if let Some(new) = new.cloned() {
+                    record["path"] = Value::String(new);
+                }
+            }
+
+            records.sort_by(|a, b| a["path"].as_str().cmp(&b["path"].as_str()));
+            serde_json::to_vec_pretty(&records)
+        }
+        value => {
+            let mut hashes: BTreeMap<u32, u32> =
+                serde_json::from_value(value).context("invalid hashes")?;
+
+            for (old, new) in renamed {
+                if let Some(crc32) = hashes.remove(&path_crc32(old)) {
+                    hashes.insert(path_crc32(new), crc32);
+                }
+            }
+
+            serde_json::to_vec_pretty(&hashes)
+        }
+    }
+    .context("failed to serialize file hashes")
+}
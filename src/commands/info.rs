@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path of the file inside the archive, for example `some/dir/file.bin`
+    pub path: PathBuf,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        let info = archive
+            .file_info(&self.path)
+            .with_context(|| format!("can't find {} in the archive", self.path.display()))?;
+
+        let compression = info.compression.map(|c| format!("{c:?}"));
+
+        output::report(
+            "info",
+            json!({
+                "path": info.path,
+                "name": info.name,
+                "dir": info.dir,
+                "stored_size": info.stored_size,
+                "uncompressed_size": info.uncompressed_size,
+                "compression": compression,
+                "stored_checksum": info.stored_checksum,
+                "checksum_match": info.checksum_match,
+                "data_offset": info.data_offset,
+                "name_crc32": info.name_crc32,
+            }),
+        );
+
+        let status = match info.checksum_match {
+            Some(true) => "ok".green().to_string(),
+            Some(false) => "mismatch".red().to_string(),
+            None => "not checked".to_owned(),
+        };
+
+        println!("{} {}:", "[?]".green(), info.path.display());
+        println!(" {} directory: {}", "|>".cyan(), info.dir.display());
+        println!(" {} stored size: {}", "|>".cyan(), info.stored_size);
+        println!(
+            " {} uncompressed size: {}",
+            "|>".cyan(),
+            info.uncompressed_size
+        );
+        println!(
+            " {} compression: {}",
+            "|>".cyan(),
+            compression.as_deref().unwrap_or("none")
+        );
+        println!(
+            " {} stored checksum: {:#010x} [{status}]",
+            "|>".cyan(),
+            info.stored_checksum
+        );
+        println!(" {} data offset: {:#x}", "|>".cyan(), info.data_offset);
+        if let Some(crc32) = info.name_crc32 {
+            println!(" {} name crc32: {crc32:#010x}", "|>".cyan());
+        }
+
+        Ok(())
+    }
+}
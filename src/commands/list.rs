@@ -0,0 +1,346 @@
+use std::{
+    cmp,
+    io::{BufWriter, ErrorKind, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum, ValueHint};
+use hvp_archive::{
+    archive::{
+        Archive, Options,
+        entry::{Entry, FileEntry},
+    },
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+
+use super::{
+    name_maps_for_game, names,
+    output::{self, SortOrder, println},
+    size, utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// print the directories and files as a tree instead of a path per file
+    #[arg(long, short = 't', default_value_t = false, required = false)]
+    pub tree: bool,
+    /// print the stored and uncompressed size, compression ratio, checksum status and
+    /// whatever the name is resolved for every file
+    #[arg(long, short = 'l', default_value_t = false, required = false, conflicts_with_all = ["tree", "format"])]
+    pub long: bool,
+    /// order of the entries inside each directory
+    #[arg(long, short = 's', value_enum, default_value_t)]
+    pub sort: SortOrder,
+    /// format of the listing, `jsonl` write a json object per entry to stdout while walking
+    /// the TOC, without keeping the listing in memory
+    #[arg(
+        long,
+        short = 'f',
+        value_enum,
+        default_value_t,
+        conflicts_with = "tree"
+    )]
+    pub format: ListFormat,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ListFormat {
+    /// the path and size of every file, or a tree with `--tree`
+    #[default]
+    Text,
+    /// newline delimited json, a line for every directory and file
+    Jsonl,
+}
+
+/// a entry of the listing, with the children of directories already sorted
+struct Node<'a> {
+    name: String,
+    file: Option<&'a FileEntry<'a>>,
+    children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    fn new(entry: &'a Entry<'a>, sort: SortOrder) -> Self {
+        match entry {
+            Entry::File(file) => Node {
+                name: file.name().to_owned(),
+                file: Some(file),
+                children: Vec::new(),
+            },
+            Entry::Dir(dir) => Node {
+                name: dir.name.clone(),
+                file: None,
+                children: nodes(&dir.entries, sort),
+            },
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self.file {
+            Some(file) => json!({ "name": self.name, "size": file.uncompressed_size() }),
+            None => json!({
+                "name": self.name,
+                "entries": self.children.iter().map(Node::to_json).collect::<Vec<_>>(),
+            }),
+        }
+    }
+}
+
+fn nodes<'a>(entries: &'a [Entry<'a>], sort: SortOrder) -> Vec<Node<'a>> {
+    let mut nodes: Vec<_> = entries.iter().map(|e| Node::new(e, sort)).collect();
+    sort.sort_by_name(&mut nodes, |n| &n.name);
+    nodes
+}
+
+/// path of every file in the listing order
+fn files<'a>(nodes: &[Node<'a>], parent: &str, out: &mut Vec<(String, &'a FileEntry<'a>)>) {
+    for node in nodes {
+        let path = match parent.is_empty() {
+            true => node.name.clone(),
+            false => format!("{parent}/{}", node.name),
+        };
+
+        match node.file {
+            Some(file) => out.push((path, file)),
+            None => files(&node.children, &path, out),
+        }
+    }
+}
+
+fn print_tree(nodes: &[Node], prefix: &str) {
+    for (idx, node) in nodes.iter().enumerate() {
+        let last = idx + 1 == nodes.len();
+        let branch = if last { "└── " } else { "├── " };
+
+        match node.file {
+            Some(file) => println!(
+                "{prefix}{}{} ({})",
+                branch.cyan(),
+                node.name,
+                file.uncompressed_size()
+            ),
+            None => {
+                println!("{prefix}{}{}/", branch.cyan(), node.name.blue());
+                let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+                print_tree(&node.children, &prefix);
+            }
+        }
+    }
+}
+
+/// stored size divided by the uncompressed size, 1 for files that aren't compressed
+fn ratio(file: &FileEntry) -> f64 {
+    match file.uncompressed_size() {
+        0 => 1.0,
+        size => file.raw_bytes.len() as f64 / size as f64,
+    }
+}
+
+/// print a line with the details of every file, like `ls -l`
+fn print_long(paths: &[(String, &FileEntry)], sort: SortOrder, has_checksums: bool) {
+    let rows: Vec<_> = paths
+        .iter()
+        .map(|(path, file)| {
+            let checksum = has_checksums.then(|| file.checksum_match());
+            let resolved = !names::is_unknown(file.name());
+            (path, file, checksum, resolved)
+        })
+        .collect();
+
+    println!(
+        "{}",
+        format!(
+            "{:>10} {:>10} {:>6} {:<5} {:<8} {:<4} path",
+            "stored", "size", "ratio", "comp", "checksum", "name"
+        )
+        .dimmed()
+    );
+
+    for (path, file, checksum, resolved) in &rows {
+        let compression = file
+            .compression_info()
+            .map_or("-".to_owned(), |c| format!("{:?}", c.compression_type));
+        let checksum = match checksum {
+            Some(true) => format!("{:<8}", "ok").green().to_string(),
+            Some(false) => format!("{:<8}", "bad").red().to_string(),
+            None => format!("{:<8}", "-"),
+        };
+        let name = match resolved {
+            true => format!("{:<4}", "yes"),
+            false => format!("{:<4}", "no").yellow().to_string(),
+        };
+
+        println!(
+            "{:>10} {:>10} {:>5.1}% {compression:<5} {checksum} {name} {path}",
+            size::format(file.raw_bytes.len() as u64),
+            size::format(file.uncompressed_size() as u64),
+            ratio(file) * 100.0,
+        );
+    }
+
+    output::report(
+        "list",
+        json!({
+            "sort": format!("{:?}", sort),
+            "files": rows
+                .iter()
+                .map(|(path, file, checksum, resolved)| json!({
+                    "path": path,
+                    "size": file.uncompressed_size(),
+                    "stored_size": file.raw_bytes.len(),
+                    "ratio": ratio(file),
+                    "compression": file.compression_info().map(|c| format!("{:?}", c.compression_type)),
+                    "checksum_match": checksum,
+                    "name_resolved": resolved,
+                }))
+                .collect::<Vec<_>>(),
+        }),
+    );
+}
+
+/// write a json line for every entry, the entries of each directory are sorted
+/// right before they are written, so only a single directory is held at a time
+fn write_jsonl(
+    entries: &[Entry],
+    parent: &str,
+    sort: SortOrder,
+    writer: &mut impl Write,
+) -> std::io::Result<()> {
+    let mut entries: Vec<_> = entries.iter().collect();
+    sort.sort_by_name(&mut entries, |e| match e {
+        Entry::File(file) => file.name(),
+        Entry::Dir(dir) => &dir.name,
+    });
+
+    for entry in entries {
+        let name = match entry {
+            Entry::File(file) => file.name(),
+            Entry::Dir(dir) => &dir.name,
+        };
+        let path = match parent.is_empty() {
+            true => name.to_owned(),
+            false => format!("{parent}/{name}"),
+        };
+
+        let line = match entry {
+            Entry::File(file) => json!({
+                "kind": "File",
+                "path": path,
+                "stored_size": file.raw_bytes.len(),
+                "uncompressed_size": file.uncompressed_size(),
+                "compression": file.compression_info().map(|c| format!("{:?}", c.compression_type)),
+                "stored_checksum": file.stored_checksum(),
+            }),
+            Entry::Dir(dir) => json!({
+                "kind": "Dir",
+                "path": path,
+                "entries": dir.entries.len(),
+            }),
+        };
+
+        serde_json::to_writer(&mut *writer, &line)?;
+        writer.write_all(b"\n")?;
+
+        if let Entry::Dir(dir) = entry {
+            write_jsonl(&dir.entries, &path, sort, writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        if self.format == ListFormat::Jsonl && output::is_json() {
+            anyhow::bail!(
+                "`--format jsonl` already write json to stdout, it can't be used with `--json`"
+            );
+        }
+        output::set_streaming(self.format == ListFormat::Jsonl);
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        if self.format == ListFormat::Jsonl {
+            let mut writer = BufWriter::new(std::io::stdout().lock());
+            let result = write_jsonl(archive.entries(), "", self.sort, &mut writer)
+                .and_then(|_| writer.flush());
+            return match result {
+                // the reader is gone, like `head`, there is nothing left to do
+                Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+                result => result.context("failed to write to stdout"),
+            };
+        }
+
+        let nodes = nodes(archive.entries(), self.sort);
+
+        match self.tree {
+            true => {
+                println!("{}", self.input.display().blue());
+                print_tree(&nodes, "");
+
+                output::report(
+                    "list",
+                    json!({
+                        "sort": format!("{:?}", self.sort),
+                        "tree": nodes.iter().map(Node::to_json).collect::<Vec<_>>(),
+                    }),
+                );
+            }
+            false => {
+                let mut paths = Vec::new();
+                files(&nodes, "", &mut paths);
+
+                match self.sort {
+                    SortOrder::Size => {
+                        paths.sort_by_key(|(_, file)| cmp::Reverse(file.uncompressed_size()))
+                    }
+                    SortOrder::Ratio => {
+                        paths.sort_by(|(_, a), (_, b)| ratio(b).total_cmp(&ratio(a)))
+                    }
+                    _ => {}
+                }
+
+                if self.long {
+                    print_long(&paths, self.sort, archive.metadata().has_checksums);
+                    return Ok(());
+                }
+
+                for (path, file) in &paths {
+                    println!(
+                        "{path} {}",
+                        format!("({})", file.uncompressed_size()).dimmed()
+                    );
+                }
+
+                output::report(
+                    "list",
+                    json!({
+                        "sort": format!("{:?}", self.sort),
+                        "files": paths
+                            .iter()
+                            .map(|(path, file)| json!({ "path": path, "size": file.uncompressed_size() }))
+                            .collect::<Vec<_>>(),
+                    }),
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
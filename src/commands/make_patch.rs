@@ -0,0 +1,120 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{
+        Archive, Options,
+        patch_file::{ArchivePatch, PatchData},
+    },
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to the original hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path to the modified hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub modified: PathBuf,
+    /// path to the output patch file
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub output: PathBuf,
+    /// store changed files as a binary diff against the original file when it is smaller
+    #[arg(long, default_value_t = false, required = false)]
+    pub binary_diff: bool,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider, game: Option<Game>) -> anyhow::Result<()> {
+        let file = File::open(&self.modified).context("failed to open modified hvp archive")?;
+        let modified_provider =
+            ArchiveProvider::new(file, game).context("failed to load modified hvp archive")?;
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        let obscure2_names = name_maps_for_game(modified_provider.game())?;
+        let modified_archive = Archive::new_with_options(
+            &modified_provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        println!(
+            "{} making patch from {} to {}",
+            "[+]".green(),
+            self.input.display(),
+            self.modified.display()
+        );
+
+        let patch = ArchivePatch::generate(&archive, &modified_archive, self.binary_diff)
+            .context("failed to make patch")?;
+
+        let mut diffed = 0;
+        for entry in &patch.entries {
+            let kind = match &entry.data {
+                PatchData::Full(data) => format!("full, {} bytes", data.len()),
+                PatchData::Delta(ops) => {
+                    diffed += 1;
+                    format!("binary diff, {} operations", ops.len())
+                }
+            };
+            println!(" {} {} ({kind})", "[M]".yellow(), entry.path.display());
+        }
+
+        let mut writer =
+            BufWriter::new(File::create(&self.output).context("failed to create patch file")?);
+        patch
+            .write(&mut writer)
+            .context("failed to write patch file")?;
+        writer.flush().context("failed to flush writer")?;
+
+        let size = writer.get_ref().metadata().map_or(0, |m| m.len());
+
+        println!(
+            "{} wrote {} changed files ({diffed} as binary diff) to {}, {size} bytes",
+            "[+]".green(),
+            patch.entries.len(),
+            self.output.display(),
+        );
+
+        output::report(
+            "make_patch",
+            json!({
+                "output": self.output,
+                "size": size,
+                "files": patch.entries.iter().map(|e| json!({
+                    "path": e.path,
+                    "binary_diff": matches!(e.data, PatchData::Delta(_)),
+                })).collect::<Vec<_>>(),
+            }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! sidecar manifest that is written next to the extracted files
+//!
+//! it record how every file was stored in the original archive, so `create` can keep the
+//! compression of the files and resolve the obscure 2 names even without the name maps.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use hvp_archive::{
+    Game,
+    archive::{Archive, Obscure2NameMap, entry::CompressionType},
+};
+use serde_json::{Value, json};
+
+/// how a single file was stored in the archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub compressed: bool,
+    /// compression of the stored data, `None` in manifests that were written before it was recorded
+    pub compression: Option<CompressionType>,
+    /// size of the file after decompression, `None` in manifests that were written before it was recorded
+    pub uncompressed_size: Option<u64>,
+    /// checksum that was stored in the archive
+    pub checksum: i32,
+    /// crc32 of the name that was stored in the archive, obscure 2 only
+    pub name_crc32: Option<u32>,
+}
+
+pub struct Manifest {
+    pub game: Game,
+    /// files were written as they are stored, so the compressed files are still compressed
+    pub raw: bool,
+    /// entries by their path inside the archive
+    pub files: ahash::HashMap<PathBuf, ManifestEntry>,
+    /// postprocessors that `extract` run, it run them again when it extract to the same folder
+    /// without `--postprocess`
+    pub postprocess: Vec<String>,
+}
+
+impl Manifest {
+    /// manifest of the files of the archive that are in `paths`
+    pub fn new(archive: &Archive, paths: &ahash::HashSet<PathBuf>, raw: bool) -> Self {
+        let files = archive
+            .files_info()
+            .filter(|info| paths.contains(&info.path))
+            .map(|info| {
+                let entry = ManifestEntry {
+                    compressed: info.compression.is_some(),
+                    compression: info.compression,
+                    uncompressed_size: Some(info.uncompressed_size),
+                    checksum: info.stored_checksum,
+                    name_crc32: info.name_crc32,
+                };
+                (info.path, entry)
+            })
+            .collect();
+
+        Self {
+            game: archive.metadata().game,
+            raw,
+            files,
+            postprocess: Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut files: Vec<_> = self.files.iter().collect();
+        files.sort_by_key(|(path, _)| *path);
+
+        json!({
+            "game": format!("{:?}", self.game),
+            "raw": self.raw,
+            "postprocess": self.postprocess,
+            "files": files
+                .into_iter()
+                .map(|(path, entry)| json!({
+                    // always use `/` so the manifest is the same on every platform
+                    "path": path_str(path),
+                    "compressed": entry.compressed,
+                    "compression": entry.compression.map(|c| format!("{c:?}")),
+                    "uncompressed_size": entry.uncompressed_size,
+                    "checksum": entry.checksum,
+                    "name_crc32": entry.name_crc32,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn from_json(bytes: &[u8]) -> anyhow::Result<Self> {
+        let value: Value = serde_json::from_slice(bytes).context("invalid json")?;
+
+        let game = match value.get("game").and_then(Value::as_str) {
+            Some("Obscure1") => Game::Obscure1,
+            Some("Obscure2") => Game::Obscure2,
+            Some("FinalExam") => Game::FinalExam,
+            Some(game) => anyhow::bail!("unknown game '{game}'"),
+            None => anyhow::bail!("missing game"),
+        };
+
+        let files = value
+            .get("files")
+            .and_then(Value::as_array)
+            .context("missing files")?
+            .iter()
+            .map(|file| {
+                let path = file
+                    .get("path")
+                    .and_then(Value::as_str)
+                    .context("file without path")?;
+                let entry = ManifestEntry {
+                    compressed: file
+                        .get("compressed")
+                        .and_then(Value::as_bool)
+                        .with_context(|| format!("{path} without compressed"))?,
+                    compression: match file.get("compression").and_then(Value::as_str) {
+                        Some("Zlib") => Some(CompressionType::Zlib),
+                        Some("Lzo") => Some(CompressionType::Lzo),
+                        Some(compression) => {
+                            anyhow::bail!("{path} has unknown compression '{compression}'")
+                        }
+                        None => None,
+                    },
+                    uncompressed_size: file.get("uncompressed_size").and_then(Value::as_u64),
+                    checksum: file
+                        .get("checksum")
+                        .and_then(Value::as_i64)
+                        .and_then(|c| i32::try_from(c).ok())
+                        .with_context(|| format!("{path} without a valid checksum"))?,
+                    name_crc32: file
+                        .get("name_crc32")
+                        .and_then(Value::as_u64)
+                        .and_then(|c| u32::try_from(c).ok()),
+                };
+                Ok((path.split('/').collect(), entry))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        // older manifests don't have them
+        let raw = value.get("raw").and_then(Value::as_bool).unwrap_or(false);
+        let postprocess = match value.get("postprocess") {
+            Some(names) => names
+                .as_array()
+                .context("postprocess isn't a list")?
+                .iter()
+                .map(|name| name.as_str().map(str::to_owned))
+                .collect::<Option<_>>()
+                .context("postprocess has a name that isn't a string")?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            game,
+            raw,
+            files,
+            postprocess,
+        })
+    }
+
+    /// load the manifest from the file, `None` if it doesn't exist
+    pub fn load(path: &Path) -> anyhow::Result<Option<Self>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let bytes = std::fs::read(path).context("failed to read manifest.json")?;
+        Self::from_json(&bytes)
+            .context("failed to load manifest.json, if you modified it just remove it")
+            .map(Some)
+    }
+
+    /// names of the files and directories whose name crc32 match the recorded one,
+    /// they can be added to the obscure 2 name maps
+    pub fn obscure2_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .files
+            .iter()
+            .filter(|(path, entry)| {
+                let name = path.file_name().and_then(|n| n.to_str());
+                entry.name_crc32.is_some()
+                    && entry.name_crc32 == name.and_then(Obscure2NameMap::name_crc32)
+            })
+            .flat_map(|(path, _)| path.iter().filter_map(|c| c.to_str()))
+            .filter(|name| !name.starts_with("unk_"))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+/// path as a string that always use `/` as separator
+pub fn path_str(path: &Path) -> String {
+    path.iter()
+        .map(|c| c.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
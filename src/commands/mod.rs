@@ -1,22 +1,95 @@
 use std::{
+    ffi::OsString,
     fs::File,
     io::{BufRead, BufReader},
     path::Path,
+    time::Instant,
 };
 
-use anstream::println;
 use anyhow::Context;
 use clap::{Parser, Subcommand, ValueEnum};
-use hvp_archive::{archive::Obscure2NameMap, provider::ArchiveProvider};
+use hvp_archive::{
+    archive::Obscure2NameMap,
+    provider::{ArchiveProvider, ProviderError, ProviderOptions},
+};
 use owo_colors::OwoColorize;
+use serde_json::json;
+
+use crate::memory;
+use output::println;
 
+pub mod add;
+pub mod analyze;
+pub mod apply_patch;
+pub mod apply_renames;
+pub mod apply_toc_patch;
+mod base64;
+pub mod bench;
+#[cfg(feature = "browse")]
+pub mod browse;
+pub mod cat;
+mod codepage;
+pub mod completions;
+pub mod convert;
+pub mod crack;
 pub mod create;
+pub mod daemon;
+mod dedup;
+pub mod demo_archive;
+pub mod deps;
+pub mod diff;
+mod digest;
 #[cfg(feature = "dump")]
 mod dump;
+pub mod export_toc;
 pub mod extract;
+pub mod from_zip;
+mod glob;
+pub mod grep;
+pub mod hash;
+pub mod hash_export;
+mod hashes;
+pub mod info;
+pub mod list;
+pub mod make_patch;
+mod manifest;
+pub mod names;
+pub mod output;
+mod path_template;
+mod pattern;
+pub mod plugin;
+mod postprocess;
+pub mod prompt;
+pub mod remove;
+pub mod rename;
+pub mod reorder;
+pub mod repair;
+pub mod search;
+pub mod serve;
+pub mod sha256_manifest;
+pub mod size;
+pub mod stats;
+pub mod swap_endian;
+pub mod to_zip;
+pub mod tree;
 mod utils;
+pub mod verify;
+pub mod verify_manifest;
+mod watch;
+#[cfg(feature = "dump")]
+mod yaml;
 
 const HASHES_FILE: &str = "hashes.json";
+const MANIFEST_FILE: &str = "manifest.json";
+const DEDUP_FILE: &str = "dedup.json";
+
+/// files that are written next to the extracted files and aren't part of the archive
+fn is_sidecar(path: &Path) -> bool {
+    [HASHES_FILE, MANIFEST_FILE, DEDUP_FILE]
+        .iter()
+        .any(|sidecar| path == Path::new(sidecar))
+        || path.starts_with(postprocess::POSTPROCESSED_DIR)
+}
 
 #[derive(Parser)]
 #[command(
@@ -31,23 +104,232 @@ pub struct Commands {
     /// What game is the archive from
     #[arg(long, short = 'g', default_value_t = Game::Auto, value_enum, global = true)]
     pub game: Game,
+    /// limit the number of files that are processed in parallel so their buffers fit in this
+    /// much memory, for example `512M` or `2G`. only the thread count is limited, every file is
+    /// still read into memory whole
+    #[arg(long, value_parser = size::parse, global = true)]
+    pub max_memory: Option<u64>,
+    /// print how long the operation took and the peak memory usage at the end
+    #[arg(long, default_value_t = false, global = true)]
+    pub timings: bool,
+    /// print the results as a single json document to stdout, other messages are printed to stderr
+    #[arg(long, default_value_t = false, global = true)]
+    pub json: bool,
+    /// answer yes to every question, same as setting HVP_ASSUME_YES=1
+    #[arg(
+        long,
+        short = 'y',
+        default_value_t = false,
+        global = true,
+        conflicts_with = "no"
+    )]
+    pub yes: bool,
+    /// answer no to every question, same as setting HVP_ASSUME_YES=0
+    #[arg(long, default_value_t = false, global = true)]
+    pub no: bool,
+    /// report the file that is being processed when extracting or rebuilding
+    /// didn't progress for this many seconds, 0 to disable it
+    #[arg(long, default_value_t = 60, global = true)]
+    pub stall_timeout: u64,
+    /// how the extraction and rebuild progress is shown
+    #[arg(long, default_value_t = Progress::Bar, value_enum, global = true)]
+    pub progress: Progress,
+    /// color the output, `auto` color only when printing to a terminal and NO_COLOR isn't set
+    #[arg(long, default_value_t = output::Color::Auto, value_enum, global = true)]
+    pub color: output::Color,
+    /// read the archive into memory instead of mapping it, for archives on removable or
+    /// network drives that may go away while they are read
+    #[arg(long, default_value_t = false, global = true)]
+    pub no_mmap: bool,
 }
 
 impl Commands {
     /// handle the user command
     pub fn start(self) -> anyhow::Result<()> {
-        let hvp_path = self.operation.input_hvp_path();
-        let file = File::open(hvp_path).context("failed to open hvp archive")?;
+        let timings = self.timings;
+        let start = Instant::now();
+        if timings || self.max_memory.is_some() {
+            memory::enable();
+        }
+        output::set_json(self.json);
+        output::set_color(self.color);
+        prompt::set_answer(match (self.yes, self.no) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        });
+        utils::set_stall_timeout(self.stall_timeout);
+        utils::set_progress_json(matches!(self.progress, Progress::Json));
+
+        let result = self.run();
+
+        if timings {
+            output::report(
+                "timings",
+                json!({
+                    "elapsed_ms": start.elapsed().as_millis() as u64,
+                    "peak_memory": memory::peak(),
+                }),
+            );
+
+            println!(
+                concat!(
+                    "{} timings:\n",
+                    " {dot} elapsed: {:.2?}\n",
+                    " {dot} peak memory: {}",
+                ),
+                "[?]".green(),
+                start.elapsed(),
+                size::format(memory::peak() as _),
+                dot = "|>".cyan(),
+            );
+        }
+
+        output::finish(&result);
+
+        result
+    }
+
+    fn run(self) -> anyhow::Result<()> {
+        let Some(hvp_path) = self.operation.input_hvp_path() else {
+            return match self.operation {
+                Operation::Plugins(commands) => commands.start(),
+                Operation::Completions(commands) => commands.start(),
+                Operation::External(args) => plugin::run(
+                    args,
+                    &plugin::PluginEnv {
+                        game: self.game,
+                        json: self.json,
+                        progress: self.progress,
+                        stall_timeout: self.stall_timeout,
+                        max_memory: self.max_memory,
+                    },
+                ),
+                Operation::Daemon(commands) => commands.start(),
+                Operation::Hash(commands) => commands.start(),
+                Operation::VerifyManifest(commands) => commands.start(),
+                Operation::Crack(commands) => commands.start(),
+                Operation::DemoArchive(commands) => commands.start(self.game.into()),
+                Operation::Create(commands) => {
+                    let pool = thread_pool(self.max_memory, || commands.largest_input_size())?;
+                    install(pool, || commands.start_from_scratch(self.game.into()))
+                }
+                _ => unreachable!("only commands without input archive are handled here"),
+            };
+        };
+        let open = |path: &Path| File::open(path).context("failed to open hvp archive");
 
-        let provider = ArchiveProvider::new(file, self.game.into())
-            .context("failed to load input hvp archive")?;
+        // repair write the crc32s again, so it can load archives that have stale ones. commands
+        // that don't read the data of the files (checksums included) only load the TOC
+        let toc_only = match &self.operation {
+            #[cfg(feature = "dump")]
+            Operation::Dump(cmd) => !cmd.mapped,
+            Operation::List(cmd) => !cmd.long,
+            Operation::Stats(_) => true,
+            _ => false,
+        };
+        let provider = match self.operation {
+            Operation::Repair(_) => ArchiveProvider::new_lenient(open(hvp_path)?, self.game.into()),
+            _ if toc_only => ArchiveProvider::open_toc_only(hvp_path, self.game.into()),
+            _ => ArchiveProvider::new_with_options(
+                open(hvp_path)?,
+                self.game.into(),
+                ProviderOptions {
+                    buffered: self.no_mmap,
+                    ..Default::default()
+                },
+            ),
+        }
+        .map_err(|e| {
+            let hint = match &e {
+                ProviderError::GameMismatch { detected, .. } => format!(
+                    ", pass `--game {}` or leave --game out",
+                    Game::from(*detected)
+                        .to_possible_value()
+                        .unwrap()
+                        .get_name()
+                ),
+                _ => String::new(),
+            };
+            anyhow::Error::new(e).context(format!("failed to load input hvp archive{hint}"))
+        })?;
 
-        match self.operation {
+        // the TOC only commands don't read any file
+        let pool = thread_pool(self.max_memory.filter(|_| !toc_only), || {
+            let input = match &self.operation {
+                Operation::Create(commands) => commands.largest_input_size(),
+                _ => 0,
+            };
+            provider.largest_file_size().max(input)
+        })?;
+        let game = self.game;
+        install(pool, move || match self.operation {
             #[cfg(feature = "dump")]
             Operation::Dump(commands) => commands.start(provider),
+            #[cfg(feature = "browse")]
+            Operation::Browse(commands) => commands.start(provider),
             Operation::Extract(commands) => commands.start(provider),
             Operation::Create(commands) => commands.start(provider),
-        }
+            Operation::Add(commands) => commands.start(provider),
+            Operation::Remove(commands) => commands.start(provider),
+            Operation::Rename(commands) => commands.start(provider),
+            Operation::ApplyRenames(commands) => commands.start(provider),
+            Operation::Reorder(commands) => commands.start(provider),
+            Operation::Verify(commands) => commands.start(provider),
+            Operation::Repair(commands) => commands.start(provider),
+            Operation::Analyze(commands) => commands.start(provider),
+            Operation::Diff(commands) => commands.start(provider, game.into()),
+            Operation::MakePatch(commands) => commands.start(provider, game.into()),
+            Operation::ApplyPatch(commands) => commands.start(provider),
+            Operation::ApplyTocPatch(commands) => commands.start(provider),
+            Operation::ToZip(commands) => commands.start(provider),
+            Operation::FromZip(commands) => commands.start(provider),
+            Operation::Convert(commands) => commands.start(provider),
+            Operation::Cat(commands) => commands.start(provider),
+            Operation::Info(commands) => commands.start(provider),
+            Operation::List(commands) => commands.start(provider),
+            Operation::Tree(commands) => commands.start(provider),
+            Operation::Search(commands) => commands.start(provider),
+            Operation::Grep(commands) => commands.start(provider),
+            Operation::Serve(commands) => commands.start(provider),
+            Operation::Stats(commands) => commands.start(provider),
+            Operation::Deps(commands) => commands.start(provider),
+            Operation::SwapEndian(commands) => commands.start(provider),
+            Operation::ExportToc(commands) => commands.start(provider),
+            Operation::HashExport(commands) => commands.start(provider),
+            Operation::Bench(commands) => commands.start(provider),
+            Operation::Sha256Manifest(commands) => commands.start(provider),
+            Operation::Names(commands) => commands.start(provider),
+            Operation::Daemon(_)
+            | Operation::Hash(_)
+            | Operation::VerifyManifest(_)
+            | Operation::Crack(_)
+            | Operation::DemoArchive(_)
+            | Operation::Plugins(_)
+            | Operation::Completions(_)
+            | Operation::External(_) => {
+                unreachable!("only commands with input archive are handled here")
+            }
+        })
+    }
+}
+
+/// the thread pool of `--max-memory`, sized for the largest file the command read
+fn thread_pool(
+    max_memory: Option<u64>,
+    largest_file_size: impl FnOnce() -> u64,
+) -> anyhow::Result<Option<rayon::ThreadPool>> {
+    max_memory
+        .map(|max_memory| memory::thread_pool(max_memory, largest_file_size()))
+        .transpose()
+        .context("failed to apply memory cap")
+}
+
+/// run `op` in `pool` if there is one, the parallel iterators of `op` then use its threads
+fn install<R: Send>(pool: Option<rayon::ThreadPool>, op: impl FnOnce() -> R + Send) -> R {
+    match pool {
+        Some(pool) => pool.install(op),
+        None => op(),
     }
 }
 
@@ -56,19 +338,142 @@ pub enum Operation {
     /// dump hvp archive TOC as json
     #[cfg(feature = "dump")]
     Dump(dump::Commands),
+    /// browse the hvp archive in the terminal, to look at its files, extract some of them and replace others
+    #[cfg(feature = "browse")]
+    Browse(browse::Commands),
     /// extract files from hvp archive
     Extract(extract::Commands),
     /// create a new hvp archive based on extracted data and original archive
     Create(create::Commands),
+    /// add new files to an existing hvp archive
+    Add(add::Commands),
+    /// remove files or directories from an existing hvp archive
+    Remove(remove::Commands),
+    /// rename or move a file or directory inside an existing hvp archive
+    Rename(rename::Commands),
+    /// rename every entry whose name crc32 is in a csv of `crc32,name` rows, and the extracted files too
+    ApplyRenames(apply_renames::Commands),
+    /// change the order of the entries inside directories, using a list of entry paths
+    Reorder(reorder::Commands),
+    /// verify checksums of the hvp archive and report every invalid entry
+    #[command(visible_alias = "check")]
+    Verify(verify::Commands),
+    /// recompute the header crc32s and file checksums of a hand edited hvp archive, the data of the files isn't touched
+    Repair(repair::Commands),
+    /// look for files with unusual compression, like compressed data larger than the file or ratios far from files of the same type
+    Analyze(analyze::Commands),
+    /// compare two hvp archives and report added, removed and changed files
+    Diff(diff::Commands),
+    /// make a patch file that only contain the changed files between two hvp archives
+    MakePatch(make_patch::Commands),
+    /// apply a patch file to the original hvp archive and rebuild it
+    ApplyPatch(apply_patch::Commands),
+    /// change the compression flags, checksums and sizes of files in the TOC using a json list of edits
+    ApplyTocPatch(apply_toc_patch::Commands),
+    /// convert hvp archive to a zip file, with a manifest of hvp specific fields
+    ToZip(to_zip::Commands),
+    /// rebuild hvp archive using the files of a zip, usually made using to-zip
+    FromZip(from_zip::Commands),
+    /// write the files of an hvp archive as an archive of another game
+    Convert(convert::Commands),
+    /// print a single file from hvp archive to stdout
+    Cat(cat::Commands),
+    /// print everything that is known about a single file of hvp archive
+    Info(info::Commands),
+    /// list the files of hvp archive, or print them as a tree, with a natural or locale aware order
+    List(list::Commands),
+    /// print the archive as a tree, with the size of every directory and a bar of its share of the archive
+    Tree(tree::Commands),
+    /// find files by part of their path, a glob pattern or the crc32 of their name
+    Search(search::Commands),
+    /// search the content of the files for text, a regex or bytes
+    Grep(grep::Commands),
+    /// serve a read-only http api over the hvp archive
+    Serve(serve::Commands),
+    /// file counts, sizes and compression ratios per directory and per file extension
+    Stats(stats::Commands),
+    /// find the files that reference an entry and the entries that it reference, by looking for their names inside script and level files
+    Deps(deps::Commands),
+    /// convert a big endian archive (like the wii version of obscure 2) to little endian and back, without extracting it
+    SwapEndian(swap_endian::Commands),
+    /// export a csv row per entry with its path, offset, sizes, checksum and name crc32
+    ExportToc(export_toc::Commands),
+    /// write a sfv, md5sum or sha256sum file with the checksums of the decompressed files
+    HashExport(hash_export::Commands),
+    /// rebuild the archive in memory with several compression settings and compare the size and time of each
+    Bench(bench::Commands),
+    /// write a json manifest with the sha256 of the archive and of every file, for releases
+    Sha256Manifest(sha256_manifest::Commands),
+    /// check the extracted files (and the archive) against a manifest from sha256-manifest
+    VerifyManifest(verify_manifest::Commands),
+    /// commands about the obscure 2 name maps
+    Names(names::Commands),
+    /// run a json-rpc daemon that keep archives open for other programs
+    Daemon(daemon::Commands),
+    /// compute obscure 2 name crc32s, useful when looking for unknown names
+    Hash(hash::Commands),
+    /// try candidate names against unknown obscure 2 name crc32s
+    Crack(crack::Commands),
+    /// make a small demo hvp archive with dummy files, to try the other commands without the games
+    DemoArchive(demo_archive::Commands),
+    /// list the plugins, executables named `obscure-hvp-<name>` next to the tool or in `PATH`
+    Plugins(plugin::Commands),
+    /// print the completion script of a shell, for the subcommands, options and their values
+    Completions(completions::Commands),
+    /// run the `obscure-hvp-<name>` executable that is next to the tool or in `PATH`
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
 }
 
 impl Operation {
-    pub fn input_hvp_path(&self) -> &Path {
+    /// path to the input hvp archive, `None` if the operation doesn't have one
+    pub fn input_hvp_path(&self) -> Option<&Path> {
         match self {
             #[cfg(feature = "dump")]
-            Operation::Dump(cmd) => &cmd.input,
-            Operation::Extract(cmd) => &cmd.input,
-            Operation::Create(cmd) => &cmd.input_hvp,
+            Operation::Dump(cmd) => Some(&cmd.input),
+            #[cfg(feature = "browse")]
+            Operation::Browse(cmd) => Some(&cmd.input),
+            Operation::Extract(cmd) => Some(&cmd.input),
+            Operation::Create(cmd) if cmd.from_scratch => None,
+            Operation::Create(cmd) => Some(&cmd.input_hvp),
+            Operation::Add(cmd) => Some(&cmd.input),
+            Operation::Remove(cmd) => Some(&cmd.input),
+            Operation::Rename(cmd) => Some(&cmd.input),
+            Operation::ApplyRenames(cmd) => Some(&cmd.input),
+            Operation::Reorder(cmd) => Some(&cmd.input),
+            Operation::Verify(cmd) => Some(&cmd.input),
+            Operation::Repair(cmd) => Some(&cmd.input),
+            Operation::Analyze(cmd) => Some(&cmd.input),
+            Operation::Diff(cmd) => Some(&cmd.input),
+            Operation::MakePatch(cmd) => Some(&cmd.input),
+            Operation::ApplyPatch(cmd) => Some(&cmd.input),
+            Operation::ApplyTocPatch(cmd) => Some(&cmd.input),
+            Operation::ToZip(cmd) => Some(&cmd.input),
+            Operation::FromZip(cmd) => Some(&cmd.input),
+            Operation::Convert(cmd) => Some(&cmd.input),
+            Operation::Cat(cmd) => Some(&cmd.input),
+            Operation::Info(cmd) => Some(&cmd.input),
+            Operation::List(cmd) => Some(&cmd.input),
+            Operation::Tree(cmd) => Some(&cmd.input),
+            Operation::Search(cmd) => Some(&cmd.input),
+            Operation::Grep(cmd) => Some(&cmd.input),
+            Operation::Serve(cmd) => Some(&cmd.input),
+            Operation::Stats(cmd) => Some(&cmd.input),
+            Operation::Deps(cmd) => Some(&cmd.input),
+            Operation::SwapEndian(cmd) => Some(&cmd.input),
+            Operation::ExportToc(cmd) => Some(&cmd.input),
+            Operation::HashExport(cmd) => Some(&cmd.input),
+            Operation::Bench(cmd) => Some(&cmd.input),
+            Operation::Sha256Manifest(cmd) => Some(&cmd.input),
+            Operation::Names(cmd) => Some(cmd.input()),
+            Operation::Daemon(_)
+            | Operation::Hash(_)
+            | Operation::VerifyManifest(_)
+            | Operation::Crack(_)
+            | Operation::DemoArchive(_)
+            | Operation::Plugins(_)
+            | Operation::Completions(_)
+            | Operation::External(_) => None,
         }
     }
 }
@@ -86,6 +491,16 @@ pub enum Game {
     FinalExam,
 }
 
+impl From<hvp_archive::Game> for Game {
+    fn from(value: hvp_archive::Game) -> Self {
+        match value {
+            hvp_archive::Game::Obscure1 => Game::Obscure1,
+            hvp_archive::Game::Obscure2 => Game::Obscure2,
+            hvp_archive::Game::FinalExam => Game::FinalExam,
+        }
+    }
+}
+
 impl From<Game> for Option<hvp_archive::Game> {
     fn from(value: Game) -> Self {
         match value {
@@ -108,14 +523,64 @@ pub enum ChecksumValidation {
     Prompt,
 }
 
-fn load_name_maps() -> std::io::Result<Option<Obscure2NameMap>> {
+#[derive(ValueEnum, Copy, Clone, Debug, Default)]
+pub enum Progress {
+    /// show a progress bar
+    #[default]
+    Bar,
+    /// print newline delimited json events to stderr, for programs that show their own progress
+    Json,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default)]
+pub enum Orphans {
+    /// ignore entries that aren't reachable from the root directory
+    #[default]
+    Drop,
+    /// extract the unreachable entries under a `_orphans` directory
+    Attach,
+}
+
+impl From<Orphans> for hvp_archive::archive::OrphanMode {
+    fn from(value: Orphans) -> Self {
+        match value {
+            Orphans::Drop => Self::Drop,
+            Orphans::Attach => Self::Attach,
+        }
+    }
+}
+
+/// load the name maps if the game need them, otherwise return a empty name map
+fn name_maps_for_game(game: hvp_archive::Game) -> anyhow::Result<Obscure2NameMap> {
+    match game {
+        hvp_archive::Game::Obscure2 => {
+            match load_name_maps(true).context("failed to load name maps")? {
+                Some(names) => Ok(names),
+                None => {
+                    println!(
+                        "{} failed to load obscure2 (or alone in the dark 2008) name maps because no hash file was found",
+                        "[!]".yellow()
+                    );
+
+                    Ok(Obscure2NameMap::default())
+                }
+            }
+        }
+        _ => Ok(Obscure2NameMap::default()), // we don't need to load name map for any other game
+    }
+}
+
+/// load the obscure 2 name maps from hashes directory, if `verbose` is false nothing will be printed
+fn load_name_maps(verbose: bool) -> std::io::Result<Option<Obscure2NameMap>> {
     let path = Path::new("hashes");
 
-    println!(
-        "{} loading name maps from {} directory",
-        "[?]".green(),
-        path.display(),
-    );
+    if verbose {
+        println!(
+            "{} loading name maps from {} directory",
+            "[?]".green(),
+            path.display(),
+        );
+    }
 
     if !path.is_dir() {
         return Ok(None);
@@ -132,7 +597,9 @@ fn load_name_maps() -> std::io::Result<Option<Obscure2NameMap>> {
             continue;
         }
 
-        println!("{} loading name map from {}", "[?]".green(), path.display(),);
+        if verbose {
+            println!("{} loading name map from {}", "[?]".green(), path.display(),);
+        }
 
         let file = File::open(path)?;
         let reader = BufReader::new(file);
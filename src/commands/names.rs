@@ -0,0 +1,230 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::{Parser, Subcommand, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Options, entry::Entry},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    #[command(subcommand)]
+    pub command: NamesCommand,
+}
+
+#[derive(Subcommand)]
+pub enum NamesCommand {
+    /// report how many obscure 2 entries have a known name, grouped by directory
+    Coverage(Coverage),
+}
+
+#[derive(Parser)]
+pub struct Coverage {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// group the entries by only this many leading directories, for example 1 for the top level directories
+    #[arg(long)]
+    pub depth: Option<usize>,
+    /// only print this many directories, the ones with the most unknown names first
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+impl Commands {
+    pub fn input(&self) -> &PathBuf {
+        match &self.command {
+            NamesCommand::Coverage(cmd) => &cmd.input,
+        }
+    }
+
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        match self.command {
+            NamesCommand::Coverage(cmd) => cmd.start(provider),
+        }
+    }
+}
+
+/// known and total names of a group of entries
+#[derive(Default, Clone, Copy)]
+struct Count {
+    files: usize,
+    known_files: usize,
+    dirs: usize,
+    known_dirs: usize,
+}
+
+impl Count {
+    fn add(&mut self, other: Count) {
+        self.files += other.files;
+        self.known_files += other.known_files;
+        self.dirs += other.dirs;
+        self.known_dirs += other.known_dirs;
+    }
+
+    fn unknown(&self) -> usize {
+        self.files - self.known_files + self.dirs - self.known_dirs
+    }
+
+    fn percent(&self) -> f64 {
+        match self.files + self.dirs {
+            0 => 100.0,
+            total => (self.known_files + self.known_dirs) as f64 * 100.0 / total as f64,
+        }
+    }
+
+    fn to_json(self, dir: &str) -> Value {
+        json!({
+            "dir": dir,
+            "files": self.files,
+            "known_files": self.known_files,
+            "dirs": self.dirs,
+            "known_dirs": self.known_dirs,
+        })
+    }
+}
+
+/// whatever the name is a placeholder for an entry without a known name
+pub fn is_unknown(name: &str) -> bool {
+    let crc32 = name
+        .strip_prefix("unk_file_")
+        .and_then(|n| n.strip_suffix(".dat"))
+        .or_else(|| name.strip_prefix("unk_folder_"));
+
+    crc32.is_some_and(|crc32| crc32.parse::<u32>().is_ok())
+}
+
+impl Coverage {
+    fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        if provider.game() != Game::Obscure2 {
+            anyhow::bail!("only obscure 2 archives store the crc32 of names instead of the names");
+        }
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let mut total = Count::default();
+        let mut dirs: BTreeMap<String, Count> = BTreeMap::new();
+        self.count(archive.entries(), &mut Vec::new(), &mut total, &mut dirs);
+
+        let mut rows: Vec<(String, Count)> = dirs.into_iter().collect();
+        rows.sort_by_key(|(_, count)| std::cmp::Reverse(count.unknown()));
+
+        let shown = &rows[..self.limit.unwrap_or(rows.len()).min(rows.len())];
+        let width = shown
+            .iter()
+            .map(|(dir, _)| dir.chars().count())
+            .chain(["directory".len()])
+            .max()
+            .unwrap_or_default();
+
+        println!(
+            "{} {:<width$} {:>13} {:>13} {:>7}",
+            "[?]".green(),
+            "directory",
+            "known files",
+            "known dirs",
+            "known",
+        );
+        for (dir, count) in shown {
+            let line = format!(
+                "{dir:<width$} {:>13} {:>13} {:>6.1}%",
+                format!("{}/{}", count.known_files, count.files),
+                format!("{}/{}", count.known_dirs, count.dirs),
+                count.percent(),
+            );
+            match count.unknown() {
+                0 => println!(" {} {}", "|>".cyan(), line.green()),
+                _ => println!(" {} {line}", "|>".cyan()),
+            }
+        }
+
+        println!(
+            "{} {}/{} file names and {}/{} directory names are known ({:.1}%)",
+            "[+]".green(),
+            total.known_files,
+            total.files,
+            total.known_dirs,
+            total.dirs,
+            total.percent(),
+        );
+
+        output::report(
+            "names_coverage",
+            json!({
+                "total": total.to_json(""),
+                "directories": rows
+                    .iter()
+                    .map(|(dir, count)| count.to_json(dir))
+                    .collect::<Vec<_>>(),
+            }),
+        );
+
+        Ok(())
+    }
+
+    /// count the names of the entries of a directory, `path` is the names of its parents
+    fn count<'a>(
+        &self,
+        entries: &'a [Entry],
+        path: &mut Vec<&'a str>,
+        total: &mut Count,
+        dirs: &mut BTreeMap<String, Count>,
+    ) {
+        let depth = path.len().min(self.depth.unwrap_or(usize::MAX));
+        let group = dirs
+            .entry(format!("{}/", path[..depth].join("/")))
+            .or_default();
+
+        let mut count = Count::default();
+        for entry in entries {
+            let (name, is_dir) = match entry {
+                Entry::File(file) => (file.name(), false),
+                Entry::Dir(dir) => (dir.name.as_str(), true),
+            };
+            let known = !is_unknown(name) as usize;
+
+            match is_dir {
+                true => {
+                    count.dirs += 1;
+                    count.known_dirs += known;
+                }
+                false => {
+                    count.files += 1;
+                    count.known_files += known;
+                }
+            }
+        }
+
+        group.add(count);
+        total.add(count);
+
+        for entry in entries {
+            if let Entry::Dir(dir) = entry {
+                path.push(&dir.name);
+                self.count(&dir.entries, path, total, dirs);
+                path.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! output of the commands, colored text for humans or a single json report with `--json`
+//!
+//! in json mode the text messages are moved to stderr, and the commands add their
+//! results as sections of a report that is printed to stdout at the end.
+
+use std::{
+    cmp,
+    io::Write,
+    iter::Peekable,
+    str::Chars,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static STREAMING: AtomicBool = AtomicBool::new(false);
+static REPORT: Mutex<Vec<(&'static str, Value)>> = Mutex::new(Vec::new());
+
+/// switch to json output
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// whatever the json output is enabled
+#[inline]
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// the command write its own data to stdout, so the text messages are moved to stderr
+pub fn set_streaming(streaming: bool) {
+    STREAMING.store(streaming, Ordering::Relaxed);
+}
+
+/// whatever the text messages are printed to stderr
+#[inline]
+pub fn to_stderr() -> bool {
+    is_json() || STREAMING.load(Ordering::Relaxed)
+}
+
+/// when the text messages and the progress bars are colored
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Color {
+    /// color when printing to a terminal, unless NO_COLOR is set
+    #[default]
+    Auto,
+    /// always color, even when the output is redirected to a file
+    Always,
+    /// never color
+    Never,
+}
+
+/// apply the color choice to everything that print colored text
+pub fn set_color(color: Color) {
+    let (choice, enabled) = match color {
+        Color::Auto => return,
+        Color::Always => (anstream::ColorChoice::Always, true),
+        Color::Never => (anstream::ColorChoice::Never, false),
+    };
+    // anstream strip the escape codes of the messages, owo-colors decide the `if_supports_color`
+    // ones and console the colors of the progress bars
+    choice.write_global();
+    owo_colors::set_override(enabled);
+    console::set_colors_enabled(enabled);
+    console::set_colors_enabled_stderr(enabled);
+}
+
+/// add a section to the json report, does nothing when json output is disabled
+pub fn report(key: &'static str, value: Value) {
+    if !is_json() {
+        return;
+    }
+
+    let mut report = REPORT.lock().unwrap_or_else(|e| e.into_inner());
+    match report.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, old)) => *old = value,
+        None => report.push((key, value)),
+    }
+}
+
+/// print the json report to stdout, does nothing when json output is disabled
+pub fn finish(result: &anyhow::Result<()>) {
+    if !is_json() {
+        return;
+    }
+
+    let mut map = Map::new();
+    map.insert("ok".to_owned(), result.is_ok().into());
+    if let Err(e) = result {
+        map.insert("error".to_owned(), format!("{e:#}").into());
+    }
+
+    let report = std::mem::take(&mut *REPORT.lock().unwrap_or_else(|e| e.into_inner()));
+    for (key, value) in report {
+        map.insert(key.to_owned(), value);
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    let _ = serde_json::to_writer_pretty(&mut stdout, &Value::Object(map));
+    let _ = writeln!(stdout);
+}
+
+/// order of the entries in the listings
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// same order that the entries are stored in the archive
+    #[default]
+    Archive,
+    /// byte by byte, uppercase names come before lowercase ones
+    Name,
+    /// numbers inside the names are compared by their value, so `level2` comes before `level10`
+    Natural,
+    /// like natural, but case and accents are ignored the same way file managers do
+    Locale,
+    /// largest files first, only the flat listings are sorted by size
+    Size,
+    /// files with the largest stored to uncompressed ratio (the least compressed) first,
+    /// only the flat listings are sorted by ratio
+    Ratio,
+}
+
+impl SortOrder {
+    /// compare two names, names that are only equal after folding are compared byte by byte
+    pub fn compare(self, a: &str, b: &str) -> cmp::Ordering {
+        match self {
+            SortOrder::Archive | SortOrder::Size | SortOrder::Ratio => cmp::Ordering::Equal,
+            SortOrder::Name => a.cmp(b),
+            SortOrder::Natural => {
+                natural_cmp(a, b, |c| c.to_ascii_lowercase()).then_with(|| a.cmp(b))
+            }
+            SortOrder::Locale => natural_cmp(a, b, fold_accent).then_with(|| a.cmp(b)),
+        }
+    }
+
+    /// sort the items by their name, the orders that don't look at the names keep them as they are
+    pub fn sort_by_name<T>(self, items: &mut [T], name: impl Fn(&T) -> &str) {
+        if !matches!(
+            self,
+            SortOrder::Archive | SortOrder::Size | SortOrder::Ratio
+        ) {
+            items.sort_by(|a, b| self.compare(name(a), name(b)));
+        }
+    }
+}
+
+/// compare the runs of digits by their value and the other characters after folding them
+fn natural_cmp(a: &str, b: &str, fold: fn(char) -> char) -> cmp::Ordering {
+    fn digits(chars: &mut Peekable<Chars>) -> String {
+        let mut digits = String::new();
+        while let Some(c) = chars.next_if(char::is_ascii_digit) {
+            digits.push(c);
+        }
+        digits
+    }
+
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ord = match (a.peek(), b.peek()) {
+            (None, None) => return cmp::Ordering::Equal,
+            (None, Some(_)) => return cmp::Ordering::Less,
+            (Some(_), None) => return cmp::Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x, y) = (digits(&mut a), digits(&mut b));
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+            }
+            (Some(&x), Some(&y)) => {
+                a.next();
+                b.next();
+                fold(x).cmp(&fold(y))
+            }
+        };
+
+        if ord != cmp::Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+/// lowercase the character and remove the accent of the latin letters
+fn fold_accent(c: char) -> char {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        c => c,
+    }
+}
+
+/// like [`anstream::println`], but print to stderr in json or streaming mode
+macro_rules! println {
+    ($($arg:tt)*) => {
+        if $crate::commands::output::to_stderr() {
+            anstream::eprintln!($($arg)*)
+        } else {
+            anstream::println!($($arg)*)
+        }
+    };
+}
+
+/// like [`anstream::print`], but print to stderr in json or streaming mode
+macro_rules! print {
+    ($($arg:tt)*) => {
+        if $crate::commands::output::to_stderr() {
+            anstream::eprint!($($arg)*)
+        } else {
+            anstream::print!($($arg)*)
+        }
+    };
+}
+
+pub(crate) use {print, println};
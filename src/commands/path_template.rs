@@ -0,0 +1,255 @@
+//! templates for the output path of extracted files
+//!
+//! supported placeholders:
+//! - `{game}` game of the archive, for example `Obscure2`
+//! - `{archive_name}` and `{archive_stem}` file name of the archive with and without extension
+//! - `{entry_path}` full path of the file inside the archive
+//! - `{entry_dir}` directory of the file inside the archive, empty for files in the root
+//! - `{entry_name}`, `{entry_stem}` and `{entry_ext}` file name, without extension and extension
+//!
+//! both `/` and `\` can be used as separator and empty directories are ignored,
+//! so `{game}/{entry_dir}/{entry_name}` works for files in the root too.
+
+use std::path::{Path, PathBuf};
+
+use hvp_archive::Game;
+
+/// a parsed path template
+#[derive(Debug, Clone)]
+pub struct PathTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Game,
+    ArchiveName,
+    ArchiveStem,
+    EntryPath,
+    EntryDir,
+    EntryName,
+    EntryStem,
+    EntryExt,
+}
+
+/// values that are the same for every file of the archive
+pub struct ArchiveFields {
+    pub game: Game,
+    pub archive: PathBuf,
+}
+
+impl PathTemplate {
+    /// parse the template, return an error if it has an unknown placeholder
+    pub fn new(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start != 0 {
+                parts.push(Part::Text(rest[..start].to_owned()));
+            }
+
+            let end = rest[start..]
+                .find('}')
+                .ok_or("unclosed '{' in path template")?;
+            let field = match &rest[start + 1..start + end] {
+                "game" => Field::Game,
+                "archive_name" => Field::ArchiveName,
+                "archive_stem" => Field::ArchiveStem,
+                "entry_path" => Field::EntryPath,
+                "entry_dir" => Field::EntryDir,
+                "entry_name" => Field::EntryName,
+                "entry_stem" => Field::EntryStem,
+                "entry_ext" => Field::EntryExt,
+                other => {
+                    return Err(format!(
+                        "unknown placeholder '{{{other}}}' in path template"
+                    ));
+                }
+            };
+            parts.push(Part::Field(field));
+            rest = &rest[start + end + 1..];
+        }
+
+        if rest.contains('}') {
+            return Err("unopened '}' in path template".to_owned());
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_owned()));
+        }
+
+        if !parts.iter().any(|p| {
+            matches!(
+                p,
+                Part::Field(Field::EntryPath | Field::EntryName | Field::EntryStem)
+            )
+        }) {
+            return Err(
+                "path template must have {entry_path}, {entry_name} or {entry_stem}".to_owned(),
+            );
+        }
+
+        Ok(Self { parts })
+    }
+
+    /// output path of the file with the given path inside the archive
+    pub fn render(&self, fields: &ArchiveFields, entry: &Path) -> PathBuf {
+        let text = |path: Option<&std::ffi::OsStr>| {
+            path.map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+
+        let mut rendered = String::new();
+        for part in &self.parts {
+            let value = match part {
+                Part::Text(text) => text.clone(),
+                Part::Field(Field::Game) => format!("{:?}", fields.game),
+                Part::Field(Field::ArchiveName) => text(fields.archive.file_name()),
+                Part::Field(Field::ArchiveStem) => text(fields.archive.file_stem()),
+                Part::Field(Field::EntryPath) => entry.to_string_lossy().into_owned(),
+                Part::Field(Field::EntryDir) => text(entry.parent().map(Path::as_os_str)),
+                Part::Field(Field::EntryName) => text(entry.file_name()),
+                Part::Field(Field::EntryStem) => text(entry.file_stem()),
+                Part::Field(Field::EntryExt) => text(entry.extension()),
+            };
+            rendered.push_str(&value);
+        }
+
+        let mut path: PathBuf = rendered
+            .split(['/', '\\'])
+            .filter(|c| !c.is_empty())
+            .collect();
+
+        // keep absolute templates absolute, a placeholder that is empty at the start doesn't
+        // make the path absolute
+        if matches!(self.parts.first(), Some(Part::Text(text)) if text.starts_with(['/', '\\'])) {
+            path = Path::new("/").join(path);
+        }
+
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> ArchiveFields {
+        ArchiveFields {
+            game: Game::Obscure2,
+            archive: PathBuf::from("dumps/cachpack.hvp"),
+        }
+    }
+
+    fn render(template: &str, entry: &str) -> PathBuf {
+        PathTemplate::new(template)
+            .unwrap()
+            .render(&fields(), Path::new(entry))
+    }
+
+    fn path(components: &[&str]) -> PathBuf {
+        components.iter().collect()
+    }
+
+    #[test]
+    fn every_placeholder() {
+        assert_eq!(
+            render(
+                "{game}/{archive_name}/{archive_stem}/{entry_dir}/{entry_stem}.{entry_ext}",
+                "sound/music/intro.ogg"
+            ),
+            path(&[
+                "Obscure2",
+                "cachpack.hvp",
+                "cachpack",
+                "sound",
+                "music",
+                "intro.ogg"
+            ])
+        );
+        assert_eq!(
+            render("{archive_stem}/{entry_path}", "sound/music/intro.ogg"),
+            path(&["cachpack", "sound", "music", "intro.ogg"])
+        );
+        assert_eq!(
+            render("{entry_name}", "sound/music/intro.ogg"),
+            path(&["intro.ogg"])
+        );
+    }
+
+    #[test]
+    fn empty_components_are_dropped() {
+        // files in the root don't have a directory
+        assert_eq!(
+            render("{game}/{entry_dir}/{entry_name}", "config.txt"),
+            path(&["Obscure2", "config.txt"])
+        );
+        // and files without extension don't have an extension
+        assert_eq!(
+            render("{entry_ext}/{entry_stem}", "scripts/README"),
+            path(&["README"])
+        );
+        assert_eq!(
+            render("out//{entry_path}/", "a/b.bin"),
+            path(&["out", "a", "b.bin"])
+        );
+    }
+
+    #[test]
+    fn both_separators() {
+        assert_eq!(
+            render(r"{game}\{entry_dir}\{entry_name}", "a/b/c.bin"),
+            path(&["Obscure2", "a", "b", "c.bin"])
+        );
+        assert_eq!(
+            render("{game}_{entry_stem}", "levels/l01.lvl"),
+            path(&["Obscure2_l01"])
+        );
+    }
+
+    #[test]
+    fn absolute_templates() {
+        assert_eq!(
+            render("/dumps/{entry_path}", "a/b.bin"),
+            path(&["/", "dumps", "a", "b.bin"])
+        );
+        assert!(render("/{entry_path}", "a/b.bin").is_absolute());
+        assert!(render("{entry_path}", "a/b.bin").is_relative());
+    }
+
+    #[test]
+    fn invalid_templates() {
+        for (template, error) in [
+            ("{entry_path", "unclosed '{' in path template"),
+            ("{entry_path}}", "unopened '}' in path template"),
+            (
+                "{nope}/{entry_path}",
+                "unknown placeholder '{nope}' in path template",
+            ),
+            (
+                "{}/{entry_path}",
+                "unknown placeholder '{}' in path template",
+            ),
+            (
+                "{game}/{entry_dir}",
+                "path template must have {entry_path}, {entry_name} or {entry_stem}",
+            ),
+            (
+                "",
+                "path template must have {entry_path}, {entry_name} or {entry_stem}",
+            ),
+        ] {
+            assert_eq!(
+                PathTemplate::new(template).unwrap_err(),
+                error,
+                "{template}"
+            );
+        }
+    }
+}
@@ -0,0 +1,652 @@
+//! a small byte pattern matcher for searching the content of files
+//!
+//! patterns can be plain text, hex bytes or a regex with this syntax:
+//! - `.` any byte except `\n`, `[abc]`, `[a-z]` and `[^abc]` a byte from (or not from) the set
+//! - `\d`, `\w`, `\s` (and `\D`, `\W`, `\S`), `\xHH`, `\n`, `\r`, `\t`, `\0` and escaped characters
+//! - `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}` greedy repeats
+//! - `(...)`, `(?:...)` groups, `|` alternation, `^` and `$` for the start and end of the file
+//!
+//! text is encoded using the selected [`Encoding`] before matching. with utf-16 each byte set
+//! match a single code unit, so classes only cover the first 256 characters.
+//!
+//! the parsed pattern is turned into a byte regex, so matching run in linear time.
+
+use clap::ValueEnum;
+use regex::bytes::{Regex, RegexBuilder};
+
+use super::codepage;
+
+/// how text is stored inside the files
+#[derive(ValueEnum, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// single byte per character, used by most of the game files
+    Latin1,
+    /// central european texts of the pc versions
+    #[value(name = "windows-1250")]
+    Windows1250,
+    /// russian texts of the pc versions
+    #[value(name = "windows-1251")]
+    Windows1251,
+    /// like latin1, with extra characters like `€`, `œ` and the curly quotes
+    #[value(name = "windows-1252")]
+    Windows1252,
+    Utf16le,
+    Utf16be,
+}
+
+impl Encoding {
+    /// encode a single character, `None` if the encoding can't hold it
+    fn encode(self, c: char) -> Option<Vec<u8>> {
+        match self {
+            Self::Utf8 => Some(c.to_string().into_bytes()),
+            Self::Latin1 => u8::try_from(c as u32).ok().map(|b| vec![b]),
+            Self::Windows1250 | Self::Windows1251 | Self::Windows1252 => {
+                codepage::encode(c, self.code_page()?).map(|b| vec![b])
+            }
+            Self::Utf16le => Some(
+                c.encode_utf16(&mut [0; 2])
+                    .iter()
+                    .flat_map(|u| u.to_le_bytes())
+                    .collect(),
+            ),
+            Self::Utf16be => Some(
+                c.encode_utf16(&mut [0; 2])
+                    .iter()
+                    .flat_map(|u| u.to_be_bytes())
+                    .collect(),
+            ),
+        }
+    }
+
+    /// decode the bytes for printing, invalid characters are replaced
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Self::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+            Self::Windows1250 | Self::Windows1251 | Self::Windows1252 => {
+                codepage::decode(bytes, self.code_page().expect("single byte code page"))
+            }
+            Self::Utf16le | Self::Utf16be => {
+                let units = bytes.chunks_exact(2).map(|c| match self {
+                    Self::Utf16le => u16::from_le_bytes([c[0], c[1]]),
+                    _ => u16::from_be_bytes([c[0], c[1]]),
+                });
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect()
+            }
+        }
+    }
+
+    /// guess the encoding of a text, using the byte order mark or the code page that
+    /// give the most natural looking text when it's not valid utf-8
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes {
+            [0xff, 0xfe, ..] => Self::Utf16le,
+            [0xfe, 0xff, ..] => Self::Utf16be,
+            _ if std::str::from_utf8(bytes).is_ok() => Self::Utf8,
+            // on a tie the first one is used, the games are mostly in western languages
+            _ => [Self::Windows1252, Self::Windows1250, Self::Windows1251]
+                .into_iter()
+                .rev()
+                .max_by_key(|e| codepage::score(&e.decode(bytes)))
+                .unwrap_or(Self::Windows1252),
+        }
+    }
+
+    /// byte order mark at the start of the text, if there is one
+    pub fn bom_len(self, bytes: &[u8]) -> usize {
+        match (self, bytes) {
+            (Self::Utf8, [0xef, 0xbb, 0xbf, ..]) => 3,
+            (Self::Utf16le, [0xff, 0xfe, ..]) | (Self::Utf16be, [0xfe, 0xff, ..]) => 2,
+            _ => 0,
+        }
+    }
+
+    /// upper half of the windows code pages
+    fn code_page(self) -> Option<&'static [char; 128]> {
+        match self {
+            Self::Windows1250 => Some(&codepage::WINDOWS_1250),
+            Self::Windows1251 => Some(&codepage::WINDOWS_1251),
+            Self::Windows1252 => Some(&codepage::WINDOWS_1252),
+            Self::Utf8 | Self::Latin1 | Self::Utf16le | Self::Utf16be => None,
+        }
+    }
+}
+
+/// a compiled pattern
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    /// a single code unit with a value from the set
+    Set(Box<[bool; 256]>),
+    /// exact bytes
+    Bytes(Vec<u8>),
+    Start,
+    End,
+    /// alternatives, each one is a sequence of nodes
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: usize,
+    },
+}
+
+impl Pattern {
+    /// match the text as is
+    pub fn text(text: &str, encoding: Encoding, ignore_case: bool) -> Result<Self, String> {
+        let nodes: Vec<Node> = text
+            .chars()
+            .map(|c| literal(c, encoding, ignore_case))
+            .collect::<Result<_, _>>()?;
+
+        Self::compile(&nodes, encoding)
+    }
+
+    /// match the bytes, written as hex with optional spaces, for example `de ad be ef`
+    pub fn hex(hex: &str) -> Result<Self, String> {
+        let digits: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+        if digits.is_empty() || !digits.len().is_multiple_of(2) {
+            return Err("hex pattern must have an even number of digits".to_owned());
+        }
+
+        let bytes = digits
+            .chunks(2)
+            .map(|c| {
+                u8::from_str_radix(&c.iter().collect::<String>(), 16)
+                    .map_err(|_| format!("invalid hex byte '{}{}'", c[0], c[1]))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Self::compile(&[Node::Bytes(bytes)], Encoding::Latin1)
+    }
+
+    /// parse the regex, see the module documentation for the supported syntax
+    pub fn regex(regex: &str, encoding: Encoding, ignore_case: bool) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: regex.chars().collect(),
+            pos: 0,
+            encoding,
+            ignore_case,
+        };
+
+        let alternatives = parser.alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!(
+                "unexpected '{}' in regex",
+                parser.chars[parser.pos]
+            ));
+        }
+
+        Self::compile(&[Node::Group(alternatives)], encoding)
+    }
+
+    /// build the byte regex of the nodes
+    fn compile(nodes: &[Node], encoding: Encoding) -> Result<Self, String> {
+        let mut syntax = String::new();
+        write_nodes(&mut syntax, nodes, encoding);
+
+        let regex = RegexBuilder::new(&syntax)
+            .unicode(false)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        Ok(Self { regex })
+    }
+
+    /// ranges of every non overlapping match in the bytes
+    pub fn find_all(&self, bytes: &[u8]) -> Vec<std::ops::Range<usize>> {
+        self.regex.find_iter(bytes).map(|m| m.range()).collect()
+    }
+}
+
+fn write_nodes(syntax: &mut String, nodes: &[Node], encoding: Encoding) {
+    for node in nodes {
+        write_node(syntax, node, encoding);
+    }
+}
+
+/// write the node as regex syntax, every node is a single atom so it can be repeated
+fn write_node(syntax: &mut String, node: &Node, encoding: Encoding) {
+    match node {
+        Node::Set(set) => {
+            let mut class = String::from("[");
+            let mut b = 0;
+            while b < 256 {
+                if !set[b] {
+                    b += 1;
+                    continue;
+                }
+                let start = b;
+                while b < 256 && set[b] {
+                    b += 1;
+                }
+                class.push_str(&format!("\\x{start:02X}-\\x{:02X}", b - 1));
+            }
+            // an empty class never match
+            if class.len() == 1 {
+                class.push_str("^\\x00-\\xFF");
+            }
+            class.push(']');
+
+            match encoding {
+                Encoding::Utf16le => syntax.push_str(&format!("(?:{class}\\x00)")),
+                Encoding::Utf16be => syntax.push_str(&format!("(?:\\x00{class})")),
+                _ => syntax.push_str(&class),
+            }
+        }
+        Node::Bytes(bytes) => {
+            syntax.push_str("(?:");
+            for b in bytes {
+                syntax.push_str(&format!("\\x{b:02X}"));
+            }
+            syntax.push(')');
+        }
+        Node::Start => syntax.push_str("\\A"),
+        Node::End => syntax.push_str("\\z"),
+        Node::Group(alternatives) => {
+            syntax.push_str("(?:");
+            for (i, nodes) in alternatives.iter().enumerate() {
+                if i != 0 {
+                    syntax.push('|');
+                }
+                write_nodes(syntax, nodes, encoding);
+            }
+            syntax.push(')');
+        }
+        Node::Repeat { node, min, max } => {
+            write_node(syntax, node, encoding);
+            match *max {
+                usize::MAX => syntax.push_str(&format!("{{{min},}}")),
+                max => syntax.push_str(&format!("{{{min},{max}}}")),
+            }
+        }
+    }
+}
+
+/// node of a single literal character
+fn literal(c: char, encoding: Encoding, ignore_case: bool) -> Result<Node, String> {
+    if c.is_ascii() {
+        let mut set = empty_set();
+        set[c as usize] = true;
+        if ignore_case {
+            set[c.to_ascii_lowercase() as usize] = true;
+            set[c.to_ascii_uppercase() as usize] = true;
+        }
+        return Ok(Node::Set(set));
+    }
+
+    encoding
+        .encode(c)
+        .map(Node::Bytes)
+        .ok_or_else(|| format!("'{c}' can't be encoded as {encoding:?}"))
+}
+
+fn empty_set() -> Box<[bool; 256]> {
+    Box::new([false; 256])
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    encoding: Encoding,
+    ignore_case: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+
+        while let Some(c) = self.peek() {
+            let node = match c {
+                '|' | ')' => break,
+                '*' | '+' | '?' | '{' => return Err(format!("nothing to repeat before '{c}'")),
+                _ => self.atom()?,
+            };
+            nodes.push(self.repeat(node)?);
+        }
+
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next().expect("called with a character left") {
+            '.' => {
+                let mut set = Box::new([true; 256]);
+                set[b'\n' as usize] = false;
+                Ok(Node::Set(set))
+            }
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '(' => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let alternatives = self.alternatives()?;
+                match self.next() {
+                    Some(')') => Ok(Node::Group(alternatives)),
+                    _ => Err("unclosed '(' in regex".to_owned()),
+                }
+            }
+            '[' => self.class(),
+            '\\' => match self.escape()? {
+                Escape::Set(set) => Ok(Node::Set(set)),
+                Escape::Char(c) => literal(c, self.encoding, self.ignore_case),
+                Escape::Byte(b) => Ok(Node::Bytes(vec![b])),
+            },
+            c => literal(c, self.encoding, self.ignore_case),
+        }
+    }
+
+    fn repeat(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, usize::MAX),
+            Some('+') => (1, usize::MAX),
+            Some('?') => (0, 1),
+            Some('{') => return self.counted_repeat(node),
+            _ => return Ok(node),
+        };
+        self.pos += 1;
+
+        if matches!(self.peek(), Some('*' | '+' | '?')) {
+            return Err("lazy and nested repeats aren't supported".to_owned());
+        }
+
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+        })
+    }
+
+    fn counted_repeat(&mut self, node: Node) -> Result<Node, String> {
+        let start = self.pos;
+        let end = self.chars[start..]
+            .iter()
+            .position(|&c| c == '}')
+            .ok_or("unclosed '{' in regex")?;
+        let inner: String = self.chars[start + 1..start + end].iter().collect();
+        self.pos = start + end + 1;
+
+        let number = |s: &str| {
+            s.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("invalid repeat '{{{inner}}}'"))
+        };
+
+        let (min, max) = match inner.split_once(',') {
+            None => (number(&inner)?, number(&inner)?),
+            Some((min, "")) => (number(min)?, usize::MAX),
+            Some((min, max)) => (number(min)?, number(max)?),
+        };
+
+        if min > max {
+            return Err(format!("invalid repeat '{{{inner}}}'"));
+        }
+
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, String> {
+        let negate = self.peek() == Some('^');
+        if negate {
+            self.pos += 1;
+        }
+
+        let mut set = empty_set();
+        let mut first = true;
+
+        loop {
+            let c = match self.next() {
+                None => return Err("unclosed '[' in regex".to_owned()),
+                Some(']') if !first => break,
+                Some('\\') => match self.escape()? {
+                    Escape::Set(escaped) => {
+                        (0..256).for_each(|i| set[i] |= escaped[i]);
+                        first = false;
+                        continue;
+                    }
+                    Escape::Char(c) => c,
+                    Escape::Byte(b) => b as char,
+                },
+                Some(c) => c,
+            };
+            first = false;
+
+            let end = match (self.peek(), self.chars.get(self.pos + 1)) {
+                (Some('-'), Some(&end)) if end != ']' => {
+                    self.pos += 2;
+                    match end {
+                        '\\' => match self.escape()? {
+                            Escape::Char(end) => end,
+                            Escape::Byte(end) => end as char,
+                            Escape::Set(_) => return Err("invalid range in regex".to_owned()),
+                        },
+                        end => end,
+                    }
+                }
+                _ => c,
+            };
+
+            let (start, end) = (byte_of(c)?, byte_of(end)?);
+            if start > end {
+                return Err(format!("invalid range '{c}-{end}' in regex"));
+            }
+
+            for b in start..=end {
+                set[b as usize] = true;
+                if self.ignore_case {
+                    set[b.to_ascii_lowercase() as usize] = true;
+                    set[b.to_ascii_uppercase() as usize] = true;
+                }
+            }
+        }
+
+        if negate {
+            set.iter_mut().for_each(|b| *b = !*b);
+        }
+
+        Ok(Node::Set(set))
+    }
+
+    fn escape(&mut self) -> Result<Escape, String> {
+        let class = |f: fn(&u8) -> bool, negate: bool| {
+            let mut set = empty_set();
+            (0..=255u8).for_each(|b| set[b as usize] = f(&b) != negate);
+            Escape::Set(set)
+        };
+        let word = |b: &u8| b.is_ascii_alphanumeric() || *b == b'_';
+
+        Ok(match self.next().ok_or("unfinished escape in regex")? {
+            'd' => class(u8::is_ascii_digit, false),
+            'D' => class(u8::is_ascii_digit, true),
+            'w' => class(word, false),
+            'W' => class(word, true),
+            's' => class(u8::is_ascii_whitespace, false),
+            'S' => class(u8::is_ascii_whitespace, true),
+            'n' => Escape::Char('\n'),
+            'r' => Escape::Char('\r'),
+            't' => Escape::Char('\t'),
+            '0' => Escape::Char('\0'),
+            'x' => {
+                let hex: String = (0..2).filter_map(|_| self.next()).collect();
+                let byte = u8::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("invalid escape '\\x{hex}' in regex"))?;
+                Escape::Byte(byte)
+            }
+            c if c.is_ascii_alphanumeric() => {
+                return Err(format!("unknown escape '\\{c}' in regex"));
+            }
+            c => Escape::Char(c),
+        })
+    }
+}
+
+enum Escape {
+    Set(Box<[bool; 256]>),
+    Char(char),
+    /// a raw byte from `\xHH`
+    Byte(u8),
+}
+
+/// value of a character inside a byte set
+fn byte_of(c: char) -> Result<u8, String> {
+    u8::try_from(c as u32).map_err(|_| format!("'{c}' can't be used in a character class"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn find(pattern: &Pattern, bytes: &[u8]) -> Vec<(usize, usize)> {
+        pattern
+            .find_all(bytes)
+            .into_iter()
+            .map(|r| (r.start, r.end))
+            .collect()
+    }
+
+    fn regex(regex: &str) -> Pattern {
+        Pattern::regex(regex, Encoding::Utf8, false).unwrap()
+    }
+
+    #[test]
+    fn text() {
+        let pattern = Pattern::text("door", Encoding::Utf8, false).unwrap();
+        assert_eq!(find(&pattern, b"a door, the Door"), [(2, 6)]);
+
+        let pattern = Pattern::text("door", Encoding::Utf8, true).unwrap();
+        assert_eq!(find(&pattern, b"a door, the DoOr"), [(2, 6), (12, 16)]);
+
+        // regex characters are matched as is
+        let pattern = Pattern::text("a.b*", Encoding::Utf8, false).unwrap();
+        assert_eq!(find(&pattern, b"axb a.b*"), [(4, 8)]);
+    }
+
+    #[test]
+    fn text_encodings() {
+        let pattern = Pattern::text("é", Encoding::Utf8, false).unwrap();
+        assert_eq!(find(&pattern, "café".as_bytes()), [(3, 5)]);
+
+        let pattern = Pattern::text("é", Encoding::Latin1, false).unwrap();
+        assert_eq!(find(&pattern, b"caf\xe9"), [(3, 4)]);
+
+        let pattern = Pattern::text("€", Encoding::Windows1252, false).unwrap();
+        assert_eq!(find(&pattern, b"5 \x80"), [(2, 3)]);
+        assert!(Pattern::text("€", Encoding::Latin1, false).is_err());
+
+        let pattern = Pattern::text("Ab", Encoding::Utf16le, true).unwrap();
+        assert_eq!(find(&pattern, b"x\0a\0B\0"), [(2, 6)]);
+        // the bytes must be aligned on a code unit
+        assert_eq!(find(&pattern, b"\0a\0b"), []);
+
+        let pattern = Pattern::text("ab", Encoding::Utf16be, false).unwrap();
+        assert_eq!(find(&pattern, b"\0a\0b"), [(0, 4)]);
+    }
+
+    #[test]
+    fn hex() {
+        let pattern = Pattern::hex("de ad BE ef").unwrap();
+        assert_eq!(find(&pattern, b"\x00\xde\xad\xbe\xef"), [(1, 5)]);
+
+        assert!(Pattern::hex("").is_err());
+        assert!(Pattern::hex("abc").is_err());
+        assert!(Pattern::hex("zz").is_err());
+    }
+
+    #[test]
+    fn regex_syntax() {
+        assert_eq!(find(&regex(r"open_door_\d+"), b"open_door_12;"), [(0, 12)]);
+        assert_eq!(find(&regex("a.c"), b"abc a\nc"), [(0, 3)]);
+        assert_eq!(find(&regex("[a-c]+"), b"xabcax"), [(1, 5)]);
+        assert_eq!(find(&regex("[^a-c]+"), b"abxyc"), [(2, 4)]);
+        assert_eq!(find(&regex(r"[\d_]+"), b"a1_2b"), [(1, 4)]);
+        assert_eq!(find(&regex("[]a]+"), b"x]a]x"), [(1, 4)]);
+        assert_eq!(find(&regex(r"\x00\xff"), b"a\x00\xff"), [(1, 3)]);
+        assert_eq!(find(&regex(r"\w+\s\W"), b"ab !"), [(0, 4)]);
+        assert_eq!(find(&regex("ab?c"), b"ac abc abbc"), [(0, 2), (3, 6)]);
+        assert_eq!(find(&regex("a{2}"), b"aaaaa"), [(0, 2), (2, 4)]);
+        assert_eq!(find(&regex("a{2,}"), b"aaaaa"), [(0, 5)]);
+        assert_eq!(find(&regex("a{1,2}"), b"aaa"), [(0, 2), (2, 3)]);
+        assert_eq!(find(&regex("(?:ab)+"), b"ababa"), [(0, 4)]);
+        assert_eq!(find(&regex("cat|dog"), b"dog cat"), [(0, 3), (4, 7)]);
+        assert_eq!(find(&regex("^a"), b"aa"), [(0, 1)]);
+        assert_eq!(find(&regex("a$"), b"aa"), [(1, 2)]);
+        assert_eq!(find(&regex(r"\.\*"), b"a.*"), [(1, 3)]);
+        // empty matches move forward
+        assert_eq!(find(&regex("x*"), b"ab").len(), 3);
+    }
+
+    #[test]
+    fn regex_ignore_case() {
+        let pattern = Pattern::regex("[a-c]+d", Encoding::Utf8, true).unwrap();
+        assert_eq!(find(&pattern, b"ABcD"), [(0, 4)]);
+    }
+
+    #[test]
+    fn regex_errors() {
+        for regex in [
+            "(a", "a)", "[a", "*a", "a**", "a{2", "a{3,1}", "a{x}", r"\q", r"\xzz", "[z-a]", r"\",
+        ] {
+            assert!(
+                Pattern::regex(regex, Encoding::Utf8, false).is_err(),
+                "{regex}"
+            );
+        }
+    }
+
+    #[test]
+    fn pathological_patterns_are_linear() {
+        let bytes = vec![b'a'; 300 * 1024];
+        let start = Instant::now();
+
+        for pattern in [
+            "(a)*b",
+            "a*b",
+            "(a|aa)*b",
+            "(?:a*)*b",
+            "(a+)+b",
+            "(a?){100}b",
+        ] {
+            assert_eq!(find(&regex(pattern), &bytes), [], "{pattern}");
+        }
+
+        let mut bytes = bytes;
+        bytes.push(b'b');
+        assert_eq!(find(&regex("(a)*b"), &bytes), [(0, bytes.len())]);
+
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+}
@@ -0,0 +1,182 @@
+//! external subcommands, found by looking for executables like git and cargo do
+//!
+//! a plugin is any executable named `obscure-hvp-<name>` that is next to this tool or in `PATH`,
+//! `obscure-hvp <name> [args]` run it with the rest of the arguments. this is only discovery of
+//! executables, there is no api to add commands inside the tool: the built in commands are the
+//! clap subcommands of [`super::Operation`] and a plugin is a separate program. the global
+//! options are passed to the plugin as environment variables, so it can behave the same way:
+//!
+//! - `HVP_GAME`: `obscure1`, `obscure2` or `final-exam`, not set when the game is auto detected
+//! - `HVP_JSON`: `1` when the result should be printed as json
+//! - `HVP_ASSUME_YES`: `1` or `0` when every question is answered ahead of time
+//! - `HVP_PROGRESS`: `bar` or `json`
+//! - `HVP_STALL_TIMEOUT`: seconds without any progress before reporting a stall
+//! - `HVP_MAX_MEMORY`: the `--max-memory` thread limit in bytes, if any
+//! - `HVP_HASHES_DIR`: absolute path of the obscure 2 name maps directory, if it exists
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    Game, Progress,
+    output::{self, println},
+    prompt,
+};
+
+/// prefix of the plugin executables
+pub const PLUGIN_PREFIX: &str = "obscure-hvp-";
+
+/// global options that are passed to the plugins
+pub struct PluginEnv {
+    pub game: Game,
+    pub json: bool,
+    pub progress: Progress,
+    pub stall_timeout: u64,
+    pub max_memory: Option<u64>,
+}
+
+impl PluginEnv {
+    fn apply(&self, command: &mut Command) {
+        if !matches!(self.game, Game::Auto)
+            && let Some(game) = self.game.to_possible_value()
+        {
+            command.env("HVP_GAME", game.get_name());
+        }
+        if self.json {
+            command.env("HVP_JSON", "1");
+        }
+        if let Some(answer) = prompt::assumed_answer() {
+            command.env(prompt::ASSUME_YES_ENV, if answer { "1" } else { "0" });
+        }
+        if let Some(progress) = self.progress.to_possible_value() {
+            command.env("HVP_PROGRESS", progress.get_name());
+        }
+        command.env("HVP_STALL_TIMEOUT", self.stall_timeout.to_string());
+        if let Some(max_memory) = self.max_memory {
+            command.env("HVP_MAX_MEMORY", max_memory.to_string());
+        }
+        if let Ok(hashes) = Path::new("hashes").canonicalize() {
+            command.env("HVP_HASHES_DIR", hashes);
+        }
+    }
+}
+
+/// run the plugin for an unknown subcommand, `args` start with the name of the subcommand
+pub fn run(args: Vec<OsString>, env: &PluginEnv) -> anyhow::Result<()> {
+    let (name, args) = args.split_first().context("missing subcommand")?;
+    let name = name.to_string_lossy();
+
+    let Some(path) = find(&name) else {
+        anyhow::bail!(
+            "unknown command '{name}', and no plugin named {PLUGIN_PREFIX}{name} was found \
+            (use `plugins` to list the installed plugins)"
+        );
+    };
+
+    let mut command = Command::new(&path);
+    command.args(args);
+    env.apply(&mut command);
+
+    let status = command
+        .status()
+        .with_context(|| format!("failed to run plugin {}", path.display()))?;
+
+    if !status.success() {
+        anyhow::bail!("plugin {name} failed with {status}");
+    }
+
+    Ok(())
+}
+
+/// directories that are searched for plugins, the directory of this tool first
+fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+        .into_iter()
+        .collect();
+
+    if let Some(path) = std::env::var_os("PATH") {
+        dirs.extend(std::env::split_paths(&path));
+    }
+
+    dirs
+}
+
+/// path of the plugin executable with the given name
+fn find(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{PLUGIN_PREFIX}{name}{}", std::env::consts::EXE_SUFFIX);
+
+    search_dirs()
+        .into_iter()
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+/// name of the plugin if the path is a plugin executable
+fn plugin_name(path: &Path) -> Option<String> {
+    if !path.is_file() {
+        return None;
+    }
+
+    let file_name = path.file_name().and_then(OsStr::to_str)?;
+    let name = file_name
+        .strip_suffix(std::env::consts::EXE_SUFFIX)?
+        .strip_prefix(PLUGIN_PREFIX)?;
+
+    (!name.is_empty()).then(|| name.to_owned())
+}
+
+#[derive(Parser)]
+pub struct Commands {}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self) -> anyhow::Result<()> {
+        // the first plugin with a name is used, like `find` does
+        let mut plugins: Vec<(String, PathBuf)> = Vec::new();
+        for dir in search_dirs() {
+            let Ok(read_dir) = dir.read_dir() else {
+                continue;
+            };
+
+            let mut found: Vec<_> = read_dir
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter_map(|path| plugin_name(&path).map(|name| (name, path)))
+                .filter(|(name, _)| !plugins.iter().any(|(n, _)| n == name))
+                .collect();
+            found.sort();
+            plugins.extend(found);
+        }
+
+        if plugins.is_empty() {
+            println!(
+                "{} no plugin found, plugins are executables named {PLUGIN_PREFIX}<name> in PATH",
+                "[?]".green()
+            );
+        } else {
+            println!("{} {} plugins found:", "[+]".green(), plugins.len());
+            for (name, path) in &plugins {
+                println!(" {} {name}: {}", "|>".cyan(), path.display());
+            }
+        }
+
+        output::report(
+            "plugins",
+            plugins
+                .iter()
+                .map(|(name, path)| json!({ "name": name, "path": path }))
+                .collect(),
+        );
+
+        Ok(())
+    }
+}
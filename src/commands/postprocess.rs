@@ -0,0 +1,152 @@
+//! game specific fixups that run after a file is extracted
+//!
+//! every postprocessor is registered for some games and file extensions, `extract --postprocess`
+//! (or the `postprocess` list of the manifest.json in the output folder) choose which ones run. they never change the extracted file itself (so `create` still see it as
+//! unmodified), their output is written under [`POSTPROCESSED_DIR`] with the path of the file.
+//!
+//! the handlers are behind the `postprocess` feature, without it the registry is empty.
+
+use std::path::{Path, PathBuf};
+
+use hvp_archive::Game;
+use owo_colors::OwoColorize;
+
+use super::output::println;
+
+/// directory next to the extracted files that the postprocessors write to, `create` ignore it
+pub const POSTPROCESSED_DIR: &str = "_postprocessed";
+
+/// a fixup that run on the extracted files with one of the `extensions`
+pub struct Postprocessor {
+    /// name that is passed to `--postprocess`
+    pub name: &'static str,
+    pub description: &'static str,
+    pub games: &'static [Game],
+    /// lowercase extensions without the dot
+    pub extensions: &'static [&'static str],
+    /// process the content of the file that is extracted to `target`, return the written files
+    run: fn(target: &Path, bytes: &[u8]) -> std::io::Result<Vec<PathBuf>>,
+}
+
+/// every postprocessor that is built in
+pub static REGISTRY: &[Postprocessor] = &[
+    #[cfg(feature = "postprocess")]
+    Postprocessor {
+        name: "text-utf8",
+        description: "write a utf-8 copy of the texts that are stored with a windows code page",
+        // final exam doesn't have any text file
+        games: &[Game::Obscure1, Game::Obscure2],
+        extensions: &["txt"],
+        run: handlers::text_utf8,
+    },
+];
+
+/// find a postprocessor by its name
+pub fn find(name: &str) -> Option<&'static Postprocessor> {
+    REGISTRY.iter().find(|p| p.name == name)
+}
+
+/// value parser of `--postprocess`, `all` select every postprocessor
+pub fn parse(name: &str) -> Result<String, String> {
+    match name == "all" || find(name).is_some() {
+        true => Ok(name.to_owned()),
+        false => Err(format!(
+            "unknown postprocessor, expected `all` or one of:{}",
+            REGISTRY
+                .iter()
+                .map(|p| format!(
+                    "\n  {}: {} (.{})",
+                    p.name,
+                    p.description,
+                    p.extensions.join(", .")
+                ))
+                .collect::<String>()
+        )),
+    }
+}
+
+/// the postprocessors that were selected with `--postprocess`
+pub struct Selected(Vec<&'static Postprocessor>);
+
+impl Selected {
+    /// the postprocessors in `names` that support the game, the other names are reported and skipped
+    pub fn new(names: &[String], game: Game) -> Self {
+        for name in names {
+            match find(name) {
+                Some(p) if !p.games.contains(&game) => println!(
+                    "{} postprocessor {name} doesn't support {game:?}, skipping it",
+                    "[!]".yellow()
+                ),
+                None if name != "all" => {
+                    println!(
+                        "{} unknown postprocessor {name}, skipping it",
+                        "[!]".yellow()
+                    )
+                }
+                _ => (),
+            }
+        }
+
+        let all = names.iter().any(|n| n == "all");
+        Self(
+            REGISTRY
+                .iter()
+                .filter(|p| p.games.contains(&game))
+                .filter(|p| all || names.iter().any(|n| n == p.name))
+                .collect(),
+        )
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// run every selected postprocessor that match the extension of the file, `target` is the
+    /// path that the file is extracted to
+    pub fn run(&self, target: &Path, bytes: &[u8]) -> std::io::Result<Vec<PathBuf>> {
+        let extension = target
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let mut written = Vec::new();
+        for postprocessor in &self.0 {
+            if postprocessor.extensions.contains(&extension.as_str()) {
+                written.extend((postprocessor.run)(target, bytes)?);
+            }
+        }
+        Ok(written)
+    }
+}
+
+/// path of an output of a postprocessor for the file that is extracted to `target`
+#[cfg_attr(not(feature = "postprocess"), allow(dead_code))]
+fn output_path(target: &Path, suffix: &str) -> PathBuf {
+    let mut path = Path::new(POSTPROCESSED_DIR).join(target).into_os_string();
+    path.push(suffix);
+    path.into()
+}
+
+#[cfg(feature = "postprocess")]
+mod handlers {
+    use std::path::{Path, PathBuf};
+
+    use super::output_path;
+    use crate::commands::pattern::Encoding;
+
+    /// the texts of the pc versions use a windows code page, utf-8 texts are skipped
+    pub fn text_utf8(target: &Path, bytes: &[u8]) -> std::io::Result<Vec<PathBuf>> {
+        let encoding = Encoding::detect(bytes);
+        if encoding == Encoding::Utf8 {
+            return Ok(Vec::new());
+        }
+
+        let path = output_path(target, ".utf8.txt");
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, encoding.decode(&bytes[encoding.bom_len(bytes)..]))?;
+
+        Ok(vec![path])
+    }
+}
@@ -0,0 +1,135 @@
+//! questions that are asked from the user
+//!
+//! every question can be answered ahead of time with `--yes`/`--no` or the
+//! [`ASSUME_YES_ENV`] environment variable, so the tool can be used from scripts.
+
+use std::{
+    io::{BufRead, Write},
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use owo_colors::OwoColorize;
+
+use super::output::{self, print, println};
+
+/// environment variable that answer yes (`1`, `true` or `yes`) or no (`0`, `false` or `no`)
+/// to every question, `--yes` and `--no` take priority over it
+pub const ASSUME_YES_ENV: &str = "HVP_ASSUME_YES";
+
+const ASK: u8 = 0;
+const YES: u8 = 1;
+const NO: u8 = 2;
+
+static ANSWER: AtomicU8 = AtomicU8::new(ASK);
+
+/// set the answer of every question, `None` to ask the user (or use the environment variable)
+pub fn set_answer(answer: Option<bool>) {
+    let answer = match answer {
+        Some(true) => YES,
+        Some(false) => NO,
+        None => ASK,
+    };
+    ANSWER.store(answer, Ordering::Relaxed);
+}
+
+/// the answer that is given ahead of time, if any
+pub fn assumed_answer() -> Option<bool> {
+    match ANSWER.load(Ordering::Relaxed) {
+        YES => return Some(true),
+        NO => return Some(false),
+        _ => {}
+    }
+
+    let value = std::env::var(ASSUME_YES_ENV).ok()?;
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "y" => Some(true),
+        "0" | "false" | "no" | "n" => Some(false),
+        _ => None,
+    }
+}
+
+/// ask a yes or no question, anything other than yes is taken as no
+pub fn confirm(question: &str) -> anyhow::Result<bool> {
+    if let Some(answer) = assumed_answer() {
+        println!(
+            "{} {question} [y/n]: {} (assumed)",
+            "[!]".yellow(),
+            if answer { "y" } else { "n" }
+        );
+        return Ok(answer);
+    }
+
+    print!("{} {question} [y/n]: ", "[!]".yellow());
+    match output::is_json() {
+        true => anstream::stderr().flush()?,
+        false => anstream::stdout().flush()?,
+    }
+
+    let mut line = String::new();
+    // no input at all (closed stdin) is a no
+    if std::io::stdin().lock().read_line(&mut line)? == 0 {
+        println!();
+        return Ok(false);
+    }
+
+    match line.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(true),
+        "n" | "no" => Ok(false),
+        input => {
+            println!("{} invalid input: '{input}'", "[!]".red());
+            Ok(false)
+        }
+    }
+}
+
+/// ask the user to pick one of the choices, a choice can be answered with its name or any
+/// prefix that only match it. an empty answer, closed stdin or an answer given ahead of time pick `default`
+pub fn choose(question: &str, choices: &[&str], default: usize) -> anyhow::Result<usize> {
+    let options = choices.join("/");
+
+    if assumed_answer().is_some() {
+        println!(
+            "{} {question} [{options}]: {} (assumed)",
+            "[!]".yellow(),
+            choices[default]
+        );
+        return Ok(default);
+    }
+
+    loop {
+        print!(
+            "{} {question} [{options}] ({}): ",
+            "[!]".yellow(),
+            choices[default]
+        );
+        match output::is_json() {
+            true => anstream::stderr().flush()?,
+            false => anstream::stdout().flush()?,
+        }
+
+        let mut line = String::new();
+        if std::io::stdin().lock().read_line(&mut line)? == 0 {
+            println!();
+            return Ok(default);
+        }
+
+        let input = line.trim().to_lowercase();
+        if input.is_empty() {
+            return Ok(default);
+        }
+
+        let exact = choices.iter().position(|c| c.eq_ignore_ascii_case(&input));
+        let mut prefixed = choices
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.to_lowercase().starts_with(&input));
+        let found = exact.or_else(|| match (prefixed.next(), prefixed.next()) {
+            (Some((idx, _)), None) => Some(idx),
+            _ => None,
+        });
+        match found {
+            Some(idx) => return Ok(idx),
+            None => println!("{} invalid input: '{input}'", "[!]".red()),
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options, builder::ArchiveBuilder},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{name_maps_for_game, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// paths of the files or directories inside the archive to remove, for example `some/dir/file.bin`
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let mut builder =
+            ArchiveBuilder::from_archive(&archive).context("failed to read archive entries")?;
+
+        for path in &self.paths {
+            if !builder.remove(path) {
+                anyhow::bail!("can't find {} in the archive", path.display());
+            }
+        }
+
+        let metadata = builder.metadata();
+        if metadata.file_count == 0 {
+            anyhow::bail!("can't remove every file of the archive")
+        }
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(metadata.file_count as _);
+        utils::watch_progress(&pb, |progress| builder.build(&mut writer, progress))
+            .context("failed to build the archive")?;
+
+        pb.finish_with_message(
+            "rebuild finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} removed {} dirs and {} files",
+            "[+]".green(),
+            archive.metadata().dir_count - metadata.dir_count,
+            archive.metadata().file_count - metadata.file_count,
+        );
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            metadata,
+            json!({
+                "removed_dirs": archive.metadata().dir_count - metadata.dir_count,
+                "removed_files": archive.metadata().file_count - metadata.file_count,
+            }),
+        );
+
+        Ok(())
+    }
+}
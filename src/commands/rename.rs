@@ -0,0 +1,120 @@
+use std::{
+    ffi::OsStr,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Options, builder::ArchiveBuilder},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{name_maps_for_game, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// path of the file or directory inside the archive, for example `some/dir/file.bin`
+    pub old_path: PathBuf,
+    /// new path of the file or directory inside the archive
+    pub new_path: PathBuf,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let mut builder =
+            ArchiveBuilder::from_archive(&archive).context("failed to read archive entries")?;
+
+        builder
+            .rename(&self.old_path, &self.new_path)
+            .with_context(|| {
+                format!(
+                    "failed to rename {} to {}",
+                    self.old_path.display(),
+                    self.new_path.display()
+                )
+            })?;
+
+        if archive.metadata().game == Game::Obscure2 {
+            // obscure 2 only store the crc32 of names, so new names can't be read back without name maps
+            let unknown_names: Vec<_> = self
+                .new_path
+                .iter()
+                .filter_map(OsStr::to_str)
+                .filter(|n| !archive.options.obscure2_names.contains_name(n))
+                .collect();
+
+            if !unknown_names.is_empty() {
+                println!(
+                    "{} these names are not in the name maps, add them to a txt file inside hashes directory to be able to see them after extraction: {}",
+                    "[!]".yellow(),
+                    unknown_names.join(", ")
+                );
+            }
+        }
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(builder.metadata().file_count as _);
+        let metadata = utils::watch_progress(&pb, |progress| builder.build(&mut writer, progress))
+            .context("failed to build the archive")?;
+
+        pb.finish_with_message(
+            "rebuild finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} renamed {} to {}",
+            "[+]".green(),
+            self.old_path.display(),
+            self.new_path.display(),
+        );
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            metadata,
+            json!({ "from": self.old_path, "to": self.new_path }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{name_maps_for_game, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// text file with the path of an entry inside the archive per line, listed entries are moved to the
+    /// start of their directory in the same order, the other entries keep their order after them
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub list: PathBuf,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+/// names of the listed entries grouped by their directory, empty lines and lines that start with `#`
+/// are ignored
+fn parse_list(list: &str) -> BTreeMap<PathBuf, Vec<String>> {
+    let mut dirs: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+
+    for line in list.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut path: Vec<_> = line.split(['/', '\\']).filter(|c| !c.is_empty()).collect();
+        let Some(name) = path.pop() else {
+            continue;
+        };
+
+        dirs.entry(path.into_iter().collect())
+            .or_default()
+            .push(name.to_owned());
+    }
+
+    dirs
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let list = std::fs::read_to_string(&self.list).context("failed to read order list")?;
+        let dirs = parse_list(&list);
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let mut archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        for (dir, order) in &dirs {
+            archive
+                .reorder(dir, order)
+                .with_context(|| format!("failed to reorder {}", dir_name(dir)))?;
+        }
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(archive.metadata().file_count as _);
+        utils::watch_progress(&pb, |progress| archive.rebuild(&mut writer, progress))
+            .context("failed to rebuild the archive")?;
+
+        pb.finish_with_message(
+            "rebuild finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} reordered {} entries in {} directories",
+            "[+]".green(),
+            dirs.values().map(Vec::len).sum::<usize>(),
+            dirs.len()
+        );
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            archive.metadata(),
+            json!({
+                "directories": dirs
+                    .iter()
+                    .map(|(dir, order)| json!({ "dir": dir_name(dir), "order": order }))
+                    .collect::<Vec<_>>(),
+            }),
+        );
+
+        Ok(())
+    }
+}
+
+fn dir_name(dir: &Path) -> String {
+    match dir.as_os_str().is_empty() {
+        true => "/".to_owned(),
+        false => super::manifest::path_str(dir),
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fs::OpenOptions, path::PathBuf};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive, it's loaded even if the crc32 of its entries is stale
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath, conflicts_with = "in_place")]
+    pub output: Option<PathBuf>,
+    /// repair the input archive itself instead of writing a new one
+    #[arg(long, default_value_t = false, required = false)]
+    pub in_place: bool,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let mut archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let stale_crcs: Vec<_> = archive
+            .verify()
+            .header_crcs
+            .into_iter()
+            .filter(|crc| !crc.is_match())
+            .collect();
+        for crc in &stale_crcs {
+            println!(
+                "{} {} crc32: stored {:#010x}, computed {:#010x}",
+                "[!]".yellow(),
+                crc.name,
+                crc.stored,
+                crc.computed
+            );
+        }
+
+        let files = archive.repair_checksums();
+        for path in &files {
+            println!("{} stale checksum: {}", "[!]".yellow(), path.display());
+        }
+
+        if stale_crcs.is_empty() && files.is_empty() {
+            println!(
+                "{} every checksum is valid, nothing to repair",
+                "[+]".green()
+            );
+            output::report(
+                "repair",
+                json!({ "output": null, "header_crcs": [], "files": [] }),
+            );
+            return Ok(());
+        }
+
+        let output = match self.in_place {
+            true => self.input.clone(),
+            false => {
+                let output = self
+                    .output
+                    .clone()
+                    .unwrap_or_else(|| utils::default_output(&self.input));
+                std::fs::copy(&self.input, &output)
+                    .context("failed to copy the archive to the output path")?;
+                output
+            }
+        };
+
+        println!(
+            "{} writing repaired TOC to {}",
+            "[+]".green(),
+            output.display()
+        );
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&output)
+            .context("failed to open hvp archive for repairing")?;
+
+        // nothing is updated, so only the TOC is written again
+        archive
+            .patch(&mut file)
+            .context("failed to write the repaired TOC")?;
+
+        println!(
+            "{} repaired {} header crc32 and {} file checksums",
+            "[+]".green(),
+            stale_crcs.len(),
+            files.len()
+        );
+
+        output::report(
+            "repair",
+            json!({
+                "output": output,
+                "header_crcs": stale_crcs.iter().map(|crc| crc.name).collect::<Vec<_>>(),
+                "files": files
+                    .iter()
+                    .map(|path| super::manifest::path_str(path))
+                    .collect::<Vec<_>>(),
+            }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    Game,
+    archive::{Archive, Options, info::FileInfo},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    glob::Glob,
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// part of the path (`menus/`), a glob pattern (`*.tga`, `sounds/**/*.wav`)
+    /// or an obscure 2 name crc32 (`crc32:0x179ce296`)
+    pub query: String,
+}
+
+/// what the files are matched against
+enum Query {
+    /// case insensitive part of the path
+    Substring(String),
+    Glob(Glob),
+    /// crc32 of the file name
+    Crc32(u32),
+}
+
+impl Query {
+    fn parse(query: &str) -> anyhow::Result<Self> {
+        if let Some(crc32) = query.strip_prefix("crc32:") {
+            let crc32 = match crc32
+                .strip_prefix("0x")
+                .or_else(|| crc32.strip_prefix("0X"))
+            {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => crc32.parse().ok(),
+            };
+            return crc32
+                .map(Self::Crc32)
+                .with_context(|| format!("invalid crc32 in query '{query}'"));
+        }
+
+        match query.contains(['*', '?', '[']) {
+            true => Glob::new(query).map(Self::Glob).map_err(anyhow::Error::msg),
+            false => Ok(Self::Substring(query.replace('\\', "/").to_lowercase())),
+        }
+    }
+
+    fn is_match(&self, info: &FileInfo) -> bool {
+        match self {
+            Self::Substring(part) => info
+                .path
+                .to_string_lossy()
+                .replace('\\', "/")
+                .to_lowercase()
+                .contains(part),
+            Self::Glob(glob) => glob.is_match(&info.path),
+            Self::Crc32(crc32) => info.name_crc32 == Some(*crc32),
+        }
+    }
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let query = Query::parse(&self.query)?;
+
+        if matches!(query, Query::Crc32(_)) && provider.game() != Game::Obscure2 {
+            anyhow::bail!("only obscure 2 archives store the crc32 of names");
+        }
+
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        let matches: Vec<FileInfo> = archive
+            .files_info()
+            .filter(|info| query.is_match(info))
+            .collect();
+
+        for info in &matches {
+            let compression = match info.compression {
+                Some(compression) => format!("{compression:?}"),
+                None => "none".to_owned(),
+            };
+
+            let dir = match info.dir.as_os_str().is_empty() {
+                true => String::new(),
+                false => format!("{}/", info.dir.display()),
+            };
+
+            println!(
+                " {} {dir}{} {} bytes, compression: {compression}",
+                "|>".cyan(),
+                info.name.bold(),
+                info.stored_size,
+            );
+        }
+
+        println!(
+            "{} found {} files matching '{}'",
+            "[+]".green(),
+            matches.len(),
+            self.query
+        );
+
+        output::report(
+            "search",
+            json!(
+                matches
+                    .iter()
+                    .map(|info| json!({
+                        "path": info.path,
+                        "dir": info.dir,
+                        "name": info.name,
+                        "stored_size": info.stored_size,
+                        "uncompressed_size": info.uncompressed_size,
+                        "compression": info.compression.map(|c| format!("{c:?}")),
+                        "name_crc32": info.name_crc32,
+                    }))
+                    .collect::<Vec<_>>()
+            ),
+        );
+
+        Ok(())
+    }
+}
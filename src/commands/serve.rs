@@ -0,0 +1,312 @@
+//! a tiny read-only http server over the archive
+//!
+//! available endpoints:
+//! - `GET /metadata`: metadata of the archive as json
+//! - `GET /files`: list of all the files as json
+//! - `GET /files/<path>`: decompressed content of a file
+//! - `GET /search?q=<query>`: files that their path contain the query (case insensitive) as json
+//!
+//! requests are handled by a fixed number of workers, as many connections can wait for them and
+//! the rest get `503` right away.
+//! slow clients time out and the request line plus headers can't be bigger than [`MAX_HEAD_LEN`].
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        mpsc::{self, TrySendError},
+    },
+    time::Duration,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options, entry::FullFileEntry},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{name_maps_for_game, output::println, utils};
+
+/// most bytes of the request line and headers
+const MAX_HEAD_LEN: u64 = 8 * 1024;
+/// how long a client can stay silent while sending the request
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// how long a client can stop reading the response
+const WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// port to listen on
+    #[arg(long, short = 'p', default_value_t = 8080)]
+    pub port: u16,
+    /// address to bind to, use 0.0.0.0 to make the server accessible from other machines
+    #[arg(long, default_value = "127.0.0.1")]
+    pub bind: String,
+    /// number of connections that are handled at the same time, as many can wait in a queue
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_connections: u16,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let listener = TcpListener::bind((self.bind.as_str(), self.port))
+            .with_context(|| format!("failed to listen on {}:{}", self.bind, self.port))?;
+
+        println!(
+            "{} serving {} on http://{}",
+            "[+]".green(),
+            self.input.display(),
+            listener.local_addr()?
+        );
+
+        // the files are looked up by path on every request
+        let files: ahash::HashMap<PathBuf, FullFileEntry> =
+            archive.files().map(|f| (f.path.clone(), f)).collect();
+        let server = Server {
+            archive: &archive,
+            files,
+        };
+
+        let workers = self.max_connections as usize;
+        let (sender, receiver) = mpsc::sync_channel::<TcpStream>(workers);
+        let receiver = Mutex::new(receiver);
+
+        std::thread::scope(|s| {
+            for _ in 0..workers {
+                s.spawn(|| {
+                    loop {
+                        let Ok(stream) = receiver.lock().expect("worker panicked").recv() else {
+                            break;
+                        };
+                        if let Err(e) = server.handle_connection(stream) {
+                            println!("{} failed to handle request: {e}", "[!]".yellow());
+                        }
+                    }
+                });
+            }
+
+            for stream in listener.incoming() {
+                let stream = match stream.and_then(|stream| {
+                    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    Ok(stream)
+                }) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        println!("{} failed to accept connection: {e}", "[!]".yellow());
+                        continue;
+                    }
+                };
+
+                match sender.try_send(stream) {
+                    Ok(()) => (),
+                    Err(TrySendError::Full(mut stream)) => {
+                        let _ = respond(
+                            &mut stream,
+                            "503 Service Unavailable",
+                            "text/plain",
+                            b"too many connections",
+                        );
+                    }
+                    Err(TrySendError::Disconnected(_)) => break,
+                }
+            }
+
+            drop(sender);
+        });
+
+        Ok(())
+    }
+}
+
+struct Server<'a, 'p> {
+    archive: &'a Archive<'p>,
+    files: ahash::HashMap<PathBuf, FullFileEntry<'p>>,
+}
+
+impl Server<'_, '_> {
+    fn handle_connection(&self, mut stream: TcpStream) -> std::io::Result<()> {
+        let mut reader = BufReader::new((&stream).take(MAX_HEAD_LEN));
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+
+        // we don't care about the headers, but we need to read them
+        let mut line = String::new();
+        while reader.read_line(&mut line)? > 2 {
+            line.clear();
+        }
+
+        if reader.get_ref().limit() == 0 {
+            return respond(
+                &mut stream,
+                "431 Request Header Fields Too Large",
+                "text/plain",
+                b"request is too large",
+            );
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return respond(&mut stream, "400 Bad Request", "text/plain", b"bad request");
+        };
+
+        println!("{} {method} {target}", "[?]".green());
+
+        if method != "GET" {
+            return respond(
+                &mut stream,
+                "405 Method Not Allowed",
+                "text/plain",
+                b"only GET is supported",
+            );
+        }
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let path = percent_decode(path);
+        let archive = self.archive;
+
+        match path.as_str() {
+            "/metadata" => {
+                let metadata = archive.metadata();
+                let body = json!({
+                    "game": format!("{:?}", metadata.game),
+                    "dir_count": metadata.dir_count,
+                    "file_count": metadata.file_count,
+                });
+                respond_json(&mut stream, &body)
+            }
+            "/files" => {
+                let files: Vec<_> = archive.files().map(|f| file_json(&f)).collect();
+                respond_json(&mut stream, &json!(files))
+            }
+            "/search" => {
+                let query = query
+                    .split('&')
+                    .filter_map(|p| p.split_once('='))
+                    .find(|(k, _)| *k == "q")
+                    .map(|(_, v)| percent_decode(&v.replace('+', " ")).to_lowercase())
+                    .unwrap_or_default();
+
+                let files: Vec<_> = archive
+                    .files()
+                    .filter(|f| {
+                        f.path
+                            .to_string_lossy()
+                            .replace('\\', "/")
+                            .to_lowercase()
+                            .contains(&query)
+                    })
+                    .map(|f| file_json(&f))
+                    .collect();
+                respond_json(&mut stream, &json!(files))
+            }
+            _ => match path.strip_prefix("/files/") {
+                Some(file_path) => match self.files.get(Path::new(file_path)) {
+                    Some(entry) => match entry.get_bytes() {
+                        Ok(bytes) => {
+                            respond(&mut stream, "200 OK", "application/octet-stream", &bytes)
+                        }
+                        Err(e) => respond(
+                            &mut stream,
+                            "500 Internal Server Error",
+                            "text/plain",
+                            format!("failed to decompress file: {e}").as_bytes(),
+                        ),
+                    },
+                    None => respond(
+                        &mut stream,
+                        "404 Not Found",
+                        "text/plain",
+                        b"file not found",
+                    ),
+                },
+                None => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+            },
+        }
+    }
+}
+
+fn file_json(file: &FullFileEntry) -> serde_json::Value {
+    json!({
+        "path": file.path.to_string_lossy().replace('\\', "/"),
+        "size": file.uncompressed_size(),
+        "stored_size": file.raw_bytes.len(),
+        "compressed": file.is_compressed(),
+        "stored_checksum": file.stored_checksum(),
+    })
+}
+
+fn respond_json(stream: &mut TcpStream, body: &serde_json::Value) -> std::io::Result<()> {
+    respond(
+        stream,
+        "200 OK",
+        "application/json",
+        body.to_string().as_bytes(),
+    )
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+/// decode `%xx` sequences of a url
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match decoded {
+            Some(b) => {
+                output.push(b);
+                i += 3;
+            }
+            None => {
+                output.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&output).into_owned()
+}
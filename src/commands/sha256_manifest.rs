@@ -0,0 +1,196 @@
+//! json manifest with the sha256 of the archive and of every extracted file, for mod releases
+//!
+//! `sha256-manifest` write it from an archive and `verify-manifest` check a folder (and
+//! optionally the archive) against it.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+
+use super::{
+    digest,
+    manifest::path_str,
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// output manifest, if empty a file with the same name of input hvp and the `.sha256.json`
+    /// extension will be created
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+/// sha256 and size of a file
+pub struct FileDigest {
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// the content of a sha256 manifest
+pub struct Sha256Manifest {
+    /// file name of the archive and its digest
+    pub archive: (String, FileDigest),
+    /// digests of the extracted files by their path, paths always use `/`
+    pub files: Vec<(String, FileDigest)>,
+}
+
+impl Sha256Manifest {
+    pub fn to_json(&self) -> Value {
+        let digest = |name: &str, digest: &FileDigest, key: &str| {
+            let mut value = json!({ "size": digest.size, "sha256": digest.sha256 });
+            value[key] = json!(name);
+            value
+        };
+
+        json!({
+            "archive": digest(&self.archive.0, &self.archive.1, "name"),
+            "files": self
+                .files
+                .iter()
+                .map(|(path, d)| digest(path, d, "path"))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn from_json(bytes: &[u8]) -> anyhow::Result<Self> {
+        let value: Value = serde_json::from_slice(bytes).context("invalid json")?;
+
+        let digest = |value: &Value, key: &str| -> anyhow::Result<(String, FileDigest)> {
+            let name = value.get(key).and_then(Value::as_str);
+            let size = value.get("size").and_then(Value::as_u64);
+            let sha256 = value.get("sha256").and_then(Value::as_str);
+            match (name, size, sha256) {
+                (Some(name), Some(size), Some(sha256)) => Ok((
+                    name.to_owned(),
+                    FileDigest {
+                        size,
+                        sha256: sha256.to_ascii_lowercase(),
+                    },
+                )),
+                _ => anyhow::bail!("expected `{key}`, `size` and `sha256` in {value}"),
+            }
+        };
+
+        let archive = digest(value.get("archive").context("missing archive")?, "name")?;
+        let files = value
+            .get("files")
+            .and_then(Value::as_array)
+            .context("missing files")?
+            .iter()
+            .map(|file| digest(file, "path"))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self { archive, files })
+    }
+}
+
+/// digest of a file on disk
+pub fn file_digest(path: &Path) -> std::io::Result<FileDigest> {
+    Ok(FileDigest {
+        size: std::fs::metadata(path)?.len(),
+        sha256: digest::hex(&digest::sha256_file(path)?),
+    })
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let output = self
+            .output
+            .unwrap_or_else(|| self.input.with_extension("sha256.json"));
+
+        println!("{} output file: {}", "[+]".green(), output.display());
+
+        let archive_digest =
+            file_digest(&self.input).context("failed to compute the sha256 of the archive")?;
+
+        let pb = utils::progress_bar(archive.metadata().file_count as _);
+
+        let mut files = Vec::new();
+        for file in archive.files() {
+            pb.inc(1);
+
+            let path = path_str(&file.path);
+            let bytes = file
+                .get_bytes()
+                .with_context(|| format!("failed to decompress {path}"))?;
+            files.push((
+                path,
+                FileDigest {
+                    size: bytes.len() as _,
+                    sha256: digest::hex(&digest::sha256(&bytes)),
+                },
+            ));
+        }
+
+        pb.finish_and_clear();
+
+        // sorted, so the manifest doesn't change between runs on the same archive
+        files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let manifest = Sha256Manifest {
+            archive: (
+                self.input
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned(),
+                archive_digest,
+            ),
+            files,
+        };
+
+        let mut writer =
+            BufWriter::new(File::create(&output).context("failed to create output file")?);
+        serde_json::to_writer_pretty(&mut writer, &manifest.to_json())
+            .context("failed to write manifest")?;
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} wrote the sha256 of the archive and {} files",
+            "[+]".green(),
+            manifest.files.len()
+        );
+
+        output::report(
+            "sha256_manifest",
+            json!({
+                "output": output,
+                "archive_sha256": manifest.archive.1.sha256,
+                "files": manifest.files.len(),
+            }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+//! parsing and formatting of byte sizes, shared by the commands
+
+/// parse a size like `512M`, `2G` or `1048576`
+pub fn parse(size: &str) -> Result<u64, String> {
+    let upper = size.trim().to_ascii_uppercase();
+    let number = upper.trim_end_matches("IB").trim_end_matches('B');
+
+    let (number, unit) = match number.char_indices().last() {
+        Some((i, 'K')) => (&number[..i], 1 << 10),
+        Some((i, 'M')) => (&number[..i], 1 << 20),
+        Some((i, 'G')) => (&number[..i], 1 << 30),
+        Some((i, 'T')) => (&number[..i], 1 << 40),
+        _ => (number, 1),
+    };
+
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("invalid size '{size}', expected something like 512M or 2G"))
+}
+
+/// format a size in a human readable way
+pub fn format(size: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if size < 1024 {
+        return format!("{size} B");
+    }
+
+    let mut size = size as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse("1048576"), Ok(1 << 20));
+        assert_eq!(parse("512M"), Ok(512 << 20));
+        assert_eq!(parse(" 2g "), Ok(2 << 30));
+        assert_eq!(parse("4KiB"), Ok(4 << 10));
+        assert_eq!(parse("3MB"), Ok(3 << 20));
+        assert_eq!(parse("1T"), Ok(1 << 40));
+
+        for invalid in ["", "0", "M", "-1G", "1.5G", "12X", "99999999999T"] {
+            assert!(parse(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format(0), "0 B");
+        assert_eq!(format(1023), "1023 B");
+        assert_eq!(format(1024), "1.0 KiB");
+        assert_eq!(format(1536), "1.5 KiB");
+        assert_eq!(format(512 << 20), "512.0 MiB");
+        assert_eq!(format(3 << 30), "3.0 GiB");
+        assert_eq!(format(2048 << 40), "2048.0 TiB");
+    }
+}
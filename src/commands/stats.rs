@@ -0,0 +1,222 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use clap::{Parser, ValueEnum, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options, info::FileInfo},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    size, utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// column that the rows are sorted by
+    #[arg(long, default_value_t = SortBy::Stored, value_enum, required = false)]
+    pub sort: SortBy,
+    /// group the files by only this many leading directories, for example 1 for the top level directories
+    #[arg(long)]
+    pub depth: Option<usize>,
+    /// only print this many rows of every table
+    #[arg(long)]
+    pub limit: Option<usize>,
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug, Default)]
+pub enum SortBy {
+    /// name of the directory or extension
+    Name,
+    /// number of files, largest first
+    Files,
+    /// size of the stored data, largest first
+    #[default]
+    Stored,
+    /// size after decompression, largest first
+    Uncompressed,
+    /// stored size / uncompressed size, least compressed first
+    Ratio,
+}
+
+/// totals of a group of files
+#[derive(Default, Clone, Copy)]
+struct Stats {
+    files: usize,
+    compressed_files: usize,
+    stored: u64,
+    uncompressed: u64,
+}
+
+impl Stats {
+    fn add(&mut self, info: &FileInfo) {
+        self.files += 1;
+        self.compressed_files += info.compression.is_some() as usize;
+        self.stored += info.stored_size;
+        self.uncompressed += info.uncompressed_size;
+    }
+
+    /// stored size / uncompressed size, 1 for empty files
+    fn ratio(&self) -> f64 {
+        match self.uncompressed {
+            0 => 1.0,
+            uncompressed => self.stored as f64 / uncompressed as f64,
+        }
+    }
+
+    fn to_json(self, name: &str) -> Value {
+        json!({
+            "name": name,
+            "files": self.files,
+            "compressed_files": self.compressed_files,
+            "stored_size": self.stored,
+            "uncompressed_size": self.uncompressed,
+            "ratio": self.ratio(),
+        })
+    }
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let mut total = Stats::default();
+        let mut dirs: BTreeMap<String, Stats> = BTreeMap::new();
+        let mut extensions: BTreeMap<String, Stats> = BTreeMap::new();
+
+        for info in archive.files_info() {
+            total.add(&info);
+
+            let dir = info
+                .dir
+                .iter()
+                .take(self.depth.unwrap_or(usize::MAX))
+                .map(|c| c.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            dirs.entry(format!("{dir}/")).or_default().add(&info);
+
+            let extension = match info.name.rsplit_once('.') {
+                Some((_, ext)) if !ext.is_empty() => format!(".{}", ext.to_lowercase()),
+                _ => "(none)".to_owned(),
+            };
+            extensions.entry(extension).or_default().add(&info);
+        }
+
+        let dirs = self.sorted(dirs);
+        let extensions = self.sorted(extensions);
+
+        self.print_table("directory", &dirs, total);
+        self.print_table("extension", &extensions, total);
+
+        println!(
+            "{} {} files, {} stored, {} uncompressed ({:.1}%)",
+            "[+]".green(),
+            total.files,
+            size::format(total.stored),
+            size::format(total.uncompressed),
+            total.ratio() * 100.0,
+        );
+
+        // the archive is opened without its data, so the regions can't be classified here
+        let dead_space = archive.dead_space();
+        let dead_bytes: u64 = dead_space.iter().map(|region| region.len).sum();
+        println!(
+            "{} {} of dead space in {} regions ({:.1}% of the archive), see `analyze` for zero filled regions",
+            "[+]".green(),
+            size::format(dead_bytes),
+            dead_space.len(),
+            match provider.file_len() {
+                0 => 0.0,
+                len => dead_bytes as f64 / len as f64 * 100.0,
+            },
+        );
+
+        let to_json = |rows: &[(String, Stats)]| -> Vec<Value> {
+            rows.iter()
+                .map(|(name, stats)| stats.to_json(name))
+                .collect()
+        };
+        output::report(
+            "stats",
+            json!({
+                "total": total.to_json(""),
+                "directories": to_json(&dirs),
+                "extensions": to_json(&extensions),
+                "dead_space": {
+                    "regions": dead_space.len(),
+                    "bytes": dead_bytes,
+                },
+            }),
+        );
+
+        Ok(())
+    }
+
+    fn sorted(&self, groups: BTreeMap<String, Stats>) -> Vec<(String, Stats)> {
+        // groups are already sorted by name, so ties keep the name order
+        let mut rows: Vec<_> = groups.into_iter().collect();
+        match self.sort {
+            SortBy::Name => {}
+            SortBy::Files => rows.sort_by_key(|(_, s)| std::cmp::Reverse(s.files)),
+            SortBy::Stored => rows.sort_by_key(|(_, s)| std::cmp::Reverse(s.stored)),
+            SortBy::Uncompressed => rows.sort_by_key(|(_, s)| std::cmp::Reverse(s.uncompressed)),
+            SortBy::Ratio => rows.sort_by(|(_, a), (_, b)| b.ratio().total_cmp(&a.ratio())),
+        }
+        rows
+    }
+
+    fn print_table(&self, title: &str, rows: &[(String, Stats)], total: Stats) {
+        let rows = &rows[..self.limit.unwrap_or(rows.len()).min(rows.len())];
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .chain([title.len()])
+            .max()
+            .unwrap_or_default();
+
+        println!(
+            "{} {title:<width$} {:>7} {:>11} {:>13} {:>7} {:>7}",
+            "[?]".green(),
+            "files",
+            "stored",
+            "uncompressed",
+            "ratio",
+            "share",
+        );
+
+        for (name, stats) in rows {
+            let share = match total.stored {
+                0 => 0.0,
+                stored => stats.stored as f64 / stored as f64,
+            };
+            println!(
+                " {} {name:<width$} {:>7} {:>11} {:>13} {:>6.1}% {:>6.1}%",
+                "|>".cyan(),
+                stats.files,
+                size::format(stats.stored),
+                size::format(stats.uncompressed),
+                stats.ratio() * 100.0,
+                share * 100.0,
+            );
+        }
+    }
+}
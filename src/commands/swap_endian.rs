@@ -0,0 +1,101 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Endianness, Options, builder::ArchiveBuilder},
+    formats::Capability,
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{name_maps_for_game, output::println, utils};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// output file, if empty a new file with the same name of input hvp will be created (+ new)
+    #[arg(long, short = 'o', value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let game = provider.game();
+        if !game.format().supports(Capability::EndianConvert) {
+            anyhow::bail!("{} archives only have a single endian", game.format().name);
+        }
+
+        let obscure2_names = name_maps_for_game(game)?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let from = archive.metadata().endianness;
+        let to = match from {
+            Endianness::Big => Endianness::Little,
+            Endianness::Little => Endianness::Big,
+        };
+
+        println!(
+            "{} converting {from:?} endian archive to {to:?} endian",
+            "[+]".green()
+        );
+
+        // the data of the files is copied as is, only the TOC and checksums are written again
+        let builder = ArchiveBuilder::from_archive(&archive)
+            .context("failed to read the entries of the archive")?
+            .big_endian(to == Endianness::Big);
+
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| utils::default_output(&self.input));
+
+        println!("{} output hvp archive: {}", "[+]".green(), output.display());
+
+        let mut writer = BufWriter::new(
+            File::create(&output).context("failed to create output hvp archive file")?,
+        );
+
+        let pb = utils::progress_bar(builder.metadata().file_count as _);
+        let metadata = utils::watch_progress(&pb, |progress| builder.build(&mut writer, progress))
+            .context("failed to build the converted archive")?;
+
+        pb.finish_with_message(
+            "convert finished"
+                .if_supports_color(owo_colors::Stream::Stdout, |t| t.green())
+                .to_string(),
+        );
+
+        writer.flush().context("failed to flush writer")?;
+
+        utils::report_rebuild(
+            &output,
+            writer.get_ref().metadata().map_or(0, |m| m.len()),
+            metadata,
+            json!({
+                "from": format!("{from:?}"),
+                "to": format!("{to:?}"),
+            }),
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    convert,
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// output zip file, if empty a file with the same name of input hvp will be created
+    #[arg(value_hint = ValueHint::FilePath)]
+    pub output: Option<PathBuf>,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+
+        let output = self
+            .output
+            .unwrap_or_else(|| self.input.with_extension("zip"));
+
+        println!("{} output zip: {}", "[+]".green(), output.display());
+
+        let mut writer =
+            BufWriter::new(File::create(&output).context("failed to create output zip")?);
+        let report =
+            convert::hvp_to_zip(&archive, &mut writer).context("failed to convert to zip")?;
+        writer.flush().context("failed to flush writer")?;
+
+        println!(
+            "{} converted {} files, hvp specific fields are kept in {}",
+            "[+]".green(),
+            report.converted,
+            convert::MANIFEST_FILE
+        );
+
+        output::report(
+            "to_zip",
+            json!({ "output": output, "files": report.converted }),
+        );
+
+        Ok(())
+    }
+}
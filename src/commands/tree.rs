@@ -0,0 +1,176 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options, entry::Entry},
+    provider::ArchiveProvider,
+};
+use owo_colors::OwoColorize;
+use serde_json::json;
+
+use super::{
+    name_maps_for_game,
+    output::{self, println},
+    size, utils,
+};
+
+/// width of the size bars in characters
+const BAR_WIDTH: usize = 16;
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// only print the entries up to this depth, 1 only print the entries of the root.
+    /// the sizes of the directories still include the deeper files
+    #[arg(long, short = 'd')]
+    pub depth: Option<usize>,
+    /// print the uncompressed size of every entry, with a bar that show its share of the archive
+    #[arg(long, short = 's', default_value_t = false, required = false)]
+    pub sizes: bool,
+}
+
+/// a printed line of the tree
+struct Line {
+    path: PathBuf,
+    is_dir: bool,
+    branches: String,
+    name: String,
+    size: u64,
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        // cumulative size of every directory
+        let mut dir_sizes: ahash::HashMap<PathBuf, u64> = ahash::HashMap::default();
+        let mut total = 0;
+        for file in archive.files() {
+            let size = file.uncompressed_size() as u64;
+            total += size;
+            for dir in file.path.ancestors().skip(1) {
+                if !dir.as_os_str().is_empty() {
+                    *dir_sizes.entry(dir.to_path_buf()).or_default() += size;
+                }
+            }
+        }
+
+        // whatever the directories above the current entry were the last entry of their parent
+        let mut last_stack: Vec<bool> = Vec::new();
+        let mut lines = Vec::new();
+
+        for walked in archive.walk() {
+            last_stack.truncate(walked.depth);
+
+            if self.depth.is_none_or(|depth| walked.depth < depth) {
+                let mut branches: String = last_stack
+                    .iter()
+                    .map(|&last| if last { "    " } else { "│   " })
+                    .collect();
+                branches.push_str(if walked.is_last {
+                    "└── "
+                } else {
+                    "├── "
+                });
+
+                let (is_dir, name, size) = match walked.entry {
+                    Entry::File(file) => (
+                        false,
+                        file.name().to_owned(),
+                        file.uncompressed_size() as u64,
+                    ),
+                    Entry::Dir(dir) => (
+                        true,
+                        format!("{}/", dir.name),
+                        dir_sizes.get(&walked.path).copied().unwrap_or(0),
+                    ),
+                };
+
+                lines.push(Line {
+                    path: walked.path,
+                    is_dir,
+                    branches,
+                    name,
+                    size,
+                });
+            }
+
+            last_stack.push(walked.is_last);
+        }
+
+        let root = self.input.display().to_string();
+        let width = lines
+            .iter()
+            .map(|l| l.branches.chars().count() + l.name.chars().count())
+            .chain([root.chars().count()])
+            .max()
+            .unwrap_or(0);
+
+        let sizes = |len: usize, size: u64| match self.sizes {
+            true => format!(
+                "{}  {} {}",
+                " ".repeat(width - len),
+                bar(size, total).cyan(),
+                size::format(size)
+            ),
+            false => String::new(),
+        };
+
+        println!("{}{}", root.blue(), sizes(root.chars().count(), total));
+        for line in &lines {
+            let name = match line.is_dir {
+                true => line.name.blue().to_string(),
+                false => line.name.clone(),
+            };
+            let len = line.branches.chars().count() + line.name.chars().count();
+            println!("{}{name}{}", line.branches.dimmed(), sizes(len, line.size));
+        }
+
+        output::report(
+            "tree",
+            json!({
+                "total_size": total,
+                "entries": lines
+                    .iter()
+                    .map(|l| json!({
+                        "path": super::manifest::path_str(&l.path),
+                        "dir": l.is_dir,
+                        "size": l.size,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+        );
+
+        Ok(())
+    }
+}
+
+/// a bar of unicode blocks that show the share of `size` from `total`
+fn bar(size: u64, total: u64) -> String {
+    const PARTIAL: [char; 8] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉'];
+
+    let eighths = match total {
+        0 => 0,
+        _ => ((size as f64 / total as f64) * (BAR_WIDTH * 8) as f64).round() as usize,
+    };
+
+    let mut bar = "█".repeat(eighths / 8);
+    if eighths % 8 != 0 {
+        bar.push(PARTIAL[eighths % 8]);
+    }
+
+    let len = bar.chars().count();
+    bar + &" ".repeat(BAR_WIDTH.saturating_sub(len))
+}
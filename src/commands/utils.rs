@@ -1,8 +1,29 @@
-use std::path::{Path, PathBuf};
+use std::{
+    ffi::OsStr,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    time::Duration,
+};
 
-use anstream::println;
-use hvp_archive::archive::Metadata;
+use anyhow::Context;
+use hvp_archive::archive::{
+    Archive, Metadata, ORPHANS_DIR, OrphanMode,
+    compression_rules::CompressionRules,
+    rebuild_progress::{self, RebuildProgress, Watched},
+};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressState};
 use owo_colors::OwoColorize;
+use serde_json::{Value, json};
+
+use super::{
+    ChecksumValidation,
+    output::{self, println},
+    prompt, size,
+};
 
 pub fn is_file(path: &str) -> Result<PathBuf, String> {
     let path = Path::new(path);
@@ -20,6 +41,29 @@ pub fn is_dir(path: &str) -> Result<PathBuf, String> {
     Ok(path.to_path_buf())
 }
 
+/// path of the new archive when no output is given, `name.new.ext` next to the input
+pub fn default_output(input: &Path) -> PathBuf {
+    input.with_extension(
+        input
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(|e| format!("new.{e}"))
+            .unwrap_or("new".to_owned()),
+    )
+}
+
+pub fn is_dir_or_zip(path: &str) -> Result<PathBuf, String> {
+    let path = Path::new(path);
+    let is_zip = path.is_file()
+        && path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("zip"));
+    if !path.is_dir() && !is_zip {
+        return Err("You need to pass a valid dir or zip file path.".to_owned());
+    }
+    Ok(path.to_path_buf())
+}
+
 pub fn list_files(input: &Path, without_base: bool) -> Vec<PathBuf> {
     walkdir::WalkDir::new(input)
         .into_iter()
@@ -40,22 +84,129 @@ pub fn list_files(input: &Path, without_base: bool) -> Vec<PathBuf> {
 
 /// print the archive metadata to stdout
 pub fn print_metadata(metadata: Metadata) {
+    output::report(
+        "metadata",
+        json!({
+            "game": format!("{:?}", metadata.game),
+            "dir_count": metadata.dir_count,
+            "file_count": metadata.file_count,
+            "toc_size": metadata.toc_size,
+            "data_size": metadata.data_size,
+            "archive_len": metadata.archive_len,
+            "endianness": format!("{:?}", metadata.endianness),
+            "has_checksums": metadata.has_checksums,
+        }),
+    );
+
     println!(
         concat!(
             "{} loaded archive metadata:\n",
-            " {dot} game: {:?}\n",
+            " {dot} game: {:?} ({:?} endian)\n",
             " {dot} dir count: {}\n",
-            " {dot} file count: {}",
+            " {dot} file count: {}\n",
+            " {dot} checksums: {}",
         ),
         "[?]".green(),
         metadata.game,
+        metadata.endianness,
         metadata.dir_count,
         metadata.file_count,
+        if metadata.has_checksums { "yes" } else { "no" },
         dot = "|>".cyan(),
-    )
+    );
+
+    // sizes of a new archive are only known after building it
+    if metadata.archive_len != 0 {
+        println!(
+            " {} size: {} (toc: {}, data: {})",
+            "|>".cyan(),
+            size::format(metadata.archive_len),
+            size::format(metadata.toc_size),
+            size::format(metadata.data_size),
+        );
+    }
+}
+
+/// read a compression rules file, every line is `stored <dir>` or `compressed <dir>` and
+/// lines that start with `#` are ignored
+pub fn load_compression_rules(path: &Path) -> anyhow::Result<CompressionRules> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+
+    let mut rules = CompressionRules::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (compress, dir) = match line.split_once(char::is_whitespace) {
+            Some(("stored", dir)) => (false, dir),
+            Some(("compressed", dir)) => (true, dir),
+            _ => anyhow::bail!(
+                "{}:{}: expected `stored <dir>` or `compressed <dir>`",
+                path.display(),
+                i + 1
+            ),
+        };
+        rules.set(dir.trim().trim_matches('/'), compress);
+    }
+
+    Ok(rules)
 }
 
+/// add the result of a rebuild to the json report, fields of `details` are added to it
+pub fn report_rebuild(output: &Path, size: u64, metadata: Metadata, details: Value) {
+    let mut report = json!({
+        "output": output,
+        "size": size,
+        "dir_count": metadata.dir_count,
+        "file_count": metadata.file_count,
+    });
+
+    if let (Some(report), Value::Object(details)) = (report.as_object_mut(), details) {
+        report.extend(details);
+    }
+
+    output::report("rebuild", report);
+}
+
+pub fn print_orphans(archive: &Archive) {
+    let orphans = archive.orphans();
+    if orphans.is_empty() {
+        return;
+    }
+
+    output::report("orphans", json!(orphans));
+
+    println!(
+        "{} found {} entries that aren't reachable from the root directory: {:?}",
+        "[!]".yellow(),
+        orphans.len(),
+        orphans,
+    );
+
+    if archive.options.orphans == OrphanMode::Attach {
+        println!(
+            " {} orphan entries are attached under {ORPHANS_DIR}",
+            "|>".cyan()
+        );
+    }
+}
+
+/// whatever the progress is printed as json lines instead of a progress bar
+static PROGRESS_JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_progress_json(json: bool) {
+    PROGRESS_JSON.store(json, Ordering::Relaxed);
+}
+
+/// progress bar with `len` steps, it's hidden when the progress is printed as json lines
 pub fn progress_bar(len: u64) -> indicatif::ProgressBar {
+    if PROGRESS_JSON.load(Ordering::Relaxed) {
+        return ProgressBar::with_draw_target(Some(len), ProgressDrawTarget::hidden());
+    }
+
     indicatif::ProgressBar::new(len)
         .with_style(
             indicatif::ProgressStyle::with_template(
@@ -71,14 +222,196 @@ pub fn progress_bar(len: u64) -> indicatif::ProgressBar {
         )
 }
 
-pub fn prompt() -> anyhow::Result<String> {
-    use std::io::BufRead;
+/// style of a progress bar that count bytes, with the number of files that are done next to them,
+/// the throughput and the remaining time
+fn bytes_style(files: Arc<AtomicU64>, total_files: Option<u64>) -> indicatif::ProgressStyle {
+    indicatif::ProgressStyle::with_template(
+        "{prefix} [{elapsed_precise}] [{bar:40.cyan/blue}] [{files}] [{bytes}/{total_bytes}] {binary_bytes_per_sec} eta {eta} {msg}",
+    )
+    .unwrap()
+    .with_key("files", move |_: &ProgressState, w: &mut dyn std::fmt::Write| {
+        let files = files.load(Ordering::Relaxed);
+        let _ = match total_files {
+            Some(total) => write!(w, "{files:>4}/{total:4}"),
+            None => write!(w, "{files:>4}"),
+        };
+    })
+    .progress_chars("=> ")
+}
+
+/// check the checksum of every file, based on the validation mode fail or ask the user on mismatch
+pub fn validate_checksums(archive: &Archive, validation: ChecksumValidation) -> anyhow::Result<()> {
+    if validation == ChecksumValidation::No {
+        return Ok(());
+    }
+
+    println!("{} validating entries checksum", "[+]".green());
+    if archive.entries_checksum_match() {
+        return Ok(());
+    }
+
+    if validation == ChecksumValidation::Prompt
+        && prompt::confirm("checksum mismatch, continue anyway?")?
+    {
+        return Ok(());
+    }
+
+    anyhow::bail!("archive entries checksum doesn't match, maybe the archive is broken?")
+}
+
+/// show the progress of rebuilding an archive using a progress bar,
+/// or as json lines on stderr with `--progress json`
+pub struct RebuildProgressCli {
+    pb: ProgressBar,
+    json: bool,
+    /// shared with the bar, that show it next to the bytes
+    files: Arc<AtomicU64>,
+    total_files: Option<u64>,
+    bytes: AtomicU64,
+    /// set once the total is known, the bar count bytes instead of files from then
+    total_bytes: OnceLock<u64>,
+}
+
+impl RebuildProgressCli {
+    pub fn new(pb: ProgressBar, total_bytes: Option<u64>) -> Self {
+        let progress = Self {
+            json: PROGRESS_JSON.load(Ordering::Relaxed),
+            files: Arc::new(AtomicU64::new(0)),
+            total_files: pb.length(),
+            bytes: AtomicU64::new(0),
+            total_bytes: OnceLock::new(),
+            pb,
+        };
+        if let Some(total_bytes) = total_bytes {
+            progress.count_bytes(total_bytes);
+        }
+        progress
+    }
+
+    /// switch the bar to bytes, so a few big files don't make the remaining time useless
+    fn count_bytes(&self, total_bytes: u64) {
+        if self.total_bytes.set(total_bytes).is_err() {
+            return;
+        }
+
+        self.pb
+            .set_style(bytes_style(self.files.clone(), self.total_files));
+        self.pb.set_length(total_bytes);
+        self.pb.set_position(self.bytes.load(Ordering::Relaxed));
+    }
+
+    fn inc_files(&self, n: u64) {
+        self.files.fetch_add(n, Ordering::Relaxed);
+        if self.total_bytes.get().is_none() {
+            self.pb.inc(n);
+        }
+    }
+
+    /// print a progress event as a single json line to stderr
+    fn event(&self, event: &str, extra: Value) {
+        if !self.json {
+            return;
+        }
+
+        let mut value = json!({
+            "event": event,
+            "files_done": self.files.load(Ordering::Relaxed),
+            "total_files": self.total_files,
+            "bytes_done": self.bytes.load(Ordering::Relaxed),
+            "total_bytes": self.total_bytes.get(),
+        });
+        if let (Some(value), Value::Object(extra)) = (value.as_object_mut(), extra) {
+            value.extend(extra);
+        }
+
+        // a single write per line, so lines of different threads aren't mixed
+        let line = format!("{value}\n");
+        let _ = std::io::stderr().lock().write_all(line.as_bytes());
+    }
+
+    fn progressed(&self, message: Option<String>) {
+        if self.json {
+            self.event("file", json!({ "file": message }));
+        } else if let Some(msg) = message {
+            self.pb.set_message(msg);
+        }
+    }
+}
+
+impl RebuildProgress for RebuildProgressCli {
+    fn start(&self, total_bytes: Option<u64>) {
+        if let Some(total_bytes) = total_bytes {
+            self.count_bytes(total_bytes);
+        }
+    }
+
+    fn inc(&self, message: Option<String>) {
+        self.inc_files(1);
+        self.progressed(message);
+    }
+
+    fn inc_n(&self, n: usize, message: Option<String>) {
+        self.inc_files(n as _);
+        self.progressed(message);
+    }
+
+    fn stalled(&self, message: Option<&str>, elapsed: Duration) {
+        if self.json {
+            self.event(
+                "stalled",
+                json!({ "file": message, "elapsed_secs": elapsed.as_secs() }),
+            );
+            return;
+        }
+
+        self.pb.suspend(|| {
+            println!(
+                "{} no progress for {}s, still working on {}",
+                "[!]".yellow(),
+                elapsed.as_secs(),
+                message.unwrap_or("the first file")
+            )
+        });
+    }
+
+    fn written(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        if self.total_bytes.get().is_some() {
+            self.pb.inc(bytes);
+        }
+    }
+}
+
+/// seconds without any progress before reporting a stall, zero to disable it
+static STALL_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+pub fn set_stall_timeout(seconds: u64) {
+    STALL_TIMEOUT.store(seconds, Ordering::Relaxed);
+}
+
+/// run `work` with a progress that report a stall when nothing progressed for `--stall-timeout` seconds
+pub fn watch_progress<R>(
+    pb: &ProgressBar,
+    work: impl FnOnce(&Watched<&RebuildProgressCli>) -> R,
+) -> R {
+    watch_progress_with_bytes(pb, None, work)
+}
 
-    let stdin = std::io::stdin();
-    let mut stdin = stdin.lock();
+/// same as [`watch_progress`], with the total number of bytes that will be written when it's known
+pub fn watch_progress_with_bytes<R>(
+    pb: &ProgressBar,
+    total_bytes: Option<u64>,
+    work: impl FnOnce(&Watched<&RebuildProgressCli>) -> R,
+) -> R {
+    let timeout = match STALL_TIMEOUT.load(Ordering::Relaxed) {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    };
 
-    let mut line = String::new();
-    stdin.read_line(&mut line)?;
+    let progress = RebuildProgressCli::new(pb.clone(), total_bytes);
+    progress.event("start", json!({}));
+    let result = rebuild_progress::watch(&progress, timeout, work);
+    progress.event("finish", json!({}));
 
-    Ok(line.trim().to_owned())
+    result
 }
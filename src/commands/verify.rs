@@ -0,0 +1,306 @@
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, ValueHint};
+use hvp_archive::{
+    archive::{Archive, Options},
+    provider::ArchiveProvider,
+};
+use indicatif::ParallelProgressIterator;
+use owo_colors::OwoColorize;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde_json::json;
+
+use super::{
+    is_sidecar, name_maps_for_game,
+    output::{self, println},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// path to input hvp archive
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub input: PathBuf,
+    /// also audit the structure: compression flags, sizes and directory coverage of the entry table
+    #[arg(long, default_value_t = false, required = false)]
+    pub cross: bool,
+    /// also decompress every file and print the checksum of its content, the stored checksum only cover the stored (maybe compressed) bytes
+    #[arg(long, default_value_t = false, required = false)]
+    pub content: bool,
+    /// also decompress every file and compare it with the file at the same path in this folder,
+    /// for example one that was extracted by another tool
+    #[arg(long, value_hint = ValueHint::DirPath, value_parser = utils::is_dir)]
+    pub against: Option<PathBuf>,
+}
+
+/// how the files of the archive differ from the files of a folder
+#[derive(Default)]
+struct Against {
+    /// files of the archive that aren't in the folder
+    missing: Vec<PathBuf>,
+    /// files whose content isn't the same
+    different: Vec<PathBuf>,
+    /// files of the archive that can't be decompressed
+    failed: Vec<PathBuf>,
+    /// files of the folder that aren't in the archive
+    extra: Vec<PathBuf>,
+}
+
+/// result of comparing a single file
+enum Comparison {
+    Same,
+    Missing,
+    Different,
+    Failed,
+}
+
+impl Against {
+    fn compare(archive: &Archive, folder: &Path) -> Self {
+        let files: Vec<_> = archive.files().collect();
+        let pb = utils::progress_bar(files.len() as _);
+
+        let mut results: Vec<_> = files
+            .into_par_iter()
+            .progress_with(pb.clone())
+            .map(|file| {
+                let comparison = match (file.get_bytes(), std::fs::read(folder.join(&file.path))) {
+                    (Err(_), _) => Comparison::Failed,
+                    (_, Err(_)) => Comparison::Missing,
+                    (Ok(bytes), Ok(on_disk)) if *bytes == *on_disk => Comparison::Same,
+                    _ => Comparison::Different,
+                };
+                (file.path, comparison)
+            })
+            .collect();
+        pb.finish_and_clear();
+
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut against = Self::default();
+        let known: ahash::HashSet<_> = results.iter().map(|(path, _)| path.clone()).collect();
+        for (path, comparison) in results {
+            match comparison {
+                Comparison::Same => {}
+                Comparison::Missing => against.missing.push(path),
+                Comparison::Different => against.different.push(path),
+                Comparison::Failed => against.failed.push(path),
+            }
+        }
+
+        against.extra = utils::list_files(folder, true)
+            .into_iter()
+            .filter(|path| !is_sidecar(path) && !known.contains(path))
+            .collect();
+        against.extra.sort();
+
+        against
+    }
+
+    /// files that make the verification fail, extra files don't
+    fn failures(&self) -> usize {
+        self.missing.len() + self.different.len() + self.failed.len()
+    }
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self, provider: ArchiveProvider) -> anyhow::Result<()> {
+        let obscure2_names = name_maps_for_game(provider.game())?;
+
+        let archive = Archive::new_with_options(
+            &provider,
+            Options {
+                obscure2_names,
+                ..Default::default()
+            },
+        );
+
+        utils::print_metadata(archive.metadata());
+        utils::print_orphans(&archive);
+
+        println!("{} verifying archive checksums", "[+]".green());
+
+        let (report, issues, anomalies) = match self.cross {
+            true => {
+                let report = archive.cross_check();
+                (report.verify, Some(report.issues), Some(report.anomalies))
+            }
+            false => (archive.verify(), None, None),
+        };
+
+        if report.header_crcs.is_empty() {
+            println!("{} archive doesn't have any header crc32", "[?]".green());
+        }
+
+        for crc in &report.header_crcs {
+            let status = if crc.is_match() {
+                "ok".green().to_string()
+            } else {
+                "mismatch".red().to_string()
+            };
+
+            println!(
+                " {} {} crc32: stored {:#010x}, computed {:#010x} [{status}]",
+                "|>".cyan(),
+                crc.name,
+                crc.stored,
+                crc.computed,
+            );
+        }
+
+        for mismatch in &report.mismatches {
+            println!(
+                "{} {}: stored checksum {:#010x}, computed from stored bytes {:#010x}",
+                "[!]".red(),
+                mismatch.path.display(),
+                mismatch.expected,
+                mismatch.computed,
+            );
+        }
+
+        println!(
+            "{} checked {} files, {} with invalid checksum",
+            "[+]".green(),
+            report.files_checked,
+            report.mismatches.len(),
+        );
+
+        let content = self.content.then(|| {
+            println!(
+                "{} computing checksums of decompressed content",
+                "[+]".green()
+            );
+            archive.content_checksums()
+        });
+
+        for checksum in content.iter().flatten() {
+            match checksum.content {
+                Some(content) => println!(
+                    " {} {}: stored checksum {:#010x}, content checksum {content:#010x}",
+                    "|>".cyan(),
+                    checksum.path.display(),
+                    checksum.stored,
+                ),
+                None => println!(
+                    "{} {}: can't be decompressed",
+                    "[!]".red(),
+                    checksum.path.display()
+                ),
+            }
+        }
+
+        let not_decompressable = content
+            .iter()
+            .flatten()
+            .filter(|c| c.content.is_none())
+            .count();
+
+        if content.is_some() {
+            println!(
+                "{} computed content checksums, {not_decompressable} files can't be decompressed",
+                "[+]".green(),
+            );
+        }
+
+        let against = self.against.as_ref().map(|folder| {
+            println!(
+                "{} comparing the files with {}",
+                "[+]".green(),
+                folder.display()
+            );
+            Against::compare(&archive, folder)
+        });
+
+        if let Some(against) = &against {
+            for (paths, reason) in [
+                (&against.missing, "missing from the folder"),
+                (&against.different, "content is different"),
+                (&against.failed, "can't be decompressed"),
+            ] {
+                for path in paths {
+                    println!("{} {}: {reason}", "[!]".red(), path.display());
+                }
+            }
+
+            for path in &against.extra {
+                println!("{} {}: not in the archive", "[?]".yellow(), path.display());
+            }
+
+            println!(
+                "{} compared files with the folder, {} missing, {} different and {} extra",
+                "[+]".green(),
+                against.missing.len(),
+                against.different.len(),
+                against.extra.len(),
+            );
+        }
+
+        if let Some(issues) = &issues {
+            for issue in issues {
+                println!("{} {issue}", "[!]".red());
+            }
+
+            println!(
+                "{} audited archive structure, found {} issues",
+                "[+]".green(),
+                issues.len(),
+            );
+        }
+
+        // anomalies are only unusual, so they don't fail the verification
+        for anomaly in anomalies.iter().flatten() {
+            println!("{} {anomaly}", "[?]".yellow());
+        }
+
+        output::report(
+            "verify",
+            json!({
+                "header_crcs": report.header_crcs.iter().map(|crc| json!({
+                    "name": crc.name,
+                    "stored": crc.stored,
+                    "computed": crc.computed,
+                    "match": crc.is_match(),
+                })).collect::<Vec<_>>(),
+                "files_checked": report.files_checked,
+                "mismatches": report.mismatches.iter().map(|m| json!({
+                    "path": m.path,
+                    "stored_checksum": m.expected,
+                    "computed_stored_checksum": m.computed,
+                })).collect::<Vec<_>>(),
+                "content": content.as_ref().map(|content| {
+                    content.iter().map(|c| json!({
+                        "path": c.path,
+                        "stored_checksum": c.stored,
+                        "content_checksum": c.content,
+                    })).collect::<Vec<_>>()
+                }),
+                "issues": issues.as_ref().map(|issues| {
+                    issues.iter().map(ToString::to_string).collect::<Vec<_>>()
+                }),
+                "anomalies": anomalies.as_ref().map(|anomalies| {
+                    anomalies.iter().map(ToString::to_string).collect::<Vec<_>>()
+                }),
+                "against": against.as_ref().map(|against| json!({
+                    "folder": self.against,
+                    "missing": against.missing,
+                    "different": against.different,
+                    "failed": against.failed,
+                    "extra": against.extra,
+                })),
+            }),
+        );
+
+        if !report.is_ok()
+            || issues.is_some_and(|i| !i.is_empty())
+            || not_decompressable != 0
+            || against.is_some_and(|a| a.failures() != 0)
+        {
+            anyhow::bail!("archive verification failed, maybe the archive is broken?");
+        }
+
+        println!("{} archive verified successfully", "[+]".green());
+
+        Ok(())
+    }
+}
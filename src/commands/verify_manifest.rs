@@ -0,0 +1,132 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::{Parser, ValueHint};
+use owo_colors::OwoColorize;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde_json::json;
+
+use super::{
+    is_sidecar,
+    manifest::path_str,
+    output::{self, println},
+    sha256_manifest::{FileDigest, Sha256Manifest, file_digest},
+    utils,
+};
+
+#[derive(Parser)]
+#[command(arg_required_else_help = true)]
+pub struct Commands {
+    /// manifest that is written by `sha256-manifest`
+    #[arg(value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub manifest: PathBuf,
+    /// folder of the extracted files
+    #[arg(value_hint = ValueHint::DirPath, value_parser = utils::is_dir)]
+    pub folder: PathBuf,
+    /// also check the hvp archive itself
+    #[arg(long, value_hint = ValueHint::FilePath, value_parser = utils::is_file)]
+    pub archive: Option<PathBuf>,
+}
+
+/// why a file doesn't match the manifest
+enum Mismatch {
+    Missing,
+    Size(u64),
+    Sha256,
+}
+
+impl Mismatch {
+    fn check(expected: &FileDigest, actual: std::io::Result<FileDigest>) -> Option<Self> {
+        match actual {
+            Err(_) => Some(Self::Missing),
+            Ok(actual) if actual.size != expected.size => Some(Self::Size(actual.size)),
+            Ok(actual) if actual.sha256 != expected.sha256 => Some(Self::Sha256),
+            Ok(_) => None,
+        }
+    }
+
+    fn describe(&self, expected: &FileDigest) -> String {
+        match self {
+            Self::Missing => "missing".to_owned(),
+            Self::Size(size) => format!("size is {size}, expected {}", expected.size),
+            Self::Sha256 => "sha256 doesn't match".to_owned(),
+        }
+    }
+}
+
+impl Commands {
+    /// handle the user command
+    pub fn start(self) -> anyhow::Result<()> {
+        let manifest = std::fs::read(&self.manifest)
+            .context("failed to read manifest")
+            .and_then(|bytes| Sha256Manifest::from_json(&bytes))
+            .with_context(|| format!("failed to parse {}", self.manifest.display()))?;
+
+        println!(
+            "{} checking {} files of {} in {}",
+            "[+]".green(),
+            manifest.files.len(),
+            manifest.archive.0,
+            self.folder.display()
+        );
+
+        let mut failed = Vec::new();
+
+        if let Some(archive) = &self.archive {
+            let expected = &manifest.archive.1;
+            if let Some(mismatch) = Mismatch::check(expected, file_digest(archive)) {
+                let reason = mismatch.describe(expected);
+                println!("{} {}: {reason}", "[!]".yellow(), archive.display());
+                failed.push(json!({ "path": archive, "reason": reason }));
+            }
+        }
+
+        let pb = utils::progress_bar(manifest.files.len() as _);
+        let mut mismatches: Vec<_> = manifest
+            .files
+            .par_iter()
+            .filter_map(|(path, expected)| {
+                let mismatch = Mismatch::check(expected, file_digest(&self.folder.join(path)));
+                pb.inc(1);
+                mismatch.map(|mismatch| (path, mismatch.describe(expected)))
+            })
+            .collect();
+        pb.finish_and_clear();
+
+        mismatches.sort();
+        for (path, reason) in mismatches {
+            println!("{} {path}: {reason}", "[!]".yellow());
+            failed.push(json!({ "path": path, "reason": reason }));
+        }
+
+        // files that aren't in the manifest don't fail the check, a release may ship more
+        let known: ahash::HashSet<&str> = manifest.files.iter().map(|(p, _)| p.as_str()).collect();
+        let mut extra: Vec<_> = utils::list_files(&self.folder, true)
+            .into_iter()
+            .filter(|p| !is_sidecar(p))
+            .map(|p| path_str(&p))
+            .filter(|p| !known.contains(p.as_str()))
+            .collect();
+        extra.sort();
+        for path in &extra {
+            println!("{} {path}: not in the manifest", "[?]".cyan());
+        }
+
+        output::report(
+            "verify_manifest",
+            json!({
+                "files": manifest.files.len(),
+                "failed": failed,
+                "extra": extra,
+            }),
+        );
+
+        match failed.len() {
+            0 => {
+                println!("{} everything match the manifest", "[+]".green());
+                Ok(())
+            }
+            n => anyhow::bail!("{n} files don't match the manifest"),
+        }
+    }
+}
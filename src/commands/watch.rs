@@ -0,0 +1,123 @@
+//! a watcher for the files that `create --watch` read, over the file system events of `notify`
+
+use std::{
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+use notify::{
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    event::{MetadataKind, ModifyKind},
+};
+use owo_colors::OwoColorize;
+
+use super::output::println;
+
+/// how long the files should stay unchanged before the changes are reported, editors that write
+/// in several steps only trigger once
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+pub struct FolderWatcher {
+    root: PathBuf,
+    ignore: Vec<PathBuf>,
+    /// files outside of the folder that are watched too, see [`FolderWatcher::watch_files`]
+    files: ahash::HashSet<PathBuf>,
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl FolderWatcher {
+    /// start watching the folder, changes to the `ignore` files (relative to the folder) are ignored
+    pub fn new(root: PathBuf, ignore: Vec<PathBuf>) -> notify::Result<Self> {
+        // some platforms report the events with the canonical paths
+        let root = root.canonicalize().unwrap_or(root);
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&root, RecursiveMode::Recursive)?;
+
+        Ok(Self {
+            root,
+            ignore,
+            files: ahash::HashSet::default(),
+            watcher,
+            events,
+        })
+    }
+
+    /// also watch these files, like the sources of the deduplicated files that are outside of
+    /// the folder. their directories are watched, so files that are replaced are still seen
+    pub fn watch_files(&mut self, files: impl IntoIterator<Item = PathBuf>) -> notify::Result<()> {
+        for file in files {
+            let file = file.canonicalize().unwrap_or(file);
+            if file.starts_with(&self.root) || self.files.contains(&file) {
+                continue;
+            }
+
+            if let Some(parent) = file.parent()
+                && !self.files.iter().any(|f| f.parent() == Some(parent))
+            {
+                self.watcher.watch(parent, RecursiveMode::NonRecursive)?;
+            }
+            self.files.insert(file);
+        }
+
+        Ok(())
+    }
+
+    /// block until files are added, removed or modified and then stay unchanged for a moment.
+    /// return the changed files, relative to the folder if they are inside it
+    pub fn wait_for_change(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+
+        // wait for the first change, then for the files to stay unchanged
+        while changed.is_empty() {
+            let Ok(event) = self.events.recv() else {
+                return changed;
+            };
+            self.add_changed(event, &mut changed);
+        }
+        while let Ok(event) = self.events.recv_timeout(DEBOUNCE) {
+            self.add_changed(event, &mut changed);
+        }
+
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+
+    fn add_changed(&self, event: notify::Result<Event>, changed: &mut Vec<PathBuf>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                println!("{} failed to watch for changes: {e}", "[!]".yellow());
+                return;
+            }
+        };
+
+        // the rebuild read every file, only the events that change them count
+        match event.kind {
+            EventKind::Access(_)
+            | EventKind::Modify(ModifyKind::Metadata(MetadataKind::AccessTime))
+            | EventKind::Other => return,
+            _ => (),
+        }
+
+        changed.extend(event.paths.into_iter().filter_map(|p| self.relevant(p)));
+    }
+
+    /// path of the changed file if it's one of the watched files, directories are only
+    /// watched for the files inside them
+    fn relevant(&self, path: PathBuf) -> Option<PathBuf> {
+        if path.is_dir() {
+            return None;
+        }
+
+        match path.strip_prefix(&self.root) {
+            Ok(relative) => {
+                (!self.ignore.iter().any(|p| p == relative)).then(|| relative.to_path_buf())
+            }
+            Err(_) => self.files.contains(&path).then_some(path),
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! a minimal yaml writer for json values, enough for dumping the archive entries
+
+use std::io::{self, Write};
+
+use serde_json::Value;
+
+/// write the value as a yaml document
+pub fn to_writer<W: Write>(writer: &mut W, value: &Value) -> io::Result<()> {
+    match value {
+        Value::Array(items) if !items.is_empty() => write_seq(writer, items, 0),
+        Value::Object(map) if !map.is_empty() => write_map(writer, map, 0),
+        scalar => writeln!(writer, "{}", to_scalar(scalar)),
+    }
+}
+
+fn write_seq<W: Write>(writer: &mut W, items: &[Value], indent: usize) -> io::Result<()> {
+    for item in items {
+        write!(writer, "{:indent$}-", "")?;
+        write_nested(writer, item, indent + 2, true)?;
+    }
+    Ok(())
+}
+
+fn write_map<W: Write>(
+    writer: &mut W,
+    map: &serde_json::Map<String, Value>,
+    indent: usize,
+) -> io::Result<()> {
+    for (key, value) in map {
+        write!(writer, "{:indent$}{}:", "", to_string(key))?;
+        write_nested(writer, value, indent + 2, false)?;
+    }
+    Ok(())
+}
+
+/// write a value that come after `- ` or `key:`
+fn write_nested<W: Write>(
+    writer: &mut W,
+    value: &Value,
+    indent: usize,
+    in_seq: bool,
+) -> io::Result<()> {
+    match value {
+        Value::Array(items) if !items.is_empty() => {
+            writeln!(writer)?;
+            // sequences inside a mapping don't need extra indentation
+            let indent = if in_seq { indent } else { indent - 2 };
+            write_seq(writer, items, indent)
+        }
+        Value::Object(map) if !map.is_empty() && in_seq => {
+            // the first key is written on the same line of the dash
+            let mut iter = map.iter();
+            let (key, first) = iter.next().expect("map isn't empty");
+            write!(writer, " {}:", to_string(key))?;
+            write_nested(writer, first, indent + 2, false)?;
+            for (key, value) in iter {
+                write!(writer, "{:indent$}{}:", "", to_string(key))?;
+                write_nested(writer, value, indent + 2, false)?;
+            }
+            Ok(())
+        }
+        Value::Object(map) if !map.is_empty() => {
+            writeln!(writer)?;
+            write_map(writer, map, indent)
+        }
+        scalar => writeln!(writer, " {}", to_scalar(scalar)),
+    }
+}
+
+fn to_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_owned(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => to_string(s),
+        Value::Array(_) => "[]".to_owned(),
+        Value::Object(_) => "{}".to_owned(),
+    }
+}
+
+/// strings are written as is when they can't be confused with other types, otherwise quoted
+fn to_string(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+        && !s.starts_with(['-', '.'])
+        // numbers like `0x10`, `1_000` or dates like `2008-10-01` are read as other types
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.parse::<f64>().is_err()
+        && !matches!(
+            s.to_ascii_lowercase().as_str(),
+            "null" | "true" | "false" | "yes" | "no" | "on" | "off" | "y" | "n" | "~"
+        );
+
+    match plain {
+        true => s.to_owned(),
+        // json strings are valid yaml double quoted strings
+        false => Value::String(s.to_owned()).to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn yaml(value: &Value) -> String {
+        let mut output = Vec::new();
+        to_writer(&mut output, value).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(yaml(&json!(null)), "null\n");
+        assert_eq!(yaml(&json!(true)), "true\n");
+        assert_eq!(yaml(&json!(-12)), "-12\n");
+        assert_eq!(yaml(&json!(1.5)), "1.5\n");
+        assert_eq!(yaml(&json!("menu.tga")), "menu.tga\n");
+        assert_eq!(yaml(&json!([])), "[]\n");
+        assert_eq!(yaml(&json!({})), "{}\n");
+    }
+
+    #[test]
+    fn ambiguous_strings_are_quoted() {
+        for s in [
+            "",
+            "null",
+            "True",
+            "no",
+            "Y",
+            "~",
+            "12",
+            "1e3",
+            "0x10",
+            ".5",
+            "-a",
+            "a b",
+            "a: b",
+            "#c",
+            "é",
+            "a\"b",
+            "0x10",
+            "0o17",
+            "1_000",
+            "2008-10-01",
+            "inf",
+        ] {
+            let quoted = yaml(&json!(s));
+            assert!(quoted.starts_with('"'), "{s:?} isn't quoted: {quoted}");
+        }
+        assert_eq!(yaml(&json!("a\"b\n")), "\"a\\\"b\\n\"\n");
+        assert_eq!(yaml(&json!("sound/music_01.ogg")), "sound/music_01.ogg\n");
+    }
+
+    #[test]
+    fn nested_values() {
+        let value = json!({
+            "game": "Obscure2",
+            "entries": [
+                { "name": "sound", "entries": [
+                    { "name": "intro.ogg", "size": 120 },
+                ]},
+                { "name": "empty", "entries": [] },
+                "loose",
+                [1, 2],
+            ],
+            "meta": { "crc32": 12, "tags": ["a", "b"] },
+        });
+
+        assert_eq!(
+            yaml(&value),
+            concat!(
+                "entries:\n",
+                "- entries:\n",
+                "  - name: intro.ogg\n",
+                "    size: 120\n",
+                "  name: sound\n",
+                "- entries: []\n",
+                "  name: empty\n",
+                "- loose\n",
+                "-\n",
+                "  - 1\n",
+                "  - 2\n",
+                "game: Obscure2\n",
+                "meta:\n",
+                "  crc32: 12\n",
+                "  tags:\n",
+                "  - a\n",
+                "  - b\n",
+            )
+        );
+    }
+}
@@ -1,55 +1,180 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
-use commands::{Commands, Game, Operation, create, extract};
+use commands::{
+    Commands, Game, Operation, create,
+    extract::{self, OnError},
+};
 
-use crate::commands::ChecksumValidation;
+use owo_colors::OwoColorize;
+
+use crate::commands::{ChecksumValidation, Orphans, Progress, prompt};
 
 mod commands;
+mod memory;
+
+#[global_allocator]
+static ALLOC: memory::CountingAlloc = memory::CountingAlloc;
 
 fn main() -> anyhow::Result<()> {
     let cmd = match commands::Commands::try_parse() {
+        // plugins are external commands too, but a plugin name is never a path that exist
+        Ok(Commands {
+            operation: Operation::External(args),
+            ..
+        }) if args.first().is_some_and(|arg| Path::new(arg).exists()) => {
+            return drag_and_drop();
+        }
         Ok(cmd) => cmd,
-        Err(e) => {
-            // a simple hack to allow drag and droping files to the program
-
-            let mut hvp = None;
-            let mut folder = None;
-
-            for arg in std::env::args().skip(1) {
-                let path = Path::new(&arg);
-                if arg.to_lowercase().ends_with(".hvp") && path.is_file() {
-                    hvp = Some(path.to_path_buf());
-                } else if path.is_dir() {
-                    folder = Some(path.to_path_buf());
-                }
-            }
+        Err(e) => match drag_and_drop_paths() {
+            Some(_) => return drag_and_drop(),
+            None => e.exit(),
+        },
+    };
+
+    cmd.start()
+}
+
+/// the archives and the folder that are passed to the program, `None` if there isn't any archive
+fn drag_and_drop_paths() -> Option<(Vec<PathBuf>, Option<PathBuf>)> {
+    // a simple hack to allow drag and droping files to the program
+
+    let mut hvps = Vec::new();
+    let mut folder = None;
+
+    for arg in std::env::args().skip(1) {
+        // extract change the working directory, so relative paths would break the next archive
+        let Ok(path) = std::path::absolute(&arg) else {
+            continue;
+        };
+        if arg.to_lowercase().ends_with(".hvp") && path.is_file() {
+            hvps.push(path);
+        } else if path.is_dir() {
+            folder = Some(path);
+        }
+    }
+
+    (!hvps.is_empty()).then_some((hvps, folder))
+}
 
-            let Some(hvp) = hvp else { e.exit() };
-
-            let operation = match folder {
-                Some(input_folder) => Operation::Create(create::Commands {
-                    input_hvp: hvp,
-                    input_folder,
-                    output: None,
-                    skip_compression: false,
-                    checksum_validation: ChecksumValidation::Prompt,
-                    update_all_files: false,
-                    generate_anyway: false,
-                }),
-                None => Operation::Extract(extract::Commands {
-                    input: hvp,
-                    output_folder: None,
-                    checksum_validation: ChecksumValidation::Prompt,
-                }),
-            };
-
-            Commands {
-                operation,
-                game: Game::Auto,
+/// handle the archives and folder that are dropped on the program
+fn drag_and_drop() -> anyhow::Result<()> {
+    let Some((hvps, folder)) = drag_and_drop_paths() else {
+        anyhow::bail!("no hvp archive is given");
+    };
+
+    let operations: Vec<_> = match (folder, hvps.as_slice()) {
+        // a single archive and a folder can mean both, so we ask instead of guessing
+        (Some(folder), [hvp]) => {
+            let choice = prompt::choose(
+                &format!(
+                    "extract {} into {} or create it from the folder?",
+                    hvp.display(),
+                    folder.display()
+                ),
+                &["extract", "create"],
+                1,
+            )?;
+
+            match choice {
+                0 => vec![extract_operation(hvp.clone(), Some(folder))],
+                _ => vec![create_operation(hvp.clone(), folder)],
             }
         }
+        // only a single archive can be created from a folder, so every archive is extracted into it
+        (Some(folder), hvps) => hvps
+            .iter()
+            .map(|hvp| {
+                let stem = hvp.file_stem().unwrap_or(hvp.as_os_str());
+                extract_operation(hvp.clone(), Some(folder.join(stem)))
+            })
+            .collect(),
+        (None, hvps) => hvps
+            .iter()
+            .map(|hvp| extract_operation(hvp.clone(), None))
+            .collect(),
     };
 
-    cmd.start()
+    let count = operations.len();
+    let mut failed = 0;
+
+    for operation in operations {
+        let cmd = Commands {
+            operation,
+            game: Game::Auto,
+            max_memory: None,
+            timings: false,
+            json: false,
+            yes: false,
+            no: false,
+            stall_timeout: 60,
+            progress: Progress::Bar,
+            color: Default::default(),
+            no_mmap: false,
+        };
+
+        match cmd.start() {
+            Ok(()) => {}
+            // with a single archive the error is printed by the caller
+            Err(e) if count == 1 => return Err(e),
+            Err(e) => {
+                anstream::eprintln!("{} {e:#}", "[!]".red());
+                failed += 1;
+            }
+        }
+    }
+
+    if failed != 0 {
+        anyhow::bail!("failed to handle {failed} of {count} archives");
+    }
+
+    Ok(())
+}
+
+fn extract_operation(input: PathBuf, output_folder: Option<PathBuf>) -> Operation {
+    Operation::Extract(extract::Commands {
+        input,
+        output_folder,
+        checksum_validation: ChecksumValidation::Prompt,
+        include: Vec::new(),
+        exclude: Vec::new(),
+        dir: None,
+        strip_components: 0,
+        orphans: Orphans::Drop,
+        path_template: None,
+        hashes_format: Default::default(),
+        dedup_index: None,
+        raw: false,
+        on_error: OnError::Ask,
+        zip: None,
+        overwrite: Default::default(),
+        skip_existing: false,
+        postprocess: Vec::new(),
+    })
+}
+
+fn create_operation(input_hvp: PathBuf, input_folder: PathBuf) -> Operation {
+    Operation::Create(create::Commands {
+        input_hvp,
+        input_folder: Some(input_folder),
+        output: None,
+        skip_compression: false,
+        compression_level: None,
+        align: None,
+        checksum_validation: ChecksumValidation::Prompt,
+        update_all_files: false,
+        generate_anyway: false,
+        guarantee_roundtrip: false,
+        verify: false,
+        verify_updated: false,
+        input_zip: None,
+        from_scratch: false,
+        big_endian: false,
+        compression_rules: None,
+        dry_run: false,
+        max_size: None,
+        watch: false,
+        ignore_case: false,
+        entry: None,
+    })
 }
@@ -0,0 +1,105 @@
+//! memory accounting, used for the peak memory report and the thread limit of --max-memory
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicBool, AtomicIsize, Ordering},
+};
+
+use owo_colors::OwoColorize;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::commands::{output::println, size};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// signed, blocks that were allocated before the counting started can be freed after it
+static CURRENT: AtomicIsize = AtomicIsize::new(0);
+static PEAK: AtomicIsize = AtomicIsize::new(0);
+
+/// system allocator that keep track of the allocated bytes once [`enable`] is called
+pub struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        sub(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            sub(layout.size());
+            add(new_size);
+        }
+        new_ptr
+    }
+}
+
+#[inline]
+fn add(size: usize) {
+    if ENABLED.load(Ordering::Relaxed) {
+        let current = CURRENT.fetch_add(size as _, Ordering::Relaxed) + size as isize;
+        PEAK.fetch_max(current, Ordering::Relaxed);
+    }
+}
+
+#[inline]
+fn sub(size: usize) {
+    if ENABLED.load(Ordering::Relaxed) {
+        CURRENT.fetch_sub(size as _, Ordering::Relaxed);
+    }
+}
+
+/// start counting the allocated bytes, without it every allocation only cost a load
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// the highest number of bytes that was allocated at the same time since [`enable`] was called,
+/// memory mapped archives aren't counted
+pub fn peak() -> usize {
+    PEAK.load(Ordering::Relaxed).max(0) as _
+}
+
+/// a thread pool that process few enough files at the same time for the buffers of all of
+/// them to fit in `max_memory`, each file need an input and an output buffer.
+///
+/// this only limit the number of threads, the files are still read into memory whole, so a
+/// single file that doesn't fit is processed alone and go over `max_memory`
+pub fn thread_pool(max_memory: u64, largest_file_size: u64) -> anyhow::Result<ThreadPool> {
+    let per_file = largest_file_size.saturating_mul(2).max(1);
+    if per_file > max_memory {
+        println!(
+            "{} the largest file need about {} of memory, more than --max-memory {}",
+            "[!]".yellow(),
+            size::format(per_file),
+            size::format(max_memory),
+        );
+    }
+
+    let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = ((max_memory / per_file) as usize).clamp(1, available);
+    if threads < available {
+        println!(
+            "{} processing {threads} files at the same time to stay under --max-memory",
+            "[?]".green(),
+        );
+    }
+
+    Ok(ThreadPoolBuilder::new().num_threads(threads).build()?)
+}
@@ -0,0 +1,51 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn run(dir: &Path, args: &[&str], threads: usize) {
+    let status = Command::new(env!("CARGO_BIN_EXE_obscure-hvp"))
+        .args(args)
+        .current_dir(dir)
+        .env("RAYON_NUM_THREADS", threads.to_string())
+        .output()
+        .expect("failed to run obscure-hvp")
+        .status;
+    assert!(status.success(), "obscure-hvp {args:?} failed");
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("obscure-hvp-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("failed to create temp dir");
+    dir
+}
+
+#[test]
+fn hashes_json_is_the_same_between_extractions() {
+    let dir = temp_dir("hashes");
+
+    for game in ["obscure1", "obscure2", "final-exam"] {
+        let archive = format!("{game}.hvp");
+        run(&dir, &["demo-archive", "-g", game, &archive], 1);
+
+        for format in ["compact", "records"] {
+            let extract = |out: &str, threads| {
+                run(
+                    &dir,
+                    &["extract", &archive, out, "--hashes-format", format],
+                    threads,
+                );
+                std::fs::read(dir.join(out).join("hashes.json")).expect("missing hashes.json")
+            };
+
+            assert_eq!(
+                extract("single", 1),
+                extract("parallel", 8),
+                "{game} {format}"
+            );
+        }
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+}